@@ -1,4 +1,5 @@
 use std::sync::{Arc, Mutex};
+use crate::error::dberror::DbError;
 use crate::file::blockid::BlockId;
 use crate::file::filemgr::FileMgr;
 use crate::log::logmgr::LogMgr;
@@ -42,13 +43,20 @@ impl Buffer {
         &mut self.contents
     }
 
+    // The name of the file of the assigned block.
+    pub(crate) fn filename(&self) -> Option<Arc<str>> {
+        self.block.and_then(|block| self.fm.filename(block.file))
+    }
+
     pub(crate) fn block(&self) -> &Option<BlockId> {
         &self.block
     }
 
     pub(crate) fn set_modified(&mut self, txnum: i32, lsn: i32) {
+        debug_assert!(self.txnum.is_none_or(|t| t == txnum), "buffer modified by transaction {} while dirty for {:?}", txnum, self.txnum);
         self.txnum = Some(txnum);
         if lsn >= 0 {
+            debug_assert!(self.lsn.is_none_or(|l| l <= lsn), "buffer LSN moved backwards from {:?} to {}", self.lsn, lsn);
             self.lsn = Some(lsn);
         }
     }
@@ -64,31 +72,57 @@ impl Buffer {
     // Flushes the buffer to disk if it is dirty. The buffer is
     // unpinned and the transaction that modified the buffer is
     // cleared. It ensures the assigned disk block has the same
-    // contents as its page.
-    pub(crate) fn flush(&mut self) {
+    // contents as its page. Following the write-ahead logging rule,
+    // the log is flushed up to the buffer's LSN before the page is
+    // written; debug builds check it as the file manager writes the page.
+    pub(crate) fn flush(&mut self) -> Result<(), DbError> {
         if self.txnum.is_some() {
-            match self.lsn {
-                None => {}
-                Some(lsn) => {
-                    self.lm.lock().unwrap().flush_record(lsn);
-                }
-            }
+            self.flush_log()?;
             if let Some(ref block) = self.block {
-                self.fm.write(block, &mut self.contents);
+                self.fm.write(block, &self.contents)?;
             }
             self.txnum = None;
         }
+        Ok(())
+    }
+
+    // Flushes the log up to the buffer's LSN. It must be called before the
+    // page is written by anyone other than `flush`.
+    pub(crate) fn flush_log(&self) -> Result<(), DbError> {
+        if let Some(lsn) = self.lsn {
+            self.lm.lock().unwrap().flush_record(lsn)?;
+        }
+        Ok(())
+    }
+
+    // Marks the buffer as clean after its page was written on its behalf.
+    pub(crate) fn clear_modified(&mut self) {
+        self.txnum = None;
+    }
+
+    pub(crate) fn page(&self) -> &Page {
+        &self.contents
     }
 
     // Assigns the buffer to the specified disk block. The buffer
     // is first flushed, so that any modifications to the previous
     // block are written to disk. Its contents are then replaced by
-    // the page contents of the new block.
-    pub(crate) fn assign_to_block(&mut self, block: BlockId) {
-        self.flush();
-        self.block = Some(block.clone());
-        self.fm.read(&block, &mut self.contents);
+    // the page contents of the new block. If either step fails, the buffer
+    // is left unassigned.
+    pub(crate) fn assign_to_block(&mut self, block: BlockId) -> Result<(), DbError> {
+        self.flush()?;
+        self.block = None;
+        self.fm.read(&block, &mut self.contents)?;
+        self.block = Some(block);
         self.pins = 0;
+        Ok(())
+    }
+
+    // Unassigns the buffer from its block, whose file is being deleted, so
+    // that the block is read from disk again if the file is recreated.
+    pub(crate) fn discard(&mut self) {
+        debug_assert!(self.pins == 0 && self.txnum.is_none(), "discarding {:?} while in use", self.block);
+        self.block = None;
     }
 
     pub(crate) fn pin(&mut self) {
@@ -96,6 +130,7 @@ impl Buffer {
     }
 
     pub(crate) fn unpin(&mut self) {
+        debug_assert!(self.pins > 0, "unpinning {:?} which is not pinned", self.block);
         self.pins -= 1;
     }
 }
@@ -109,15 +144,15 @@ mod tests {
 
     #[test]
     fn test_buffer() {
-        let fm = Arc::new(FileMgr::new(PathBuf::from("testdb"), 400));
-        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log")));
+        let fm = Arc::new(FileMgr::new(PathBuf::from("testdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
         let mut buffer = Buffer::new(fm.clone(), lm.clone());
+        buffer.assign_to_block(fm.block("testfile", 1)).unwrap();
 
         assert_eq!(buffer.is_pinned(), false);
         buffer.pin();
         assert_eq!(buffer.is_pinned(), true);
 
-        buffer.assign_to_block(BlockId::new("testfile", 1));
         let page = buffer.contents();
         let number = page.get_int(80);
         page.set_int(80, number + 1);
@@ -126,11 +161,37 @@ mod tests {
 
         assert_eq!(buffer.is_pinned(), false);
         assert_eq!(buffer.transaction(), Some(1));
-        assert_eq!(buffer.block(), &Some(BlockId::new("testfile", 1)));
+        assert_eq!(buffer.block(), &Some(fm.block("testfile", 1)));
         assert_eq!(buffer.contents().get_int(80), number + 1);
 
-        buffer.flush();
+        buffer.flush().unwrap();
 
         assert_eq!(buffer.transaction(), None);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_buffer_flush_follows_wal() {
+        let fm = Arc::new(FileMgr::new(PathBuf::from("testdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
+        let mut buffer = Buffer::new(fm.clone(), lm.clone());
+        buffer.assign_to_block(fm.block("testfile", 2)).unwrap();
+        buffer.pin();
+
+        let lsn = lm.lock().unwrap().append(&vec![0; 8]).unwrap();
+        buffer.contents().set_int(80, 1);
+        buffer.set_modified(1, lsn);
+        buffer.flush().unwrap();
+
+        assert!(lm.lock().unwrap().last_saved_lsn() >= lsn);
+        buffer.unpin();
+    }
+
+    #[test]
+    #[should_panic(expected = "not pinned")]
+    fn test_buffer_unpin_unpinned() {
+        let fm = Arc::new(FileMgr::new(PathBuf::from("testdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
+        let mut buffer = Buffer::new(fm, lm);
+        buffer.unpin();
+    }
+}
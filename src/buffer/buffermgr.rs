@@ -1,19 +1,38 @@
-use crate::file::blockid::BlockId;
+use crate::error::dberror::DbError;
+use crate::file::blockid::{BlockId, FileId};
 use crate::buffer::buffer::Buffer;
 use crate::file::filemgr::FileMgr;
+use crate::file::page::Page;
 use crate::log::logmgr::LogMgr;
-use std::sync::{Arc, Mutex};
-use std::thread::{current, park_timeout};
+use std::collections::HashSet;
+use std::sync::{Arc, Condvar, Mutex};
 use std::time::{Duration, Instant};
 
 pub(crate) struct BufferMgr {
+    fm: Arc<FileMgr>,
     pool: Vec<Buffer>,
     available: usize,
+    reserved: usize,
+    high_water: usize,
+    low_water: usize,
+    resident: HashSet<FileId>,
+    resident_budget: usize,
+    work_mem: usize,
     max_time: u128,
+    // Notified whenever a buffer is unpinned or reserved buffers are
+    // released. It is shared so that a waiter can wait on it with the buffer
+    // manager unlocked, see `wait_for`.
+    unpinned: Arc<Condvar>,
 }
 
 impl BufferMgr {
    const MAX_TIME : u128 = 10000;
+   const DIRTY_HIGH_WATER: usize = 75;
+   const DIRTY_LOW_WATER: usize = 50;
+   const RESIDENT_BUDGET: usize = 25;
+   const WORK_MEM: usize = 25;
+   // A merge needs a buffer for each of two inputs and one for its output.
+   const MIN_WORK_MEM: usize = 3;
 
     // Creates a new buffer manager with the specified number of buffers.
     // Each buffer is initialized with an empty block. The buffer manager
@@ -25,9 +44,17 @@ impl BufferMgr {
             pool.push(Buffer::new(fm.clone(), lm.clone()));
         }
         BufferMgr {
+            fm,
             pool,
             available: buffsize,
+            reserved: 0,
+            high_water: buffsize * Self::DIRTY_HIGH_WATER / 100,
+            low_water: buffsize * Self::DIRTY_LOW_WATER / 100,
+            resident: HashSet::new(),
+            resident_budget: buffsize * Self::RESIDENT_BUDGET / 100,
+            work_mem: (buffsize * Self::WORK_MEM / 100).max(Self::MIN_WORK_MEM),
             max_time: Self::MAX_TIME,
+            unpinned: Arc::new(Condvar::new()),
         }
     }
 
@@ -42,43 +69,181 @@ impl BufferMgr {
         self.available
     }
 
+    // Reserves the specified number of buffers for a multibuffer operator.
+    // The buffers are claimed all at once or not at all, so two operators
+    // can never each hold half of the pool while waiting for the rest. The
+    // reserved buffers are then pinned with `pin_reserved`, and whatever is
+    // left of the reservation must be handed back with `release`. If the
+    // buffers do not become available within the maximum time, or the
+    // reservation is larger than the pool, the buffer manager returns an
+    // error.
+    pub(crate) fn reserve(bm: &Mutex<BufferMgr>, n: usize) -> Result<(), DbError> {
+        if n > bm.lock().unwrap().pool.len() {
+            return Err(DbError::BufferAbort);
+        }
+        Self::wait_for(bm, |mgr| Ok(mgr.try_reserve(n).then_some(())))
+    }
+
+    // The number of buffers a single operator, such as a sort, may work
+    // with: it holds that many blocks of records in memory, and merges
+    // one fewer runs at a time, keeping a buffer for the output. What does
+    // not fit is spilled to temporary tables. Operators ask for this budget
+    // rather than each guessing how much of the pool it may take.
+    pub(crate) fn work_mem(&self) -> usize {
+        self.work_mem
+    }
+
+    // The budget is never below what a two-way merge needs, nor above the
+    // pool, as the buffers are reserved up front.
+    pub(crate) fn set_work_mem(&mut self, buffers: usize) {
+        self.work_mem = buffers.clamp(Self::MIN_WORK_MEM, self.pool.len().max(Self::MIN_WORK_MEM));
+    }
+
+    fn try_reserve(&mut self, n: usize) -> bool {
+        if self.available - self.reserved < n {
+            return false;
+        }
+        self.reserved += n;
+        true
+    }
+
+    // Returns unused buffers of a reservation to the pool.
+    pub(crate) fn release(&mut self, n: usize) {
+        debug_assert!(n <= self.reserved, "releasing {} buffers but only {} are reserved", n, self.reserved);
+        self.reserved -= n;
+        self.unpinned.notify_all();
+    }
+
+    // Pins the block using one of the caller's reserved buffers. It never
+    // waits: the buffer was set aside by `reserve`. If the block is already
+    // pinned, the reservation is left untouched; the flag returned tells
+    // whether a reserved buffer was used up.
+    pub(crate) fn pin_reserved(&mut self, block: &BlockId) -> Result<(usize, bool), DbError> {
+        debug_assert!(self.reserved > 0, "pinning {:?} without a reservation", block);
+        let claims_buffer = self.find_existing_buffer(block).is_none_or(|idx| !self.pool[idx].is_pinned());
+        Ok((self.try_pin(block, true)?.unwrap(), claims_buffer))
+    }
+
+    // Unpins a buffer that was pinned with `pin_reserved`. Once it is no
+    // longer pinned it goes back to the caller's reservation rather than to
+    // the pool, and true is returned.
+    pub(crate) fn unpin_reserved(&mut self, idx: usize) -> bool {
+        self.pool[idx].unpin();
+        if self.pool[idx].is_pinned() {
+            return false;
+        }
+        self.available += 1;
+        self.reserved += 1;
+        true
+    }
+
+    // Advises the buffer manager to keep the blocks of the file in memory,
+    // for small tables that are read all the time and should not be pushed
+    // out by large scans. It is only a hint: see `choose_unpinned_buffer`.
+    pub(crate) fn keep_resident(&mut self, filename: &str) {
+        self.resident.insert(self.fm.file_id(filename));
+    }
+
+    // Forgets the blocks of a file that is being deleted. The caller makes
+    // sure that none of them is pinned or dirty.
+    pub(crate) fn discard_file(&mut self, filename: &str) {
+        let file = self.fm.file_id(filename);
+        self.pool.iter_mut()
+            .filter(|buffer| buffer.block().is_some_and(|block| block.file == file))
+            .for_each(|buffer| buffer.discard());
+    }
+
     // Flushes all buffers assigned to the specified transaction.
-    pub fn flush_all(&mut self, txnum: i32) {
-        for buffer in self.pool.iter_mut() {
-            if buffer.transaction().eq(&Some(txnum)) {
-                buffer.flush();
-            }
+    pub fn flush_all(&mut self, txnum: i32) -> Result<(), DbError> {
+        let dirty: Vec<(BlockId, usize)> = self.pool.iter().enumerate()
+            .filter(|(_, buffer)| buffer.transaction() == Some(txnum))
+            .filter_map(|(idx, buffer)| buffer.block().map(|block| (block, idx)))
+            .collect();
+        self.write_dirty(dirty)
+    }
+
+    pub(crate) fn dirty(&self) -> usize {
+        self.pool.iter().filter(|buffer| buffer.transaction().is_some()).count()
+    }
+
+    // Once more than the high-water mark of the pool is dirty, a burst of
+    // updates could leave no clean buffer to replace, and every pin would pay
+    // for a synchronous write, or wait if the dirty buffers are pinned. There
+    // is no background writer, so the pinning thread catches up instead: it
+    // writes unpinned dirty buffers, whichever transaction they belong to,
+    // until the dirty count is back at the low-water mark. This is safe
+    // because the log is flushed before each page, and recovery undoes the
+    // changes of transactions that do not commit.
+    fn write_behind(&mut self) -> Result<(), DbError> {
+        let dirty = self.dirty();
+        if dirty <= self.high_water {
+            return Ok(());
+        }
+        let victims: Vec<(BlockId, usize)> = self.pool.iter().enumerate()
+            .filter(|(_, buffer)| buffer.transaction().is_some() && !buffer.is_pinned())
+            .filter_map(|(idx, buffer)| buffer.block().map(|block| (block, idx)))
+            .take(dirty - self.low_water)
+            .collect();
+        self.write_dirty(victims)
+    }
+
+    // Writes the dirty buffers in (file, block) order, coalescing runs of
+    // adjacent blocks into a single write. The log is flushed first, so that
+    // every page in a run satisfies the write-ahead rule.
+    fn write_dirty(&mut self, mut dirty: Vec<(BlockId, usize)>) -> Result<(), DbError> {
+        dirty.sort_by_key(|(block, _)| (block.file, block.number()));
+        for (_, idx) in &dirty {
+            self.pool[*idx].flush_log()?;
         }
+
+        for run in dirty.chunk_by(|(a, _), (b, _)| a.file == b.file && a.number + 1 == b.number) {
+            let pages: Vec<&Page> = run.iter().map(|(_, idx)| self.pool[*idx].page()).collect();
+            self.fm.write_run(&run[0].0, &pages)?;
+        }
+        dirty.iter().for_each(|(_, idx)| self.pool[*idx].clear_modified());
+        Ok(())
     }
 
     // Unpins the buffer at the specified index, making it available
-    // for other threads to use. The thread is also unparked to allow
-    // other threads to continue execution.
+    // for other threads to use. Threads waiting for a buffer are
+    // notified so that they can try again.
     pub(crate) fn unpin(&mut self, idx: usize) {
         self.pool[idx].unpin();
         if !self.pool[idx].is_pinned() {
             self.available += 1;
-            current().unpark();
+            self.unpinned.notify_all();
         }
     }
 
-    // Pins the buffer containing the specified block. If the buffer
-    // is already pinned, the thread is placed on a waiting state until
-    // the buffer is unpinned. If the buffer is not unpinned after the
-    // maximum time, the buffer manager returns an error.
-    pub(crate) fn pin(&mut self, block: &BlockId) -> Result<usize, &str> {
+    // Pins the buffer containing the specified block. If no buffer
+    // is available, the thread waits until one is unpinned. If none
+    // is unpinned after the maximum time, the buffer manager returns
+    // an error.
+    pub(crate) fn pin(bm: &Mutex<BufferMgr>, block: &BlockId) -> Result<usize, DbError> {
+        bm.lock().unwrap().write_behind()?;
+        Self::wait_for(bm, |mgr| mgr.try_pin(block, false))
+    }
+
+    // Makes attempts until one succeeds. In between, the thread waits for a
+    // buffer to be unpinned or released, with the buffer manager unlocked so
+    // that other threads can unpin theirs. We keep track of how long we've
+    // been waiting, and if it exceeds the maximum time, the buffer manager
+    // assumes the caller is in a deadlock and returns an error that must be
+    // handled by the caller.
+    fn wait_for<T>(bm: &Mutex<BufferMgr>, mut attempt: impl FnMut(&mut BufferMgr) -> Result<Option<T>, DbError>) -> Result<T, DbError> {
         let timestamp = Instant::now();
-        let mut idx = self.try_pin(block);
-        // we keep track of how long we've been waiting for a buffer to be unpinned
-        // and if it exceeds the maximum time, the buffer manager assumes the caller
-        // is in a deadlock and returns an error that must be handled by the caller.
-        while idx.is_none() && timestamp.elapsed().as_millis() < self.max_time {
-            park_timeout(Duration::from_millis(self.max_time as u64));
-            idx = self.try_pin(block);
-        }
-        match idx {
-            Some(idx) => Ok(idx),
-            None => Err("Timeout while waiting for buffer to be unpinned"),
+        let mut mgr = bm.lock().unwrap();
+        let unpinned = mgr.unpinned.clone();
+        loop {
+            if let Some(value) = attempt(&mut mgr)? {
+                return Ok(value);
+            }
+            let elapsed = timestamp.elapsed().as_millis();
+            if elapsed >= mgr.max_time {
+                return Err(DbError::BufferAbort);
+            }
+            let timeout = Duration::from_millis((mgr.max_time - elapsed) as u64);
+            mgr = unpinned.wait_timeout(mgr, timeout).unwrap().0;
         }
     }
 
@@ -87,26 +252,33 @@ impl BufferMgr {
     // buffer is not pinned, the function assigns the block to the buffer and
     // returns the index of the buffer. If there are no available buffers, the
     // function returns None, indicating that the caller must wait for a buffer
-    // to be unpinned.
-    fn try_pin(&mut self, block: &BlockId) -> Option<usize> {
-        if let Some(idx) = self.find_existing_buffer(block) {
-            if !self.pool[idx].is_pinned() {
-                self.available -= 1;
-            }
-            self.pool[idx].pin();
-            return Some(idx);
+    // to be unpinned. Buffers set aside by reservations are only handed out to
+    // callers pinning from their reservation.
+    fn try_pin(&mut self, block: &BlockId, from_reservation: bool) -> Result<Option<usize>, DbError> {
+        let existing = self.find_existing_buffer(block);
+        let claims_buffer = existing.is_none_or(|idx| !self.pool[idx].is_pinned());
+        if claims_buffer && !from_reservation && self.available <= self.reserved {
+            return Ok(None);
         }
 
-        if let Some(idx) = self.choose_unpinned_buffer() {
-            self.pool[idx].assign_to_block(block.clone());
-            if !self.pool[idx].is_pinned() {
-                self.available -= 1;
+        let idx = match existing {
+            Some(idx) => idx,
+            None => {
+                let Some(idx) = self.choose_unpinned_buffer() else {
+                    return Ok(None);
+                };
+                self.pool[idx].assign_to_block(*block)?;
+                idx
+            }
+        };
+        if claims_buffer {
+            self.available -= 1;
+            if from_reservation {
+                self.reserved -= 1;
             }
-            self.pool[idx].pin();
-            return Some(idx);
         }
-
-        None
+        self.pool[idx].pin();
+        Ok(Some(idx))
     }
 
     // Sequentially searches for a buffer containing the specified block.
@@ -127,16 +299,26 @@ impl BufferMgr {
     // Sequentially searches for the first unpinned buffer in the pool.
     // This is the Naive Buffer Replacement Strategy, which is not efficient
     // but is good enough for the purpose of this engine.
+    // Blocks of resident files are passed over as long as they hold no more
+    // than the resident budget of the pool. They are still replaced when
+    // no other buffer is unpinned, since the hint must never make a pin
+    // wait.
     fn choose_unpinned_buffer(&mut self) -> Option<usize> {
+        let resident = self.pool.iter().filter(|buffer| self.is_resident(buffer)).count();
+        let protected = |buffer: &Buffer| resident <= self.resident_budget && self.is_resident(buffer);
         for (idx, buffer) in self.pool.iter().enumerate() {
-            if !buffer.is_pinned() {
+            if !buffer.is_pinned() && !protected(buffer) {
                 // Instead of returning the buffer, we're returning
                 // the index of the buffer. It differs from the original
                 // implementation but avoids the need to clone the buffer.
                 return Some(idx);
             }
         }
-        None
+        self.pool.iter().position(|buffer| !buffer.is_pinned())
+    }
+
+    fn is_resident(&self, buffer: &Buffer) -> bool {
+        buffer.block().is_some_and(|block| self.resident.contains(&block.file))
     }
 }
 
@@ -149,43 +331,196 @@ mod tests {
 
     #[test]
     fn test_buffer_mgr() {
-        let fm = Arc::new(FileMgr::new(PathBuf::from("testdb"), 400));
-        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log")));
-        let mut bm = BufferMgr::new(fm.clone(), lm.clone(), 3);
+        let fm = Arc::new(FileMgr::new(PathBuf::from("testdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
+        let bm = Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 3));
 
         // Set the maximum time to wait for a buffer to be unpinned to 1ms
         // to test the timeout functionality without waiting for too long.
-        bm.max_time = 1;
+        bm.lock().unwrap().max_time = 1;
 
         // Let's have our own buffer pool here instead of using
         // the one in the `BufferMgr` struct.
         let mut buff = Vec::with_capacity(6);
 
-        buff.push(bm.pin(&BlockId::new("testfile", 0)).unwrap());
-        buff.push(bm.pin(&BlockId::new("testfile", 1)).unwrap());
-        buff.push(bm.pin(&BlockId::new("testfile", 2)).unwrap());
-        bm.unpin(buff[1]);
-        buff.push(bm.pin(&BlockId::new("testfile", 0)).unwrap());
-        buff.push(bm.pin(&BlockId::new("testfile", 1)).unwrap());
-        println!("Available buffers: {}", bm.available());
+        buff.push(BufferMgr::pin(&bm, &fm.block("testfile", 0)).unwrap());
+        buff.push(BufferMgr::pin(&bm, &fm.block("testfile", 1)).unwrap());
+        buff.push(BufferMgr::pin(&bm, &fm.block("testfile", 2)).unwrap());
+        bm.lock().unwrap().unpin(buff[1]);
+        buff.push(BufferMgr::pin(&bm, &fm.block("testfile", 0)).unwrap());
+        buff.push(BufferMgr::pin(&bm, &fm.block("testfile", 1)).unwrap());
+        println!("Available buffers: {}", bm.lock().unwrap().available());
 
         // The buffer pool is full at this point, attempting to pin block 3
         // will place the thread on a waiting state until a buffer is unpinned.
         // Given that no buffer will be unpinned, the buffer manager will return
         // a timeout error.
         println!("Attempting to pin block 3...");
-        match bm.pin(&BlockId::new("testfile", 3)) {
+        match BufferMgr::pin(&bm, &fm.block("testfile", 3)) {
             Ok(_) => println!("Block 3 pinned successfully"),
             Err(error) => println!("{}", error),
         }
 
         // Unpinning buffer 2 will make it available for pinning block 3.
-        bm.unpin(buff[2]);
-        buff.push(bm.pin(&BlockId::new("testfile", 3)).unwrap());
+        bm.lock().unwrap().unpin(buff[2]);
+        buff.push(BufferMgr::pin(&bm, &fm.block("testfile", 3)).unwrap());
+
+        assert_eq!(*bm.lock().unwrap().buffer(buff[0]).block().as_ref().unwrap(), fm.block("testfile", 0));
+        assert_eq!(*bm.lock().unwrap().buffer(buff[3]).block().as_ref().unwrap(), fm.block("testfile", 0));
+        assert_eq!(*bm.lock().unwrap().buffer(buff[4]).block().as_ref().unwrap(), fm.block("testfile", 1));
+        assert_eq!(*bm.lock().unwrap().buffer(buff[5]).block().as_ref().unwrap(), fm.block("testfile", 3));
+    }
+
+    #[test]
+    fn test_buffer_reservation() {
+        let fm = Arc::new(FileMgr::new(PathBuf::from("testdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
+        let bm = Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 4));
+        bm.lock().unwrap().max_time = 1;
+
+        // Reserving three buffers leaves a single one for regular pins.
+        BufferMgr::reserve(&bm, 3).unwrap();
+        let other = BufferMgr::pin(&bm, &fm.block("testfile", 0)).unwrap();
+        assert!(BufferMgr::pin(&bm, &fm.block("testfile", 1)).is_err());
+
+        // A second reservation cannot be granted in part.
+        bm.lock().unwrap().unpin(other);
+        assert!(BufferMgr::reserve(&bm, 2).is_err());
 
-        assert_eq!(*bm.buffer(buff[0]).block().as_ref().unwrap(), BlockId::new("testfile", 0));
-        assert_eq!(*bm.buffer(buff[3]).block().as_ref().unwrap(), BlockId::new("testfile", 0));
-        assert_eq!(*bm.buffer(buff[4]).block().as_ref().unwrap(), BlockId::new("testfile", 1));
-        assert_eq!(*bm.buffer(buff[5]).block().as_ref().unwrap(), BlockId::new("testfile", 3));
+        // The reserved buffers are pinned without waiting. Pinning a block
+        // that is already pinned does not use up the reservation.
+        let first = bm.lock().unwrap().pin_reserved(&fm.block("testfile", 1)).unwrap();
+        let second = bm.lock().unwrap().pin_reserved(&fm.block("testfile", 2)).unwrap();
+        assert!(first.1);
+        assert_eq!(bm.lock().unwrap().pin_reserved(&fm.block("testfile", 2)).unwrap(), (second.0, false));
+        assert_eq!(bm.lock().unwrap().reserved, 1);
+
+        // A reserved buffer goes back to the reservation once it is unpinned
+        // for good.
+        assert!(!bm.lock().unwrap().unpin_reserved(second.0));
+        assert!(bm.lock().unwrap().unpin_reserved(second.0));
+        assert_eq!(bm.lock().unwrap().reserved, 2);
+        bm.lock().unwrap().pin_reserved(&fm.block("testfile", 2)).unwrap();
+
+        // Releasing the rest of the reservation makes it available again.
+        bm.lock().unwrap().release(1);
+        BufferMgr::pin(&bm, &fm.block("testfile", 3)).unwrap();
+        BufferMgr::pin(&bm, &fm.block("testfile", 4)).unwrap();
+        assert_eq!(bm.lock().unwrap().available(), 0);
+        assert!(BufferMgr::reserve(&bm, 1).is_err());
+
+        bm.lock().unwrap().unpin(first.0);
+        BufferMgr::reserve(&bm, 1).unwrap();
+    }
+
+    #[test]
+    fn test_reserve_waits_unlocked() {
+        let fm = Arc::new(FileMgr::new(PathBuf::from("testdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
+        let bm = Arc::new(Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 2)));
+        let first = BufferMgr::pin(&bm, &fm.block("testfile", 0)).unwrap();
+        let second = BufferMgr::pin(&bm, &fm.block("testfile", 1)).unwrap();
+
+        // The reservation waits for both buffers without holding the buffer
+        // manager, so the other thread can unpin them.
+        let waiter = {
+            let bm = bm.clone();
+            std::thread::spawn(move || BufferMgr::reserve(&bm, 2))
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        bm.lock().unwrap().unpin(first);
+        bm.lock().unwrap().unpin(second);
+        waiter.join().unwrap().unwrap();
+        assert_eq!(bm.lock().unwrap().reserved, 2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_keep_resident() {
+        let fm = Arc::new(FileMgr::new(PathBuf::from("testdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
+        let bm = Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 4));
+        bm.lock().unwrap().max_time = 1;
+        bm.lock().unwrap().keep_resident("dim.tbl");
+
+        // A scan over another file cycles through the other buffers.
+        let dim = fm.block("dim.tbl", 0);
+        let idx = BufferMgr::pin(&bm, &dim).unwrap();
+        bm.lock().unwrap().unpin(idx);
+        for n in 0..10 {
+            let idx = BufferMgr::pin(&bm, &fm.block("testfile", n)).unwrap();
+            bm.lock().unwrap().unpin(idx);
+        }
+        assert!(bm.lock().unwrap().find_existing_buffer(&dim).is_some());
+
+        // Once it is the only unpinned buffer, it is replaced all the same.
+        for n in 0..3 {
+            BufferMgr::pin(&bm, &fm.block("testfile", n)).unwrap();
+        }
+        BufferMgr::pin(&bm, &fm.block("testfile", 3)).unwrap();
+        assert!(bm.lock().unwrap().find_existing_buffer(&dim).is_none());
+    }
+
+    #[test]
+    fn test_flush_all_coalesces_adjacent_blocks() {
+        let fm = Arc::new(FileMgr::new(PathBuf::from("buffermgrtestdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
+        let bm = Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 4));
+
+        // Dirty blocks 3, 1, 2 and 5: two runs, written out of pool order.
+        // They stay pinned so that none of them is flushed on replacement.
+        let blocks: Vec<BlockId> = [3, 1, 2, 5].iter().map(|n| fm.block("testfile", *n)).collect();
+        for (i, block) in blocks.iter().enumerate() {
+            let idx = BufferMgr::pin(&bm, block).unwrap();
+            bm.lock().unwrap().buffer(idx).contents().set_int(80, 100 + i as i32);
+            bm.lock().unwrap().buffer(idx).set_modified(7, -1);
+        }
+
+        let written = fm.stats().written_blocks();
+        let runs = fm.stats().written_runs();
+        bm.lock().unwrap().flush_all(7).unwrap();
+        assert_eq!(fm.stats().written_blocks(), written + 4);
+        // Blocks 1 to 3 go out in one write, block 5 in another.
+        assert_eq!(fm.stats().written_runs(), runs + 2);
+
+        for (i, block) in blocks.iter().enumerate() {
+            let mut page = Page::new(fm.block_size());
+            fm.read(block, &mut page).unwrap();
+            assert_eq!(page.get_int(80), 100 + i as i32);
+        }
+        assert!((0..4).all(|idx| bm.lock().unwrap().buffer(idx).transaction().is_none()));
+    }
+
+    #[test]
+    fn test_write_behind_above_high_water() {
+        let fm = Arc::new(FileMgr::new(PathBuf::from("writebehindtestdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
+        let bm = Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 4));
+
+        // Three dirty buffers stay at the high-water mark of a four buffer pool.
+        // They are unpinned only once all are assigned, so that none of them is
+        // replaced.
+        let idx = BufferMgr::pin(&bm, &fm.block("testfile", 3)).unwrap();
+        let dirty: Vec<usize> = (0..3).map(|n| {
+            let idx = BufferMgr::pin(&bm, &fm.block("testfile", n)).unwrap();
+            bm.lock().unwrap().buffer(idx).contents().set_int(80, n as i32);
+            bm.lock().unwrap().buffer(idx).set_modified(1, -1);
+            idx
+        }).collect();
+        dirty.iter().for_each(|idx| bm.lock().unwrap().unpin(*idx));
+        let written = fm.stats().written_blocks();
+        BufferMgr::pin(&bm, &fm.block("testfile", 2)).unwrap();
+        assert_eq!(fm.stats().written_blocks(), written);
+
+        // A fourth one goes over it, so the next pin writes unpinned buffers
+        // until only the low-water mark is dirty. The pinned one is skipped.
+        bm.lock().unwrap().buffer(idx).set_modified(1, -1);
+        BufferMgr::pin(&bm, &fm.block("testfile", 0)).unwrap();
+        assert_eq!(bm.lock().unwrap().dirty(), 2);
+        assert_eq!(fm.stats().written_blocks(), written + 2);
+        assert!(bm.lock().unwrap().buffer(idx).transaction().is_some());
+
+        let mut page = Page::new(fm.block_size());
+        fm.read(&fm.block("testfile", 0), &mut page).unwrap();
+        assert_eq!(page.get_int(80), 0);
+    }
+}
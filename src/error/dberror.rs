@@ -0,0 +1,71 @@
+use std::fmt::Display;
+use crate::file::blockid::BlockId;
+
+// Errors raised by the storage layers underneath the scans: the file, log,
+// buffer and transaction managers. None of them is a bug in the caller;
+// the transaction that gets one should roll back, and may retry if the
+// error was a timeout.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DbError {
+    // Reading or writing a database file failed.
+    Io(String),
+    // No buffer became available within the buffer manager's maximum wait.
+    BufferAbort,
+    // A lock could not be granted within the lock table's maximum wait.
+    // Waits are not tracked in a waits-for graph, so a timeout is how a
+    // deadlock shows up.
+    LockTimeout(BlockId),
+    // The transaction was not admitted within the admission controller's
+    // maximum wait.
+    AdmissionTimeout,
+    // The files on disk are not what this version of the engine wrote.
+    Corruption(String),
+    // Appending a block would take the table past its quota of blocks.
+    TableQuotaExceeded { table: String, blocks: usize },
+    // Appending a block would take the tables of the database past their
+    // combined quota of blocks.
+    DatabaseQuotaExceeded { blocks: usize },
+    // The disk has no space left for a write. Nothing that was not written
+    // is lost: the operation can be retried once space is freed.
+    DiskFull,
+    // A record of a variable-length table grew past the free space of its
+    // block. Records do not move to another block, as that would change
+    // their RecordId.
+    RecordTooLarge(BlockId),
+    // An optimistic transaction read the block while another transaction
+    // wrote it. The transaction is rolled back and can be retried.
+    Conflict(BlockId),
+    // The transaction was killed by an administrator and rolled back.
+    Killed(i32),
+    // A checkpoint was asked for by a transaction that has changes of its
+    // own, which recovery would then no longer undo.
+    PendingChanges,
+}
+
+impl From<std::io::Error> for DbError {
+    fn from(error: std::io::Error) -> DbError {
+        match error.kind() {
+            std::io::ErrorKind::StorageFull => DbError::DiskFull,
+            _ => DbError::Io(error.to_string()),
+        }
+    }
+}
+
+impl Display for DbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DbError::Io(error) => write!(f, "I/O error: {}", error),
+            DbError::BufferAbort => write!(f, "timeout while waiting for a buffer, retry later"),
+            DbError::LockTimeout(blk) => write!(f, "lock wait timed out on {}, possible deadlock", blk),
+            DbError::AdmissionTimeout => write!(f, "timeout while waiting for a transaction slot, retry later"),
+            DbError::Corruption(error) => write!(f, "corrupted database: {}", error),
+            DbError::TableQuotaExceeded { table, blocks } => write!(f, "table {} is at its quota of {} blocks", table, blocks),
+            DbError::DatabaseQuotaExceeded { blocks } => write!(f, "database is at its quota of {} blocks", blocks),
+            DbError::DiskFull => write!(f, "no space left on disk, retry once space is freed"),
+            DbError::RecordTooLarge(blk) => write!(f, "record does not fit in the free space of {}", blk),
+            DbError::Conflict(blk) => write!(f, "conflicting write on {}, retry the transaction", blk),
+            DbError::Killed(txnum) => write!(f, "transaction {} was killed", txnum),
+            DbError::PendingChanges => write!(f, "the transaction has uncommitted changes, checkpoint in a new one"),
+        }
+    }
+}
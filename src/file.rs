@@ -1,3 +1,6 @@
 pub mod filemgr;
 pub mod blockid;
-pub mod page;
\ No newline at end of file
+pub mod page;
+pub mod superblock;
+pub mod doublewrite;
+pub mod freespacemap;
\ No newline at end of file
@@ -1,34 +1,96 @@
+use std::collections::HashMap;
 use std::fmt::Display;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
 
-#[derive(Clone, Debug, Eq, Hash)]
-pub struct BlockId {
-    pub filename: String,
-    pub number: usize,
+// Blocks refer to their file by a small FileId rather than by name. This
+// keeps BlockId a cheap Copy value, as it is used as a key in the buffer
+// list, the lock table, and the free space map. Each file manager interns
+// the names of its files, see `FileNames`. Ids are drawn from a counter
+// shared by every database in the process and are never reused, so a block
+// of a deleted file, or of another database, never passes for a block of
+// another file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct FileId(u64);
+
+static NEXT_FILE_ID: AtomicU64 = AtomicU64::new(0);
+
+// The names of the files of a database and their ids. A name is registered
+// the first time one of its blocks is referred to, and forgotten when the
+// file is deleted, so that the names of temporary tables do not pile up.
+#[derive(Default)]
+pub struct FileNames {
+    files: RwLock<Files>,
 }
 
-impl BlockId {
-    pub fn new(filename: &str, number: usize) -> BlockId {
-        BlockId { filename: filename.to_string(), number }
+#[derive(Default)]
+struct Files {
+    ids: HashMap<Arc<str>, FileId>,
+    names: HashMap<FileId, Arc<str>>,
+}
+
+impl FileNames {
+    pub fn new() -> FileNames {
+        FileNames::default()
     }
 
-    pub fn filename(&self) -> &str {
-        &self.filename
+    // Returns the id of the filename, registering it on first use.
+    pub fn id(&self, filename: &str) -> FileId {
+        if let Some(id) = self.files.read().unwrap().ids.get(filename) {
+            return *id;
+        }
+        let mut files = self.files.write().unwrap();
+        if let Some(id) = files.ids.get(filename) {
+            return *id;
+        }
+        let name: Arc<str> = Arc::from(filename);
+        let id = FileId(NEXT_FILE_ID.fetch_add(1, Ordering::SeqCst));
+        files.ids.insert(name.clone(), id);
+        files.names.insert(id, name);
+        id
     }
 
-    pub fn number(&self) -> usize {
-        self.number
+    pub fn block(&self, filename: &str, number: usize) -> BlockId {
+        BlockId::new(self.id(filename), number)
+    }
+
+    // Returns the name of the file, or None if it was deleted since.
+    pub fn name(&self, file: FileId) -> Option<Arc<str>> {
+        self.files.read().unwrap().names.get(&file).cloned()
+    }
+
+    pub fn forget(&self, filename: &str) {
+        let mut files = self.files.write().unwrap();
+        if let Some(id) = files.ids.remove(filename) {
+            files.names.remove(&id);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.read().unwrap().ids.len()
     }
 }
 
-impl PartialEq for BlockId {
-    fn eq(&self, other: &BlockId) -> bool {
-        self.filename == other.filename && self.number == other.number
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct BlockId {
+    pub file: FileId,
+    pub number: usize,
+}
+
+impl BlockId {
+    pub fn new(file: FileId, number: usize) -> BlockId {
+        BlockId { file, number }
+    }
+
+    pub fn number(&self) -> usize {
+        self.number
     }
 }
 
+// Blocks show the id of their file; the file manager knows its name.
 impl Display for BlockId {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "[file {}, block {}]", self.filename, self.number)
+        write!(f, "[file #{}, block {}]", self.file.0, self.number)
     }
 }
 
@@ -37,9 +99,32 @@ mod tests {
 
     #[test]
     fn test_block_id() {
-        let block_id = BlockId::new("test.txt", 42);
-        assert_eq!(block_id.filename(), "test.txt");
+        let files = FileNames::new();
+        let block_id = files.block("test.txt", 42);
+        assert_eq!(files.name(block_id.file).as_deref(), Some("test.txt"));
         assert_eq!(block_id.number(), 42);
-        assert_eq!(block_id.to_string(), "[file test.txt, block 42]");
+        assert_eq!(block_id.to_string(), format!("[file #{}, block 42]", block_id.file.0));
+    }
+
+    #[test]
+    fn test_file_id_interning() {
+        let files = FileNames::new();
+        let a = files.block("interned.tbl", 1);
+        let b = files.block("interned.tbl", 1);
+        let c = files.block("other.tbl", 1);
+        assert_eq!(a, b);
+        assert_eq!(a.file, b.file);
+        assert_ne!(a, c);
+        assert_eq!(files.len(), 2);
+
+        // A forgotten name gets a new id, so the blocks of the old file are
+        // not mistaken for blocks of the new one.
+        files.forget("interned.tbl");
+        assert_eq!(files.name(a.file), None);
+        assert_eq!(files.len(), 1);
+        assert_ne!(files.id("interned.tbl"), a.file);
+
+        // Ids are not shared with other databases.
+        assert_ne!(FileNames::new().id("other.tbl"), c.file);
     }
 }
@@ -0,0 +1,167 @@
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use crate::error::dberror::DbError;
+use crate::file::page::Page;
+
+// The double-write area protects pages against torn writes. A page is larger
+// than a disk sector, so a crash in the middle of writing it can leave the
+// block half old and half new, which the log cannot repair since it only
+// records the changed values. With double writes enabled, pages are first
+// appended to a staging file and synced; only then are they written to their
+// home location. If the crash happens while writing the staging file, the
+// home blocks are untouched; if it happens afterwards, the staged copies are
+// written again when the database is reopened.
+//
+// Each staged entry holds the filename, the block number, a checksum of the
+// page and the page itself. Entries whose checksum does not match were torn
+// while staging and are ignored.
+pub struct DoubleWrite {
+    file: Mutex<File>,
+}
+
+impl DoubleWrite {
+    pub const FILENAME: &'static str = "simpledb.dw";
+
+    pub fn open(db_dir: &Path) -> Result<DoubleWrite, DbError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(db_dir.join(Self::FILENAME))?;
+        Ok(DoubleWrite { file: Mutex::new(file) })
+    }
+
+    // Stages the pages, then calls `home` to write them to their home
+    // location. `home` must sync the data before returning, because the
+    // staged copies are discarded right after. If `home` fails, the staged
+    // copies are kept so that they are written again on the next open.
+    pub fn write(&self, pages: &[(&str, usize, &Page)], home: impl FnOnce() -> Result<(), DbError>) -> Result<(), DbError> {
+        let mut file = self.file.lock().unwrap();
+        let mut staged = Vec::new();
+        for (filename, number, page) in pages {
+            staged.extend(Self::encode(filename, *number, page));
+        }
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&staged)?;
+        file.sync_data()?;
+        home()?;
+        file.set_len(0)?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    // Returns the intact pages left in the staging file of the database in
+    // the specified directory by a crash, with their filename and block
+    // number. The caller writes them back to their home location and then
+    // calls `clear`.
+    pub fn pending(db_dir: &Path, block_size: usize) -> Result<Vec<(String, usize, Page)>, DbError> {
+        let mut bytes = Vec::new();
+        if let Ok(mut file) = File::open(db_dir.join(Self::FILENAME)) {
+            file.read_to_end(&mut bytes)?;
+        }
+        let mut pages = Vec::new();
+        let mut pos = 0;
+        while let Some((filename, number, page, len)) = Self::decode(&bytes[pos..], block_size) {
+            pages.push((filename, number, page));
+            pos += len;
+        }
+        Ok(pages)
+    }
+
+    pub fn clear(db_dir: &Path) -> Result<(), DbError> {
+        let path = db_dir.join(Self::FILENAME);
+        if fs::exists(&path).unwrap_or(false) {
+            File::options().write(true).open(path)?.set_len(0)?;
+        }
+        Ok(())
+    }
+
+    fn encode(filename: &str, number: usize, page: &Page) -> Vec<u8> {
+        let numpos = Page::max_length(filename.len());
+        let checkpos = numpos + 4;
+        let pagepos = checkpos + 4;
+        let mut entry = Page::new(pagepos + page.bytebuffer.len());
+        entry.set_string(0, filename);
+        entry.set_int(numpos, number as i32);
+        entry.set_int(checkpos, Self::checksum(&page.bytebuffer));
+        entry.bytebuffer[pagepos..].copy_from_slice(&page.bytebuffer);
+        entry.bytebuffer
+    }
+
+    // Decodes the entry at the start of the bytes, returning it along with
+    // its length. Entries are staged in order, so a missing or torn entry
+    // means there is nothing valid after it.
+    fn decode(bytes: &[u8], block_size: usize) -> Option<(String, usize, Page, usize)> {
+        if bytes.len() < 4 {
+            return None;
+        }
+        let namelen = Page::wrap(bytes[..4].to_vec()).get_int(0) as usize;
+        let numpos = Page::max_length(namelen);
+        let checkpos = numpos + 4;
+        let pagepos = checkpos + 4;
+        let len = pagepos + block_size;
+        if bytes.len() < len {
+            return None;
+        }
+        let entry = Page::wrap(bytes[..len].to_vec());
+        let contents = entry.bytebuffer[pagepos..].to_vec();
+        if entry.get_int(checkpos) != Self::checksum(&contents) {
+            return None;
+        }
+        let filename = String::from_utf8(entry.bytebuffer[4..numpos].to_vec()).ok()?;
+        let number = entry.get_int(numpos) as usize;
+        Some((filename, number, Page::wrap(contents), len))
+    }
+
+    // 32-bit FNV-1a hash of the page contents.
+    pub(crate) fn checksum(bytes: &[u8]) -> i32 {
+        bytes.iter().fold(0x811c9dc5u32, |hash, byte| (hash ^ *byte as u32).wrapping_mul(0x01000193)) as i32
+    }
+}
+
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_double_write() {
+        let dir = PathBuf::from("doublewritetestdb");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        let block_size = 100;
+
+        // A completed write leaves nothing behind.
+        let dw = DoubleWrite::open(&dir).unwrap();
+        let mut page = Page::new(block_size);
+        page.set_int(8, 42);
+        let mut called = false;
+        dw.write(&[("testfile", 3, &page)], || {
+            called = true;
+            Ok(())
+        }).unwrap();
+        assert!(called);
+        assert!(DoubleWrite::pending(&dir, block_size).unwrap().is_empty());
+
+        // A crash after staging leaves the pages to be written again.
+        let mut staged = DoubleWrite::encode("testfile", 3, &page);
+        fs::write(dir.join(DoubleWrite::FILENAME), &staged).unwrap();
+        let pending = DoubleWrite::pending(&dir, block_size).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!((pending[0].0.as_str(), pending[0].1), ("testfile", 3));
+        assert_eq!(pending[0].2.get_int(8), 42);
+
+        // A torn staging entry is ignored.
+        let last = staged.len() - 1;
+        staged[last] ^= 0xff;
+        fs::write(dir.join(DoubleWrite::FILENAME), &staged).unwrap();
+        assert!(DoubleWrite::pending(&dir, block_size).unwrap().is_empty());
+
+        DoubleWrite::clear(&dir).unwrap();
+        assert_eq!(fs::metadata(dir.join(DoubleWrite::FILENAME)).unwrap().len(), 0);
+    }
+}
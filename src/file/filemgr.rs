@@ -1,94 +1,474 @@
 use std::collections::HashMap;
 use std::fs;
 use std::fs::{File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{IoSlice, Read, Seek, SeekFrom, Write};
 use std::ops::Deref;
-use std::path::PathBuf;
-use std::sync::RwLock;
-use crate::file::blockid::BlockId;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use crate::error::dberror::DbError;
+use crate::file::blockid::{BlockId, FileId, FileNames};
+use crate::file::doublewrite::DoubleWrite;
+use crate::file::freespacemap::FreeSpaceMap;
 use crate::file::page::Page;
+use crate::file::superblock::Superblock;
 
 
 pub struct FileMgr {
     db_dir: PathBuf,
     block_size: usize,
+    is_new: bool,
+    files: FileNames,
     open_files: RwLock<HashMap<String, File>>,
-    stats: RwLock<Stats>
+    stats: RwLock<Stats>,
+    double_write: Option<DoubleWrite>,
+    free_space: FreeSpaceMap,
+    quotas: RwLock<Quotas>,
+    // The number of blocks taken by the tables of the database, counted
+    // once when it opens and then kept up to date by every change to the
+    // length of a table's file, so that checking the database quota does
+    // not list the directory on each append.
+    usage: AtomicUsize,
+    // The bytes left on a simulated disk; see `simulate_disk_space`.
+    disk_space: RwLock<Option<usize>>,
+    // The log file and the LSN of the last log record on disk, kept up to
+    // date by the log manager, so that debug builds can check every page
+    // written against the write-ahead rule.
+    log_file: RwLock<Option<FileId>>,
+    saved_lsn: AtomicI32,
+}
+
+// The maximum number of blocks of each table, and of all tables together.
+#[derive(Default)]
+struct Quotas {
+    tables: HashMap<String, usize>,
+    database: Option<usize>,
 }
 
 impl FileMgr {
-    pub(crate) fn new(db_dir: PathBuf, block_size: usize) -> FileMgr {
-        if !fs::exists(&db_dir).unwrap_or(false) {
-            fs::create_dir(&db_dir).unwrap();
+    const SNAPSHOT_MAGIC: i32 = 0x53444258;
+    const SNAPSHOT_HEADER_SIZE: usize = 12;
+
+    pub(crate) fn new(db_dir: PathBuf, block_size: usize) -> Result<FileMgr, DbError> {
+        let is_new = !fs::exists(&db_dir).unwrap_or(false);
+        if is_new {
+            fs::create_dir(&db_dir)?;
         }
 
-        fs::read_dir(&db_dir).unwrap().for_each(|entry| {
-            let path = entry.unwrap().path();
-            if path.is_file() && path.file_name().unwrap().to_str().unwrap().starts_with("temp") {
-                fs::remove_file(path).unwrap();
+        for entry in fs::read_dir(&db_dir)? {
+            let path = entry?.path();
+            if path.is_file() && path.file_name().is_some_and(|name| name.to_string_lossy().starts_with("temp")) {
+                fs::remove_file(path)?;
+            }
+        }
+
+        // Reject the directory if it was created with different parameters,
+        // rather than misreading every page with the wrong block size.
+        Superblock::open(&db_dir, block_size, is_new)?;
+
+        let fm = FileMgr { db_dir, block_size, is_new, files: FileNames::new(), open_files: RwLock::new(HashMap::new()), stats: RwLock::new(Stats::new()), double_write: None, free_space: FreeSpaceMap::new(), quotas: RwLock::new(Quotas::default()), usage: AtomicUsize::new(0), disk_space: RwLock::new(None), log_file: RwLock::new(None), saved_lsn: AtomicI32::new(0) };
+
+        // Pages staged in the double-write area before a crash may have been
+        // torn at their home location, so they are written again.
+        for (filename, number, page) in DoubleWrite::pending(&fm.db_dir, block_size)? {
+            fm.write_home(&fm.block(&filename, number), &[&page])?;
+        }
+        DoubleWrite::clear(&fm.db_dir)?;
+        let mut usage = 0;
+        for entry in fs::read_dir(&fm.db_dir)? {
+            let entry = entry?;
+            if Self::is_table(&entry.file_name().to_string_lossy()) {
+                usage += entry.metadata()?.len() as usize / block_size;
             }
-        });
+        }
+        fm.usage.store(usage, Ordering::SeqCst);
+        Ok(fm)
+    }
+
+    pub(crate) fn db_dir(&self) -> &Path {
+        &self.db_dir
+    }
+
+    // Returns true if the database directory did not exist before this
+    // file manager created it.
+    pub(crate) fn is_new(&self) -> bool {
+        self.is_new
+    }
+
+    // Routes every page write through the double-write area, protecting
+    // pages against being torn by a crash at the cost of writing them twice.
+    pub(crate) fn enable_double_write(&mut self) -> Result<(), DbError> {
+        self.double_write = Some(DoubleWrite::open(&self.db_dir)?);
+        Ok(())
+    }
 
-        FileMgr { db_dir, block_size, open_files: RwLock::new(HashMap::new()), stats: RwLock::new(Stats::new()) }
+    // Returns the block of the file, registering the filename on first use.
+    pub(crate) fn block(&self, filename: &str, number: usize) -> BlockId {
+        self.files.block(filename, number)
     }
 
-    pub(crate) fn read(&self, block: &BlockId, page: &mut Page) {
-        let path = self.db_dir.join(block.filename());
+    pub(crate) fn file_id(&self, filename: &str) -> FileId {
+        self.files.id(filename)
+    }
+
+    // Returns the name of the file, or None if it was deleted since.
+    pub(crate) fn filename(&self, file: FileId) -> Option<Arc<str>> {
+        self.files.name(file)
+    }
+
+    // The number of filenames registered, which goes down as files are
+    // deleted.
+    pub(crate) fn file_count(&self) -> usize {
+        self.files.len()
+    }
+
+    fn name(&self, block: &BlockId) -> Result<Arc<str>, DbError> {
+        self.files.name(block.file).ok_or_else(|| DbError::Io(format!("{} belongs to a deleted file", block)))
+    }
+
+    fn path(&self, block: &BlockId) -> Result<PathBuf, DbError> {
+        Ok(self.db_dir.join(&*self.name(block)?))
+    }
+
+    pub(crate) fn read(&self, block: &BlockId, page: &mut Page) -> Result<(), DbError> {
+        let path = self.path(block)?;
         let number = block.number() as usize;
-        let mut file = self.open_file(path);
-        file.seek(SeekFrom::Start(((number * self.block_size) as u64).into())).unwrap();
-        file.read(page.bytebuffer.as_mut_slice()).unwrap();
+        let mut file = self.open_file(path)?;
+        file.seek(SeekFrom::Start((number * self.block_size) as u64))?;
+        file.read(page.bytebuffer.as_mut_slice())?;
         self.stats.write().unwrap().increment_read_blocks();
+        Ok(())
     }
 
-    pub(crate) fn write(&self, block: &BlockId, page: &Page) {
-        let filename = self.db_dir.join(block.filename());
-        let number = block.number() as usize;
-        let mut file = self.open_file(filename);
-        file.seek(SeekFrom::Start(((number * self.block_size) as u64).into())).unwrap();
-        file.write(page.bytebuffer.as_slice()).unwrap();
-        self.stats.write().unwrap().increment_written_blocks();
+    // Called by the log manager when it opens the log file and each time
+    // it flushes the log.
+    pub(crate) fn set_log(&self, filename: &str, saved_lsn: i32) {
+        *self.log_file.write().unwrap() = Some(self.file_id(filename));
+        self.saved_lsn.store(saved_lsn, Ordering::SeqCst);
+    }
+
+    pub(crate) fn set_saved_lsn(&self, saved_lsn: i32) {
+        self.saved_lsn.store(saved_lsn, Ordering::SeqCst);
+    }
+
+    // A page must not reach disk before the log record of its last change,
+    // whose LSN is in the page header. The log's own blocks have no such
+    // header, and nothing is checked until there is a log.
+    fn check_write_ahead(&self, first: &BlockId, pages: &[&Page]) {
+        let log_file = *self.log_file.read().unwrap();
+        if log_file.is_none_or(|file| file == first.file) {
+            return;
+        }
+        let saved_lsn = self.saved_lsn.load(Ordering::SeqCst);
+        for (i, page) in pages.iter().enumerate() {
+            assert!(page.lsn() <= saved_lsn, "WAL violation: block {} of {} written before log record {}",
+                first.number() + i, self.name(first).as_deref().unwrap_or("a deleted file"), page.lsn());
+        }
+    }
+
+    pub(crate) fn write(&self, block: &BlockId, page: &Page) -> Result<(), DbError> {
+        self.write_run(block, &[page])
+    }
+
+    // Writes the pages to consecutive blocks starting at the specified block,
+    // with a single seek and a vectored write.
+    pub(crate) fn write_run(&self, first: &BlockId, pages: &[&Page]) -> Result<(), DbError> {
+        if cfg!(debug_assertions) {
+            self.check_write_ahead(first, pages);
+        }
+        match &self.double_write {
+            None => self.write_home(first, pages),
+            Some(dw) => {
+                let filename = self.name(first)?;
+                let path = self.db_dir.join(&*filename);
+                let staged: Vec<(&str, usize, &Page)> = pages.iter().enumerate()
+                    .map(|(i, page)| (&*filename, first.number() + i, *page))
+                    .collect();
+                dw.write(&staged, || {
+                    self.write_home(first, pages)?;
+                    self.open_file(path.clone())?.sync_data()?;
+                    Ok(())
+                })
+            }
+        }
     }
 
-    pub(crate) fn append(&self, filename: &str) -> BlockId {
-        let buffer = vec![0; self.block_size];
-        let number = self.length(filename) as usize;
-        let block = BlockId::new(filename, number);
-        let filename = self.db_dir.join(block.filename());
-        let mut file = self.open_file(filename);
-        file.seek(SeekFrom::Start(((number * self.block_size) as u64).into())).unwrap();
-        file.write(buffer.as_slice()).unwrap();
+    fn write_home(&self, first: &BlockId, pages: &[&Page]) -> Result<(), DbError> {
+        let mut file = self.open_file(self.path(first)?)?;
+        file.seek(SeekFrom::Start((first.number() * self.block_size) as u64))?;
+        if let Some(fits) = self.take_disk_space(pages.len() * self.block_size) {
+            let bytes: Vec<u8> = pages.iter().flat_map(|page| page.bytebuffer.iter().copied()).take(fits).collect();
+            file.write_all(&bytes)?;
+            return Err(DbError::DiskFull);
+        }
+        let mut slices: Vec<IoSlice> = pages.iter().map(|page| IoSlice::new(&page.bytebuffer)).collect();
+        let mut slices = slices.as_mut_slice();
+        while !slices.is_empty() {
+            let written = file.write_vectored(slices)?;
+            // A write that makes no progress would otherwise loop forever.
+            if written == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::WriteZero).into());
+            }
+            IoSlice::advance_slices(&mut slices, written);
+        }
+        let mut stats = self.stats.write().unwrap();
+        pages.iter().for_each(|_| stats.increment_written_blocks());
+        stats.increment_written_runs();
+        Ok(())
+    }
+
+    // A block of a data file starts out stamped with the LSN of the last
+    // log record on disk, as formatting it is not logged, so that an
+    // incremental backup since an earlier LSN still includes it.
+    pub(crate) fn append(&self, filename: &str) -> Result<BlockId, DbError> {
+        let mut page = Page::new(self.block_size);
+        let number = self.length(filename)? as usize;
+        self.check_quota(filename, number)?;
+        let block = self.block(filename, number);
+        if self.log_file.read().unwrap().is_some_and(|file| file != block.file) {
+            page.set_lsn(self.saved_lsn.load(Ordering::SeqCst));
+        }
+        let mut file = self.open_file(self.db_dir.join(filename))?;
+        file.seek(SeekFrom::Start((number * self.block_size) as u64))?;
+        if let Some(fits) = self.take_disk_space(self.block_size) {
+            file.write_all(&page.bytebuffer[..fits])?;
+            return Err(DbError::DiskFull);
+        }
+        file.write_all(&page.bytebuffer)?;
+        if Self::is_table(filename) {
+            self.usage.fetch_add(1, Ordering::SeqCst);
+        }
         self.stats.write().unwrap().increment_written_blocks();
-        block
+        Ok(block)
     }
 
-    fn open_file(&self, path: PathBuf) -> File {
-        let filename = path.to_str().unwrap().to_string();
+    // Limits the table to the number of blocks, or lifts its limit. Only
+    // appends are checked, so a table already past a new quota keeps its
+    // blocks but cannot grow. Quotas are not persisted.
+    pub(crate) fn set_table_quota(&self, table: &str, blocks: Option<usize>) {
+        let mut quotas = self.quotas.write().unwrap();
+        match blocks {
+            Some(blocks) => quotas.tables.insert(table.to_string(), blocks),
+            None => quotas.tables.remove(table),
+        };
+    }
+
+    // Limits the combined size of the tables of the database, including
+    // the catalog, the index buckets and temporary tables. The log is not
+    // counted, as a transaction that cannot log cannot roll back either.
+    pub(crate) fn set_database_quota(&self, blocks: Option<usize>) {
+        self.quotas.write().unwrap().database = blocks;
+    }
+
+    pub(crate) fn table_quota(&self, table: &str) -> Option<usize> {
+        self.quotas.read().unwrap().tables.get(table).copied()
+    }
+
+    // The number of blocks taken by the tables of the database.
+    pub(crate) fn database_usage(&self) -> usize {
+        self.usage.load(Ordering::SeqCst)
+    }
+
+    fn is_table(filename: &str) -> bool {
+        filename.ends_with(".tbl")
+    }
+
+    // The number of blocks of the file on disk, or 0 if there is none.
+    fn blocks_on_disk(&self, path: &Path) -> Result<usize, DbError> {
+        match fs::metadata(path) {
+            Ok(metadata) => Ok(metadata.len() as usize / self.block_size),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn check_quota(&self, filename: &str, blocks: usize) -> Result<(), DbError> {
+        let Some(table) = filename.strip_suffix(".tbl") else {
+            return Ok(());
+        };
+        let quotas = self.quotas.read().unwrap();
+        if let Some(&quota) = quotas.tables.get(table) && blocks >= quota {
+            return Err(DbError::TableQuotaExceeded { table: table.to_string(), blocks: quota });
+        }
+        if let Some(quota) = quotas.database && self.database_usage() >= quota {
+            return Err(DbError::DatabaseQuotaExceeded { blocks: quota });
+        }
+        Ok(())
+    }
+
+    // Removes the file from disk, along with its cached handle, free space
+    // and interned name. A file that does not exist is already deleted.
+    pub(crate) fn delete_file(&self, filename: &str) -> Result<(), DbError> {
+        let path = self.db_dir.join(filename);
+        self.open_files.write().unwrap().remove(path.to_string_lossy().as_ref());
+        self.free_space.forget(self.file_id(filename));
+        self.files.forget(filename);
+        let blocks = if Self::is_table(filename) { self.blocks_on_disk(&path)? } else { 0 };
+        match fs::remove_file(&path) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error.into()),
+            _ => {
+                self.usage.fetch_sub(blocks, Ordering::SeqCst);
+                Ok(())
+            }
+        }
+    }
+
+    // Simulates a disk with only the given number of bytes left, to test
+    // how a full disk is handled; None lifts the limit. Every block written
+    // uses up space, even one that overwrites another. A write that does not
+    // fit writes the bytes that do, as a real one may, and fails with
+    // `DbError::DiskFull`.
+    pub(crate) fn simulate_disk_space(&self, bytes: Option<usize>) {
+        *self.disk_space.write().unwrap() = bytes;
+    }
+
+    // Uses up the space for the bytes on the simulated disk. Returns how
+    // many of them fit if not all do.
+    fn take_disk_space(&self, bytes: usize) -> Option<usize> {
+        let mut disk_space = self.disk_space.write().unwrap();
+        let left = (*disk_space)?;
+        *disk_space = Some(left.saturating_sub(bytes));
+        (left < bytes).then_some(left)
+    }
+
+    fn open_file(&self, path: PathBuf) -> Result<File, DbError> {
+        let filename = path.to_string_lossy().to_string();
         let mut files = self.open_files.write().unwrap();
         match files.get(filename.as_str()) {
-            Some(file) => file.try_clone().unwrap(),
+            Some(file) => Ok(file.try_clone()?),
             None => {
                 let file = OpenOptions::new()
                     .read(true)
                     .write(true)
                     .create(true)
-                    .open(path)
-                    .unwrap();
-                files.insert(filename.clone(), file.try_clone().unwrap());
-                file
+                    .truncate(false)
+                    .open(path)?;
+                files.insert(filename, file.try_clone()?);
+                Ok(file)
             }
         }
     }
 
-    pub(crate) fn length(&self, file: &str) -> u32 {
+    // Writes the blocks of the table's file to the writer as they are on
+    // disk: a header with the block size and the number of blocks, then each
+    // block preceded by its checksum. Changes still in the buffer pool are
+    // not included, so the table should be exported once the transactions
+    // that wrote it have committed. Returns the number of blocks written.
+    pub(crate) fn export_file(&self, table: &str, writer: &mut impl Write) -> Result<usize, DbError> {
+        let filename = format!("{}.tbl", table);
+        let blocks = self.length(&filename)? as usize;
+        let mut header = Page::new(Self::SNAPSHOT_HEADER_SIZE);
+        header.set_int(0, Self::SNAPSHOT_MAGIC);
+        header.set_int(4, self.block_size as i32);
+        header.set_int(8, blocks as i32);
+        writer.write_all(&header.bytebuffer)?;
+        let mut page = Page::new(self.block_size);
+        let mut checksum = Page::new(4);
+        for number in 0..blocks {
+            self.read(&self.block(&filename, number), &mut page)?;
+            checksum.set_int(0, DoubleWrite::checksum(&page.bytebuffer));
+            writer.write_all(&checksum.bytebuffer)?;
+            writer.write_all(&page.bytebuffer)?;
+        }
+        Ok(blocks)
+    }
+
+    // Replaces the table's file with the blocks written by `export_file`.
+    // The blocks are streamed to a temporary file, which only replaces the
+    // table's file once every block has passed its checksum, so a truncated
+    // or corrupted snapshot leaves the table as it was. Blocks of the table
+    // already in the buffer pool are not refreshed, so a table should be
+    // imported before it is read, typically right after creating it.
+    // Returns the number of blocks read.
+    pub(crate) fn import_file(&self, table: &str, reader: &mut impl Read) -> Result<usize, DbError> {
+        let mut header = Page::new(Self::SNAPSHOT_HEADER_SIZE);
+        reader.read_exact(&mut header.bytebuffer)?;
+        if header.get_int(0) != Self::SNAPSHOT_MAGIC {
+            return Err(DbError::Corruption("not a table snapshot".to_string()));
+        }
+        if header.get_int(4) as usize != self.block_size {
+            return Err(DbError::Corruption(format!("snapshot has block size {} but the database uses {}", header.get_int(4), self.block_size)));
+        }
+        let blocks = header.get_int(8) as usize;
+
+        let filename = format!("{}.tbl", table);
+        let temp = self.db_dir.join(format!("temp_import_{}", filename));
+        let result = self.import_blocks(&temp, blocks, reader);
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result?;
+        let path = self.db_dir.join(&filename);
+        let replaced = self.blocks_on_disk(&path)?;
+        fs::rename(&temp, &path)?;
+        self.usage.fetch_sub(replaced, Ordering::SeqCst);
+        self.usage.fetch_add(blocks, Ordering::SeqCst);
+        // The cached handle still refers to the file that was replaced.
+        self.open_files.write().unwrap().remove(path.to_string_lossy().as_ref());
+        self.free_space.forget(self.file_id(&filename));
+        Ok(blocks)
+    }
+
+    fn import_blocks(&self, temp: &Path, blocks: usize, reader: &mut impl Read) -> Result<(), DbError> {
+        let mut file = File::create(temp)?;
+        let mut checksum = Page::new(4);
+        let mut page = Page::new(self.block_size);
+        for number in 0..blocks {
+            reader.read_exact(&mut checksum.bytebuffer)?;
+            reader.read_exact(&mut page.bytebuffer)?;
+            if checksum.get_int(0) != DoubleWrite::checksum(&page.bytebuffer) {
+                return Err(DbError::Corruption(format!("block {} of the snapshot does not match its checksum", number)));
+            }
+            file.write_all(&page.bytebuffer)?;
+        }
+        file.sync_data()?;
+        Ok(())
+    }
+
+    // Copies the files of the database, other than temporary ones, into a
+    // directory that must not exist yet, and returns how many were copied.
+    // Nothing may be written to the database meanwhile; see
+    // `Transaction::copy_database`.
+    pub(crate) fn copy_to(&self, dest: &Path) -> Result<usize, DbError> {
+        fs::create_dir(dest)?;
+        let filenames = Self::filenames(&self.db_dir)?;
+        for name in &filenames {
+            fs::copy(self.db_dir.join(name), dest.join(name))?;
+            File::open(dest.join(name))?.sync_all()?;
+        }
+        Ok(filenames.len())
+    }
+
+    // The files of the database in the directory, other than temporary
+    // ones, in order of name.
+    pub(crate) fn filenames(db_dir: &Path) -> Result<Vec<String>, DbError> {
+        let mut filenames = Vec::new();
+        for entry in fs::read_dir(db_dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_name().map(|name| name.to_string_lossy().to_string()) else {
+                continue;
+            };
+            if path.is_file() && !name.starts_with("temp") {
+                filenames.push(name);
+            }
+        }
+        filenames.sort();
+        Ok(filenames)
+    }
+
+    pub(crate) fn length(&self, file: &str) -> Result<u32, DbError> {
         let path = self.db_dir.join(file);
-        self.open_file(path).metadata().unwrap().len() as u32 / self.block_size as u32
+        Ok(self.open_file(path)?.metadata()?.len() as u32 / self.block_size as u32)
     }
 
     pub(crate) fn block_size(&self) -> usize {
         self.block_size
     }
 
+    pub(crate) fn free_space(&self) -> &FreeSpaceMap {
+        &self.free_space
+    }
+
     pub(crate) fn stats(&self) -> Stats {
         self.stats.read().unwrap().clone()
     }
@@ -97,22 +477,28 @@ impl FileMgr {
 #[derive(Debug, Clone)]
 pub struct Stats {
     read_blocks: u32,
-    written_blocks: u32
+    written_blocks: u32,
+    // The number of writes of adjacent blocks, each a single system call.
+    written_runs: u32,
 }
 
 impl Stats {
     fn new() -> Stats {
-        Stats { read_blocks: 0, written_blocks: 0 }
+        Stats { read_blocks: 0, written_blocks: 0, written_runs: 0 }
     }
 
-    fn read_blocks(&self) -> u32 {
+    pub(crate) fn read_blocks(&self) -> u32 {
         self.read_blocks
     }
 
-    fn written_blocks(&self) -> u32 {
+    pub(crate) fn written_blocks(&self) -> u32 {
         self.written_blocks
     }
 
+    pub(crate) fn written_runs(&self) -> u32 {
+        self.written_runs
+    }
+
     fn increment_read_blocks(&mut self) {
         self.read_blocks += 1;
     }
@@ -120,26 +506,32 @@ impl Stats {
     fn increment_written_blocks(&mut self) {
         self.written_blocks += 1;
     }
+
+    fn increment_written_runs(&mut self) {
+        self.written_runs += 1;
+    }
 }
 
 
 mod tests {
     use super::*;
+    use crate::log::logmgr::LogMgr;
+    use std::panic::AssertUnwindSafe;
 
     #[test]
     fn test_file_mgr() {
         let block_size = 200;
-        let fm = FileMgr::new(PathBuf::from("../../testdb"), block_size);
-        let block = BlockId::new("testfile", 2);
+        let fm = FileMgr::new(PathBuf::from("../../testdb"), block_size).unwrap();
+        let block = fm.block("testfile", 2);
 
         let mut page1 = Page::new(block_size);
         page1.set_string(88, "abcdefghijklm");
         let offset = Page::max_length("abcdefghijklm".len());
         page1.set_byte(88 + offset, 255);
-        fm.write(&block, &page1);
+        fm.write(&block, &page1).unwrap();
 
         let mut page2 = Page::new(block_size);
-        fm.read(&block, &mut page2);
+        fm.read(&block, &mut page2).unwrap();
 
         assert_eq!(page2.get_string(88), "abcdefghijklm");
         assert_eq!(page2.get_byte(88 + offset), Some(255));
@@ -150,15 +542,145 @@ mod tests {
     #[test]
     fn test_file_length() {
         let block_size = 200;
-        let fm = FileMgr::new(PathBuf::from("../../testdb"), block_size);
-        let block = BlockId::new("testfile", 2);
+        let fm = FileMgr::new(PathBuf::from("../../testdb"), block_size).unwrap();
+        let block = fm.block("testfile", 2);
 
         let mut page1 = Page::new(block_size);
         page1.set_string(88, "abcdefghijklm");
         let offset = Page::max_length("abcdefghijklm".len());
         page1.set_byte(88 + offset, 255);
-        fm.write(&block, &page1);
+        fm.write(&block, &page1).unwrap();
+
+        assert_eq!(fm.length("testfile").unwrap(), 3);
+    }
+
+    #[test]
+    fn test_export_import_file() {
+        let block_size = 200;
+        let _ = fs::remove_dir_all("exportfiletestdb");
+        let source = FileMgr::new(PathBuf::from("exportfiletestdb"), block_size).unwrap();
+        let mut page = Page::new(block_size);
+        for number in 0..3 {
+            page.set_int(20, number as i32 * 10);
+            source.write(&source.block("t.tbl", number), &page).unwrap();
+        }
+        let mut snapshot = Vec::new();
+        assert_eq!(source.export_file("t", &mut snapshot).unwrap(), 3);
+
+        let _ = fs::remove_dir_all("importfiletestdb");
+        let target = FileMgr::new(PathBuf::from("importfiletestdb"), block_size).unwrap();
+        assert_eq!(target.import_file("t", &mut snapshot.as_slice()).unwrap(), 3);
+        assert_eq!(target.length("t.tbl").unwrap(), 3);
+        target.read(&target.block("t.tbl", 2), &mut page).unwrap();
+        assert_eq!(page.get_int(20), 20);
+
+        // A corrupted or truncated snapshot leaves the table untouched.
+        let mut corrupted = snapshot.clone();
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0xff;
+        assert_eq!(target.import_file("t", &mut corrupted.as_slice()), Err(DbError::Corruption("block 2 of the snapshot does not match its checksum".to_string())));
+        assert!(target.import_file("t", &mut &snapshot[..snapshot.len() - 1]).is_err());
+        assert_eq!(target.length("t.tbl").unwrap(), 3);
+        target.read(&target.block("t.tbl", 2), &mut page).unwrap();
+        assert_eq!(page.get_int(20), 20);
+
+        let other = FileMgr::new(PathBuf::from("importfile400testdb"), 400).unwrap();
+        assert!(matches!(other.import_file("t", &mut snapshot.as_slice()), Err(DbError::Corruption(_))));
+    }
+
+    #[test]
+    fn test_quotas() {
+        let _ = fs::remove_dir_all("quotatestdb");
+        let fm = FileMgr::new(PathBuf::from("quotatestdb"), 200).unwrap();
+        fm.set_table_quota("t", Some(2));
+        fm.append("t.tbl").unwrap();
+        fm.append("t.tbl").unwrap();
+        assert_eq!(fm.append("t.tbl"), Err(DbError::TableQuotaExceeded { table: "t".to_string(), blocks: 2 }));
+        fm.set_table_quota("t", None);
+        fm.append("t.tbl").unwrap();
+
+        // Files other than tables are never limited.
+        fm.set_database_quota(Some(4));
+        fm.append("u.tbl").unwrap();
+        assert_eq!(fm.database_usage(), 4);
+        assert_eq!(fm.append("u.tbl"), Err(DbError::DatabaseQuotaExceeded { blocks: 4 }));
+        fm.append("other.log").unwrap();
+        assert_eq!(fm.length("u.tbl").unwrap(), 1);
+
+        // Deleting a table frees its blocks, and the usage is counted
+        // again when the database reopens.
+        fm.delete_file("t.tbl").unwrap();
+        assert_eq!(fm.database_usage(), 1);
+        fm.append("u.tbl").unwrap();
+        drop(fm);
+        assert_eq!(FileMgr::new(PathBuf::from("quotatestdb"), 200).unwrap().database_usage(), 2);
+    }
+
+    #[test]
+    #[cfg_attr(not(debug_assertions), ignore)]
+    #[should_panic(expected = "WAL violation: block 0 of testfile written before log record")]
+    fn test_write_ahead_rule() {
+        let _ = fs::remove_dir_all("writeaheadtestdb");
+        let fm = Arc::new(FileMgr::new(PathBuf::from("writeaheadtestdb"), 400).unwrap());
+        let mut lm = LogMgr::new(fm.clone(), "testlog.log").unwrap();
+        let block = fm.append("testfile").unwrap();
+        let mut page = Page::new(400);
+
+        // A page whose change is logged on disk may be written.
+        let lsn = lm.append(&vec![0; 8]).unwrap();
+        page.set_lsn(lsn);
+        lm.flush_record(lsn).unwrap();
+        fm.write(&block, &page).unwrap();
+
+        // One whose log record is still in memory may not.
+        page.set_lsn(lm.append(&vec![0; 8]).unwrap());
+        fm.write(&block, &page).unwrap();
+    }
+
+    #[test]
+    fn test_delete_file_forgets_name() {
+        let _ = fs::remove_dir_all("deletefiletestdb");
+        let fm = FileMgr::new(PathBuf::from("deletefiletestdb"), 200).unwrap();
+        let block = fm.append("temp1.tbl").unwrap();
+        let count = fm.file_count();
+        fm.delete_file("temp1.tbl").unwrap();
+        assert_eq!(fm.file_count(), count - 1);
+        assert_eq!(fm.filename(block.file), None);
+        assert!(fm.read(&block, &mut Page::new(200)).is_err());
+        assert_ne!(fm.block("temp1.tbl", 0), block);
 
-        assert_eq!(fm.length("testfile"), 3);
+        // A temporary file left behind by a crash goes when the database is
+        // opened again.
+        fm.append("temp2.tbl").unwrap();
+        drop(fm);
+        FileMgr::new(PathBuf::from("deletefiletestdb"), 200).unwrap();
+        assert!(!PathBuf::from("deletefiletestdb/temp2.tbl").exists());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_double_write_recovery() {
+        let block_size = 200;
+        let dir = PathBuf::from("doublewritefmtestdb");
+        let mut fm = FileMgr::new(dir.clone(), block_size).unwrap();
+        fm.enable_double_write().unwrap();
+        let block = fm.block("testfile", 1);
+
+        let mut page = Page::new(block_size);
+        page.set_int(12, 7);
+        fm.write(&block, &page).unwrap();
+        assert_eq!(fs::metadata(dir.join(DoubleWrite::FILENAME)).unwrap().len(), 0);
+
+        // Simulate a crash that staged a newer version of the page but died
+        // before writing it to its home location.
+        page.set_int(12, 8);
+        let dw = DoubleWrite::open(&dir).unwrap();
+        let crash = std::panic::catch_unwind(AssertUnwindSafe(|| dw.write(&[("testfile", 1, &page)], || panic!("crash"))));
+        assert!(crash.is_err());
+
+        let fm = FileMgr::new(dir.clone(), block_size).unwrap();
+        let mut recovered = Page::new(block_size);
+        fm.read(&fm.block("testfile", 1), &mut recovered).unwrap();
+        assert_eq!(recovered.get_int(12), 8);
+        assert_eq!(fs::metadata(dir.join(DoubleWrite::FILENAME)).unwrap().len(), 0);
+    }
+}
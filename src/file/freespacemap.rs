@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use crate::file::blockid::{BlockId, FileId};
+
+#[derive(Default)]
+struct FileSpace {
+    full: Vec<bool>,
+    first_free: usize,
+}
+
+// The free-space map remembers which blocks of each file are known to be
+// full, so that an insert can jump straight to the first block that may
+// have room instead of reading every block before it. It is a hint kept in
+// memory: blocks start out as possibly free, and callers still verify that
+// a block has room. A block filled by a transaction that later rolls back
+// stays marked as full until one of its records is deleted.
+pub struct FreeSpaceMap {
+    files: Mutex<HashMap<FileId, FileSpace>>,
+}
+
+impl FreeSpaceMap {
+    pub fn new() -> FreeSpaceMap {
+        FreeSpaceMap { files: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn mark_full(&self, block: &BlockId) {
+        let mut files = self.files.lock().unwrap();
+        let space = files.entry(block.file).or_default();
+        if space.full.len() <= block.number {
+            space.full.resize(block.number + 1, false);
+        }
+        space.full[block.number] = true;
+        while space.full.get(space.first_free).copied().unwrap_or(false) {
+            space.first_free += 1;
+        }
+    }
+
+    pub fn mark_free(&self, block: &BlockId) {
+        let mut files = self.files.lock().unwrap();
+        if let Some(space) = files.get_mut(&block.file) {
+            if let Some(full) = space.full.get_mut(block.number) {
+                *full = false;
+            }
+            space.first_free = space.first_free.min(block.number);
+        }
+    }
+
+    // Drops what is known about the file, for when its blocks are replaced
+    // wholesale.
+    pub fn forget(&self, file: FileId) {
+        self.files.lock().unwrap().remove(&file);
+    }
+
+    // Returns the first block of the file that may have room, given the
+    // number of blocks in the file, or None if all of them are full.
+    pub fn first_free(&self, file: FileId, size: usize) -> Option<usize> {
+        let files = self.files.lock().unwrap();
+        let first_free = files.get(&file).map_or(0, |space| space.first_free);
+        if first_free < size {
+            return Some(first_free);
+        }
+        None
+    }
+}
+
+mod tests {
+    use super::*;
+    use crate::file::blockid::FileNames;
+
+    #[test]
+    fn test_free_space_map() {
+        let fsm = FreeSpaceMap::new();
+        let files = FileNames::new();
+        let file = files.id("freespace.tbl");
+        assert_eq!(fsm.first_free(file, 3), Some(0));
+        assert_eq!(fsm.first_free(file, 0), None);
+
+        fsm.mark_full(&BlockId::new(file, 1));
+        assert_eq!(fsm.first_free(file, 3), Some(0));
+        fsm.mark_full(&BlockId::new(file, 0));
+        assert_eq!(fsm.first_free(file, 3), Some(2));
+        fsm.mark_full(&BlockId::new(file, 2));
+        assert_eq!(fsm.first_free(file, 3), None);
+
+        fsm.mark_free(&BlockId::new(file, 1));
+        assert_eq!(fsm.first_free(file, 3), Some(1));
+    }
+}
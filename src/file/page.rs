@@ -3,6 +3,12 @@ pub struct Page {
 }
 
 impl Page {
+    // Data pages reserve a header at the start of the block that holds the
+    // LSN of the last logged change applied to the page. It lets recovery
+    // tell whether a log record is already reflected on disk.
+    pub const LSN_POS: usize = 0;
+    pub const HEADER_SIZE: usize = 4;
+
     pub fn new(blocksize: usize) -> Page {
         Page { bytebuffer: vec![0; blocksize] }
     }
@@ -19,13 +25,15 @@ impl Page {
         self.bytebuffer[offset] = value;
     }
 
+    // Bytes are prefixed by their length as a whole int, so that strings
+    // and log records can be longer than 255 bytes in big blocks.
     pub fn get_bytes(&self, offset: usize) -> &[u8] {
-        let len = self.get_byte(offset).unwrap() as usize;
+        let len = self.get_int(offset) as usize;
         &self.bytebuffer[offset + 4..offset + 4 + len]
     }
 
     pub fn set_bytes(&mut self, offset: usize, value: &[u8]) {
-        self.set_byte(offset, value.len() as u8);
+        self.set_int(offset, value.len() as i32);
         self.bytebuffer[offset + 4..offset + 4 + value.len()].copy_from_slice(value);
     }
 
@@ -49,6 +57,15 @@ impl Page {
         self.bytebuffer[offset..offset + 8].copy_from_slice(&value.to_be_bytes());
     }
 
+    // Doubles are stored as the bits of their IEEE 754 representation.
+    pub fn get_double(&self, offset: usize) -> f64 {
+        f64::from_bits(self.get_long(offset) as u64)
+    }
+
+    pub fn set_double(&mut self, offset: usize, value: f64) {
+        self.set_long(offset, value.to_bits() as i64);
+    }
+
     pub fn get_string(&self, offset: usize) -> String {
         let bytes = self.get_bytes(offset);
         String::from_utf8(bytes.to_vec()).unwrap()
@@ -66,11 +83,38 @@ impl Page {
         self.get_byte(offset).unwrap() != 0
     }
 
+    // Blobs are stored as bytes, with their length.
+    pub fn get_blob(&self, offset: usize) -> &[u8] {
+        self.get_bytes(offset)
+    }
+
+    pub fn set_blob(&mut self, offset: usize, value: &[u8]) {
+        self.set_bytes(offset, value);
+    }
+
+    // A range is a run of bytes without a length prefix, whose length the
+    // caller keeps track of.
+    pub fn get_range(&self, offset: usize, len: usize) -> &[u8] {
+        &self.bytebuffer[offset..offset + len]
+    }
+
+    pub fn set_range(&mut self, offset: usize, value: &[u8]) {
+        self.bytebuffer[offset..offset + value.len()].copy_from_slice(value);
+    }
+
     pub fn max_length(strlen: usize) -> usize {
         let max_bytes_per_char = 1;
         4 + strlen * max_bytes_per_char
     }
 
+    pub fn lsn(&self) -> i32 {
+        self.get_int(Self::LSN_POS)
+    }
+
+    pub fn set_lsn(&mut self, lsn: i32) {
+        self.set_int(Self::LSN_POS, lsn);
+    }
+
     pub(crate) fn contents(&mut self) -> &mut Vec<u8> {
         &mut self.bytebuffer
     }
@@ -103,6 +147,25 @@ mod tests {
         assert_eq!(page.get_long(8), 9876543210123);
     }
 
+    #[test]
+    fn test_page_double() {
+        let mut page = Page::new(16);
+        page.set_double(0, -2.5);
+        page.set_double(8, f64::MAX);
+        assert_eq!(page.get_double(0), -2.5);
+        assert_eq!(page.get_double(8), f64::MAX);
+    }
+
+    #[test]
+    fn test_page_blob() {
+        let mut page = Page::new(400);
+        let blob: Vec<u8> = (0..300).map(|i| i as u8).collect();
+        page.set_blob(10, &blob);
+        assert_eq!(page.get_blob(10), blob.as_slice());
+        page.set_blob(10, &[]);
+        assert!(page.get_blob(10).is_empty());
+    }
+
     #[test]
     fn test_page_bytes() {
         let mut page = Page::new(20);
@@ -112,10 +175,9 @@ mod tests {
 
         println!("{:?}", page.contents());
 
-        assert_eq!(page.get_byte(0), Some(3));
-        assert_eq!(page.get_byte(1), Some(0));
+        assert_eq!(page.get_int(0), 3);
         assert_eq!(page.get_bytes(0), &[1, 2, 3]);
-        assert_eq!(page.get_byte(7), Some(3));
+        assert_eq!(page.get_int(7), 3);
         assert_eq!(page.get_bytes(7), &[4, 5, 6]);
     }
 
@@ -129,6 +191,12 @@ mod tests {
 
         assert_eq!(page.get_string(0), "hello");
         assert_eq!(page.get_string(0 + Page::max_length("world".len())), "world");
+
+        // A string that fills a whole 16KB block.
+        let long = "x".repeat(16384 - 4);
+        let mut page = Page::new(16384);
+        page.set_string(0, &long);
+        assert_eq!(page.get_string(0), long);
     }
 
     #[test]
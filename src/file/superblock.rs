@@ -0,0 +1,161 @@
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::error::dberror::DbError;
+use crate::file::page::Page;
+
+// The superblock lives in block 0 of a well-known file inside the database
+// directory. It records the parameters the database was created with so that
+// reopening it with a different block size fails fast instead of silently
+// misreading every page. The fields are stored at fixed offsets from the start
+// of the block, which means they can be read back without knowing the block size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Superblock {
+    version: i32,
+    block_size: usize,
+    created_at: i64,
+}
+
+impl Superblock {
+    pub const FILENAME: &'static str = "simpledb.sb";
+
+    const MAGIC: i32 = 0x5344_4221; // "SDB!"
+    // Version 2 added the LSN header to data and log pages. Version 3 stores
+    // the length of strings and log records in the whole 4-byte prefix, rather
+    // than in its first byte. Version 4 records in the index catalog whether
+    // each index is published, and version 5 the predicate of each partial
+    // index. Version 6 keeps a null bitmap in every record slot, the catalog
+    // tables' included. Version 7 adds the fill factor of each table to tblcat.
+    // Version 8 adds the schemacat catalog table. Version 9 adds the
+    // constraintcat catalog table. Version 10 records the record format of each
+    // table in tblcat. Version 11 adds the fill factor of each index to idxcat.
+    // Version 12 records the kind of each index, hash or text, in idxcat.
+    const VERSION: i32 = 12;
+
+    const MAGIC_POS: usize = 0;
+    const VERSION_POS: usize = 4;
+    const BLOCK_SIZE_POS: usize = 8;
+    const CREATED_AT_POS: usize = 12;
+    const HEADER_SIZE: usize = 20;
+
+    pub fn new(block_size: usize) -> Superblock {
+        let created_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64);
+        Superblock { version: Self::VERSION, block_size, created_at }
+    }
+
+    // Reads the superblock of the database in the specified directory, writing
+    // a fresh one if the database is new. An existing superblock is validated
+    // against the expected block size. A directory that already holds files
+    // but no superblock was written before there were superblocks, and its
+    // pages cannot be read by this version, so it is rejected rather than
+    // stamped as current.
+    pub fn open(db_dir: &Path, block_size: usize, is_new: bool) -> Result<Superblock, DbError> {
+        let path = db_dir.join(Self::FILENAME);
+        if !fs::exists(&path).unwrap_or(false) {
+            if !is_new && fs::read_dir(db_dir)?.next().is_some() {
+                return Err(DbError::Corruption("database files have no superblock; they were written by an unsupported older version".to_string()));
+            }
+            let superblock = Superblock::new(block_size);
+            fs::write(&path, superblock.to_page().bytebuffer)?;
+            return Ok(superblock);
+        }
+        let bytes = fs::read(&path)?;
+        let superblock = Superblock::from_bytes(bytes)?;
+        superblock.validate(block_size)?;
+        Ok(superblock)
+    }
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<Superblock, DbError> {
+        if bytes.len() < Self::HEADER_SIZE {
+            return Err(DbError::Corruption("superblock is truncated".to_string()));
+        }
+        let page = Page::wrap(bytes);
+        if page.get_int(Self::MAGIC_POS) != Self::MAGIC {
+            return Err(DbError::Corruption("superblock has an invalid magic number".to_string()));
+        }
+        Ok(Superblock {
+            version: page.get_int(Self::VERSION_POS),
+            block_size: page.get_int(Self::BLOCK_SIZE_POS) as usize,
+            created_at: page.get_long(Self::CREATED_AT_POS),
+        })
+    }
+
+    fn to_page(&self) -> Page {
+        let mut page = Page::new(self.block_size.max(Self::HEADER_SIZE));
+        page.set_int(Self::MAGIC_POS, Self::MAGIC);
+        page.set_int(Self::VERSION_POS, self.version);
+        page.set_int(Self::BLOCK_SIZE_POS, self.block_size as i32);
+        page.set_long(Self::CREATED_AT_POS, self.created_at);
+        page
+    }
+
+    fn validate(&self, block_size: usize) -> Result<(), DbError> {
+        if self.version != Self::VERSION {
+            return Err(DbError::Corruption(format!("unsupported format version {} (expected {})", self.version, Self::VERSION)));
+        }
+        if self.block_size != block_size {
+            return Err(DbError::Corruption(format!("database was created with block size {} but opened with {}", self.block_size, block_size)));
+        }
+        Ok(())
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub fn created_at(&self) -> i64 {
+        self.created_at
+    }
+}
+
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_superblock() {
+        let dir = PathBuf::from("superblocktestdb");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+
+        let created = Superblock::open(&dir, 400, true).unwrap();
+        let reopened = Superblock::open(&dir, 400, false).unwrap();
+        assert_eq!(created, reopened);
+        assert_eq!(reopened.block_size(), 400);
+        assert_eq!(reopened.version(), 12);
+
+        assert!(Superblock::open(&dir, 4096, false).is_err());
+    }
+
+    #[test]
+    fn test_previous_version() {
+        // A database written by the previous version has pages this one
+        // cannot read, so it is rejected.
+        let dir = PathBuf::from("oldversiontestdb");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        let old = Superblock { version: 11, block_size: 400, created_at: 0 };
+        fs::write(dir.join(Superblock::FILENAME), old.to_page().bytebuffer).unwrap();
+        let error = Superblock::open(&dir, 400, false).unwrap_err();
+        assert_eq!(error, DbError::Corruption(format!("unsupported format version {} (expected {})", old.version, Superblock::VERSION)));
+    }
+
+    #[test]
+    fn test_no_superblock() {
+        // A database from before superblocks has files but no superblock.
+        let dir = PathBuf::from("nosuperblocktestdb");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("tblcat.tbl"), [0; 400]).unwrap();
+        assert!(matches!(Superblock::open(&dir, 400, false), Err(DbError::Corruption(_))));
+        assert!(!fs::exists(dir.join(Superblock::FILENAME)).unwrap());
+
+        // An empty directory gets a fresh one.
+        fs::remove_file(dir.join("tblcat.tbl")).unwrap();
+        assert_eq!(Superblock::open(&dir, 400, false).unwrap().version(), 12);
+    }
+}
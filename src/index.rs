@@ -0,0 +1,3 @@
+pub mod index;
+pub mod hashindex;
+pub mod textindex;
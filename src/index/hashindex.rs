@@ -0,0 +1,182 @@
+use std::sync::{Arc, Mutex};
+use crate::index::index::Index;
+use crate::query::constant::Constant;
+use crate::query::scan::Scan;
+use crate::record::layout::Layout;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::FieldType;
+use crate::record::tablescan::{RecordId, TableScan, UpdateScan};
+use crate::tx::transaction::Transaction;
+
+// A static hash index. The index records are spread over a fixed number of
+// buckets, each stored as a table named after the index and the bucket
+// number; a search only scans the bucket of the search key. The number of
+// buckets never grows, so a bucket gets longer as the table does.
+pub struct HashIndex {
+    tx: Arc<Mutex<Transaction>>,
+    index: String,
+    layout: Arc<Layout>,
+    search_key: Option<Constant>,
+    ts: Option<TableScan>,
+}
+
+impl HashIndex {
+    pub const NUM_BUCKETS: usize = 100;
+
+    pub fn new(tx: Arc<Mutex<Transaction>>, index: &str, layout: Arc<Layout>) -> HashIndex {
+        HashIndex { tx, index: index.to_string(), layout, search_key: None, ts: None }
+    }
+
+    // The bucket of the value. The hash ends up on disk, so it must not
+    // change between runs: integers are used as is, and strings and blobs
+    // are hashed with 32-bit FNV-1a. Longs, dates and doubles fold their two halves
+    // together. Nulls are not indexed, but a search for one still needs a
+    // bucket to come up empty in.
+    fn bucket(val: &Constant) -> usize {
+        let fold = |bits: u64| (bits ^ (bits >> 32)) as u32;
+        let fnv = |bytes: &[u8]| bytes.iter().fold(0x811c9dc5u32, |hash, byte| (hash ^ *byte as u32).wrapping_mul(0x01000193));
+        let hash = match val {
+            Constant::Int(val) => *val as u32,
+            Constant::Long(val) | Constant::Date(val) => fold(*val as u64),
+            Constant::Double(val) => fold(val.to_bits()),
+            Constant::Bool(val) => *val as u32,
+            Constant::String(val) => fnv(val.as_bytes()),
+            Constant::Blob(val) => fnv(val),
+            Constant::Null => 0,
+        };
+        hash as usize % Self::NUM_BUCKETS
+    }
+
+    fn current(&mut self) -> Result<&mut TableScan, ScanError> {
+        self.ts.as_mut().ok_or(ScanError::NoCurrentRecord)
+    }
+}
+
+impl Index for HashIndex {
+    // The key is converted to the type of the indexed field first, so that
+    // an INTEGER constant finds the same BIGINT or DOUBLE value.
+    fn before_first(&mut self, search_key: &Constant) -> Result<(), ScanError> {
+        let ftype = FieldType::try_from(self.layout.schema().ftype("dataval")).unwrap();
+        let search_key = &search_key.coerce(ftype).unwrap_or_else(|| search_key.clone());
+        self.close();
+        let table = format!("{}{}", self.index, Self::bucket(search_key));
+        self.ts = Some(TableScan::new(self.tx.clone(), self.layout.clone(), &table)?);
+        self.search_key = Some(search_key.clone());
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<bool, ScanError> {
+        let search_key = self.search_key.clone().ok_or(ScanError::NoCurrentRecord)?;
+        let ts = self.current()?;
+        while ts.next()? {
+            if ts.get_val("dataval")? == search_key {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn data_rid(&mut self) -> Result<RecordId, ScanError> {
+        let ts = self.current()?;
+        Ok(RecordId { blocknum: ts.get_int("block")?, slot: ts.get_int("id")? })
+    }
+
+    fn insert(&mut self, val: &Constant, rid: &RecordId) -> Result<(), ScanError> {
+        self.before_first(val)?;
+        let ts = self.current()?;
+        ts.insert()?;
+        ts.set_int("block", rid.blocknum)?;
+        ts.set_int("id", rid.slot)?;
+        ts.set_val("dataval", val)
+    }
+
+    fn delete(&mut self, val: &Constant, rid: &RecordId) -> Result<(), ScanError> {
+        self.before_first(val)?;
+        while self.next()? {
+            if &self.data_rid()? == rid {
+                return self.current()?.delete();
+            }
+        }
+        Ok(())
+    }
+
+    // Buckets without a file are skipped, as opening a scan on one would
+    // allocate its first block.
+    fn clear(&mut self) -> Result<(), ScanError> {
+        self.close();
+        self.search_key = None;
+        for bucket in 0..Self::NUM_BUCKETS {
+            let table = format!("{}{}", self.index, bucket);
+            if self.tx.lock().unwrap().size(&format!("{}.tbl", table))? == 0 {
+                continue;
+            }
+            let mut ts = TableScan::new(self.tx.clone(), self.layout.clone(), &table)?;
+            while ts.next()? {
+                ts.delete()?;
+            }
+            ts.close();
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        if let Some(mut ts) = self.ts.take() {
+            ts.close();
+        }
+    }
+}
+
+mod tests {
+    use std::fs;
+    use super::*;
+    use crate::record::schema::Schema;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_hash_index() {
+        let _ = fs::remove_dir_all("hashindextestdb");
+        let db = SimpleDB::new("hashindextestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mut schema = Schema::new();
+        schema.add_int_field("block");
+        schema.add_int_field("id");
+        schema.add_string_field("dataval", 10);
+        let mut idx = HashIndex::new(tx.clone(), "testidx", Arc::new(Layout::new(schema)));
+
+        let key = |i: i32| Constant::String(format!("key{}", i % 3));
+        for i in 0..9 {
+            idx.insert(&key(i), &RecordId { blocknum: i / 4, slot: i % 4 }).unwrap();
+        }
+        idx.delete(&key(4), &RecordId { blocknum: 1, slot: 0 }).unwrap();
+
+        idx.before_first(&key(1)).unwrap();
+        let mut rids = Vec::new();
+        while idx.next().unwrap() {
+            let rid = idx.data_rid().unwrap();
+            rids.push((rid.blocknum, rid.slot));
+        }
+        rids.sort();
+        assert_eq!(rids, [(0, 1), (1, 3)]);
+
+        // The pair must match: the RecordId of a record with another value
+        // deletes nothing.
+        idx.delete(&key(1), &RecordId { blocknum: 0, slot: 0 }).unwrap();
+        for (i, count) in [(0, 3), (1, 2)] {
+            idx.before_first(&key(i)).unwrap();
+            let mut n = 0;
+            while idx.next().unwrap() {
+                n += 1;
+            }
+            assert_eq!(n, count);
+        }
+
+        idx.before_first(&Constant::String("nokey".to_string())).unwrap();
+        assert!(!idx.next().unwrap());
+
+        idx.clear().unwrap();
+        idx.before_first(&key(0)).unwrap();
+        assert!(!idx.next().unwrap());
+        idx.close();
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
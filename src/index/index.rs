@@ -0,0 +1,81 @@
+use std::collections::BTreeSet;
+use crate::query::constant::Constant;
+use crate::record::scanerror::ScanError;
+use crate::record::tablescan::RecordId;
+
+// What an index holds a record under. A hash index keys the record by the
+// value of its field. A text index, on a VARCHAR field, keys it by each word
+// of the value, so that it finds the records whose text contains a word:
+// a posting list of RecordIds per word, kept in the buckets of a hash
+// index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexKind {
+    Hash,
+    Text,
+}
+
+impl IndexKind {
+    // The name the catalog records the kind under.
+    pub fn name(&self) -> &'static str {
+        match self {
+            IndexKind::Hash => "hash",
+            IndexKind::Text => "text",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<IndexKind> {
+        match name {
+            "hash" => Some(IndexKind::Hash),
+            "text" => Some(IndexKind::Text),
+            _ => None,
+        }
+    }
+
+    // The keys the index holds a record with the value under.
+    pub fn keys(&self, val: &Constant) -> Vec<Constant> {
+        match (self, val) {
+            (IndexKind::Text, Constant::String(text)) => Self::words(text).into_iter().map(Constant::String).collect(),
+            (IndexKind::Text, _) => Vec::new(),
+            (IndexKind::Hash, _) => vec![val.clone()],
+        }
+    }
+
+    // The words of a text: its runs of letters and digits, in lower case,
+    // each once.
+    pub fn words(text: &str) -> BTreeSet<String> {
+        text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()).map(str::to_lowercase).collect()
+    }
+}
+
+// An Index maps the values of a field to the records that hold them. Each
+// index record pairs a data value with the RecordId of a record of the
+// indexed table; `before_first` positions the index on a search key, and
+// `next` moves through the index records with that key.
+//
+// Indexes are not unique. Records with the same value each have an index
+// record of their own, so a key has as many index records as there are
+// records that hold it, in no particular order. The RecordId tells them
+// apart: `delete` removes the index record of the given value and RecordId
+// only, and leaves those of the other records with that value. Since a
+// record has one value per field, it has at most one index record in each
+// index, and callers never insert the same pair twice.
+pub trait Index {
+    fn before_first(&mut self, search_key: &Constant) -> Result<(), ScanError>;
+
+    // Moves to the next index record with the search key, returning false
+    // when there are no more.
+    fn next(&mut self) -> Result<bool, ScanError>;
+
+    // The RecordId stored in the current index record.
+    fn data_rid(&mut self) -> Result<RecordId, ScanError>;
+
+    fn insert(&mut self, val: &Constant, rid: &RecordId) -> Result<(), ScanError>;
+
+    // Deleting a pair that is not in the index does nothing.
+    fn delete(&mut self, val: &Constant, rid: &RecordId) -> Result<(), ScanError>;
+
+    // Deletes every index record.
+    fn clear(&mut self) -> Result<(), ScanError>;
+
+    fn close(&mut self);
+}
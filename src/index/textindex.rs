@@ -0,0 +1,155 @@
+use std::sync::{Arc, Mutex};
+use crate::index::hashindex::HashIndex;
+use crate::index::index::{Index, IndexKind};
+use crate::query::constant::Constant;
+use crate::record::layout::Layout;
+use crate::record::scanerror::ScanError;
+use crate::record::tablescan::RecordId;
+use crate::tx::transaction::Transaction;
+
+// The TextIndex is an inverted index over a VARCHAR field: it keeps an
+// index record for each word of a value, in the buckets of a hash index,
+// so that searching it for a word finds every record whose value contains
+// the word. See `IndexKind::words` for what a word is.
+pub struct TextIndex {
+    idx: HashIndex,
+}
+
+impl TextIndex {
+    pub fn new(tx: Arc<Mutex<Transaction>>, index: &str, layout: Arc<Layout>) -> TextIndex {
+        TextIndex { idx: HashIndex::new(tx, index, layout) }
+    }
+}
+
+impl Index for TextIndex {
+    // The search key is a single word, in lower case.
+    fn before_first(&mut self, search_key: &Constant) -> Result<(), ScanError> {
+        self.idx.before_first(search_key)
+    }
+
+    fn next(&mut self) -> Result<bool, ScanError> {
+        self.idx.next()
+    }
+
+    fn data_rid(&mut self) -> Result<RecordId, ScanError> {
+        self.idx.data_rid()
+    }
+
+    fn insert(&mut self, val: &Constant, rid: &RecordId) -> Result<(), ScanError> {
+        for word in IndexKind::Text.keys(val) {
+            self.idx.insert(&word, rid)?;
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, val: &Constant, rid: &RecordId) -> Result<(), ScanError> {
+        for word in IndexKind::Text.keys(val) {
+            self.idx.delete(&word, rid)?;
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), ScanError> {
+        self.idx.clear()
+    }
+
+    fn close(&mut self) {
+        self.idx.close();
+    }
+}
+
+mod tests {
+    use std::fs;
+    use super::*;
+    use crate::metadata::indexinfo::IndexInfo;
+    use crate::opt::heuristicqueryplanner::HeuristicQueryPlanner;
+    use crate::parse::parser::Parser;
+    use crate::plan::basicqueryplanner::BasicQueryPlanner;
+    use crate::plan::basicupdateplanner::BasicUpdatePlanner;
+    use crate::plan::plan::Plan;
+    use crate::record::schema::Schema;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_text_index() {
+        assert_eq!(IndexKind::words("Disk full; retry  in 5s (disk-2)"), ["2", "5s", "disk", "full", "in", "retry"].map(String::from).into());
+
+        let _ = fs::remove_dir_all("textindextestdb");
+        let db = SimpleDB::new("textindextestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mut schema = Schema::new();
+        schema.add_string_field("msg", 40);
+        let mut idx = TextIndex::new(tx.clone(), "msgidx", Arc::new(IndexInfo::index_layout("msg", &schema)));
+        let rid = |slot| RecordId { blocknum: 0, slot };
+        idx.insert(&Constant::String("Disk full on disk 2".to_string()), &rid(1)).unwrap();
+        idx.insert(&Constant::String("retry later".to_string()), &rid(2)).unwrap();
+        idx.insert(&Constant::String("disk ok".to_string()), &rid(3)).unwrap();
+        idx.insert(&Constant::Null, &rid(4)).unwrap();
+        let search = |idx: &mut TextIndex, word: &str| {
+            idx.before_first(&Constant::String(word.to_string())).unwrap();
+            let mut rids = Vec::new();
+            while idx.next().unwrap() {
+                rids.push(idx.data_rid().unwrap().slot);
+            }
+            rids.sort();
+            rids
+        };
+        // A word repeated in a value is only indexed once.
+        assert_eq!(search(&mut idx, "disk"), [1, 3]);
+        assert_eq!(search(&mut idx, "later"), [2]);
+        assert!(search(&mut idx, "Disk").is_empty());
+
+        idx.delete(&Constant::String("Disk full on disk 2".to_string()), &rid(1)).unwrap();
+        assert_eq!(search(&mut idx, "disk"), [3]);
+        assert!(search(&mut idx, "full").is_empty());
+        idx.close();
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_contains() {
+        let _ = fs::remove_dir_all("containstestdb");
+        let db = SimpleDB::new("containstestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let updater = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| updater.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone());
+        update("create table logs (id int, msg varchar(30))").unwrap();
+        for i in 0..500 {
+            let msg = if i % 100 == 7 { format!("Disk {} is full", i) } else { format!("request {} served", i) };
+            update(&format!("insert into logs (id, msg) values ({}, '{}')", i, msg)).unwrap();
+        }
+        update("create text index msg_idx on logs (msg)").unwrap();
+        assert!(matches!(update("create text index id_idx on logs (id)"), Err(ScanError::TypeMismatch { .. })));
+
+        let run = |plan: Box<dyn Plan>| {
+            let mut scan = plan.open().unwrap();
+            let mut ids = Vec::new();
+            while scan.next().unwrap() {
+                ids.push(scan.get_int("id").unwrap());
+            }
+            scan.close();
+            ids.sort();
+            ids
+        };
+        let basic = BasicQueryPlanner::new(mdm.clone());
+        let heuristic = HeuristicQueryPlanner::new(mdm.clone());
+        let data = Parser::new("select id from logs where contains(msg, 'DISK')").unwrap().query().unwrap();
+        for plan in [basic.create_plan(&data, tx.clone()).unwrap(), heuristic.create_plan(&data, tx.clone()).unwrap()] {
+            assert!(plan.explain().contains("IndexSelect contains(msg, 'disk') on logs with msg_idx"), "{}", plan.explain());
+            assert_eq!(run(plan), [7, 107, 207, 307, 407]);
+        }
+
+        // The index is kept up to date, and a word it does not hold finds
+        // nothing.
+        update("update logs set msg = 'disk replaced' where id = 207").unwrap();
+        update("delete from logs where id = 307").unwrap();
+        update("insert into logs (id, msg) values (500, 'full disk')").unwrap();
+        assert_eq!(run(basic.create_plan(&data, tx.clone()).unwrap()), [7, 107, 207, 407, 500]);
+        let data = Parser::new("select id from logs where contains(msg, 'full')").unwrap().query().unwrap();
+        assert_eq!(run(basic.create_plan(&data, tx.clone()).unwrap()), [7, 107, 407, 500]);
+        let data = Parser::new("select id from logs where contains(msg, 'absent')").unwrap().query().unwrap();
+        assert!(run(basic.create_plan(&data, tx.clone()).unwrap()).is_empty());
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error::dberror::DbError;
 use crate::file::blockid::BlockId;
 use crate::file::filemgr::FileMgr;
 use crate::file::page::Page;
@@ -12,23 +13,26 @@ pub struct LogIterator {
 }
 
 impl LogIterator {
-    pub fn new(fm: Arc<FileMgr>, block: &BlockId) -> LogIterator {
+    pub fn new(fm: Arc<FileMgr>, block: &BlockId) -> Result<LogIterator, DbError> {
         let buffer = vec![0; fm.block_size()];
         let page = Page::wrap(buffer);
-        let mut iterator = LogIterator { fm, block: block.clone(), page, currentpos: 0, boundary: 0 };
-        iterator.move_to_block(block);
-        iterator
+        let mut iterator = LogIterator { fm, block: *block, page, currentpos: 0, boundary: 0 };
+        iterator.move_to_block(block)?;
+        Ok(iterator)
     }
 
-    fn move_to_block(&mut self, block: &BlockId) {
-        self.fm.read(block, &mut self.page);
+    fn move_to_block(&mut self, block: &BlockId) -> Result<(), DbError> {
+        self.fm.read(block, &mut self.page)?;
         self.boundary = self.page.get_int(0);
         self.currentpos = self.boundary;
+        Ok(())
     }
 }
 
+// Moving to the previous block reads it from disk, so each record comes
+// back as a result; after an error the iterator should not be used again.
 impl Iterator for LogIterator {
-    type Item = Vec<u8>;
+    type Item = Result<Vec<u8>, DbError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.currentpos >= self.fm.block_size() as i32 && self.block.number() <= 0 {
@@ -36,12 +40,15 @@ impl Iterator for LogIterator {
         }
 
         if self.currentpos == self.fm.block_size() as i32 {
-            self.block = BlockId::new(self.block.filename(), self.block.number() - 1);
-            self.move_to_block(&self.block.clone());
+            self.block = BlockId::new(self.block.file, self.block.number() - 1);
+            let block = self.block;
+            if let Err(error) = self.move_to_block(&block) {
+                return Some(Err(error));
+            }
         }
 
         let record = self.page.get_bytes(self.currentpos as usize);
         self.currentpos += 4 + record.len() as i32;
-        Some(record.to_vec())
+        Some(Ok(record.to_vec()))
     }
 }
\ No newline at end of file
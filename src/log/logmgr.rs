@@ -1,4 +1,5 @@
 use std::sync::Arc;
+use crate::error::dberror::DbError;
 use crate::file::blockid::BlockId;
 use crate::file::page::Page;
 use crate::file::filemgr::FileMgr;
@@ -7,7 +8,15 @@ use crate::log::logiterator::LogIterator;
 // The log manager is responsible for writing log records
 // to the log file. The tail of the log file is kept in a
 // bytebuffer in memory, which is flushed to disk when it
-// becomes full.
+// becomes full. Each log block starts with the boundary
+// of its records followed by the LSN of the last record
+// written to it, so that LSNs keep increasing across restarts
+// and can be compared with the LSNs stored in data pages.
+//
+// A write of the log that fails, say because the disk is full, leaves the
+// tail in memory as it was: the records that were not written are written
+// by the next flush, which rewrites the whole block, so a block torn by
+// the failed write is repaired as well.
 pub struct LogMgr {
     fm: Arc<FileMgr>,
     file: String,
@@ -18,41 +27,49 @@ pub struct LogMgr {
 }
 
 impl LogMgr {
+    const BOUNDARY_POS: usize = 0;
+    const LSN_POS: usize = 4;
+    const HEADER_SIZE: i32 = 8;
 
     // Creates a new log manager for the specified log file.
     // If the log file does not exist, it is created with an
     // empty first block.
-    pub fn new(fm: Arc<FileMgr>, file: &str) -> LogMgr {
+    pub fn new(fm: Arc<FileMgr>, file: &str) -> Result<LogMgr, DbError> {
         let buffer = vec![0; fm.block_size()];
         let mut page = Page::wrap(buffer);
-        let logsize = fm.length(file);
+        let logsize = fm.length(file)?;
         let current_block = if logsize == 0 {
-            let block = fm.append(file);
-            page.set_int(0, fm.block_size() as i32);
-            fm.write(&block, &page);
+            let block = fm.append(file)?;
+            page.set_int(Self::BOUNDARY_POS, fm.block_size() as i32);
+            page.set_int(Self::LSN_POS, 0);
+            fm.write(&block, &page)?;
             block
         } else {
-            let block = BlockId::new(file, logsize as usize - 1);
-            fm.read(&block, &mut page);
+            let block = fm.block(file, logsize as usize - 1);
+            fm.read(&block, &mut page)?;
             block
         };
-        LogMgr {
+        let lsn = page.get_int(Self::LSN_POS);
+        fm.set_log(file, lsn);
+        Ok(LogMgr {
             fm,
             file: file.to_string(),
             page,
             current_block,
-            latest_lsn: 0,
-            last_saved_lsn: 0
-        }
+            latest_lsn: lsn,
+            last_saved_lsn: lsn,
+        })
     }
 
     // Flushes the log record with the specified LSN to disk.
     // All log records with LSN less than the specified value
     // are also written to disk.
-    pub(crate) fn flush_record(&mut self, lsn: i32) {
+    pub(crate) fn flush_record(&mut self, lsn: i32) -> Result<(), DbError> {
+        debug_assert!(lsn <= self.latest_lsn, "flushing log record {} past the end of the log ({})", lsn, self.latest_lsn);
         if lsn >= self.last_saved_lsn {
-            self.flush()
+            self.flush()?;
         }
+        Ok(())
     }
 
     // Appends a new log record to the log file and returns
@@ -62,44 +79,70 @@ impl LogMgr {
     // the "boundary" enabling the iterator to read the records
     // in reverse order starting from the position where the last
     // record was written.
-    pub(crate) fn append(&mut self, record: &Vec<u8>) -> i32 {
-        let mut boundary = self.page.get_int(0);
+    pub(crate) fn append(&mut self, record: &Vec<u8>) -> Result<i32, DbError> {
+        let mut boundary = self.page.get_int(Self::BOUNDARY_POS);
         let record_size = record.len() as i32;
         let bytes_needed = record_size + 4;
-        if boundary - bytes_needed < 4 {
+        assert!(bytes_needed <= self.fm.block_size() as i32 - Self::HEADER_SIZE, "log record of {} bytes does not fit in a block", record_size);
+        if boundary - bytes_needed < Self::HEADER_SIZE {
             // If the log record doesn't fit in the current block
             // we need to flush the current block and move to a
             // new block.
-            self.flush();
-            self.current_block = self.append_new_block();
-            boundary = self.page.get_int(0);
+            self.flush()?;
+            self.current_block = self.append_new_block()?;
+            boundary = self.page.get_int(Self::BOUNDARY_POS);
         }
         let record_position = boundary - bytes_needed;
         self.page.set_bytes(record_position as usize, &record);
-        self.page.set_int(0, record_position);
+        self.page.set_int(Self::BOUNDARY_POS, record_position);
         self.latest_lsn += 1;
-        self.latest_lsn
+        self.page.set_int(Self::LSN_POS, self.latest_lsn);
+        Ok(self.latest_lsn)
     }
 
     // Returns an iterator that reads log records from the log
     // file in reverse order starting from the most recent record.
     // It flushes the current block before returning the iterator
     // to ensure the entire log file is on disk.
-    pub(crate) fn iterator(&mut self) -> LogIterator {
-        self.flush();
+    pub(crate) fn iterator(&mut self) -> Result<LogIterator, DbError> {
+        self.flush()?;
         LogIterator::new(self.fm.clone(), &self.current_block)
     }
 
-    fn append_new_block(&mut self) -> BlockId {
-        let block = self.fm.append(&self.file);
-        self.page.set_int(0, self.fm.block_size() as i32);
-        self.fm.write(&block, &self.page);
-        block
+    // Returns true if no record was ever appended to the log.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.latest_lsn == 0
+    }
+
+    // The LSN of the last record written to a block of the log, as read
+    // from its header.
+    pub(crate) fn block_lsn(page: &Page) -> i32 {
+        page.get_int(Self::LSN_POS)
     }
 
-    fn flush(&mut self) {
-        self.fm.write(&self.current_block, &self.page);
+    // Returns the LSN of the most recent log record known to be on disk.
+    pub(crate) fn last_saved_lsn(&self) -> i32 {
+        self.last_saved_lsn
+    }
+
+    // The new block is written in place of the one after the current block
+    // rather than appended, so that retrying after a failed write does not
+    // leave a hole in the log.
+    fn append_new_block(&mut self) -> Result<BlockId, DbError> {
+        let block = BlockId::new(self.current_block.file, self.current_block.number() + 1);
+        let mut page = Page::new(self.fm.block_size());
+        page.set_int(Self::BOUNDARY_POS, self.fm.block_size() as i32);
+        page.set_int(Self::LSN_POS, self.latest_lsn);
+        self.fm.write(&block, &page)?;
+        self.page = page;
+        Ok(block)
+    }
+
+    fn flush(&mut self) -> Result<(), DbError> {
+        self.fm.write(&self.current_block, &self.page)?;
         self.last_saved_lsn = self.latest_lsn;
+        self.fm.set_saved_lsn(self.last_saved_lsn);
+        Ok(())
     }
 }
 
@@ -111,21 +154,83 @@ mod tests {
     #[test]
     fn test_log_mgr() {
         let block_size = 400;
-        let fm = Arc::new(FileMgr::new(PathBuf::from("testdb"), block_size));
-        let mut lm = LogMgr::new(fm, "testlog.log");
+        let fm = Arc::new(FileMgr::new(PathBuf::from("testdb"), block_size).unwrap());
+        let mut lm = LogMgr::new(fm, "testlog.log").unwrap();
         print_log_records(&mut lm, "The inital empty log file:");
         create_log_records(&mut lm, 1, 35);
         print_log_records(&mut lm, "The log file now has these records:");
         create_log_records(&mut lm, 36, 70);
-        lm.flush_record(65);
+        lm.flush_record(65).unwrap();
         print_log_records(&mut lm, "The log file now has these records:");
     }
 
+    #[test]
+    fn test_lsn_survives_restart() {
+        let fm = Arc::new(FileMgr::new(PathBuf::from("logmgrtestdb"), 400).unwrap());
+        let mut lm = LogMgr::new(fm.clone(), "testlog.log").unwrap();
+        create_log_records(&mut lm, 1, 35);
+        let lsn = lm.append(&create_log_record("last", 0)).unwrap();
+        lm.flush_record(lsn).unwrap();
+
+        let mut reopened = LogMgr::new(fm, "testlog.log").unwrap();
+        assert_eq!(reopened.last_saved_lsn(), lsn);
+        assert_eq!(reopened.append(&create_log_record("next", 0)).unwrap(), lsn + 1);
+    }
+
+    #[test]
+    fn test_log_record_fills_big_block() {
+        let dir = PathBuf::from("biglogtestdb");
+        let _ = std::fs::remove_dir_all(&dir);
+        let block_size = 16384;
+        let fm = Arc::new(FileMgr::new(dir, block_size).unwrap());
+        let mut lm = LogMgr::new(fm, "testlog.log").unwrap();
+        let record: Vec<u8> = (0..block_size - LogMgr::HEADER_SIZE as usize - 4).map(|i| i as u8).collect();
+        lm.append(&create_log_record("small", 1)).unwrap();
+        lm.append(&record).unwrap();
+        let mut iter = lm.iterator().unwrap();
+        assert_eq!(iter.next().unwrap().unwrap(), record);
+        assert_eq!(Page::wrap(iter.next().unwrap().unwrap()).get_string(0), "small");
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_disk_full() {
+        let _ = std::fs::remove_dir_all("logdiskfulltestdb");
+        let fm = Arc::new(FileMgr::new(PathBuf::from("logdiskfulltestdb"), 400).unwrap());
+        let mut lm = LogMgr::new(fm.clone(), "testlog.log").unwrap();
+        create_log_records(&mut lm, 1, 10);
+        fm.simulate_disk_space(Some(100));
+        assert_eq!(lm.flush_record(10), Err(DbError::DiskFull));
+        assert_eq!(lm.last_saved_lsn(), 0);
+        // Records keep going to the tail until it needs a new block.
+        create_log_records(&mut lm, 11, 20);
+        let mut lsn = 20;
+        let record = create_log_record("more", 0);
+        while lm.append(&record).is_ok() {
+            lsn += 1;
+        }
+        assert_eq!(lm.append(&record), Err(DbError::DiskFull));
+
+        // With space again, the torn block is written over and nothing is lost.
+        fm.simulate_disk_space(None);
+        lm.flush_record(lsn).unwrap();
+        assert_eq!(lm.append(&record), Ok(lsn + 1));
+        lm.flush_record(lsn + 1).unwrap();
+        let mut reopened = LogMgr::new(fm, "testlog.log").unwrap();
+        let mut iter = reopened.iterator().unwrap();
+        let mut count = 0;
+        while let Some(record) = iter.next() {
+            record.unwrap();
+            count += 1;
+        }
+        assert_eq!(count, lsn + 1);
+    }
+
     fn print_log_records(lm: &mut LogMgr, message: &str) {
         println!("{}", message);
-        let mut iter = lm.iterator();
+        let mut iter = lm.iterator().unwrap();
         while let Some(record) = iter.next() {
-            let page = Page::wrap(record);
+            let page = Page::wrap(record.unwrap());
             let string = page.get_string(0);
             let number = page.get_int(Page::max_length(string.len()));
             println!("[{}, {}]", string, number);
@@ -136,7 +241,7 @@ mod tests {
         println!("Appending log records from {} to {}", start, end);
         for i in start..end+1 {
             let record = create_log_record(&format!("record{}", i), 100 + i);
-            let lsn = lm.append(&record);
+            let lsn = lm.append(&record).unwrap();
             print!("{} ", lsn);
         }
         println!();
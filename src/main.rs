@@ -1,9 +1,17 @@
+mod error;
 mod log;
 mod buffer;
 mod file;
 mod tx;
 mod record;
+mod index;
 mod metadata;
+mod query;
+mod parse;
+mod plan;
+mod materialize;
+mod opt;
+mod server;
 
 fn main() {
     println!("Hello, world!");
@@ -0,0 +1,7 @@
+pub mod temptable;
+pub mod materializeplan;
+pub mod recordcomparator;
+pub mod sortscan;
+pub mod sortplan;
+pub mod mergejoinscan;
+pub mod mergejoinplan;
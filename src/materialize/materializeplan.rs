@@ -0,0 +1,126 @@
+use std::sync::{Arc, Mutex};
+use crate::file::page::Page;
+use crate::materialize::temptable::TempTable;
+use crate::plan::plan::Plan;
+use crate::query::scan::Scan;
+use crate::record::layout::Layout;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::Schema;
+use crate::record::tablescan::{TableScan, UpdateScan};
+use crate::tx::transaction::Transaction;
+
+// The MaterializePlan copies the output of the underlying plan into a
+// temporary table when it is opened, and reads the table from then on. It
+// pays off when the output is read several times, as it is only computed
+// once.
+pub struct MaterializePlan {
+    tx: Arc<Mutex<Transaction>>,
+    plan: Box<dyn Plan>,
+}
+
+impl MaterializePlan {
+    pub fn new(tx: Arc<Mutex<Transaction>>, plan: Box<dyn Plan>) -> MaterializePlan {
+        MaterializePlan { tx, plan }
+    }
+
+    // The number of blocks of a temporary table that holds the records of a
+    // plan, for plans that write one.
+    pub(crate) fn blocks(tx: &Arc<Mutex<Transaction>>, plan: &dyn Plan) -> usize {
+        plan.records_output().div_ceil(Self::records_per_block(tx, plan.schema()))
+    }
+
+    // How many records with the schema a block of a temporary table holds.
+    pub(crate) fn records_per_block(tx: &Arc<Mutex<Transaction>>, schema: &Schema) -> usize {
+        let slot_size = Layout::new(schema.clone()).slot_size() as usize;
+        (tx.lock().unwrap().block_size() - Page::HEADER_SIZE) / slot_size
+    }
+
+    // Copies the current record of the scan into a new record of the table.
+    pub(crate) fn copy_record(schema: &Schema, src: &mut dyn Scan, dest: &mut TableScan) -> Result<(), ScanError> {
+        dest.insert()?;
+        for field in schema.fields() {
+            dest.set_val(field, &src.get_val(field)?)?;
+        }
+        Ok(())
+    }
+}
+
+impl Plan for MaterializePlan {
+    fn open(&self) -> Result<Box<dyn Scan>, ScanError> {
+        let temp = TempTable::new(self.tx.clone(), self.plan.schema().clone());
+        let mut src = self.plan.open()?;
+        let mut dest = temp.open()?;
+        while src.next()? {
+            Self::copy_record(self.plan.schema(), &mut src, &mut dest)?;
+        }
+        src.close();
+        dest.before_first()?;
+        Ok(Box::new(dest))
+    }
+
+    // Only reading the table is counted: writing it is a one-time cost
+    // paid when the plan is opened.
+    fn blocks_accessed(&self) -> usize {
+        Self::blocks(&self.tx, self.plan.as_ref())
+    }
+
+    fn records_output(&self) -> usize {
+        self.plan.records_output()
+    }
+
+    fn distinct_values(&self, field: &str) -> usize {
+        self.plan.distinct_values(field)
+    }
+
+    fn schema(&self) -> &Schema {
+        self.plan.schema()
+    }
+
+    fn describe(&self) -> String {
+        "Materialize".to_string()
+    }
+
+    fn inputs(&self) -> Vec<&dyn Plan> {
+        vec![self.plan.as_ref()]
+    }
+}
+
+mod tests {
+    use std::fs;
+    use super::*;
+    use crate::parse::parser::Parser;
+    use crate::plan::basicupdateplanner::BasicUpdatePlanner;
+    use crate::plan::tableplan::TablePlan;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_materialize() {
+        let _ = fs::remove_dir_all("materializetestdb");
+        let db = SimpleDB::new("materializetestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let planner = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone()).unwrap();
+        update("create table t (a int, b varchar(10))");
+        for i in 0..50 {
+            update(&format!("insert into t (a, b) values ({}, 'rec{}')", i, i));
+        }
+
+        let plan = MaterializePlan::new(tx.clone(), Box::new(TablePlan::new(tx.clone(), "t", &mdm).unwrap()));
+        assert_eq!(plan.records_output(), 50);
+        // 50 records of 26 bytes, 15 to a block.
+        assert_eq!(plan.blocks_accessed(), 4);
+        let mut scan = plan.open().unwrap();
+        let mut count = 0;
+        while scan.next().unwrap() {
+            assert_eq!(scan.get_string("b").unwrap(), format!("rec{}", scan.get_int("a").unwrap()));
+            count += 1;
+        }
+        scan.close();
+        assert_eq!(count, 50);
+
+        // The temporary table goes with the transaction.
+        tx.lock().unwrap().commit().unwrap();
+        assert!(!fs::read_dir("materializetestdb").unwrap().any(|entry| entry.unwrap().file_name().to_string_lossy().starts_with("temp")));
+    }
+}
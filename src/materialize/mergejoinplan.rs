@@ -0,0 +1,147 @@
+use std::sync::{Arc, Mutex};
+use crate::materialize::mergejoinscan::MergeJoinScan;
+use crate::materialize::sortplan::SortPlan;
+use crate::plan::plan::Plan;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::Schema;
+use crate::tx::transaction::Transaction;
+
+// The MergeJoinPlan joins two plans on a field of each by sorting both on
+// their join field and merging the sorted outputs. Unlike an index join it
+// needs no index, and unlike a product it reads each input only once,
+// apart from re-reading the records of the second that share a value.
+pub struct MergeJoinPlan {
+    tx: Arc<Mutex<Transaction>>,
+    p1: SortPlan,
+    p2: SortPlan,
+    field1: String,
+    field2: String,
+    schema: Schema,
+}
+
+impl MergeJoinPlan {
+    pub fn new(tx: Arc<Mutex<Transaction>>, p1: Box<dyn Plan>, p2: Box<dyn Plan>, field1: &str, field2: &str) -> MergeJoinPlan {
+        let mut schema = Schema::new();
+        schema.add_all(p1.schema());
+        schema.add_all(p2.schema());
+        let p1 = SortPlan::new(tx.clone(), p1, vec![field1.to_string()]);
+        let p2 = SortPlan::new(tx.clone(), p2, vec![field2.to_string()]);
+        MergeJoinPlan { tx, p1, p2, field1: field1.to_string(), field2: field2.to_string(), schema }
+    }
+}
+
+impl Plan for MergeJoinPlan {
+    // The buffers for the final merges of both sorts are reserved at once,
+    // so that two joins cannot each hold the buffers of one side while
+    // waiting for those of the other.
+    fn open(&self) -> Result<Box<dyn Scan>, ScanError> {
+        let runs1 = self.p1.sorted_runs()?;
+        let runs2 = self.p2.sorted_runs()?;
+        self.tx.lock().unwrap().reserve_buffers(runs1.len() + runs2.len())?;
+        let s1 = Box::new(self.p1.open_runs(&runs1)?);
+        Ok(Box::new(MergeJoinScan::new(s1, self.p2.open_runs(&runs2)?, &self.field1, &self.field2)?))
+    }
+
+    // Each sorted input is read once; the sorts themselves are paid for
+    // when the plan is opened, as for a SortPlan.
+    fn blocks_accessed(&self) -> usize {
+        self.p1.blocks_accessed() + self.p2.blocks_accessed()
+    }
+
+    fn rand_blocks(&self) -> usize {
+        self.p1.rand_blocks() + self.p2.rand_blocks()
+    }
+
+    // Each value of the join fields is assumed to occur in the input with
+    // fewer distinct values, matching the other input's records evenly.
+    fn records_output(&self) -> usize {
+        let distinct = self.p1.distinct_values(&self.field1).max(self.p2.distinct_values(&self.field2)).max(1);
+        self.p1.records_output() * self.p2.records_output() / distinct
+    }
+
+    fn distinct_values(&self, field: &str) -> usize {
+        if self.p1.schema().has_field(field) {
+            self.p1.distinct_values(field)
+        } else {
+            self.p2.distinct_values(field)
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn describe(&self) -> String {
+        format!("MergeJoin {}={}", self.field1, self.field2)
+    }
+
+    fn inputs(&self) -> Vec<&dyn Plan> {
+        vec![&self.p1, &self.p2]
+    }
+}
+
+mod tests {
+    use std::fs;
+    use super::*;
+    use crate::parse::parser::Parser;
+    use crate::plan::basicqueryplanner::BasicQueryPlanner;
+    use crate::plan::basicupdateplanner::BasicUpdatePlanner;
+    use crate::plan::tableplan::TablePlan;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_merge_join() {
+        let _ = fs::remove_dir_all("mergejointestdb");
+        let db = SimpleDB::new("mergejointestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let updater = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| updater.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone()).unwrap();
+        update("create table dept (did int, dname varchar(10))");
+        update("create table student (sid int, sname varchar(10), majorid int)");
+        // Two departments share an id, and one has no students.
+        for (did, dname) in [(30, "drama"), (10, "compsci"), (20, "math"), (10, "cs"), (40, "art")] {
+            update(&format!("insert into dept (did, dname) values ({}, '{}')", did, dname));
+        }
+        for i in 0..40 {
+            update(&format!("insert into student (sid, sname, majorid) values ({}, 'rec{}', {})", i, i, 10 * (i * 7 % 4)));
+        }
+
+        let table = |name: &str| Box::new(TablePlan::new(tx.clone(), name, &mdm).unwrap());
+        let plan = MergeJoinPlan::new(tx.clone(), table("dept"), table("student"), "did", "majorid");
+        assert_eq!(plan.schema().fields().len(), 5);
+        let mut scan = plan.open().unwrap();
+        let mut records = Vec::new();
+        while scan.next().unwrap() {
+            assert_eq!(scan.get_int("did").unwrap(), scan.get_int("majorid").unwrap());
+            records.push((scan.get_string("dname").unwrap(), scan.get_int("sid").unwrap()));
+        }
+        scan.close();
+
+        let mut expected = Vec::new();
+        for (did, dname) in [(30, "drama"), (10, "compsci"), (20, "math"), (10, "cs")] {
+            expected.extend((0..40).filter(|i| 10 * (i * 7 % 4) == did).map(|i| (dname.to_string(), i)));
+        }
+        records.sort();
+        expected.sort();
+        assert_eq!(records, expected);
+
+        // Without an index on majorid, the planner merge joins the tables,
+        // as that reads each of them once rather than student once per dept.
+        let planner = BasicQueryPlanner::new(mdm.clone());
+        let data = Parser::new("select dname, sid from dept, student where did = majorid").unwrap().query().unwrap();
+        let plan = planner.create_plan(&data, tx.clone()).unwrap();
+        let (dept, student) = (table("dept"), table("student"));
+        assert!(plan.blocks_accessed() < dept.blocks_accessed() + dept.records_output() * student.blocks_accessed());
+        let mut scan = plan.open().unwrap();
+        let mut records = Vec::new();
+        while scan.next().unwrap() {
+            records.push((scan.get_string("dname").unwrap(), scan.get_int("sid").unwrap()));
+        }
+        scan.close();
+        records.sort();
+        assert_eq!(records, expected);
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
@@ -0,0 +1,101 @@
+use std::cmp::Ordering;
+use crate::materialize::sortscan::{SortPosition, SortScan};
+use crate::query::constant::Constant;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+
+// The MergeJoinScan joins two scans that are sorted on their join fields by
+// reading them side by side. The records of the second scan that match a
+// join value form a group; its position is saved when the group is found,
+// so that each further record of the first scan with the same value can
+// read the group again.
+pub struct MergeJoinScan {
+    s1: Box<dyn Scan>,
+    s2: SortScan,
+    field1: String,
+    field2: String,
+    // The join value of the current group and the position of its first
+    // record, if a group has been found.
+    group: Option<(Constant, SortPosition)>,
+}
+
+impl MergeJoinScan {
+    pub(crate) fn new(s1: Box<dyn Scan>, s2: SortScan, field1: &str, field2: &str) -> Result<MergeJoinScan, ScanError> {
+        let mut scan = MergeJoinScan { s1, s2, field1: field1.to_string(), field2: field2.to_string(), group: None };
+        scan.before_first()?;
+        Ok(scan)
+    }
+}
+
+impl Scan for MergeJoinScan {
+    fn before_first(&mut self) -> Result<(), ScanError> {
+        self.group = None;
+        self.s1.before_first()?;
+        self.s2.before_first()
+    }
+
+    // Reads on through the current group first, then reads it again if the
+    // next record of the first scan has the same value. Otherwise the scan
+    // with the smaller value is moved forward until the values match. Nulls
+    // join with nothing and sort last, so the join ends at the first one.
+    fn next(&mut self) -> Result<bool, ScanError> {
+        let mut has_more2 = self.s2.next()?;
+        if let Some((val, _)) = &self.group && has_more2 && self.s2.get_val(&self.field2)?.compare(val) == Ordering::Equal {
+            return Ok(true);
+        }
+        let mut has_more1 = self.s1.next()?;
+        if let Some((val, pos)) = &self.group && has_more1 && self.s1.get_val(&self.field1)?.compare(val) == Ordering::Equal {
+            self.s2.restore_position(pos)?;
+            return Ok(true);
+        }
+        while has_more1 && has_more2 {
+            let val1 = self.s1.get_val(&self.field1)?;
+            let val2 = self.s2.get_val(&self.field2)?;
+            if val1.is_null() || val2.is_null() {
+                return Ok(false);
+            }
+            match val1.compare(&val2) {
+                Ordering::Less => has_more1 = self.s1.next()?,
+                Ordering::Greater => has_more2 = self.s2.next()?,
+                Ordering::Equal => {
+                    self.group = Some((val2, self.s2.save_position()));
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    fn get_int(&mut self, field: &str) -> Result<i32, ScanError> {
+        if self.s1.has_field(field) {
+            self.s1.get_int(field)
+        } else {
+            self.s2.get_int(field)
+        }
+    }
+
+    fn get_string(&mut self, field: &str) -> Result<String, ScanError> {
+        if self.s1.has_field(field) {
+            self.s1.get_string(field)
+        } else {
+            self.s2.get_string(field)
+        }
+    }
+
+    fn get_val(&mut self, field: &str) -> Result<Constant, ScanError> {
+        if self.s1.has_field(field) {
+            self.s1.get_val(field)
+        } else {
+            self.s2.get_val(field)
+        }
+    }
+
+    fn has_field(&self, field: &str) -> bool {
+        self.s1.has_field(field) || self.s2.has_field(field)
+    }
+
+    fn close(&mut self) {
+        self.s1.close();
+        self.s2.close();
+    }
+}
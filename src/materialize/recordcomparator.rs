@@ -0,0 +1,38 @@
+use std::cmp::Ordering;
+use crate::query::constant::Constant;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+
+// The RecordComparator compares the current records of two scans on a
+// list of sort fields, the first field being the most significant.
+#[derive(Clone)]
+pub struct RecordComparator {
+    fields: Vec<String>,
+}
+
+impl RecordComparator {
+    pub fn new(fields: Vec<String>) -> RecordComparator {
+        RecordComparator { fields }
+    }
+
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    pub fn compare(&self, s1: &mut dyn Scan, s2: &mut dyn Scan) -> Result<Ordering, ScanError> {
+        for field in &self.fields {
+            let ordering = s1.get_val(field)?.cmp(&s2.get_val(field)?);
+            if ordering != Ordering::Equal {
+                return Ok(ordering);
+            }
+        }
+        Ok(Ordering::Equal)
+    }
+
+    // The values of the sort fields in the current record of the scan.
+    // Keys compare in the same order as their records, for records held
+    // in memory rather than in a scan.
+    pub fn key(&self, s: &mut dyn Scan) -> Result<Vec<Constant>, ScanError> {
+        self.fields.iter().map(|field| s.get_val(field)).collect()
+    }
+}
@@ -0,0 +1,293 @@
+use std::sync::{Arc, Mutex};
+use crate::materialize::materializeplan::MaterializePlan;
+use crate::materialize::recordcomparator::RecordComparator;
+use crate::materialize::sortscan::SortScan;
+use crate::materialize::temptable::TempTable;
+use crate::plan::plan::Plan;
+use crate::query::constant::Constant;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::Schema;
+use crate::record::tablescan::UpdateScan;
+use crate::tx::transaction::Transaction;
+
+// The SortPlan sorts the output of the underlying plan on the sort fields
+// with an external merge sort, so the output does not have to fit in the
+// buffer pool. Opening it splits the input into runs: it reads as many
+// records as fill the transaction's work_mem, sorts them in memory and
+// spills them to a temporary table, until the input is used up. It then
+// merges the runs, as many at a time as work_mem has buffers for, until at
+// most two are left. The SortScan merges those last two while it is read,
+// which saves writing the final result.
+pub struct SortPlan {
+    tx: Arc<Mutex<Transaction>>,
+    plan: Box<dyn Plan>,
+    comp: RecordComparator,
+}
+
+impl SortPlan {
+    pub fn new(tx: Arc<Mutex<Transaction>>, plan: Box<dyn Plan>, sort_fields: Vec<String>) -> SortPlan {
+        SortPlan { tx, plan, comp: RecordComparator::new(sort_fields) }
+    }
+
+    // Opens the sort as a SortScan rather than a boxed scan, for callers that
+    // need to save and restore its position. The final merge reads one block
+    // of each run at a time, from buffers reserved for it.
+    pub(crate) fn open_sort(&self) -> Result<SortScan, ScanError> {
+        let runs = self.sorted_runs()?;
+        self.tx.lock().unwrap().reserve_buffers(runs.len())?;
+        self.open_runs(&runs)
+    }
+
+    // Splits the input into runs and merges them until at most two are left.
+    pub(crate) fn sorted_runs(&self) -> Result<Vec<TempTable>, ScanError> {
+        let mut src = self.plan.open()?;
+        let mut runs = self.split_into_runs(&mut src)?;
+        src.close();
+        while runs.len() > 2 {
+            runs = self.merge_runs(runs)?;
+        }
+        Ok(runs)
+    }
+
+    // Opens the final merge of the runs. The caller has reserved a buffer for
+    // each run, which the scan releases when it is closed.
+    pub(crate) fn open_runs(&self, runs: &[TempTable]) -> Result<SortScan, ScanError> {
+        SortScan::new(self.tx.clone(), runs, self.comp.clone())
+    }
+
+    // Each run holds as many records as fit in work_mem blocks of a
+    // temporary table, sorted in memory; only the last one may hold fewer.
+    // An empty input still gets one empty run.
+    fn split_into_runs(&self, src: &mut dyn Scan) -> Result<Vec<TempTable>, ScanError> {
+        let schema = self.plan.schema();
+        let work_mem = self.tx.lock().unwrap().work_mem();
+        let capacity = work_mem * MaterializePlan::records_per_block(&self.tx, schema);
+        let mut runs = Vec::new();
+        let mut records = Vec::new();
+        src.before_first()?;
+        while src.next()? {
+            let vals = schema.fields().iter().map(|field| src.get_val(field)).collect::<Result<Vec<_>, _>>()?;
+            records.push((self.comp.key(src)?, vals));
+            if records.len() == capacity {
+                runs.push(self.spill(&mut records)?);
+            }
+        }
+        if !records.is_empty() || runs.is_empty() {
+            runs.push(self.spill(&mut records)?);
+        }
+        Ok(runs)
+    }
+
+    // Sorts the records held in memory and writes them to a new run,
+    // leaving the memory empty for the next one.
+    fn spill(&self, records: &mut Vec<(Vec<Constant>, Vec<Constant>)>) -> Result<TempTable, ScanError> {
+        let schema = self.plan.schema();
+        records.sort_by(|(key1, _), (key2, _)| key1.cmp(key2));
+        let run = TempTable::new(self.tx.clone(), schema.clone());
+        let mut dest = run.open()?;
+        for (_, vals) in records.drain(..) {
+            dest.insert()?;
+            for (field, val) in schema.fields().iter().zip(&vals) {
+                dest.set_val(field, val)?;
+            }
+        }
+        dest.close();
+        Ok(run)
+    }
+
+    // Merges the runs in groups of one fewer than work_mem, as each run
+    // being merged and the merged output take a buffer. A single run left
+    // over is carried over as is.
+    fn merge_runs(&self, runs: Vec<TempTable>) -> Result<Vec<TempTable>, ScanError> {
+        let fan_in = self.tx.lock().unwrap().work_mem() - 1;
+        let mut merged = Vec::new();
+        let mut runs = runs.into_iter().peekable();
+        while runs.peek().is_some() {
+            let group: Vec<TempTable> = runs.by_ref().take(fan_in).collect();
+            match group.len() {
+                1 => merged.extend(group),
+                _ => merged.push(self.merge_group(&group)?),
+            }
+        }
+        Ok(merged)
+    }
+
+    // A merge reads a block of each run and writes one of the output at a
+    // time. Its buffers are reserved up front, so that concurrent sorts
+    // cannot each pin part of what they need and wait for the rest.
+    fn merge_group(&self, group: &[TempTable]) -> Result<TempTable, ScanError> {
+        self.tx.lock().unwrap().reserve_buffers(group.len() + 1)?;
+        let merged = self.merge(group);
+        self.tx.lock().unwrap().release_buffers(group.len() + 1);
+        merged
+    }
+
+    // Outputs the smallest of the current records of the runs each time,
+    // the earliest run first among equal ones, which keeps the sort stable.
+    fn merge(&self, group: &[TempTable]) -> Result<TempTable, ScanError> {
+        let schema = self.plan.schema();
+        let mut scans = group.iter().map(|run| run.open()).collect::<Result<Vec<_>, _>>()?;
+        let mut keys = Vec::with_capacity(scans.len());
+        for scan in scans.iter_mut() {
+            keys.push(if scan.next()? { Some(self.comp.key(scan)?) } else { None });
+        }
+        let result = TempTable::new(self.tx.clone(), schema.clone());
+        let mut dest = result.open()?;
+        while let Some(i) = (0..keys.len()).filter(|&i| keys[i].is_some()).min_by(|&i, &j| keys[i].cmp(&keys[j])) {
+            MaterializePlan::copy_record(schema, &mut scans[i], &mut dest)?;
+            keys[i] = if scans[i].next()? { Some(self.comp.key(&mut scans[i])?) } else { None };
+        }
+        scans.iter_mut().for_each(|scan| scan.close());
+        dest.close();
+        Ok(result)
+    }
+}
+
+impl Plan for SortPlan {
+    fn open(&self) -> Result<Box<dyn Scan>, ScanError> {
+        Ok(Box::new(self.open_sort()?))
+    }
+
+    // Only reading the sorted output is counted, as for a MaterializePlan:
+    // splitting and merging the runs is a one-time cost paid on opening.
+    fn blocks_accessed(&self) -> usize {
+        MaterializePlan::blocks(&self.tx, self.plan.as_ref())
+    }
+
+    fn records_output(&self) -> usize {
+        self.plan.records_output()
+    }
+
+    fn distinct_values(&self, field: &str) -> usize {
+        self.plan.distinct_values(field)
+    }
+
+    fn schema(&self) -> &Schema {
+        self.plan.schema()
+    }
+
+    fn describe(&self) -> String {
+        format!("Sort {}", self.comp.fields().join(", "))
+    }
+
+    fn inputs(&self) -> Vec<&dyn Plan> {
+        vec![self.plan.as_ref()]
+    }
+}
+
+mod tests {
+    use std::fs;
+    use super::*;
+    use crate::parse::parser::Parser;
+    use crate::plan::basicupdateplanner::BasicUpdatePlanner;
+    use crate::buffer::buffermgr::BufferMgr;
+    use crate::plan::tableplan::TablePlan;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_sort() {
+        let _ = fs::remove_dir_all("sorttestdb");
+        let db = SimpleDB::new("sorttestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let planner = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone()).unwrap();
+        update("create table t (a int, b varchar(10))");
+        // The records come in a scrambled order, which makes many runs.
+        for i in 0..200 {
+            update(&format!("insert into t (a, b) values ({}, 'rec{}')", i * 37 % 50, i * 37 % 200));
+        }
+
+        let table = || Box::new(TablePlan::new(tx.clone(), "t", &mdm).unwrap());
+        let sort = SortPlan::new(tx.clone(), table(), vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(sort.records_output(), 200);
+        let bm = db.buffer_mgr();
+        let available = bm.lock().unwrap().available();
+        let files = db.file_mgr().file_count();
+        let mut scan = sort.open_sort().unwrap();
+        let mut records = Vec::new();
+        while scan.next().unwrap() {
+            records.push((scan.get_int("a").unwrap(), scan.get_string("b").unwrap()));
+        }
+        let mut expected = records.clone();
+        expected.sort();
+        assert_eq!(records.len(), 200);
+        assert_eq!(records, expected);
+
+        // A saved position is returned to after reading further.
+        scan.before_first().unwrap();
+        for _ in 0..10 {
+            scan.next().unwrap();
+        }
+        let pos = scan.save_position();
+        let b = scan.get_string("b").unwrap();
+        for _ in 0..20 {
+            scan.next().unwrap();
+        }
+        scan.restore_position(&pos).unwrap();
+        assert_eq!(scan.get_string("b").unwrap(), b);
+        assert!(scan.next().unwrap());
+        assert_eq!(scan.get_string("b").unwrap(), records[10].1);
+        scan.close();
+
+        // Closing the scan hands back the buffers reserved for its runs.
+        assert_eq!(bm.lock().unwrap().available(), available);
+        BufferMgr::reserve(&bm, available).unwrap();
+        bm.lock().unwrap().release(available);
+
+        update("delete from t");
+        let mut scan = SortPlan::new(tx.clone(), table(), vec!["a".to_string()]).open().unwrap();
+        assert!(!scan.next().unwrap());
+        scan.close();
+        tx.lock().unwrap().commit().unwrap();
+
+        // The files of the runs go with the transaction, names and all.
+        assert_eq!(db.file_mgr().file_count(), files);
+        assert!(!fs::read_dir("sorttestdb").unwrap().any(|entry| entry.unwrap().file_name().to_string_lossy().starts_with("temp")));
+    }
+
+    #[test]
+    fn test_work_mem() {
+        let _ = fs::remove_dir_all("workmemtestdb");
+        let db = SimpleDB::new("workmemtestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let planner = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone()).unwrap();
+        update("create table t (a int, b varchar(10))");
+        for i in 0..200 {
+            update(&format!("insert into t (a, b) values ({}, 'rec{}')", i * 37 % 200, i));
+        }
+        let sort = SortPlan::new(tx.clone(), Box::new(TablePlan::new(tx.clone(), "t", &mdm).unwrap()), vec!["a".to_string()]);
+        let per_block = MaterializePlan::records_per_block(&tx, sort.schema());
+        let runs = |sort: &SortPlan| {
+            let mut src = sort.plan.open().unwrap();
+            let runs = sort.split_into_runs(&mut src).unwrap();
+            src.close();
+            runs.len()
+        };
+
+        // The budget is a quarter of the pool, but never less than a merge
+        // of two runs needs, and never more than the pool.
+        assert_eq!(tx.lock().unwrap().work_mem(), 3);
+        assert_eq!(runs(&sort), 200usize.div_ceil(3 * per_block));
+        db.set_work_mem(6);
+        assert_eq!(runs(&sort), 200usize.div_ceil(6 * per_block));
+        db.set_work_mem(100);
+        assert_eq!(tx.lock().unwrap().work_mem(), 8);
+        db.set_work_mem(0);
+        assert_eq!(tx.lock().unwrap().work_mem(), 3);
+
+        // The runs are merged three at a time, two in and one out, and
+        // the output is in order.
+        let mut scan = sort.open().unwrap();
+        let mut values = Vec::new();
+        while scan.next().unwrap() {
+            values.push(scan.get_int("a").unwrap());
+        }
+        scan.close();
+        assert_eq!(values, (0..200).collect::<Vec<_>>());
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+use std::cmp::Ordering;
+use std::sync::{Arc, Mutex};
+use crate::materialize::recordcomparator::RecordComparator;
+use crate::materialize::temptable::TempTable;
+use crate::query::constant::Constant;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+use crate::record::tablescan::{RecordId, TableScan, UpdateScan};
+use crate::tx::transaction::Transaction;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Side {
+    First,
+    Second,
+}
+
+// A position of a SortScan saved by `save_position`: the record each run
+// is on, and which run the current record comes from.
+#[derive(Clone)]
+pub struct SortPosition {
+    rid1: Option<RecordId>,
+    rid2: Option<RecordId>,
+    current: Option<Side>,
+    has_more1: bool,
+    has_more2: bool,
+}
+
+// The SortScan performs the final merge of a sort: it reads the last one
+// or two sorted runs at the same time and outputs the smaller of their
+// current records each time. It holds a reserved buffer for each run, and
+// releases them when it is closed.
+pub struct SortScan {
+    tx: Arc<Mutex<Transaction>>,
+    reserved: usize,
+    s1: TableScan,
+    s2: Option<TableScan>,
+    current: Option<Side>,
+    has_more1: bool,
+    has_more2: bool,
+    comp: RecordComparator,
+}
+
+impl SortScan {
+    pub(crate) fn new(tx: Arc<Mutex<Transaction>>, runs: &[TempTable], comp: RecordComparator) -> Result<SortScan, ScanError> {
+        debug_assert!(matches!(runs.len(), 1 | 2), "the final merge reads one or two runs, not {}", runs.len());
+        let s1 = runs[0].open()?;
+        let s2 = runs.get(1).map(|run| run.open()).transpose()?;
+        let reserved = runs.len();
+        let mut scan = SortScan { tx, reserved, s1, s2, current: None, has_more1: false, has_more2: false, comp };
+        scan.before_first()?;
+        Ok(scan)
+    }
+
+    // Remembers the current position, so that the scan can go back to it
+    // with `restore_position`; a merge join uses this to read a group of
+    // equal records again.
+    pub fn save_position(&self) -> SortPosition {
+        SortPosition {
+            rid1: self.s1.rid(),
+            rid2: self.s2.as_ref().and_then(|s2| s2.rid()),
+            current: self.current,
+            has_more1: self.has_more1,
+            has_more2: self.has_more2,
+        }
+    }
+
+    pub fn restore_position(&mut self, pos: &SortPosition) -> Result<(), ScanError> {
+        if let Some(rid) = &pos.rid1 {
+            self.s1.move_to_rid(rid)?;
+        }
+        if let (Some(s2), Some(rid)) = (self.s2.as_mut(), &pos.rid2) {
+            s2.move_to_rid(rid)?;
+        }
+        self.current = pos.current;
+        self.has_more1 = pos.has_more1;
+        self.has_more2 = pos.has_more2;
+        Ok(())
+    }
+
+    fn current_scan(&mut self) -> Result<&mut TableScan, ScanError> {
+        match self.current {
+            Some(Side::First) => Ok(&mut self.s1),
+            Some(Side::Second) => self.s2.as_mut().ok_or(ScanError::NoCurrentRecord),
+            None => Err(ScanError::NoCurrentRecord),
+        }
+    }
+}
+
+impl Scan for SortScan {
+    fn before_first(&mut self) -> Result<(), ScanError> {
+        self.current = None;
+        self.s1.before_first()?;
+        self.has_more1 = self.s1.next()?;
+        if let Some(s2) = self.s2.as_mut() {
+            s2.before_first()?;
+            self.has_more2 = s2.next()?;
+        }
+        Ok(())
+    }
+
+    // Moves past the record that was output last, then picks the smaller of
+    // the current records of the two runs.
+    fn next(&mut self) -> Result<bool, ScanError> {
+        match (self.current, self.s2.as_mut()) {
+            (Some(Side::First), _) => self.has_more1 = self.s1.next()?,
+            (Some(Side::Second), Some(s2)) => self.has_more2 = s2.next()?,
+            _ => {}
+        }
+        self.current = match (self.has_more1, self.has_more2, self.s2.as_mut()) {
+            (true, true, Some(s2)) => match self.comp.compare(&mut self.s1, s2)? {
+                Ordering::Greater => Some(Side::Second),
+                _ => Some(Side::First),
+            },
+            (true, _, _) => Some(Side::First),
+            (false, true, _) => Some(Side::Second),
+            (false, false, _) => None,
+        };
+        Ok(self.current.is_some())
+    }
+
+    fn get_int(&mut self, field: &str) -> Result<i32, ScanError> {
+        self.current_scan()?.get_int(field)
+    }
+
+    fn get_string(&mut self, field: &str) -> Result<String, ScanError> {
+        self.current_scan()?.get_string(field)
+    }
+
+    fn get_val(&mut self, field: &str) -> Result<Constant, ScanError> {
+        self.current_scan()?.get_val(field)
+    }
+
+    fn has_field(&self, field: &str) -> bool {
+        self.s1.has_field(field)
+    }
+
+    fn close(&mut self) {
+        self.s1.close();
+        if let Some(s2) = self.s2.as_mut() {
+            s2.close();
+        }
+        self.tx.lock().unwrap().release_buffers(self.reserved);
+        self.reserved = 0;
+    }
+}
@@ -0,0 +1,45 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use crate::record::layout::{Layout, Schema};
+use crate::record::scanerror::ScanError;
+use crate::record::tablescan::TableScan;
+use crate::tx::transaction::Transaction;
+
+static NEXT_TABLE_NUM: AtomicUsize = AtomicUsize::new(0);
+
+// A TempTable holds intermediate results of a query, such as the runs of a
+// sort. It is not in the catalog and is only known to the plan that
+// created it. Its file is deleted when the transaction ends; its name
+// starts with "temp", so that the file manager deletes a file left behind
+// by a crash the next time the database is opened.
+pub struct TempTable {
+    tx: Arc<Mutex<Transaction>>,
+    table: String,
+    layout: Arc<Layout>,
+}
+
+impl TempTable {
+    // The layout is fitted to the block size, as for a table in the catalog.
+    pub fn new(tx: Arc<Mutex<Transaction>>, schema: Schema) -> TempTable {
+        let table = format!("temp{}", NEXT_TABLE_NUM.fetch_add(1, Ordering::SeqCst) + 1);
+        let layout = {
+            let mut tx = tx.lock().unwrap();
+            tx.add_temp_file(&format!("{}.tbl", table));
+            tx.add_temp_file(&format!("{}.blob", table));
+            Layout::new(schema).fit(tx.block_size())
+        };
+        TempTable { tx, table, layout: Arc::new(layout) }
+    }
+
+    pub(crate) fn open(&self) -> Result<TableScan, ScanError> {
+        TableScan::new(self.tx.clone(), self.layout.clone(), &self.table)
+    }
+
+    pub fn table_name(&self) -> &str {
+        &self.table
+    }
+
+    pub fn layout(&self) -> Arc<Layout> {
+        self.layout.clone()
+    }
+}
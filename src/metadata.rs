@@ -1 +1,8 @@
+pub mod catalogsnapshot;
+pub mod indexinfo;
+pub mod indexmgr;
+pub mod metadatamgr;
+pub mod schemamgr;
+pub mod statmgr;
 pub mod tablemgr;
+pub mod viewmgr;
@@ -0,0 +1,95 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use crate::index::index::IndexKind;
+use crate::metadata::indexmgr::IndexDef;
+use crate::metadata::schemamgr::SchemaMgr;
+use crate::query::predicate::Predicate;
+use crate::record::layout::Layout;
+
+// The CatalogSnapshot is an immutable in-memory copy of the committed
+// catalog: the layout of every table, the definition of every view, the
+// indexes of every table and the schemas. It is tagged with the catalog
+// version it was read at, and stays valid until a transaction that changed
+// the catalog commits and advances the version.
+pub(crate) struct CatalogSnapshot {
+    version: u64,
+    layouts: HashMap<String, Arc<Layout>>,
+    view_defs: HashMap<String, String>,
+    // The (index, field) pairs of the indexes on each table, published or
+    // not, the names of those that are not, and the definition of each.
+    indexes: HashMap<String, Vec<(String, String)>>,
+    unpublished: HashSet<String>,
+    index_defs: HashMap<String, IndexDef>,
+    schemas: HashSet<String>,
+}
+
+impl CatalogSnapshot {
+    pub(crate) fn new(
+        version: u64,
+        layouts: HashMap<String, Arc<Layout>>,
+        view_defs: HashMap<String, String>,
+        indexes: HashMap<String, Vec<(String, String)>>,
+        unpublished: HashSet<String>,
+        index_defs: HashMap<String, IndexDef>,
+        schemas: HashSet<String>,
+    ) -> CatalogSnapshot {
+        CatalogSnapshot { version, layouts, view_defs, indexes, unpublished, index_defs, schemas }
+    }
+
+    pub(crate) fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub(crate) fn layout(&self, table: &str) -> Option<Arc<Layout>> {
+        self.layouts.get(table).cloned()
+    }
+
+    pub(crate) fn view_def(&self, name: &str) -> Option<String> {
+        self.view_defs.get(name).cloned()
+    }
+
+    pub(crate) fn indexes(&self, table: &str) -> &[(String, String)] {
+        self.indexes.get(table).map_or(&[], |indexes| indexes.as_slice())
+    }
+
+    pub(crate) fn index_exists(&self, index: &str) -> bool {
+        self.indexes.values().flatten().any(|(name, _)| name == index)
+    }
+
+    pub(crate) fn is_published(&self, index: &str) -> bool {
+        !self.unpublished.contains(index)
+    }
+
+    pub(crate) fn index_defs(&self) -> &HashMap<String, IndexDef> {
+        &self.index_defs
+    }
+
+    pub(crate) fn kind(&self, index: &str) -> IndexKind {
+        self.index_defs.get(index).map_or(IndexKind::Hash, |def| def.kind)
+    }
+
+    // The predicate of the index if it is partial.
+    pub(crate) fn predicate(&self, index: &str) -> Option<&Predicate> {
+        self.index_defs.get(index).map(|def| &def.pred).filter(|pred| !pred.is_empty())
+    }
+
+    pub(crate) fn schema_exists(&self, name: &str) -> bool {
+        name == SchemaMgr::DEFAULT || self.schemas.contains(name)
+    }
+
+    pub(crate) fn layouts(&self) -> &HashMap<String, Arc<Layout>> {
+        &self.layouts
+    }
+
+    pub(crate) fn view_defs(&self) -> &HashMap<String, String> {
+        &self.view_defs
+    }
+
+    pub(crate) fn all_indexes(&self) -> &HashMap<String, Vec<(String, String)>> {
+        &self.indexes
+    }
+
+    pub(crate) fn schemas(&self) -> &HashSet<String> {
+        &self.schemas
+    }
+}
@@ -0,0 +1,123 @@
+use std::sync::{Arc, Mutex};
+use crate::index::hashindex::HashIndex;
+use crate::index::index::{Index, IndexKind};
+use crate::index::textindex::TextIndex;
+use crate::metadata::statmgr::{IndexStats, StatInfo};
+use crate::query::constant::Constant;
+use crate::query::predicate::Predicate;
+use crate::query::scan::Scan;
+use crate::record::layout::{Layout, Schema};
+use crate::record::scanerror::ScanError;
+use crate::tx::transaction::Transaction;
+
+// The IndexInfo describes an index on a field of a table, and the
+// predicate of the records it holds if it is partial: it opens the index
+// and estimates the cost of searching it from the statistics of its
+// buckets, which the planners use to decide whether the index is worth it.
+#[derive(Clone)]
+pub struct IndexInfo {
+    index: String,
+    field: String,
+    kind: IndexKind,
+    pred: Predicate,
+    tx: Arc<Mutex<Transaction>>,
+    index_layout: Arc<Layout>,
+    info: StatInfo,
+    stats: IndexStats,
+}
+
+impl IndexInfo {
+    pub(crate) fn new(index: &str, field: &str, kind: IndexKind, pred: Predicate, index_layout: Arc<Layout>, tx: Arc<Mutex<Transaction>>, info: StatInfo, stats: IndexStats) -> IndexInfo {
+        IndexInfo { index: index.to_string(), field: field.to_string(), kind, pred, tx, index_layout, info, stats }
+    }
+
+    pub fn open(&self) -> Box<dyn Index> {
+        match self.kind {
+            IndexKind::Hash => Box::new(HashIndex::new(self.tx.clone(), &self.index, self.index_layout.clone())),
+            IndexKind::Text => Box::new(TextIndex::new(self.tx.clone(), &self.index, self.index_layout.clone())),
+        }
+    }
+
+    pub fn kind(&self) -> IndexKind {
+        self.kind
+    }
+
+    pub fn index_name(&self) -> &str {
+        &self.index
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    // The empty predicate for an index on the whole table.
+    pub fn pred(&self) -> &Predicate {
+        &self.pred
+    }
+
+    // Returns the search key that finds the records the predicate selects
+    // through the index, if there is one: the constant the predicate
+    // equates the field with, for a hash index, or the word it searches
+    // the field for, for a text index.
+    pub fn search_key<'a>(&self, pred: &'a Predicate) -> Option<&'a Constant> {
+        match self.kind {
+            IndexKind::Hash => pred.equates_with_constant(&self.field),
+            IndexKind::Text => pred.contains_word(&self.field),
+        }
+    }
+
+    // The term a search of the index for the key stands for.
+    pub fn describe_search(&self, key: &Constant) -> String {
+        match self.kind {
+            IndexKind::Hash => format!("{}={}", self.field, key),
+            IndexKind::Text => format!("contains({}, {})", self.field, key),
+        }
+    }
+
+    // Returns the value the index holds the current record of the scan
+    // under, or None if it does not hold the record: its value is null, or
+    // it does not satisfy the predicate of a partial index.
+    pub(crate) fn key(&self, scan: &mut dyn Scan) -> Result<Option<Constant>, ScanError> {
+        let val = scan.get_val(&self.field)?;
+        if val.is_null() || !self.pred.is_satisfied(scan)? {
+            return Ok(None);
+        }
+        Ok(Some(val))
+    }
+
+    pub fn layout(&self) -> Arc<Layout> {
+        self.index_layout.clone()
+    }
+
+    pub fn stats(&self) -> &IndexStats {
+        &self.stats
+    }
+
+    // The estimated number of block accesses to find the index records of
+    // a search key: the blocks of a bucket in use.
+    pub fn blocks_accessed(&self) -> usize {
+        self.stats.search_blocks()
+    }
+
+    // The estimated number of records of the table with a given value of
+    // the indexed field, as the index records per distinct key.
+    pub fn records_output(&self) -> usize {
+        self.stats.records() / self.stats.distinct_keys().max(1)
+    }
+
+    // The records a text index finds for a word have the word in common,
+    // not their values.
+    pub fn distinct_values(&self, field: &str) -> usize {
+        if field == self.field && self.kind == IndexKind::Hash { 1 } else { self.info.distinct_values(field) }
+    }
+
+    // An index record holds the RecordId of a data record, as a block
+    // number and a slot, and the value of the indexed field.
+    pub(crate) fn index_layout(field: &str, table_schema: &Schema) -> Layout {
+        let mut schema = Schema::new();
+        schema.add_int_field("block");
+        schema.add_int_field("id");
+        schema.add_field("dataval", table_schema.ftype(field), table_schema.length(field));
+        Layout::new(schema)
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use crate::index::hashindex::HashIndex;
+use crate::index::index::IndexKind;
+use crate::metadata::indexinfo::IndexInfo;
+use crate::metadata::statmgr::StatMgr;
+use crate::metadata::tablemgr::{RelationSize, TableMgr};
+use crate::parse::parser::Parser;
+use crate::query::predicate::Predicate;
+use crate::query::scan::Scan;
+use crate::record::layout::{Layout, Schema};
+use crate::record::scanerror::ScanError;
+use crate::record::tablescan::{TableScan, UpdateScan};
+use crate::tx::transaction::Transaction;
+
+// The IndexMgr records each index, with the table and field it is on and
+// its kind, in the idxcat catalog table. The predicate of a partial index
+// is kept as its text, which is empty for an index on the whole table, and
+// the fill factor of its bucket pages next to it. An index that is being
+// built online is not published yet: updates keep it up to date, but
+// queries do not use it, see `build_index` in the server.
+// How an index is defined, besides the table and field it is on.
+#[derive(Clone)]
+pub(crate) struct IndexDef {
+    pub(crate) kind: IndexKind,
+    pub(crate) pred: Predicate,
+    pub(crate) fill_factor: i32,
+}
+
+pub(crate) struct IndexMgr {
+    index_catalog_layout: Arc<Layout>,
+    tm: Arc<TableMgr>,
+    sm: Arc<StatMgr>,
+}
+
+impl IndexMgr {
+    const MAX_PREDICATE: i32 = 100;
+    const MAX_KIND: i32 = 10;
+
+    pub(crate) fn new(is_new: bool, tm: Arc<TableMgr>, sm: Arc<StatMgr>, tx: Arc<Mutex<Transaction>>) -> Result<IndexMgr, ScanError> {
+        let mut index_catalog_schema = Schema::new();
+        index_catalog_schema.add_string_field("index_name", TableMgr::MAX_NAME);
+        index_catalog_schema.add_string_field("table_name", TableMgr::MAX_NAME);
+        index_catalog_schema.add_string_field("field_name", TableMgr::MAX_NAME);
+        index_catalog_schema.add_boolean_field("published");
+        index_catalog_schema.add_string_field("predicate", IndexMgr::MAX_PREDICATE);
+        index_catalog_schema.add_int_field("fill_factor");
+        index_catalog_schema.add_string_field("kind", IndexMgr::MAX_KIND);
+        if is_new {
+            tm.create_table("idxcat", &index_catalog_schema, tx)?;
+        }
+        Ok(IndexMgr { index_catalog_layout: Arc::new(Layout::new(index_catalog_schema)), tm, sm })
+    }
+
+    pub(crate) fn create_index(&self, index: &str, table: &str, field: &str, def: &IndexDef, published: bool, tx: Arc<Mutex<Transaction>>) -> Result<(), ScanError> {
+        let IndexDef { kind, pred, fill_factor } = def;
+        let mut index_catalog = TableScan::new(tx, self.index_catalog_layout.clone(), "idxcat")?;
+        index_catalog.insert()?;
+        index_catalog.set_string("index_name", index)?;
+        index_catalog.set_string("table_name", table)?;
+        index_catalog.set_string("field_name", field)?;
+        index_catalog.set_string("kind", kind.name())?;
+        index_catalog.set_bool("published", published)?;
+        assert!((1..=100).contains(fill_factor), "fill factor must be between 1 and 100, got {}", fill_factor);
+        index_catalog.set_int("fill_factor", *fill_factor)?;
+        // A predicate that does not fit must not leave half an index behind.
+        let result = index_catalog.set_string("predicate", &pred.to_string());
+        if result.is_err() {
+            index_catalog.delete()?;
+        }
+        index_catalog.close();
+        result
+    }
+
+    // Lets queries use the index. Returns false if there is no index with
+    // that name.
+    pub(crate) fn publish_index(&self, index: &str, tx: Arc<Mutex<Transaction>>) -> Result<bool, ScanError> {
+        let mut index_catalog = TableScan::new(tx, self.index_catalog_layout.clone(), "idxcat")?;
+        let mut found = false;
+        while !found && index_catalog.next()? {
+            if index_catalog.get_string("index_name")? == index {
+                index_catalog.set_bool("published", true)?;
+                found = true;
+            }
+        }
+        index_catalog.close();
+        Ok(found)
+    }
+
+    // Removes the index from the catalog and deletes its buckets when the
+    // transaction commits. Returns false if there is no index with that name.
+    pub(crate) fn drop_index(&self, index: &str, tx: Arc<Mutex<Transaction>>) -> Result<bool, ScanError> {
+        let mut index_catalog = TableScan::new(tx.clone(), self.index_catalog_layout.clone(), "idxcat")?;
+        let mut found = false;
+        while index_catalog.next()? {
+            if index_catalog.get_string("index_name")? == index {
+                index_catalog.delete()?;
+                found = true;
+            }
+        }
+        index_catalog.close();
+        if found {
+            let mut tx = tx.lock().unwrap();
+            for bucket in 0..HashIndex::NUM_BUCKETS {
+                tx.delete_file(&format!("{}{}.tbl", index, bucket))?;
+            }
+        }
+        Ok(found)
+    }
+
+    // The names of the indexes on the table.
+    pub(crate) fn indexes(&self, table: &str, tx: Arc<Mutex<Transaction>>) -> Result<Vec<String>, ScanError> {
+        let mut indexes = Vec::new();
+        let mut index_catalog = TableScan::new(tx, self.index_catalog_layout.clone(), "idxcat")?;
+        while index_catalog.next()? {
+            if index_catalog.get_string("table_name")? == table {
+                indexes.push(index_catalog.get_string("index_name")?);
+            }
+        }
+        index_catalog.close();
+        Ok(indexes)
+    }
+
+    // Index names are unique across tables.
+    pub(crate) fn index_exists(&self, index: &str, tx: Arc<Mutex<Transaction>>) -> Result<bool, ScanError> {
+        let mut index_catalog = TableScan::new(tx, self.index_catalog_layout.clone(), "idxcat")?;
+        let mut found = false;
+        while !found && index_catalog.next()? {
+            found = index_catalog.get_string("index_name")? == index;
+        }
+        index_catalog.close();
+        Ok(found)
+    }
+
+    // Returns the published indexes on the table.
+    pub(crate) fn published_indexes(&self, table: &str, tx: Arc<Mutex<Transaction>>) -> Result<Vec<IndexInfo>, ScanError> {
+        let unpublished = self.unpublished(tx.clone())?;
+        let indexes = self.maintained_indexes(table, tx)?;
+        Ok(indexes.into_iter().filter(|ii| !unpublished.contains(ii.index_name())).collect())
+    }
+
+    // Returns every index on the table, published or not, which are the
+    // indexes an update of the table has to keep up to date.
+    pub(crate) fn maintained_indexes(&self, table: &str, tx: Arc<Mutex<Transaction>>) -> Result<Vec<IndexInfo>, ScanError> {
+        let indexes = self.all_indexes(tx.clone())?.remove(table).unwrap_or_default();
+        if indexes.is_empty() {
+            return Ok(Vec::new());
+        }
+        let layout = self.tm.layout(table, tx.clone())?;
+        let defs = self.index_defs(tx.clone())?;
+        self.index_info_of(table, &indexes, &defs, &layout, tx)
+    }
+
+    // Returns the given (index, field) pairs of the table as index infos,
+    // with their definitions.
+    pub(crate) fn index_info_of(&self, table: &str, indexes: &[(String, String)], defs: &HashMap<String, IndexDef>, layout: &Layout, tx: Arc<Mutex<Transaction>>) -> Result<Vec<IndexInfo>, ScanError> {
+        let info = self.sm.stat_info(table, tx.clone())?;
+        indexes.iter().map(|(index, field)| {
+            let def = defs.get(index).cloned().unwrap_or(IndexDef { kind: IndexKind::Hash, pred: Predicate::new(), fill_factor: Layout::DEFAULT_FILL_FACTOR });
+            let index_layout = Arc::new(IndexInfo::index_layout(field, layout.schema()).with_fill_factor(def.fill_factor));
+            let stats = self.sm.index_stats(index, &index_layout, tx.clone())?;
+            Ok(IndexInfo::new(index, field, def.kind, def.pred, index_layout, tx.clone(), info.clone(), stats))
+        }).collect()
+    }
+
+    // The disk usage of the index, summed over its buckets.
+    pub(crate) fn index_size(&self, index: &str, table: &str, field: &str, tx: Arc<Mutex<Transaction>>) -> Result<RelationSize, ScanError> {
+        let layout = Arc::new(IndexInfo::index_layout(field, self.tm.layout(table, tx.clone())?.schema()));
+        let mut size = RelationSize { table: table.to_string(), index: Some(index.to_string()), blocks: 0, bytes: 0, records: 0, free_slots: 0, quota: None };
+        for bucket in 0..HashIndex::NUM_BUCKETS {
+            let (blocks, records, free_slots) = TableMgr::file_size(&format!("{}{}", index, bucket), &layout, tx.clone())?;
+            size.blocks += blocks;
+            size.records += records;
+            size.free_slots += free_slots;
+        }
+        size.bytes = size.blocks * tx.lock().unwrap().block_size();
+        Ok(size)
+    }
+
+    // The names of the indexes that are not published yet.
+    pub(crate) fn unpublished(&self, tx: Arc<Mutex<Transaction>>) -> Result<HashSet<String>, ScanError> {
+        let mut unpublished = HashSet::new();
+        let mut index_catalog = TableScan::new(tx, self.index_catalog_layout.clone(), "idxcat")?;
+        while index_catalog.next()? {
+            if !index_catalog.get_bool("published")? {
+                unpublished.insert(index_catalog.get_string("index_name")?);
+            }
+        }
+        index_catalog.close();
+        Ok(unpublished)
+    }
+
+    // The definition of every index, by index name. The predicate of an
+    // index on the whole table is empty.
+    pub(crate) fn index_defs(&self, tx: Arc<Mutex<Transaction>>) -> Result<HashMap<String, IndexDef>, ScanError> {
+        let mut defs = HashMap::new();
+        let mut index_catalog = TableScan::new(tx, self.index_catalog_layout.clone(), "idxcat")?;
+        while index_catalog.next()? {
+            let text = index_catalog.get_string("predicate")?;
+            let pred = match text.is_empty() {
+                true => Predicate::new(),
+                // The text was written from a parsed predicate, so it parses.
+                false => Parser::new(&text).and_then(|mut parser| parser.predicate()).expect("a stored index predicate parses"),
+            };
+            // The kind was written from an IndexKind, so it is a known one.
+            let kind = IndexKind::from_name(&index_catalog.get_string("kind")?).expect("a stored index kind is known");
+            let def = IndexDef { kind, pred, fill_factor: index_catalog.get_int("fill_factor")? };
+            defs.insert(index_catalog.get_string("index_name")?, def);
+        }
+        index_catalog.close();
+        Ok(defs)
+    }
+
+    // Reads the (index, field) pairs of all indexes, keyed by table name.
+    pub(crate) fn all_indexes(&self, tx: Arc<Mutex<Transaction>>) -> Result<HashMap<String, Vec<(String, String)>>, ScanError> {
+        let mut indexes: HashMap<String, Vec<(String, String)>> = HashMap::new();
+        let mut index_catalog = TableScan::new(tx, self.index_catalog_layout.clone(), "idxcat")?;
+        while index_catalog.next()? {
+            let entry = (index_catalog.get_string("index_name")?, index_catalog.get_string("field_name")?);
+            indexes.entry(index_catalog.get_string("table_name")?).or_default().push(entry);
+        }
+        index_catalog.close();
+        Ok(indexes)
+    }
+}
+
+mod tests {
+    use std::fs;
+    use super::*;
+    use crate::parse::parser::Parser;
+    use crate::file::page::Page;
+    use crate::plan::basicupdateplanner::BasicUpdatePlanner;
+    use crate::plan::indexupdateplanner::IndexUpdatePlanner;
+    use crate::query::constant::Constant;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_index_mgr() {
+        let _ = fs::remove_dir_all("indexmgrtestdb");
+        let db = SimpleDB::new("indexmgrtestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let planner = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone());
+
+        update("create table t (a int, b varchar(10))").unwrap();
+        for i in 0..20 {
+            update(&format!("insert into t (a, b) values ({}, 'rec{}')", i, i % 5)).unwrap();
+        }
+        assert!(mdm.index_info("t", tx.clone()).unwrap().is_empty());
+        update("create index tb on t (b)").unwrap();
+        assert_eq!(update("create index tc on t (c)"), Err(ScanError::UnknownField("c".to_string())));
+
+        let indexes = mdm.index_info("t", tx.clone()).unwrap();
+        assert_eq!(indexes.len(), 1);
+        let ii = &indexes["b"];
+        assert_eq!((ii.index_name(), ii.field()), ("tb", "b"));
+        assert_eq!(ii.layout().schema().ftype("dataval"), 12);
+        assert_eq!(ii.layout().schema().length("dataval"), 10);
+        assert_eq!(ii.records_output(), 4);
+        assert_eq!(ii.distinct_values("b"), 1);
+        assert_eq!(ii.distinct_values("a"), 20);
+
+        // The index was filled with the records already in the table.
+        let mut idx = ii.open();
+        idx.before_first(&Constant::String("rec2".to_string())).unwrap();
+        let mut count = 0;
+        while idx.next().unwrap() {
+            count += 1;
+        }
+        idx.close();
+        assert_eq!(count, 4);
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_index_stats() {
+        let _ = fs::remove_dir_all("indexstatstestdb");
+        let db = SimpleDB::new("indexstatstestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let planner = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone());
+
+        update("create table istats (a int, b varchar(10))").unwrap();
+        for i in 0..100 {
+            update(&format!("insert into istats (a, b) values ({}, 'rec{}')", i % 10, i)).unwrap();
+        }
+        update("create index istatsa on istats (a)").unwrap();
+
+        // Each of the 10 keys hashes to a bucket of its own, whose 10 index
+        // records fit in one block.
+        let ii = &mdm.index_info("istats", tx.clone()).unwrap()["a"];
+        let stats = ii.stats();
+        assert_eq!((stats.records(), stats.distinct_keys()), (100, 10));
+        assert_eq!((stats.buckets(), stats.blocks(), stats.max_bucket_blocks()), (10, 10, 1));
+        assert_eq!(ii.blocks_accessed(), 1);
+        assert_eq!(ii.records_output(), 10);
+
+        // Every value of b is distinct.
+        update("create index istatsb on istats (b)").unwrap();
+        let stats = mdm.index_info("istats", tx.clone()).unwrap()["b"].stats().clone();
+        assert_eq!((stats.records(), stats.distinct_keys()), (100, 100));
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_index_fill_factor() {
+        let db = SimpleDB::ephemeral().unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let planner = IndexUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone()).unwrap();
+        update("create table t (a int, b int)");
+        update("create index tfull on t (a)");
+        mdm.create_index_with_fill_factor("thalf", "t", "b", &Predicate::new(), 50, tx.clone()).unwrap();
+        for _ in 0..60 {
+            update("insert into t (a, b) values (1, 1)");
+        }
+
+        // Every record has the same key in both indexes, so each fills one
+        // bucket, the half-full one taking half as many records per block.
+        let indexes = mdm.index_info("t", tx.clone()).unwrap();
+        let (full, half) = (&indexes["a"], &indexes["b"]);
+        assert_eq!((full.layout().fill_factor(), half.layout().fill_factor()), (100, 50));
+        let slots = (tx.lock().unwrap().block_size() - Page::HEADER_SIZE) / full.layout().slot_size() as usize;
+        assert_eq!(full.stats().blocks(), 60usize.div_ceil(slots));
+        assert_eq!(half.stats().blocks(), 60usize.div_ceil(slots / 2));
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use crate::index::index::IndexKind;
+use crate::metadata::catalogsnapshot::CatalogSnapshot;
+use crate::metadata::indexinfo::IndexInfo;
+use crate::metadata::indexmgr::{IndexDef, IndexMgr};
+use crate::metadata::schemamgr::SchemaMgr;
+use crate::metadata::statmgr::{StatInfo, StatMgr};
+use crate::metadata::tablemgr::{RelationSize, TableMgr};
+use crate::metadata::viewmgr::ViewMgr;
+use crate::query::predicate::Predicate;
+use crate::record::layout::{Layout, Schema};
+use crate::record::scanerror::ScanError;
+use crate::tx::transaction::Transaction;
+
+// The MetadataMgr is the single entry point to the catalog for the
+// planners: it hides which manager keeps each kind of metadata.
+//
+// Lookups are answered from a snapshot of the committed catalog rather
+// than by scanning the catalog tables, so they take no locks on catalog
+// blocks and pin no buffers. The snapshot is read again, with the
+// transaction of the lookup, the first time it is used after a
+// transaction that changed the catalog commits. A transaction that changed
+// the catalog itself keeps reading the catalog tables, since only they
+// have its uncommitted changes. Statistics are kept by the StatMgr, which
+// caches them already.
+pub(crate) struct MetadataMgr {
+    tm: Arc<TableMgr>,
+    vm: ViewMgr,
+    sm: Arc<StatMgr>,
+    im: IndexMgr,
+    scm: SchemaMgr,
+    version: Arc<AtomicU64>,
+    snapshot: RwLock<Option<Arc<CatalogSnapshot>>>,
+}
+
+impl MetadataMgr {
+    pub(crate) fn new(is_new: bool, tx: Arc<Mutex<Transaction>>) -> Result<MetadataMgr, ScanError> {
+        let tm = Arc::new(TableMgr::new(is_new, tx.clone())?);
+        let vm = ViewMgr::new(is_new, &tm, tx.clone())?;
+        let sm = Arc::new(StatMgr::new(tm.clone()));
+        let im = IndexMgr::new(is_new, tm.clone(), sm.clone(), tx.clone())?;
+        let scm = SchemaMgr::new(is_new, &tm, tx)?;
+        Ok(MetadataMgr { tm, vm, sm, im, scm, version: Arc::new(AtomicU64::new(0)), snapshot: RwLock::new(None) })
+    }
+
+    pub(crate) fn create_table(&self, name: &str, schema: &Schema, tx: Arc<Mutex<Transaction>>) -> Result<(), ScanError> {
+        self.change(&tx);
+        self.tm.create_table(name, schema, tx)
+    }
+
+    // Drops the table along with its indexes.
+    pub(crate) fn drop_table(&self, name: &str, tx: Arc<Mutex<Transaction>>) -> Result<(), ScanError> {
+        self.change(&tx);
+        for index in self.im.indexes(name, tx.clone())? {
+            self.im.drop_index(&index, tx.clone())?;
+            self.sm.forget(&index);
+        }
+        self.tm.drop_table(name, tx)?;
+        self.sm.forget(name);
+        Ok(())
+    }
+
+    // Tables and views share one namespace, as a query names either.
+    pub(crate) fn relation_exists(&self, name: &str, tx: Arc<Mutex<Transaction>>) -> Result<bool, ScanError> {
+        match self.layout(name, tx.clone()) {
+            Ok(_) => Ok(true),
+            Err(ScanError::UnknownTable(_)) => Ok(self.view_def(name, tx)?.is_some()),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub(crate) fn layout(&self, table: &str, tx: Arc<Mutex<Transaction>>) -> Result<Arc<Layout>, ScanError> {
+        match self.snapshot(&tx)? {
+            Some(snapshot) => snapshot.layout(table).ok_or_else(|| ScanError::UnknownTable(table.to_string())),
+            None => self.tm.layout(table, tx),
+        }
+    }
+
+    pub(crate) fn create_view(&self, name: &str, def: &str, tx: Arc<Mutex<Transaction>>) -> Result<(), ScanError> {
+        self.change(&tx);
+        self.vm.create_view(name, def, tx)
+    }
+
+    pub(crate) fn drop_view(&self, name: &str, tx: Arc<Mutex<Transaction>>) -> Result<bool, ScanError> {
+        self.change(&tx);
+        self.vm.drop_view(name, tx)
+    }
+
+    pub(crate) fn view_def(&self, name: &str, tx: Arc<Mutex<Transaction>>) -> Result<Option<String>, ScanError> {
+        match self.snapshot(&tx)? {
+            Some(snapshot) => Ok(snapshot.view_def(name)),
+            None => self.vm.view_def(name, tx),
+        }
+    }
+
+    pub(crate) fn stat_info(&self, table: &str, tx: Arc<Mutex<Transaction>>) -> Result<StatInfo, ScanError> {
+        self.sm.stat_info(table, tx)
+    }
+
+    // Drops the cached statistics of a table or index whose contents have
+    // changed wholesale, so that the next plan measures them again.
+    pub(crate) fn forget_stats(&self, name: &str) {
+        self.sm.forget(name);
+    }
+
+    // An index with a non-empty predicate only holds the records that
+    // satisfy it.
+    pub(crate) fn create_index(&self, index: &str, table: &str, field: &str, kind: IndexKind, pred: &Predicate, tx: Arc<Mutex<Transaction>>) -> Result<(), ScanError> {
+        self.change(&tx);
+        let def = IndexDef { kind, pred: pred.clone(), fill_factor: Layout::DEFAULT_FILL_FACTOR };
+        self.im.create_index(index, table, field, &def, true, tx)
+    }
+
+    // Creates a hash index whose bucket pages only take inserts up to the
+    // fill factor, as `TableMgr::create_table_with_fill_factor` does for
+    // tables.
+    pub(crate) fn create_index_with_fill_factor(&self, index: &str, table: &str, field: &str, pred: &Predicate, fill_factor: i32, tx: Arc<Mutex<Transaction>>) -> Result<(), ScanError> {
+        self.change(&tx);
+        let def = IndexDef { kind: IndexKind::Hash, pred: pred.clone(), fill_factor };
+        self.im.create_index(index, table, field, &def, true, tx)
+    }
+
+    // Records an index that updates of the table maintain but queries do
+    // not use until it is published.
+    pub(crate) fn create_unpublished_index(&self, index: &str, table: &str, field: &str, kind: IndexKind, pred: &Predicate, tx: Arc<Mutex<Transaction>>) -> Result<(), ScanError> {
+        self.change(&tx);
+        let def = IndexDef { kind, pred: pred.clone(), fill_factor: Layout::DEFAULT_FILL_FACTOR };
+        self.im.create_index(index, table, field, &def, false, tx)
+    }
+
+    pub(crate) fn publish_index(&self, index: &str, tx: Arc<Mutex<Transaction>>) -> Result<bool, ScanError> {
+        self.change(&tx);
+        self.im.publish_index(index, tx)
+    }
+
+    pub(crate) fn drop_index(&self, index: &str, tx: Arc<Mutex<Transaction>>) -> Result<bool, ScanError> {
+        self.change(&tx);
+        self.sm.forget(index);
+        self.im.drop_index(index, tx)
+    }
+
+    pub(crate) fn index_exists(&self, index: &str, tx: Arc<Mutex<Transaction>>) -> Result<bool, ScanError> {
+        match self.snapshot(&tx)? {
+            Some(snapshot) => Ok(snapshot.index_exists(index)),
+            None => self.im.index_exists(index, tx),
+        }
+    }
+
+    // The indexes on the whole table that queries may use, keyed by the
+    // indexed field.
+    pub(crate) fn index_info(&self, table: &str, tx: Arc<Mutex<Transaction>>) -> Result<HashMap<String, IndexInfo>, ScanError> {
+        self.usable_indexes(table, &Predicate::new(), tx)
+    }
+
+    // The hash indexes a query with the predicate may use, keyed by the
+    // indexed field: the published ones whose predicate the query's
+    // implies, so that every record the query selects is in the index. Of
+    // several on one field, the one with the fewest index records is used.
+    pub(crate) fn usable_indexes(&self, table: &str, pred: &Predicate, tx: Arc<Mutex<Transaction>>) -> Result<HashMap<String, IndexInfo>, ScanError> {
+        self.usable_indexes_of_kind(table, IndexKind::Hash, pred, tx)
+    }
+
+    // The text indexes a query with the predicate may use, keyed by the
+    // indexed field, as for `usable_indexes`.
+    pub(crate) fn usable_text_indexes(&self, table: &str, pred: &Predicate, tx: Arc<Mutex<Transaction>>) -> Result<HashMap<String, IndexInfo>, ScanError> {
+        self.usable_indexes_of_kind(table, IndexKind::Text, pred, tx)
+    }
+
+    fn usable_indexes_of_kind(&self, table: &str, kind: IndexKind, pred: &Predicate, tx: Arc<Mutex<Transaction>>) -> Result<HashMap<String, IndexInfo>, ScanError> {
+        let mut usable: HashMap<String, IndexInfo> = HashMap::new();
+        for ii in self.published_indexes(table, tx)? {
+            if ii.kind() != kind || !pred.implies(ii.pred()) {
+                continue;
+            }
+            match usable.get(ii.field()) {
+                Some(other) if other.stats().records() <= ii.stats().records() => {}
+                _ => {
+                    usable.insert(ii.field().to_string(), ii);
+                }
+            }
+        }
+        Ok(usable)
+    }
+
+    fn published_indexes(&self, table: &str, tx: Arc<Mutex<Transaction>>) -> Result<Vec<IndexInfo>, ScanError> {
+        let Some(snapshot) = self.snapshot(&tx)? else {
+            return self.im.published_indexes(table, tx);
+        };
+        let indexes: Vec<(String, String)> = snapshot.indexes(table).iter().filter(|(index, _)| snapshot.is_published(index)).cloned().collect();
+        match snapshot.layout(table) {
+            Some(layout) if !indexes.is_empty() => self.im.index_info_of(table, &indexes, snapshot.index_defs(), &layout, tx),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    // The indexes an update of the table keeps up to date, published or
+    // not. A field may have more than one.
+    pub(crate) fn maintained_indexes(&self, table: &str, tx: Arc<Mutex<Transaction>>) -> Result<Vec<IndexInfo>, ScanError> {
+        let Some(snapshot) = self.snapshot(&tx)? else {
+            return self.im.maintained_indexes(table, tx);
+        };
+        let indexes = snapshot.indexes(table);
+        match snapshot.layout(table) {
+            Some(layout) if !indexes.is_empty() => self.im.index_info_of(table, indexes, snapshot.index_defs(), &layout, tx),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    // Reports the disk usage of every table in the catalog, each followed
+    // by its indexes in the order of their names.
+    pub(crate) fn disk_usage(&self, tx: Arc<Mutex<Transaction>>) -> Result<Vec<RelationSize>, ScanError> {
+        let mut indexes = self.im.all_indexes(tx.clone())?;
+        let mut usage = Vec::new();
+        for size in self.tm.disk_usage(tx.clone())? {
+            let mut table_indexes = indexes.remove(&size.table).unwrap_or_default();
+            table_indexes.sort();
+            let table = size.table.clone();
+            usage.push(size);
+            for (index, field) in table_indexes {
+                usage.push(self.im.index_size(&index, &table, &field, tx.clone())?);
+            }
+        }
+        Ok(usage)
+    }
+
+    pub(crate) fn create_schema(&self, name: &str, tx: Arc<Mutex<Transaction>>) -> Result<(), ScanError> {
+        self.change(&tx);
+        self.scm.create_schema(name, tx)
+    }
+
+    pub(crate) fn schema_exists(&self, name: &str, tx: Arc<Mutex<Transaction>>) -> Result<bool, ScanError> {
+        match self.snapshot(&tx)? {
+            Some(snapshot) => Ok(snapshot.schema_exists(name)),
+            None => self.scm.schema_exists(name, tx),
+        }
+    }
+
+    // Marks the transaction as changing the catalog, before it writes to
+    // the catalog tables.
+    fn change(&self, tx: &Arc<Mutex<Transaction>>) {
+        tx.lock().unwrap().change_catalog(self.version.clone());
+    }
+
+    // Returns the snapshot to answer a lookup from, reading a new one if
+    // the catalog changed since the last, or None if the transaction has
+    // changes of its own to see.
+    fn snapshot(&self, tx: &Arc<Mutex<Transaction>>) -> Result<Option<Arc<CatalogSnapshot>>, ScanError> {
+        if tx.lock().unwrap().changed_catalog() {
+            return Ok(None);
+        }
+        let version = self.version.load(Ordering::SeqCst);
+        if let Some(snapshot) = self.snapshot.read().unwrap().as_ref() && snapshot.version() == version {
+            return Ok(Some(snapshot.clone()));
+        }
+        let snapshot = Arc::new(self.read_catalog(version, tx.clone())?);
+        // A snapshot read concurrently at a later version is kept.
+        let mut current = self.snapshot.write().unwrap();
+        if current.as_ref().is_none_or(|current| current.version() <= version) {
+            *current = Some(snapshot.clone());
+        }
+        Ok(Some(snapshot))
+    }
+
+    // Returns the whole catalog as the transaction sees it.
+    pub(crate) fn catalog(&self, tx: Arc<Mutex<Transaction>>) -> Result<Arc<CatalogSnapshot>, ScanError> {
+        match self.snapshot(&tx)? {
+            Some(snapshot) => Ok(snapshot),
+            None => Ok(Arc::new(self.read_catalog(self.version.load(Ordering::SeqCst), tx)?)),
+        }
+    }
+
+    fn read_catalog(&self, version: u64, tx: Arc<Mutex<Transaction>>) -> Result<CatalogSnapshot, ScanError> {
+        Ok(CatalogSnapshot::new(
+            version,
+            self.tm.layouts(tx.clone())?,
+            self.vm.view_defs(tx.clone())?,
+            self.im.all_indexes(tx.clone())?,
+            self.im.unpublished(tx.clone())?,
+            self.im.index_defs(tx.clone())?,
+            self.scm.schemas(tx)?,
+        ))
+    }
+
+    pub(crate) fn table_mgr(&self) -> Arc<TableMgr> {
+        self.tm.clone()
+    }
+}
+
+mod tests {
+    use std::fs;
+    use super::*;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_catalog_snapshot() {
+        let _ = fs::remove_dir_all("catalogsnapshottestdb");
+        let db = SimpleDB::new("catalogsnapshottestdb", 400, 8).unwrap();
+        let mdm = db.metadata_mgr();
+        let mut schema = Schema::new();
+        schema.add_int_field("a");
+        let tx = db.new_tx().unwrap();
+        mdm.create_table("t", &schema, tx.clone()).unwrap();
+        mdm.create_index("ta", "t", "a", IndexKind::Hash, &Predicate::new(), tx.clone()).unwrap();
+        tx.lock().unwrap().commit().unwrap();
+
+        let reader = db.new_tx().unwrap();
+        assert_eq!(mdm.index_info("t", reader.clone()).unwrap().len(), 1);
+        reader.lock().unwrap().commit().unwrap();
+
+        // A transaction in the middle of changing the catalog sees its own
+        // changes, and holds locks on the catalog tables meanwhile.
+        let writer = db.new_tx().unwrap();
+        mdm.create_table("u", &schema, writer.clone()).unwrap();
+        assert!(mdm.layout("u", writer.clone()).is_ok());
+
+        // Other transactions read the committed catalog from the snapshot,
+        // without waiting for those locks.
+        let reader = db.new_tx().unwrap();
+        assert!(mdm.layout("t", reader.clone()).is_ok());
+        assert_eq!(mdm.layout("u", reader.clone()).err(), Some(ScanError::UnknownTable("u".to_string())));
+        assert!(mdm.index_exists("ta", reader.clone()).unwrap());
+        assert_eq!(mdm.view_def("t", reader.clone()), Ok(None));
+        reader.lock().unwrap().commit().unwrap();
+
+        // The commit makes the next lookup read the catalog again.
+        writer.lock().unwrap().commit().unwrap();
+        let reader = db.new_tx().unwrap();
+        assert!(mdm.layout("u", reader.clone()).is_ok());
+        reader.lock().unwrap().commit().unwrap();
+    }
+}
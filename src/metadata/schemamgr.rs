@@ -0,0 +1,79 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use crate::metadata::tablemgr::TableMgr;
+use crate::query::scan::Scan;
+use crate::record::layout::{Layout, Schema};
+use crate::record::scanerror::ScanError;
+use crate::record::tablescan::{TableScan, UpdateScan};
+use crate::tx::transaction::Transaction;
+
+// The SchemaMgr records each schema, a namespace for tables, views and
+// indexes, in the schemacat catalog table. An object in a schema is
+// catalogued under its qualified name, `schema.name`, except in the
+// default schema, which is not in schemacat and whose objects keep their
+// bare names, so a database created before schemas needs no change.
+pub(crate) struct SchemaMgr {
+    schema_catalog_layout: Arc<Layout>,
+}
+
+impl SchemaMgr {
+    pub(crate) const DEFAULT: &'static str = "public";
+
+    pub(crate) fn new(is_new: bool, tm: &TableMgr, tx: Arc<Mutex<Transaction>>) -> Result<SchemaMgr, ScanError> {
+        let mut schema_catalog_schema = Schema::new();
+        schema_catalog_schema.add_string_field("schema_name", TableMgr::MAX_NAME);
+        if is_new {
+            tm.create_table("schemacat", &schema_catalog_schema, tx)?;
+        }
+        Ok(SchemaMgr { schema_catalog_layout: Arc::new(Layout::new(schema_catalog_schema)) })
+    }
+
+    pub(crate) fn create_schema(&self, name: &str, tx: Arc<Mutex<Transaction>>) -> Result<(), ScanError> {
+        let mut schema_catalog = TableScan::new(tx, self.schema_catalog_layout.clone(), "schemacat")?;
+        schema_catalog.insert()?;
+        let result = schema_catalog.set_string("schema_name", name);
+        if result.is_err() {
+            schema_catalog.delete()?;
+        }
+        schema_catalog.close();
+        result
+    }
+
+    pub(crate) fn schema_exists(&self, name: &str, tx: Arc<Mutex<Transaction>>) -> Result<bool, ScanError> {
+        if name == Self::DEFAULT {
+            return Ok(true);
+        }
+        let mut schema_catalog = TableScan::new(tx, self.schema_catalog_layout.clone(), "schemacat")?;
+        let mut found = false;
+        while !found && schema_catalog.next()? {
+            found = schema_catalog.get_string("schema_name")? == name;
+        }
+        schema_catalog.close();
+        Ok(found)
+    }
+
+    // Reads the names of all schemas but the default one.
+    pub(crate) fn schemas(&self, tx: Arc<Mutex<Transaction>>) -> Result<HashSet<String>, ScanError> {
+        let mut schema_catalog = TableScan::new(tx, self.schema_catalog_layout.clone(), "schemacat")?;
+        let mut schemas = HashSet::new();
+        while schema_catalog.next()? {
+            schemas.insert(schema_catalog.get_string("schema_name")?);
+        }
+        schema_catalog.close();
+        Ok(schemas)
+    }
+
+    // The name the object is catalogued under.
+    pub(crate) fn qualify(schema: &str, name: &str) -> String {
+        if schema == Self::DEFAULT {
+            name.to_string()
+        } else {
+            format!("{}.{}", schema, name)
+        }
+    }
+
+    // Splits a catalogued or written name into its schema and bare name.
+    pub(crate) fn split(name: &str) -> (&str, &str) {
+        name.split_once('.').unwrap_or((Self::DEFAULT, name))
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use crate::index::hashindex::HashIndex;
+use crate::metadata::tablemgr::TableMgr;
+use crate::query::constant::Constant;
+use crate::query::scan::Scan;
+use crate::record::layout::Layout;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::FieldType;
+use crate::record::tablescan::TableScan;
+use crate::tx::transaction::Transaction;
+
+// The statistics of a table that the planner uses to estimate the cost of
+// a plan: the number of blocks, the number of records, and the number of
+// distinct values of each field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatInfo {
+    blocks: usize,
+    records: usize,
+    distinct: HashMap<String, usize>,
+}
+
+impl StatInfo {
+    pub fn blocks_accessed(&self) -> usize {
+        self.blocks
+    }
+
+    pub fn records_output(&self) -> usize {
+        self.records
+    }
+
+    // Returns at least 1, even for an empty table, so that estimates can be
+    // divided by it.
+    pub fn distinct_values(&self, field: &str) -> usize {
+        self.distinct.get(field).copied().unwrap_or(0).max(1)
+    }
+}
+
+// The statistics of an index, measured from its buckets: the number of
+// index records and of their distinct keys, the blocks of all the buckets,
+// the number of buckets in use, and the blocks of the longest bucket, the
+// most a search reads. A hash index has no tree, so these block counts
+// stand in for the height and leaf count of one: a search reads one
+// bucket, and a full scan reads every block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexStats {
+    records: usize,
+    distinct_keys: usize,
+    blocks: usize,
+    buckets: usize,
+    max_bucket_blocks: usize,
+}
+
+impl IndexStats {
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    pub fn distinct_keys(&self) -> usize {
+        self.distinct_keys
+    }
+
+    pub fn blocks(&self) -> usize {
+        self.blocks
+    }
+
+    pub fn buckets(&self) -> usize {
+        self.buckets
+    }
+
+    pub fn max_bucket_blocks(&self) -> usize {
+        self.max_bucket_blocks
+    }
+
+    // The blocks a search reads, on average over the buckets in use.
+    pub fn search_blocks(&self) -> usize {
+        if self.buckets == 0 { 0 } else { self.blocks.div_ceil(self.buckets) }
+    }
+}
+
+struct State {
+    table_stats: HashMap<String, StatInfo>,
+    index_stats: HashMap<String, IndexStats>,
+    num_calls: usize,
+}
+
+// The StatMgr keeps the statistics of each table and index in memory. They are
+// computed by scanning the table the first time they are asked for, and
+// are not updated as the table changes; instead, every `refresh_calls`
+// calls the whole cache is dropped, so that the statistics are computed
+// again from the current contents of each table the next time it is used.
+pub(crate) struct StatMgr {
+    tm: Arc<TableMgr>,
+    state: Mutex<State>,
+    refresh_calls: usize,
+}
+
+impl StatMgr {
+    const REFRESH_CALLS: usize = 100;
+
+    pub(crate) fn new(tm: Arc<TableMgr>) -> StatMgr {
+        StatMgr {
+            tm,
+            state: Mutex::new(State { table_stats: HashMap::new(), index_stats: HashMap::new(), num_calls: 0 }),
+            refresh_calls: Self::REFRESH_CALLS,
+        }
+    }
+
+    // Drops the statistics of a table or index, which no longer exists or
+    // has changed too much for them to be of use.
+    pub(crate) fn forget(&self, name: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.table_stats.remove(name);
+        state.index_stats.remove(name);
+    }
+
+    pub(crate) fn stat_info(&self, table: &str, tx: Arc<Mutex<Transaction>>) -> Result<StatInfo, ScanError> {
+        {
+            let mut state = self.count_call();
+            if let Some(info) = state.table_stats.get(table) {
+                return Ok(info.clone());
+            }
+        }
+        // The scan can wait on locks, so the cache is not held meanwhile.
+        let info = self.calc_table_stats(table, tx)?;
+        self.state.lock().unwrap().table_stats.insert(table.to_string(), info.clone());
+        Ok(info)
+    }
+
+    // The index records are stored with the layout.
+    pub(crate) fn index_stats(&self, index: &str, layout: &Arc<Layout>, tx: Arc<Mutex<Transaction>>) -> Result<IndexStats, ScanError> {
+        if let Some(stats) = self.count_call().index_stats.get(index) {
+            return Ok(stats.clone());
+        }
+        let stats = self.calc_index_stats(index, layout, tx)?;
+        self.state.lock().unwrap().index_stats.insert(index.to_string(), stats.clone());
+        Ok(stats)
+    }
+
+    fn count_call(&self) -> std::sync::MutexGuard<'_, State> {
+        let mut state = self.state.lock().unwrap();
+        state.num_calls += 1;
+        if state.num_calls > self.refresh_calls {
+            state.table_stats.clear();
+            state.index_stats.clear();
+            state.num_calls = 0;
+        }
+        state
+    }
+
+    // Scans every bucket of the index that has a file. A key always hashes
+    // to the same bucket, so the distinct keys are counted one bucket at a
+    // time, and only those of one bucket are kept in memory.
+    fn calc_index_stats(&self, index: &str, layout: &Arc<Layout>, tx: Arc<Mutex<Transaction>>) -> Result<IndexStats, ScanError> {
+        let mut stats = IndexStats { records: 0, distinct_keys: 0, blocks: 0, buckets: 0, max_bucket_blocks: 0 };
+        for bucket in 0..HashIndex::NUM_BUCKETS {
+            let table = format!("{}{}", index, bucket);
+            let blocks = tx.lock().unwrap().size(&format!("{}.tbl", table))?;
+            if blocks == 0 {
+                continue;
+            }
+            let mut records = 0;
+            let mut keys = HashSet::new();
+            let mut ts = TableScan::new(tx.clone(), layout.clone(), &table)?;
+            while ts.next()? {
+                records += 1;
+                keys.insert(ts.get_val("dataval")?);
+            }
+            ts.close();
+            if records > 0 {
+                stats.buckets += 1;
+            }
+            stats.records += records;
+            stats.distinct_keys += keys.len();
+            stats.blocks += blocks;
+            stats.max_bucket_blocks = stats.max_bucket_blocks.max(blocks);
+        }
+        Ok(stats)
+    }
+
+    // A table that was never written to has no file yet and is not
+    // scanned, since opening a scan on it would allocate its first block.
+    // The distinct values of each field are estimated with a sketch of
+    // bounded size, and nulls are not counted among them. Values kept in
+    // the overflow blocks are not read at all, since that would read each
+    // one in full; a field of them is assumed to have 1 + records/3
+    // distinct values, the textbook guess.
+    fn calc_table_stats(&self, table: &str, tx: Arc<Mutex<Transaction>>) -> Result<StatInfo, ScanError> {
+        let layout = self.tm.layout(table, tx.clone())?;
+        let blocks = tx.lock().unwrap().size(&format!("{}.tbl", table))?;
+        let (in_place, overflow): (Vec<&Arc<str>>, Vec<&Arc<str>>) = layout.schema().fields().iter()
+            .partition(|field| layout.schema().ftype(field) != FieldType::BLOB as i32 && !layout.is_spilled(field));
+        let mut records = 0;
+        let mut sketches: Vec<DistinctSketch> = in_place.iter().map(|_| DistinctSketch::default()).collect();
+        if blocks > 0 {
+            let mut ts = TableScan::new(tx.clone(), layout.clone(), table)?;
+            while ts.next()? {
+                records += 1;
+                for (field, sketch) in in_place.iter().zip(sketches.iter_mut()) {
+                    let val = ts.get_val(field)?;
+                    if !val.is_null() {
+                        sketch.add(&val);
+                    }
+                }
+            }
+            ts.close();
+        }
+        let mut distinct: HashMap<String, usize> = in_place.iter().zip(&sketches).map(|(field, sketch)| (field.to_string(), sketch.estimate().min(records))).collect();
+        distinct.extend(overflow.iter().map(|field| (field.to_string(), 1 + records / 3)));
+        Ok(StatInfo { blocks, records, distinct })
+    }
+}
+
+// A k-minimum-values sketch of the distinct values of a field. It keeps
+// the K smallest hashes of the values added: while there are fewer than K
+// distinct values it counts them exactly, and past that it estimates their
+// number from how densely the kept hashes cover the range of hashes, with
+// a standard error of about 1/sqrt(K).
+#[derive(Default)]
+struct DistinctSketch {
+    hashes: BTreeSet<u64>,
+}
+
+impl DistinctSketch {
+    const K: usize = 256;
+
+    fn add(&mut self, val: &Constant) {
+        // The default hasher has fixed keys, so estimates do not change
+        // between runs.
+        let mut hasher = DefaultHasher::new();
+        val.hash(&mut hasher);
+        let hash = hasher.finish();
+        if self.hashes.len() < Self::K {
+            self.hashes.insert(hash);
+        } else if self.hashes.last().is_some_and(|&max| hash < max) && self.hashes.insert(hash) {
+            self.hashes.pop_last();
+        }
+    }
+
+    fn estimate(&self) -> usize {
+        match self.hashes.last() {
+            Some(&max) if self.hashes.len() == Self::K => ((Self::K - 1) as f64 * (u64::MAX as f64 / max as f64)) as usize,
+            _ => self.hashes.len(),
+        }
+    }
+}
+
+mod tests {
+    use std::fs;
+    use super::*;
+    use crate::record::schema::Schema;
+    use crate::record::tablescan::UpdateScan;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_stat_info() {
+        let _ = fs::remove_dir_all("statmgrtestdb");
+        let db = SimpleDB::new("statmgrtestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let tm = db.metadata_mgr().table_mgr();
+        let mut sm = StatMgr::new(tm.clone());
+        sm.refresh_calls = 2;
+
+        let mut schema = Schema::new();
+        schema.add_int_field("a");
+        schema.add_string_field("b", 10);
+        tm.create_table("t", &schema, tx.clone()).unwrap();
+        let insert = |range: std::ops::Range<i32>| {
+            let mut ts = TableScan::new(tx.clone(), tm.layout("t", tx.clone()).unwrap(), "t").unwrap();
+            for i in range {
+                ts.insert().unwrap();
+                ts.set_int("a", i % 4).unwrap();
+                ts.set_string("b", &format!("rec{}", i)).unwrap();
+            }
+            ts.close();
+        };
+
+        let info = sm.stat_info("t", tx.clone()).unwrap();
+        assert_eq!((info.blocks_accessed(), info.records_output()), (0, 0));
+        assert_eq!(info.distinct_values("a"), 1);
+
+        insert(0..40);
+        // Still cached: the statistics only change on a refresh.
+        assert_eq!(sm.stat_info("t", tx.clone()).unwrap(), info);
+
+        let info = sm.stat_info("t", tx.clone()).unwrap();
+        assert_eq!(info.records_output(), 40);
+        assert!(info.blocks_accessed() > 1);
+        assert_eq!(info.distinct_values("a"), 4);
+        assert_eq!(info.distinct_values("b"), 40);
+
+        assert_eq!(sm.stat_info("nosuchtable", tx.clone()).err(), Some(ScanError::UnknownTable("nosuchtable".to_string())));
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_distinct_estimates() {
+        let db = SimpleDB::ephemeral().unwrap();
+        let tx = db.new_tx().unwrap();
+        let tm = db.metadata_mgr().table_mgr();
+        let sm = StatMgr::new(tm.clone());
+        let mut schema = Schema::new();
+        schema.add_int_field("a");
+        schema.add_int_field("b");
+        schema.add_blob_field("c");
+        tm.create_table("t", &schema, tx.clone()).unwrap();
+        let mut ts = TableScan::new(tx.clone(), tm.layout("t", tx.clone()).unwrap(), "t").unwrap();
+        for i in 0..3000 {
+            ts.insert().unwrap();
+            ts.set_int("a", i).unwrap();
+            if i % 2 == 0 {
+                ts.set_int("b", i % 100).unwrap();
+            } else {
+                ts.set_null("b").unwrap();
+            }
+            ts.set_blob("c", &[i as u8]).unwrap();
+        }
+        ts.close();
+
+        // Past the size of the sketch, the count is an estimate. Nulls are
+        // not a value, and blobs are not read.
+        let info = sm.stat_info("t", tx.clone()).unwrap();
+        let a = info.distinct_values("a");
+        assert!((2400..=3000).contains(&a), "estimated {} distinct values of 3000", a);
+        assert_eq!(info.distinct_values("b"), 50);
+        assert_eq!(info.distinct_values("c"), 1001);
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
@@ -1,23 +1,59 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
+use crate::file::page::Page;
+use crate::parse::parser::Parser;
+use crate::record::check::{Check, CompareOp};
+use crate::error::dberror::DbError;
+use crate::record::scanerror::ScanError;
 use crate::tx::transaction::Transaction;
-use crate::record::layout::{Layout, Schema};
+use crate::record::layout::{Layout, RecordFormat, Schema};
+use crate::record::recordpage::RecordPage;
+use crate::record::schema::FieldType;
+use crate::query::scan::Scan;
 use crate::record::tablescan::{TableScan, UpdateScan};
 
-struct TableMgr {
-    table_catalog_layout: Layout,
-    field_catalog_layout: Layout,
+// The disk usage of a table, or of an index on it, which `index` names.
+// Slots left empty by deleted records are counted as free; they are what a
+// vacuum could reclaim. The quota is the number of blocks the table may
+// grow to, if it is limited; indexes have none.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelationSize {
+    pub table: String,
+    pub index: Option<String>,
+    pub blocks: usize,
+    pub bytes: usize,
+    pub records: usize,
+    pub free_slots: usize,
+    pub quota: Option<usize>,
+}
+
+// The TableMgr records each table in the tblcat catalog table, each of its
+// fields in fldcat, and the NOT NULL and CHECK constraints on its fields in
+// constraintcat, one record per constraint. A NOT NULL constraint is stored
+// with the kind 0; a CHECK constraint with the code of its operator as the
+// kind and the text of its constant. A VARCHAR field whose value is kept
+// in the table's overflow blocks, so that a record fits in a block, is
+// marked as spilled in fldcat. Table and field names are compared exactly:
+// it is the lexer that folds unquoted identifiers to lower case.
+pub(crate) struct TableMgr {
+    table_catalog_layout: Arc<Layout>,
+    field_catalog_layout: Arc<Layout>,
+    constraint_catalog_layout: Arc<Layout>,
 }
 
 impl TableMgr {
 
-    const MAX_NAME: i32 = 16;
+    pub(crate) const MAX_NAME: i32 = 16;
+    const MAX_CHECK_VALUE: i32 = 32;
+    const NOT_NULL: i32 = 0;
 
-    fn new (is_new: bool, tx: Arc<Mutex<Transaction>>) -> TableMgr {
+    pub(crate) fn new (is_new: bool, tx: Arc<Mutex<Transaction>>) -> Result<TableMgr, ScanError> {
         let mut table_catalog_schema = Schema::new();
         table_catalog_schema.add_string_field("table_name", TableMgr::MAX_NAME);
         table_catalog_schema.add_int_field("slot_size");
-        let table_catalog_layout = Layout::new(table_catalog_schema.clone());
+        table_catalog_schema.add_int_field("fill_factor");
+        table_catalog_schema.add_int_field("record_format");
+        let table_catalog_layout = Arc::new(Layout::new(table_catalog_schema.clone()));
 
         let mut field_catalog_schema = Schema::new();
         field_catalog_schema.add_string_field("table_name", TableMgr::MAX_NAME);
@@ -25,65 +61,237 @@ impl TableMgr {
         field_catalog_schema.add_int_field("field_type");
         field_catalog_schema.add_int_field("field_length");
         field_catalog_schema.add_int_field("field_offset");
-        let field_catalog_layout = Layout::new(field_catalog_schema.clone());
+        field_catalog_schema.add_int_field("field_spilled");
+        let field_catalog_layout = Arc::new(Layout::new(field_catalog_schema.clone()));
+
+        let mut constraint_catalog_schema = Schema::new();
+        constraint_catalog_schema.add_string_field("table_name", TableMgr::MAX_NAME);
+        constraint_catalog_schema.add_string_field("field_name", TableMgr::MAX_NAME);
+        constraint_catalog_schema.add_int_field("kind");
+        constraint_catalog_schema.add_string_field("value", TableMgr::MAX_CHECK_VALUE);
+        let constraint_catalog_layout = Arc::new(Layout::new(constraint_catalog_schema.clone()));
 
-        let tm = TableMgr { table_catalog_layout, field_catalog_layout };
+        let tm = TableMgr { table_catalog_layout, field_catalog_layout, constraint_catalog_layout };
 
         if is_new {
-            tm.create_table("tblcat", &table_catalog_schema, tx.clone());
-            tm.create_table("fldcat", &field_catalog_schema, tx.clone());
+            tm.create_table("tblcat", &table_catalog_schema, tx.clone())?;
+            tm.create_table("fldcat", &field_catalog_schema, tx.clone())?;
+            tm.create_table("constraintcat", &constraint_catalog_schema, tx.clone())?;
         }
 
-        tm
+        Ok(tm)
+    }
+
+    pub(crate) fn create_table(&self, name: &str, schema: &Schema, tx: Arc<Mutex<Transaction>>) -> Result<(), ScanError> {
+        self.create_table_with_fill_factor(name, schema, Layout::DEFAULT_FILL_FACTOR, tx)
+    }
+
+    // Creates a table whose pages only take inserts up to the fill factor,
+    // a percentage of their slots. It is stored in the catalog and applied
+    // to every layout read back for the table.
+    pub(crate) fn create_table_with_fill_factor(&self, name: &str, schema: &Schema, fill_factor: i32, tx: Arc<Mutex<Transaction>>) -> Result<(), ScanError> {
+        self.create_table_with_layout(name, &Layout::new(schema.clone()).with_fill_factor(fill_factor), tx)
+    }
+
+    // Creates a table whose records are stored in the format, which, like
+    // the fill factor, is kept in the catalog.
+    pub(crate) fn create_table_with_format(&self, name: &str, schema: &Schema, format: RecordFormat, tx: Arc<Mutex<Transaction>>) -> Result<(), ScanError> {
+        self.create_table_with_layout(name, &Layout::new(schema.clone()).with_format(format), tx)
     }
 
-    fn create_table(&self, name: &str, schema: &Schema, tx: Arc<Mutex<Transaction>>) {
-        let layout = Layout::new(schema.clone());
-        let mut table_catalog = TableScan::new(tx.clone(), self.table_catalog_layout.clone(), "tblcat");
-        table_catalog.insert();
-        table_catalog.set_string("table_name", name);
-        table_catalog.set_int("slot_size", layout.slot_size());
+    // The layout is fitted to the block size first, and a table whose
+    // records do not fit in a block even then is refused.
+    fn create_table_with_layout(&self, name: &str, layout: &Layout, tx: Arc<Mutex<Transaction>>) -> Result<(), ScanError> {
+        let block_size = tx.lock().unwrap().block_size();
+        let layout = &layout.clone().fit(block_size);
+        if !RecordPage::fits(layout, block_size) {
+            return Err(ScanError::RecordTooWide { table: name.to_string(), size: layout.slot_size() });
+        }
+        let schema = layout.schema();
+        let mut table_catalog = TableScan::new(tx.clone(), self.table_catalog_layout.clone(), "tblcat")?;
+        table_catalog.insert()?;
+        table_catalog.set_string("table_name", name)?;
+        table_catalog.set_int("slot_size", layout.slot_size())?;
+        table_catalog.set_int("fill_factor", layout.fill_factor())?;
+        table_catalog.set_int("record_format", layout.format() as i32)?;
         table_catalog.close();
 
-        let mut field_catalog = TableScan::new(tx.clone(), self.field_catalog_layout.clone(), "fldcat");
+        let mut field_catalog = TableScan::new(tx.clone(), self.field_catalog_layout.clone(), "fldcat")?;
         for field_name in schema.fields() {
-            field_catalog.insert();
-            field_catalog.set_string("table_name", name);
-            field_catalog.set_string("field_name", &field_name);
-            field_catalog.set_int("field_type", schema.ftype(&field_name));
-            field_catalog.set_int("field_length", schema.length(&field_name));
-            field_catalog.set_int("field_offset", layout.offset(&field_name));
+            field_catalog.insert()?;
+            field_catalog.set_string("table_name", name)?;
+            field_catalog.set_string("field_name", field_name)?;
+            field_catalog.set_int("field_type", schema.ftype(field_name))?;
+            field_catalog.set_int("field_length", schema.length(field_name))?;
+            field_catalog.set_int("field_offset", layout.offset(field_name))?;
+            field_catalog.set_int("field_spilled", layout.is_spilled(field_name) as i32)?;
         }
         field_catalog.close();
+
+        let mut constraint_catalog = TableScan::new(tx.clone(), self.constraint_catalog_layout.clone(), "constraintcat")?;
+        for field_name in schema.fields() {
+            let not_null = schema.is_not_null(field_name).then_some((Self::NOT_NULL, String::new()));
+            let checks = schema.checks(field_name).iter().map(|check| (check.op() as i32, check.val().to_string()));
+            for (kind, value) in not_null.into_iter().chain(checks) {
+                constraint_catalog.insert()?;
+                constraint_catalog.set_string("table_name", name)?;
+                constraint_catalog.set_string("field_name", field_name)?;
+                constraint_catalog.set_int("kind", kind)?;
+                constraint_catalog.set_string("value", &value)?;
+            }
+        }
+        constraint_catalog.close();
+        Ok(())
+    }
+
+    // Removes the table from the catalog and deletes its file when the
+    // transaction commits. Its indexes are left to the caller.
+    pub(crate) fn drop_table(&self, name: &str, tx: Arc<Mutex<Transaction>>) -> Result<(), ScanError> {
+        let mut found = false;
+        let mut table_catalog = TableScan::new(tx.clone(), self.table_catalog_layout.clone(), "tblcat")?;
+        while table_catalog.next()? {
+            if table_catalog.get_string("table_name")? == name {
+                table_catalog.delete()?;
+                found = true;
+            }
+        }
+        table_catalog.close();
+        if !found {
+            return Err(ScanError::UnknownTable(name.to_string()));
+        }
+
+        for (catalog, layout) in [("fldcat", &self.field_catalog_layout), ("constraintcat", &self.constraint_catalog_layout)] {
+            let mut ts = TableScan::new(tx.clone(), layout.clone(), catalog)?;
+            while ts.next()? {
+                if ts.get_string("table_name")? == name {
+                    ts.delete()?;
+                }
+            }
+            ts.close();
+        }
+        tx.lock().unwrap().delete_file(&format!("{}.tbl", name))?;
+        tx.lock().unwrap().delete_file(&format!("{}.blob", name))?;
+        Ok(())
+    }
+
+    // Reads the layout of the table back from the catalog.
+    pub(crate) fn layout(&self, table: &str, tx: Arc<Mutex<Transaction>>) -> Result<Arc<Layout>, ScanError> {
+        self.read_layouts(Some(table), tx)?.remove(table).ok_or_else(|| ScanError::UnknownTable(table.to_string()))
     }
 
+    // Reads the layouts of all tables in the catalog, keyed by table name.
+    pub(crate) fn layouts(&self, tx: Arc<Mutex<Transaction>>) -> Result<HashMap<String, Arc<Layout>>, ScanError> {
+        self.read_layouts(None, tx)
+    }
 
-    fn layout(&self, table: &str, tx: Arc<Mutex<Transaction>>) -> Layout {
-        let mut slot_size = -1;
-        let mut table_catalog = TableScan::new(tx.clone(), self.table_catalog_layout.clone(), "tblcat");
-        while table_catalog.next() {
-            if table_catalog.get_string("table_name") == table {
-                slot_size = table_catalog.get_int("slot_size");
-                break;
+    // Each catalog table is scanned once, whether for one table or all.
+    fn read_layouts(&self, only: Option<&str>, tx: Arc<Mutex<Transaction>>) -> Result<HashMap<String, Arc<Layout>>, ScanError> {
+        let wanted = |table: &str| only.is_none_or(|only| only == table);
+        let mut tables = HashMap::new();
+        let mut table_catalog = TableScan::new(tx.clone(), self.table_catalog_layout.clone(), "tblcat")?;
+        while table_catalog.next()? {
+            let table = table_catalog.get_string("table_name")?;
+            if wanted(&table) {
+                let slot_size = table_catalog.get_int("slot_size")?;
+                let fill_factor = table_catalog.get_int("fill_factor")?;
+                let format = table_catalog.get_int("record_format")?;
+                let format = RecordFormat::try_from(format).map_err(|format| DbError::Corruption(format!("unknown record format {}", format)))?;
+                tables.insert(table, (slot_size, fill_factor, format, Schema::new(), HashMap::new(), HashSet::new()));
             }
         }
         table_catalog.close();
+        if tables.is_empty() {
+            return Ok(HashMap::new());
+        }
 
-        let mut schema = Schema::new();
-        let mut offsets = HashMap::new();
-        let mut fc = TableScan::new(tx.clone(), self.field_catalog_layout.clone(), "fldcat");
-        while fc.next() {
-            if fc.get_string("table_name") == table {
-                let field_name = fc.get_string("field_name");
-                let field_type = fc.get_int("field_type");
-                let field_length = fc.get_int("field_length");
-                let field_offset = fc.get_int("field_offset");
+        let mut fc = TableScan::new(tx.clone(), self.field_catalog_layout.clone(), "fldcat")?;
+        while fc.next()? {
+            if let Some((_, _, _, schema, offsets, spilled)) = tables.get_mut(&fc.get_string("table_name")?) {
+                let field_name = fc.get_string("field_name")?;
+                let field_type = fc.get_int("field_type")?;
+                let field_length = fc.get_int("field_length")?;
+                let field_offset = fc.get_int("field_offset")?;
                 offsets.insert(field_name.clone(), field_offset);
+                if fc.get_int("field_spilled")? != 0 {
+                    spilled.insert(field_name.clone());
+                }
                 schema.add_field(&field_name, field_type, field_length);
             }
         }
         fc.close();
-        Layout::from(schema, offsets, slot_size)
+
+        let mut cc = TableScan::new(tx.clone(), self.constraint_catalog_layout.clone(), "constraintcat")?;
+        while cc.next()? {
+            if let Some((_, _, _, schema, _, _)) = tables.get_mut(&cc.get_string("table_name")?) {
+                let field_name = cc.get_string("field_name")?;
+                match cc.get_int("kind")? {
+                    Self::NOT_NULL => schema.set_not_null(&field_name),
+                    kind => {
+                        let check = Self::check(kind, &cc.get_string("value")?, schema.ftype(&field_name))?;
+                        schema.add_check(&field_name, check);
+                    }
+                }
+            }
+        }
+        cc.close();
+        Ok(tables.into_iter().map(|(table, (slot_size, fill_factor, format, schema, offsets, spilled))| {
+            (table, Arc::new(Layout::from(schema, offsets, slot_size, spilled).with_fill_factor(fill_factor).with_format(format)))
+        }).collect())
+    }
+
+    // The constant was written from a parsed one, so it parses back, though
+    // perhaps as a narrower type than the field's: a BIGINT 0 reads as an int.
+    fn check(kind: i32, value: &str, ftype: i32) -> Result<Check, ScanError> {
+        let op = CompareOp::try_from(kind).map_err(|kind| DbError::Corruption(format!("unknown constraint kind {}", kind)))?;
+        let val = Parser::new(value).and_then(|mut parser| parser.constant()).expect("a stored check constant parses");
+        let val = FieldType::try_from(ftype).ok().and_then(|ftype| val.coerce(ftype))
+            .ok_or_else(|| DbError::Corruption(format!("check constant {} does not match its field", value)))?;
+        Ok(Check::new(op, val))
+    }
+
+    // Reports the disk usage of every table in the catalog, including the
+    // catalog tables themselves.
+    pub(crate) fn disk_usage(&self, tx: Arc<Mutex<Transaction>>) -> Result<Vec<RelationSize>, ScanError> {
+        let mut tables = Vec::new();
+        let mut table_catalog = TableScan::new(tx.clone(), self.table_catalog_layout.clone(), "tblcat")?;
+        while table_catalog.next()? {
+            tables.push(table_catalog.get_string("table_name")?);
+        }
+        table_catalog.close();
+        tables.iter().map(|table| self.relation_size(table, tx.clone())).collect()
+    }
+
+    pub(crate) fn relation_size(&self, table: &str, tx: Arc<Mutex<Transaction>>) -> Result<RelationSize, ScanError> {
+        let layout = self.layout(table, tx.clone())?;
+        let (blocks, records, free_slots) = Self::file_size(table, &layout, tx.clone())?;
+        let (block_size, quota) = {
+            let tx = tx.lock().unwrap();
+            (tx.block_size(), tx.table_quota(table))
+        };
+        Ok(RelationSize { table: table.to_string(), index: None, blocks, bytes: blocks * block_size, records, free_slots, quota })
+    }
+
+    // Returns the blocks, records and free slots of the file of records,
+    // counting the records by scanning it. The free slots of a
+    // variable-length file are only an estimate, made as if its records
+    // took whole slots. A file that was never written to does not exist
+    // yet and is not scanned, since opening a scan on it would allocate
+    // its first block.
+    pub(crate) fn file_size(table: &str, layout: &Arc<Layout>, tx: Arc<Mutex<Transaction>>) -> Result<(usize, usize, usize), ScanError> {
+        let (blocks, block_size) = {
+            let mut tx = tx.lock().unwrap();
+            (tx.size(&format!("{}.tbl", table))?, tx.block_size())
+        };
+        let mut records = 0;
+        if blocks > 0 {
+            let mut ts = TableScan::new(tx, layout.clone(), table)?;
+            while ts.next()? {
+                records += 1;
+            }
+            ts.close();
+        }
+        let slots_per_block = (block_size - Page::HEADER_SIZE) / layout.slot_size() as usize;
+        Ok((blocks, records, (blocks * slots_per_block).saturating_sub(records)))
     }
 }
 
@@ -92,27 +300,30 @@ mod tests {
     use crate::buffer::buffermgr::BufferMgr;
     use crate::file::filemgr::FileMgr;
     use crate::log::logmgr::LogMgr;
+    use crate::tx::admission::AdmissionCtl;
+    use crate::query::constant::Constant;
     use super::*;
 
     #[test]
     fn test_table_mgr() {
-        let fm = Arc::new(FileMgr::new(PathBuf::from("tablescantestdb"), 400));
-        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log")));
+        let fm = Arc::new(FileMgr::new(PathBuf::from("tablescantestdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
         let bm = Arc::new(Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 3)));
-        let mut tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone())));
-        let tm = TableMgr::new(true, tx.clone());
+        let mut tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap()));
+        let tm = TableMgr::new(true, tx.clone()).unwrap();
 
         let mut schema = Schema::new();
         schema.add_int_field("A");
         schema.add_string_field("B", 9);
         schema.add_int_field("C");
-        tm.create_table("MyTable", &schema, tx.clone());
+        tm.create_table("MyTable", &schema, tx.clone()).unwrap();
 
-        let layout = tm.layout("MyTable", tx.clone());
-        assert_eq!(layout.offset("A"), 4);
-        assert_eq!(layout.offset("B"), 8);
-        assert_eq!(layout.offset("C"), 21);
-        assert_eq!(layout.slot_size(), 25);
+        let layout = tm.layout("MyTable", tx.clone()).unwrap();
+        assert_eq!(layout.offset("A"), 8);
+        assert_eq!(layout.offset("B"), 12);
+        assert_eq!(layout.offset("C"), 25);
+        assert_eq!(layout.slot_size(), 29);
+        assert_eq!(layout.null_bit("C"), (4, 4));
         let schema = layout.schema();
         assert_eq!(schema.ftype("A"), 4);
         assert_eq!(schema.ftype("B"), 12);
@@ -121,14 +332,172 @@ mod tests {
 
         println!("MyTable fields:");
         for fname in schema.fields() {
-            match schema.ftype(&fname) {
+            match schema.ftype(fname) {
                 4 => println!("{}: int", fname),
                 12 => println!("{}: varchar({})", fname, schema.length(&fname)),
                 _ => {}
             }
         }
 
-        tx.lock().unwrap().commit();
+        tx.lock().unwrap().commit().unwrap();
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_disk_usage() {
+        let fm = Arc::new(FileMgr::new(PathBuf::from("diskusagetestdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
+        let bm = Arc::new(Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 8)));
+        let tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap()));
+        let tm = TableMgr::new(true, tx.clone()).unwrap();
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        tm.create_table("T", &schema, tx.clone()).unwrap();
+        tm.create_table("Empty", &schema, tx.clone()).unwrap();
+
+        let mut ts = TableScan::new(tx.clone(), tm.layout("T", tx.clone()).unwrap(), "T").unwrap();
+        for i in 0..10 {
+            ts.insert().unwrap();
+            ts.set_int("A", i).unwrap();
+        }
+        ts.before_first().unwrap();
+        while ts.next().unwrap() {
+            if ts.get_int("A").unwrap() % 2 == 0 {
+                ts.delete().unwrap();
+            }
+        }
+        ts.close();
+
+        let usage = tm.disk_usage(tx.clone()).unwrap();
+        let tables: Vec<&str> = usage.iter().map(|size| size.table.as_str()).collect();
+        assert_eq!(tables, vec!["tblcat", "fldcat", "constraintcat", "T", "Empty"]);
+
+        // 12-byte slots after the 4-byte page header: 33 per 400-byte block.
+        let t = &usage[3];
+        assert_eq!((t.blocks, t.bytes, t.records, t.free_slots), (1, 400, 5, 28));
+        let empty = &usage[4];
+        assert_eq!((empty.blocks, empty.records, empty.free_slots), (0, 0, 0));
+
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_fill_factor() {
+        let fm = Arc::new(FileMgr::new(PathBuf::from("fillfactortestdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
+        let bm = Arc::new(Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 8)));
+        let tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap()));
+        let tm = TableMgr::new(true, tx.clone()).unwrap();
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        tm.create_table_with_fill_factor("T", &schema, 50, tx.clone()).unwrap();
+        let layout = tm.layout("T", tx.clone()).unwrap();
+        assert_eq!(layout.fill_factor(), 50);
+
+        // 49 slots per block, of which inserts may fill 24.
+        let mut ts = TableScan::new(tx.clone(), layout, "T").unwrap();
+        for i in 0..30 {
+            ts.insert().unwrap();
+            ts.set_int("A", i).unwrap();
+        }
+        ts.close();
+        let usage = tm.relation_size("T", tx.clone()).unwrap();
+        assert_eq!((usage.blocks, usage.records), (2, 30));
+
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_wide_records() {
+        let _ = std::fs::remove_dir_all("widerecordtestdb");
+        let fm = Arc::new(FileMgr::new(PathBuf::from("widerecordtestdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
+        let bm = Arc::new(Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 8)));
+        let tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap()));
+        let tm = TableMgr::new(true, tx.clone()).unwrap();
+
+        // A 420-byte slot does not fit in a block, so B is spilled.
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 200);
+        schema.add_string_field("C", 200);
+        tm.create_table("Wide", &schema, tx.clone()).unwrap();
+        let layout = tm.layout("Wide", tx.clone()).unwrap();
+        assert!(layout.is_spilled("B") && !layout.is_spilled("C"));
+        assert_eq!(layout.slot_size(), 224);
+
+        let mut ts = TableScan::new(tx.clone(), layout, "Wide").unwrap();
+        for i in 0..5 {
+            ts.insert().unwrap();
+            ts.set_int("A", i).unwrap();
+            ts.set_string("B", &format!("{}{}", "b".repeat(190), i)).unwrap();
+            ts.set_string("C", &format!("{}{}", "c".repeat(190), i)).unwrap();
+        }
+        ts.before_first().unwrap();
+        while ts.next().unwrap() {
+            let a = ts.get_int("A").unwrap();
+            assert_eq!(ts.get_string("B").unwrap(), format!("{}{}", "b".repeat(190), a));
+            assert_eq!(ts.get_string("C").unwrap(), format!("{}{}", "c".repeat(190), a));
+            match a {
+                0 => ts.set_string("B", "short").unwrap(),
+                1 => ts.set_null("B").unwrap(),
+                2 => ts.delete().unwrap(),
+                _ => {}
+            }
+        }
+        ts.before_first().unwrap();
+        let mut values = Vec::new();
+        while ts.next().unwrap() {
+            values.push((ts.get_int("A").unwrap(), ts.get_row().unwrap().get("B").cloned().unwrap()));
+        }
+        ts.close();
+        assert_eq!(values[..2], [(0, Constant::String("short".to_string())), (1, Constant::Null)]);
+        assert_eq!(values.iter().map(|(a, _)| *a).collect::<Vec<_>>(), [0, 1, 3, 4]);
+        assert!(tx.lock().unwrap().size("Wide.blob").unwrap() > 0);
+
+        // Records of integers cannot be made shorter.
+        let mut schema = Schema::new();
+        for i in 0..100 {
+            schema.add_int_field(&format!("f{}", i));
+        }
+        assert_eq!(tm.create_table("Ints", &schema, tx.clone()), Err(ScanError::RecordTooWide { table: "Ints".to_string(), size: 420 }));
+        let mut ts = TableScan::new(tx.clone(), Arc::new(Layout::new(schema)), "Ints").unwrap();
+        assert_eq!(ts.insert(), Err(ScanError::RecordTooWide { table: "Ints".to_string(), size: 420 }));
+        ts.close();
+
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_record_format() {
+        let _ = std::fs::remove_dir_all("recordformattestdb");
+        let fm = Arc::new(FileMgr::new(PathBuf::from("recordformattestdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
+        let bm = Arc::new(Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 8)));
+        let tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap()));
+        let tm = TableMgr::new(true, tx.clone()).unwrap();
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 100);
+        tm.create_table("F", &schema, tx.clone()).unwrap();
+        tm.create_table_with_format("V", &schema, RecordFormat::Variable, tx.clone()).unwrap();
+        assert_eq!(tm.layout("F", tx.clone()).unwrap().format(), RecordFormat::Fixed);
+        let layout = tm.layout("V", tx.clone()).unwrap();
+        assert_eq!(layout.format(), RecordFormat::Variable);
+
+        // The 112-byte slots would take 3 blocks for 10 records.
+        let mut ts = TableScan::new(tx.clone(), layout, "V").unwrap();
+        for i in 0..10 {
+            ts.insert().unwrap();
+            ts.set_int("A", i).unwrap();
+            ts.set_string("B", "short").unwrap();
+        }
+        ts.close();
+        let usage = tm.relation_size("V", tx.clone()).unwrap();
+        assert_eq!((usage.blocks, usage.records), (1, 10));
+
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
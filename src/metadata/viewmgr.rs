@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use crate::metadata::tablemgr::TableMgr;
+use crate::query::scan::Scan;
+use crate::record::layout::{Layout, Schema};
+use crate::record::scanerror::ScanError;
+use crate::record::tablescan::{TableScan, UpdateScan};
+use crate::tx::transaction::Transaction;
+
+// The ViewMgr stores the definition of each view, the text of its query,
+// in the viewcat catalog table. A view has no records of its own: the
+// query planner reads the definition back and plans it in place of the
+// view's name.
+pub(crate) struct ViewMgr {
+    view_catalog_layout: Arc<Layout>,
+}
+
+impl ViewMgr {
+    const MAX_VIEWDEF: i32 = 100;
+
+    pub(crate) fn new(is_new: bool, tm: &TableMgr, tx: Arc<Mutex<Transaction>>) -> Result<ViewMgr, ScanError> {
+        let mut view_catalog_schema = Schema::new();
+        view_catalog_schema.add_string_field("view_name", TableMgr::MAX_NAME);
+        view_catalog_schema.add_string_field("view_def", ViewMgr::MAX_VIEWDEF);
+        if is_new {
+            tm.create_table("viewcat", &view_catalog_schema, tx)?;
+        }
+        Ok(ViewMgr { view_catalog_layout: Arc::new(Layout::new(view_catalog_schema)) })
+    }
+
+    pub(crate) fn create_view(&self, name: &str, def: &str, tx: Arc<Mutex<Transaction>>) -> Result<(), ScanError> {
+        let mut view_catalog = TableScan::new(tx, self.view_catalog_layout.clone(), "viewcat")?;
+        view_catalog.insert()?;
+        let result = view_catalog.set_string("view_name", name).and_then(|_| view_catalog.set_string("view_def", def));
+        // A definition that does not fit must not leave half a view behind.
+        if result.is_err() {
+            view_catalog.delete()?;
+        }
+        view_catalog.close();
+        result
+    }
+
+    // Returns false if there is no view with that name.
+    pub(crate) fn drop_view(&self, name: &str, tx: Arc<Mutex<Transaction>>) -> Result<bool, ScanError> {
+        let mut view_catalog = TableScan::new(tx, self.view_catalog_layout.clone(), "viewcat")?;
+        let mut found = false;
+        while view_catalog.next()? {
+            if view_catalog.get_string("view_name")? == name {
+                view_catalog.delete()?;
+                found = true;
+            }
+        }
+        view_catalog.close();
+        Ok(found)
+    }
+
+    // Returns the definition of the view, or None if there is no view with
+    // that name.
+    pub(crate) fn view_def(&self, name: &str, tx: Arc<Mutex<Transaction>>) -> Result<Option<String>, ScanError> {
+        let mut view_catalog = TableScan::new(tx, self.view_catalog_layout.clone(), "viewcat")?;
+        let mut def = None;
+        while view_catalog.next()? {
+            if view_catalog.get_string("view_name")? == name {
+                def = Some(view_catalog.get_string("view_def")?);
+                break;
+            }
+        }
+        view_catalog.close();
+        Ok(def)
+    }
+
+    // Reads the definitions of all views, keyed by view name.
+    pub(crate) fn view_defs(&self, tx: Arc<Mutex<Transaction>>) -> Result<HashMap<String, String>, ScanError> {
+        let mut view_catalog = TableScan::new(tx, self.view_catalog_layout.clone(), "viewcat")?;
+        let mut defs = HashMap::new();
+        while view_catalog.next()? {
+            defs.insert(view_catalog.get_string("view_name")?, view_catalog.get_string("view_def")?);
+        }
+        view_catalog.close();
+        Ok(defs)
+    }
+}
@@ -0,0 +1,3 @@
+pub mod tableplanner;
+pub mod heuristicqueryplanner;
+pub mod adaptivejoinplan;
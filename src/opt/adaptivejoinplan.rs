@@ -0,0 +1,165 @@
+use std::sync::{Arc, Mutex};
+use crate::plan::plan::Plan;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::Schema;
+
+// How the join went the last time the plan was opened: how many records of
+// the outer plan were counted, and whether that made it switch joins.
+#[derive(Clone, Copy)]
+struct Outcome {
+    counted: usize,
+    switched: bool,
+}
+
+// The AdaptiveJoinPlan joins a table to the plan so far through an index
+// on the table, as chosen from the estimated number of records of the plan
+// so far, but checks that number when it is opened. The index is searched
+// once for each outer record, so if the outer plan has more than
+// `max_outer` records, which the planner sets well above its estimate, the
+// plan switches to a merge join of the same inputs instead. Counting reads
+// at most `max_outer + 1` records of the outer plan, which the join it
+// chooses then reads again. The join used the last time the plan was
+// opened shows in `describe`, so explaining the plan after it ran tells
+// whether it switched.
+pub struct AdaptiveJoinPlan {
+    outer: Arc<dyn Plan>,
+    index_join: Box<dyn Plan>,
+    merge_join: Box<dyn Plan>,
+    max_outer: usize,
+    outcome: Mutex<Option<Outcome>>,
+}
+
+impl AdaptiveJoinPlan {
+    pub fn new(outer: Arc<dyn Plan>, index_join: Box<dyn Plan>, merge_join: Box<dyn Plan>, max_outer: usize) -> AdaptiveJoinPlan {
+        AdaptiveJoinPlan { outer, index_join, merge_join, max_outer, outcome: Mutex::new(None) }
+    }
+
+    fn switched(&self) -> bool {
+        self.outcome.lock().unwrap().is_some_and(|outcome| outcome.switched)
+    }
+}
+
+impl Plan for AdaptiveJoinPlan {
+    fn open(&self) -> Result<Box<dyn Scan>, ScanError> {
+        let mut scan = self.outer.open()?;
+        let mut counted = 0;
+        while counted <= self.max_outer && scan.next()? {
+            counted += 1;
+        }
+        scan.close();
+        let switched = counted > self.max_outer;
+        *self.outcome.lock().unwrap() = Some(Outcome { counted, switched });
+        if switched {
+            self.merge_join.open()
+        } else {
+            self.index_join.open()
+        }
+    }
+
+    // The estimates are those of the index join, which the planner chose.
+    fn blocks_accessed(&self) -> usize {
+        self.index_join.blocks_accessed()
+    }
+
+    fn rand_blocks(&self) -> usize {
+        self.index_join.rand_blocks()
+    }
+
+    fn records_output(&self) -> usize {
+        self.index_join.records_output()
+    }
+
+    fn distinct_values(&self, field: &str) -> usize {
+        self.index_join.distinct_values(field)
+    }
+
+    fn schema(&self) -> &Schema {
+        self.index_join.schema()
+    }
+
+    fn describe(&self) -> String {
+        match *self.outcome.lock().unwrap() {
+            None => format!("AdaptiveJoin (merge join above {} outer records)", self.max_outer),
+            Some(Outcome { switched: false, counted }) => format!("AdaptiveJoin (kept index join: {} outer records)", counted),
+            Some(Outcome { switched: true, .. }) => format!(
+                "AdaptiveJoin (switched to merge join: over {} outer records, estimated {})",
+                self.max_outer,
+                self.outer.records_output()
+            ),
+        }
+    }
+
+    fn inputs(&self) -> Vec<&dyn Plan> {
+        if self.switched() {
+            vec![self.merge_join.as_ref()]
+        } else {
+            vec![self.index_join.as_ref()]
+        }
+    }
+}
+
+mod tests {
+    use std::fs;
+    use super::*;
+    use crate::materialize::mergejoinplan::MergeJoinPlan;
+    use crate::parse::parser::Parser;
+    use crate::plan::basicupdateplanner::BasicUpdatePlanner;
+    use crate::plan::indexjoinplan::IndexJoinPlan;
+    use crate::plan::tableplan::TablePlan;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_adaptive_join() {
+        let _ = fs::remove_dir_all("adaptivejointestdb");
+        let db = SimpleDB::new("adaptivejointestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let updater = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| updater.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone()).unwrap();
+        update("create table dept (did int, dname varchar(10))");
+        update("create table student (sid int, sname varchar(10), majorid int)");
+        for (did, dname) in [(10, "compsci"), (20, "math"), (30, "drama")] {
+            update(&format!("insert into dept (did, dname) values ({}, '{}')", did, dname));
+        }
+        for i in 0..40 {
+            update(&format!("insert into student (sid, sname, majorid) values ({}, 'rec{}', {})", i, i, 10 + 10 * (i % 2)));
+        }
+        update("create index majorid_idx on student (majorid)");
+
+        let ii = mdm.index_info("student", tx.clone()).unwrap()["majorid"].clone();
+        let adaptive = |max_outer: usize| {
+            let outer: Arc<dyn Plan> = Arc::new(TablePlan::new(tx.clone(), "dept", &mdm).unwrap());
+            let student = || TablePlan::new(tx.clone(), "student", &mdm).unwrap();
+            let index_join = IndexJoinPlan::new(Box::new(outer.clone()), student(), ii.clone(), "did");
+            let merge_join = MergeJoinPlan::new(tx.clone(), Box::new(outer.clone()), Box::new(student()), "did", "majorid");
+            AdaptiveJoinPlan::new(outer, Box::new(index_join), Box::new(merge_join), max_outer)
+        };
+        let run = |plan: &AdaptiveJoinPlan| {
+            let mut scan = plan.open().unwrap();
+            let mut records = Vec::new();
+            while scan.next().unwrap() {
+                records.push((scan.get_int("did").unwrap(), scan.get_int("sid").unwrap()));
+            }
+            scan.close();
+            records.sort();
+            records
+        };
+
+        // With as many outer records as allowed, the index join is kept.
+        let plan = adaptive(3);
+        assert_eq!(plan.describe(), "AdaptiveJoin (merge join above 3 outer records)");
+        let records = run(&plan);
+        assert_eq!(records.len(), 40);
+        assert_eq!(plan.describe(), "AdaptiveJoin (kept index join: 3 outer records)");
+        assert!(plan.inputs()[0].describe().starts_with("IndexJoin"));
+
+        // With one more, the plan switches to the merge join, which gives
+        // the same records.
+        let plan = adaptive(2);
+        assert_eq!(run(&plan), records);
+        assert_eq!(plan.describe(), "AdaptiveJoin (switched to merge join: over 2 outer records, estimated 3)");
+        assert!(plan.explain().lines().nth(1).unwrap().trim_start().starts_with("MergeJoin did=majorid"));
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use crate::metadata::metadatamgr::MetadataMgr;
+use crate::opt::tableplanner::TablePlanner;
+use crate::parse::parser::Parser;
+use crate::parse::querydata::QueryData;
+use crate::plan::basicqueryplanner::BasicQueryPlanner;
+use crate::plan::plan::Plan;
+use crate::plan::projectplan::ProjectPlan;
+use crate::plan::searchpath::SearchPath;
+use crate::plan::tableplan::TablePlan;
+use crate::record::scanerror::ScanError;
+use crate::tx::transaction::Transaction;
+
+// The HeuristicQueryPlanner chooses the order in which the tables of a
+// query are joined, guided by their statistics, instead of following the
+// order they are listed in. It starts with the table whose selection
+// outputs the fewest records, then repeatedly adds the table whose join
+// with the plan so far outputs the fewest, falling back to the smallest
+// product when no remaining table is related to the plan by the
+// predicate. Keeping intermediate outputs small keeps the joins after them
+// cheap. How each table is read and joined is left to its TablePlanner.
+pub struct HeuristicQueryPlanner {
+    mdm: Arc<MetadataMgr>,
+    search_path: SearchPath,
+}
+
+impl HeuristicQueryPlanner {
+    pub fn new(mdm: Arc<MetadataMgr>) -> HeuristicQueryPlanner {
+        HeuristicQueryPlanner { mdm, search_path: SearchPath::default() }
+    }
+
+    pub fn set_search_path(&mut self, search_path: SearchPath) {
+        self.search_path = search_path;
+    }
+
+    pub fn create_plan(&self, data: &QueryData, tx: Arc<Mutex<Transaction>>) -> Result<Box<dyn Plan>, ScanError> {
+        let mut planners = data.tables().iter()
+            .map(|table| self.table_planner(table, data, tx.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut current: Arc<dyn Plan> = Arc::from(Self::lowest_select_plan(&mut planners)?);
+        while !planners.is_empty() {
+            current = Arc::from(match Self::lowest_join_plan(&mut planners, &current)? {
+                Some(plan) => plan,
+                None => Self::lowest_product_plan(&mut planners, &current)?,
+            });
+        }
+
+        if let Some(field) = data.fields().iter().find(|field| !current.schema().has_field(field)) {
+            return Err(ScanError::UnknownField(field.clone()));
+        }
+        Ok(Box::new(ProjectPlan::new(Box::new(current), data.fields())))
+    }
+
+    // A view is planned by planning its definition; it has no indexes.
+    fn table_planner(&self, table: &str, data: &QueryData, tx: Arc<Mutex<Transaction>>) -> Result<TablePlanner, ScanError> {
+        let table = &BasicQueryPlanner::resolve(&self.mdm, &self.search_path, table, tx.clone())?;
+        let (base, indexes, text_indexes): (Box<dyn Plan>, _, _) = if let Some(plan) = BasicQueryPlanner::system_table_plan(&self.mdm, table, tx.clone())? {
+            (plan, HashMap::new(), HashMap::new())
+        } else if let Some(def) = self.mdm.view_def(table, tx.clone())? {
+            // The definition was written from a parsed query, so it parses.
+            let view_data = Parser::new(&def).and_then(|mut parser| parser.query()).expect("a stored view definition parses");
+            (self.create_plan(&view_data, tx.clone())?, HashMap::new(), HashMap::new())
+        } else {
+            let indexes = self.mdm.usable_indexes(table, data.pred(), tx.clone())?;
+            let text_indexes = self.mdm.usable_text_indexes(table, data.pred(), tx.clone())?;
+            (Box::new(TablePlan::new(tx.clone(), table, &self.mdm)?), indexes, text_indexes)
+        };
+        Ok(TablePlanner::new(table, base, indexes, text_indexes, data.pred(), self.mdm.clone(), tx))
+    }
+
+    fn lowest_select_plan(planners: &mut Vec<TablePlanner>) -> Result<Box<dyn Plan>, ScanError> {
+        let plans = planners.iter().map(|planner| planner.make_select_plan().map(Some)).collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::take_lowest(planners, plans).expect("a query reads at least one table"))
+    }
+
+    fn lowest_join_plan(planners: &mut Vec<TablePlanner>, current: &Arc<dyn Plan>) -> Result<Option<Box<dyn Plan>>, ScanError> {
+        let plans = planners.iter().map(|planner| planner.make_join_plan(current)).collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::take_lowest(planners, plans))
+    }
+
+    fn lowest_product_plan(planners: &mut Vec<TablePlanner>, current: &Arc<dyn Plan>) -> Result<Box<dyn Plan>, ScanError> {
+        let plans = planners.iter().map(|planner| planner.make_product_plan(current).map(Some)).collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::take_lowest(planners, plans).expect("a product plan is made for every table"))
+    }
+
+    // Picks the plan that outputs the fewest records, the first one on a
+    // tie, and removes the planner it came from.
+    fn take_lowest(planners: &mut Vec<TablePlanner>, plans: Vec<Option<Box<dyn Plan>>>) -> Option<Box<dyn Plan>> {
+        let (idx, plan) = plans.into_iter().enumerate()
+            .filter_map(|(idx, plan)| plan.map(|plan| (idx, plan)))
+            .min_by_key(|(_, plan)| plan.records_output())?;
+        planners.remove(idx);
+        Some(plan)
+    }
+}
+
+mod tests {
+    use std::fs;
+    use super::*;
+    use crate::plan::basicupdateplanner::BasicUpdatePlanner;
+    use crate::query::constant::Constant;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_heuristic_query_planner() {
+        let _ = fs::remove_dir_all("heuristicplannertestdb");
+        let db = SimpleDB::new("heuristicplannertestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let updater = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| updater.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone()).unwrap();
+        update("create table dept (did int, dname varchar(10))");
+        update("create table student (sid int, sname varchar(10), majorid int)");
+        update("create table enroll (eid int, studentid int, grade varchar(2))");
+        for (did, dname) in [(10, "compsci"), (20, "math"), (30, "drama")] {
+            update(&format!("insert into dept (did, dname) values ({}, '{}')", did, dname));
+        }
+        for i in 0..60 {
+            update(&format!("insert into student (sid, sname, majorid) values ({}, 'rec{}', {})", i, i, 10 + 10 * (i % 3)));
+        }
+        for i in 0..120 {
+            update(&format!("insert into enroll (eid, studentid, grade) values ({}, {}, '{}')", i, i * 7 % 60, ["A", "B", "C"][i % 3]));
+        }
+        update("create index majorid_idx on student (majorid)");
+        update("create view mathstudents as select sid, sname from student, dept where majorid = did and dname = 'math'");
+
+        // Whatever order the tables are joined in, each query must give the
+        // same records as with the basic planner, which keeps the order they
+        // are listed in.
+        let basic = BasicQueryPlanner::new(mdm.clone());
+        let heuristic = HeuristicQueryPlanner::new(mdm.clone());
+        let run = |plan: Box<dyn Plan>, data: &QueryData| {
+            let mut scan = plan.open().unwrap();
+            let mut records = Vec::new();
+            while scan.next().unwrap() {
+                records.push(data.fields().iter().map(|field| scan.get_val(field).unwrap()).collect::<Vec<Constant>>());
+            }
+            scan.close();
+            records.sort();
+            records
+        };
+        for (sql, count) in [
+            ("select sname, dname from student, dept where majorid = did and dname = 'math'", 20),
+            ("select eid, grade from enroll, student, dept where sid = studentid and majorid = did and dname = 'drama'", 40),
+            ("select dname, eid from dept, enroll where dname = 'math' and grade = 'A'", 40),
+            ("select sname, eid from enroll, mathstudents where sid = studentid and grade = 'B'", 40),
+        ] {
+            let data = Parser::new(sql).unwrap().query().unwrap();
+            let plan = heuristic.create_plan(&data, tx.clone()).unwrap();
+            let basic_plan = basic.create_plan(&data, tx.clone()).unwrap();
+            let records = run(plan, &data);
+            assert_eq!(records.len(), count, "{}", sql);
+            assert_eq!(records, run(basic_plan, &data), "{}", sql);
+        }
+
+        let data = Parser::new("select nosuchfield from student").unwrap().query().unwrap();
+        assert_eq!(heuristic.create_plan(&data, tx.clone()).err(), Some(ScanError::UnknownField("nosuchfield".to_string())));
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
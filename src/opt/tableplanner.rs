@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use crate::materialize::materializeplan::MaterializePlan;
+use crate::materialize::mergejoinplan::MergeJoinPlan;
+use crate::metadata::indexinfo::IndexInfo;
+use crate::metadata::metadatamgr::MetadataMgr;
+use crate::opt::adaptivejoinplan::AdaptiveJoinPlan;
+use crate::plan::bitmapandplan::BitmapAndPlan;
+use crate::plan::indexjoinplan::IndexJoinPlan;
+use crate::plan::indexselectplan::IndexSelectPlan;
+use crate::plan::plan::{Plan, RANDOM_BLOCK_COST};
+use crate::plan::productplan::ProductPlan;
+use crate::plan::selectplan::SelectPlan;
+use crate::plan::tableplan::TablePlan;
+use crate::query::predicate::Predicate;
+use crate::record::scanerror::ScanError;
+use crate::tx::transaction::Transaction;
+
+// The TablePlanner makes the plans that read one table of a query: on its
+// own, joined to the plan of the tables read before it, or in a product
+// with them. Each plan applies the terms of the predicate that the table
+// makes evaluable, and uses an index of the table where the predicate
+// allows. Views and system tables have no indexes, so their plan is only
+// ever selected from.
+pub(crate) struct TablePlanner {
+    table: String,
+    base: Arc<dyn Plan>,
+    indexes: HashMap<String, IndexInfo>,
+    text_indexes: HashMap<String, IndexInfo>,
+    pred: Predicate,
+    mdm: Arc<MetadataMgr>,
+    tx: Arc<Mutex<Transaction>>,
+}
+
+impl TablePlanner {
+    // How many times more records than estimated the plan so far must turn
+    // out to have before an index join switches to a merge join.
+    const MISESTIMATE: usize = 10;
+
+    pub(crate) fn new(table: &str, base: Box<dyn Plan>, indexes: HashMap<String, IndexInfo>, text_indexes: HashMap<String, IndexInfo>, pred: &Predicate, mdm: Arc<MetadataMgr>, tx: Arc<Mutex<Transaction>>) -> TablePlanner {
+        TablePlanner { table: table.to_string(), base: Arc::from(base), indexes, text_indexes, pred: pred.clone(), mdm, tx }
+    }
+
+    // Reads the table through an index on a field the predicate equates
+    // with a constant, or a text index on a field it searches for a word,
+    // or through all such indexes combined in a bitmap
+    // scan, whichever costs least, if that is less than reading the whole
+    // table.
+    pub(crate) fn make_select_plan(&self) -> Result<Box<dyn Plan>, ScanError> {
+        let plan = match self.make_index_select()? {
+            Some(plan) => plan,
+            None => Box::new(self.base.clone()),
+        };
+        Ok(self.add_select_pred(plan))
+    }
+
+    // Joins the table to the current plan, or returns None if no term of
+    // the predicate relates the two. An index on the join field is used if
+    // there is one, adaptively if a merge join is possible too; otherwise
+    // the table is merge joined to the current plan when that reads fewer
+    // blocks than their product.
+    pub(crate) fn make_join_plan(&self, current: &Arc<dyn Plan>) -> Result<Option<Box<dyn Plan>>, ScanError> {
+        let Some(join_pred) = self.pred.join_sub_pred(self.base.schema(), current.schema()) else {
+            return Ok(None);
+        };
+        let plan = match self.make_index_join(current)? {
+            Some(plan) => plan,
+            None => self.make_merge_join(current)?,
+        };
+        Ok(Some(Box::new(SelectPlan::new(plan, join_pred))))
+    }
+
+    pub(crate) fn make_product_plan(&self, current: &Arc<dyn Plan>) -> Result<Box<dyn Plan>, ScanError> {
+        Ok(Box::new(ProductPlan::new(Box::new(current.clone()), self.make_select_plan()?)))
+    }
+
+    fn make_index_select(&self) -> Result<Option<Box<dyn Plan>>, ScanError> {
+        let mut best: Option<Box<dyn Plan>> = None;
+        let lookups = BitmapAndPlan::lookups(self.indexes.values().chain(self.text_indexes.values()), &self.pred);
+        for (ii, val) in &lookups {
+            let plan = TablePlan::new(self.tx.clone(), &self.table, &self.mdm)?;
+            let plan = IndexSelectPlan::new(plan, ii.clone(), val.clone());
+            if plan.cost() < best.as_ref().map_or(self.base.cost(), |best| best.cost()) {
+                best = Some(Box::new(plan));
+            }
+        }
+        if lookups.len() >= 2 {
+            let plan = BitmapAndPlan::new(TablePlan::new(self.tx.clone(), &self.table, &self.mdm)?, lookups);
+            if plan.cost() < best.as_ref().map_or(self.base.cost(), |best| best.cost()) {
+                best = Some(Box::new(plan));
+            }
+        }
+        Ok(best)
+    }
+
+    fn make_index_join(&self, current: &Arc<dyn Plan>) -> Result<Option<Box<dyn Plan>>, ScanError> {
+        for (field, ii) in &self.indexes {
+            if let Some(outer_field) = self.pred.equates_with_field(field) && current.schema().has_field(outer_field) {
+                let plan = TablePlan::new(self.tx.clone(), &self.table, &self.mdm)?;
+                let join = self.add_select_pred(Box::new(IndexJoinPlan::new(Box::new(current.clone()), plan, ii.clone(), outer_field)));
+                return Ok(Some(self.make_adaptive(current, join, ii)?));
+            }
+        }
+        Ok(None)
+    }
+
+    // Lets the index join fall back to a merge join when the current plan
+    // has far more records than estimated, and more than make the merge
+    // join cheaper: each outer record costs the index join a search of the
+    // index and the random reads of its matching records.
+    fn make_adaptive(&self, current: &Arc<dyn Plan>, join: Box<dyn Plan>, ii: &IndexInfo) -> Result<Box<dyn Plan>, ScanError> {
+        let plan = self.make_select_plan()?;
+        let Some((field1, field2)) = self.merge_join_fields(plan.as_ref(), current) else {
+            return Ok(join);
+        };
+        let merge_join = MergeJoinPlan::new(self.tx.clone(), Box::new(current.clone()), plan, &field1, &field2);
+        let per_record = (ii.blocks_accessed() + RANDOM_BLOCK_COST * ii.records_output()).max(1);
+        let max_outer = (current.records_output() * Self::MISESTIMATE).max(merge_join.cost() / per_record);
+        Ok(Box::new(AdaptiveJoinPlan::new(current.clone(), join, Box::new(merge_join), max_outer)))
+    }
+
+    // The product is only a fallback here: a merge join needs a term that
+    // equates a field of the table with one of the current plan, which the
+    // join predicate may not have.
+    fn make_merge_join(&self, current: &Arc<dyn Plan>) -> Result<Box<dyn Plan>, ScanError> {
+        let plan = self.make_select_plan()?;
+        if let Some((field1, field2)) = self.merge_join_fields(plan.as_ref(), current) {
+            let product_blocks = current.blocks_accessed() + current.records_output() * plan.blocks_accessed();
+            let merge_blocks = MaterializePlan::blocks(&self.tx, current.as_ref()) + MaterializePlan::blocks(&self.tx, plan.as_ref());
+            if merge_blocks < product_blocks {
+                return Ok(Box::new(MergeJoinPlan::new(self.tx.clone(), Box::new(current.clone()), plan, &field1, &field2)));
+            }
+        }
+        Ok(Box::new(ProductPlan::new(Box::new(current.clone()), plan)))
+    }
+
+    // The field of the current plan and the field of the table that a term
+    // of the predicate equates, for a merge join.
+    fn merge_join_fields(&self, plan: &dyn Plan, current: &Arc<dyn Plan>) -> Option<(String, Arc<str>)> {
+        plan.schema().fields().iter().find_map(|field| {
+            self.pred.equates_with_field(field).filter(|outer_field| current.schema().has_field(outer_field)).map(|outer_field| (outer_field.to_string(), field.clone()))
+        })
+    }
+
+    fn add_select_pred(&self, plan: Box<dyn Plan>) -> Box<dyn Plan> {
+        match self.pred.select_sub_pred(self.base.schema()) {
+            Some(pred) => Box::new(SelectPlan::new(plan, pred)),
+            None => plan,
+        }
+    }
+}
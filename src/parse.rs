@@ -0,0 +1,14 @@
+pub mod lexer;
+pub mod parser;
+pub mod parseerror;
+pub mod querydata;
+pub mod insertdata;
+pub mod deletedata;
+pub mod modifydata;
+pub mod createtabledata;
+pub mod createviewdata;
+pub mod createindexdata;
+pub mod createschemadata;
+pub mod exportdata;
+pub mod dropdata;
+pub mod reindexdata;
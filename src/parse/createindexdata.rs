@@ -0,0 +1,47 @@
+use crate::index::index::IndexKind;
+use crate::query::predicate::Predicate;
+
+// The parsed form of a CREATE INDEX or CREATE TEXT INDEX statement. With
+// IF NOT EXISTS, the statement does nothing if an index of that name
+// already exists, whatever it is on. An index with a WHERE clause is
+// partial: it only holds the records that satisfy its predicate, which is
+// empty otherwise.
+#[derive(Clone)]
+pub struct CreateIndexData {
+    index: String,
+    table: String,
+    field: String,
+    kind: IndexKind,
+    pred: Predicate,
+    if_not_exists: bool,
+}
+
+impl CreateIndexData {
+    pub fn new(index: String, table: String, field: String, kind: IndexKind, pred: Predicate, if_not_exists: bool) -> CreateIndexData {
+        CreateIndexData { index, table, field, kind, pred, if_not_exists }
+    }
+
+    pub fn index(&self) -> &str {
+        &self.index
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    pub fn kind(&self) -> IndexKind {
+        self.kind
+    }
+
+    pub fn pred(&self) -> &Predicate {
+        &self.pred
+    }
+
+    pub fn if_not_exists(&self) -> bool {
+        self.if_not_exists
+    }
+}
@@ -0,0 +1,21 @@
+// The parsed form of a CREATE SCHEMA statement. With IF NOT EXISTS, the
+// statement does nothing if the schema already exists.
+#[derive(Clone)]
+pub struct CreateSchemaData {
+    schema: String,
+    if_not_exists: bool,
+}
+
+impl CreateSchemaData {
+    pub fn new(schema: String, if_not_exists: bool) -> CreateSchemaData {
+        CreateSchemaData { schema, if_not_exists }
+    }
+
+    pub fn schema(&self) -> &str {
+        &self.schema
+    }
+
+    pub fn if_not_exists(&self) -> bool {
+        self.if_not_exists
+    }
+}
@@ -0,0 +1,28 @@
+use crate::record::schema::Schema;
+
+// The parsed form of a CREATE TABLE statement. With IF NOT EXISTS, the
+// statement does nothing if the table already exists.
+#[derive(Clone)]
+pub struct CreateTableData {
+    table: String,
+    schema: Schema,
+    if_not_exists: bool,
+}
+
+impl CreateTableData {
+    pub fn new(table: String, schema: Schema, if_not_exists: bool) -> CreateTableData {
+        CreateTableData { table, schema, if_not_exists }
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    pub fn if_not_exists(&self) -> bool {
+        self.if_not_exists
+    }
+}
@@ -0,0 +1,33 @@
+use crate::parse::querydata::QueryData;
+
+// The parsed form of a CREATE VIEW statement. With IF NOT EXISTS, the
+// statement does nothing if the view already exists, whatever its query.
+#[derive(Clone)]
+pub struct CreateViewData {
+    view: String,
+    query: QueryData,
+    if_not_exists: bool,
+}
+
+impl CreateViewData {
+    pub fn new(view: String, query: QueryData, if_not_exists: bool) -> CreateViewData {
+        CreateViewData { view, query, if_not_exists }
+    }
+
+    pub fn view(&self) -> &str {
+        &self.view
+    }
+
+    pub fn if_not_exists(&self) -> bool {
+        self.if_not_exists
+    }
+
+    pub fn query(&self) -> &QueryData {
+        &self.query
+    }
+
+    // The text of the view's query, as stored in the catalog.
+    pub fn view_def(&self) -> String {
+        self.query.to_string()
+    }
+}
@@ -0,0 +1,22 @@
+use crate::query::predicate::Predicate;
+
+// The parsed form of a DELETE statement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeleteData {
+    table: String,
+    pred: Predicate,
+}
+
+impl DeleteData {
+    pub fn new(table: String, pred: Predicate) -> DeleteData {
+        DeleteData { table, pred }
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn pred(&self) -> &Predicate {
+        &self.pred
+    }
+}
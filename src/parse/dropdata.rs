@@ -0,0 +1,34 @@
+// The kind of object a DROP statement removes.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DropKind {
+    Table,
+    View,
+    Index,
+}
+
+// The parsed form of a DROP statement. With IF EXISTS, the statement does
+// nothing if there is no such object.
+#[derive(Clone)]
+pub struct DropData {
+    kind: DropKind,
+    name: String,
+    if_exists: bool,
+}
+
+impl DropData {
+    pub fn new(kind: DropKind, name: String, if_exists: bool) -> DropData {
+        DropData { kind, name, if_exists }
+    }
+
+    pub fn kind(&self) -> DropKind {
+        self.kind
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn if_exists(&self) -> bool {
+        self.if_exists
+    }
+}
@@ -0,0 +1,27 @@
+use crate::query::predicate::Predicate;
+
+// The parsed form of an EXPORT TABLE statement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportData {
+    table: String,
+    pred: Predicate,
+    file: String,
+}
+
+impl ExportData {
+    pub fn new(table: String, pred: Predicate, file: String) -> ExportData {
+        ExportData { table, pred, file }
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn pred(&self) -> &Predicate {
+        &self.pred
+    }
+
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+}
@@ -0,0 +1,27 @@
+use crate::query::constant::Constant;
+
+// The parsed form of an INSERT statement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InsertData {
+    table: String,
+    fields: Vec<String>,
+    vals: Vec<Constant>,
+}
+
+impl InsertData {
+    pub fn new(table: String, fields: Vec<String>, vals: Vec<Constant>) -> InsertData {
+        InsertData { table, fields, vals }
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    pub fn vals(&self) -> &[Constant] {
+        &self.vals
+    }
+}
@@ -0,0 +1,505 @@
+use std::cell::RefCell;
+use std::fmt::Display;
+use crate::parse::parseerror::{ParseError, ParseErrorKind, Position};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Delim(char),
+    IntConstant(i32),
+    LongConstant(i64),
+    DoubleConstant(f64),
+    StringConstant(String),
+    BlobConstant(Vec<u8>),
+    Keyword(String),
+    Id(String),
+    Eof,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Token::Delim(delim) => write!(f, "'{}'", delim),
+            Token::IntConstant(val) => write!(f, "{}", val),
+            Token::LongConstant(val) => write!(f, "{}", val),
+            Token::DoubleConstant(val) => write!(f, "{:?}", val),
+            Token::StringConstant(val) => write!(f, "'{}'", val.replace('\'', "''")),
+            Token::BlobConstant(val) => write!(f, "x'{}'", val.iter().map(|byte| format!("{:02x}", byte)).collect::<String>()),
+            Token::Keyword(word) => write!(f, "{}", word),
+            Token::Id(id) => write!(f, "{}", Lexer::quote(id)),
+            Token::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+// The lexer splits a statement into tokens: single-character delimiters,
+// numeric constants, string constants in single quotes, keywords, and
+// identifiers. A single quote inside a string is written twice, as in
+// 'it''s'. An integer too large for an int is a long constant, and a
+// number with a fraction or an exponent is a double constant. A number out
+// of the range of a long or a double is an error. A string of
+// hexadecimal digits prefixed with x, as in x'00ff', is a blob constant.
+// Keywords and identifiers are case-insensitive, so they are folded to lower
+// case, unless the identifier is in double quotes: "MyTable" names a table
+// other than MyTable, which is mytable, and "select" is an identifier. A
+// double quote inside a quoted identifier is written twice. The catalog
+// compares names exactly, so this is the only place case is folded.
+//
+// The parser consumes the tokens in order through the `match_*` methods,
+// which look at the current token, and the `eat_*` methods, which consume
+// it or fail with a syntax error at the position of the token. The
+// delimiters and keywords the parser looked for at the current token are
+// remembered, so that a syntax error there lists all of them.
+pub struct Lexer {
+    tokens: Vec<Token>,
+    positions: Vec<Position>,
+    pos: usize,
+    tried: RefCell<Vec<String>>,
+}
+
+impl Lexer {
+    const KEYWORDS: [&'static str; 44] = [
+        "select", "from", "where", "and", "insert", "into", "values",
+        "delete", "update", "set", "create", "table", "int", "varchar",
+        "export", "to", "view", "as", "index", "on", "pin", "if", "not",
+        "exists", "drop", "schema", "null", "check", "is", "bigint",
+        "boolean", "double", "float", "true", "false", "date", "timestamp",
+        "blob", "varbinary", "kill",
+        "checkpoint", "reindex", "text", "contains",
+    ];
+
+    pub fn new(s: &str) -> Result<Lexer, ParseError> {
+        let (tokens, positions) = Self::tokenize(s)?.into_iter().unzip();
+        Ok(Lexer { tokens, positions, pos: 0, tried: RefCell::new(Vec::new()) })
+    }
+
+    pub fn match_delim(&self, delim: char) -> bool {
+        self.try_token(format!("'{}'", delim));
+        self.current() == &Token::Delim(delim)
+    }
+
+    pub fn match_int_constant(&self) -> bool {
+        matches!(self.current(), Token::IntConstant(_))
+    }
+
+    pub fn match_long_constant(&self) -> bool {
+        matches!(self.current(), Token::LongConstant(_))
+    }
+
+    pub fn match_double_constant(&self) -> bool {
+        matches!(self.current(), Token::DoubleConstant(_))
+    }
+
+    pub fn match_string_constant(&self) -> bool {
+        matches!(self.current(), Token::StringConstant(_))
+    }
+
+    pub fn match_blob_constant(&self) -> bool {
+        matches!(self.current(), Token::BlobConstant(_))
+    }
+
+    pub fn match_keyword(&self, keyword: &str) -> bool {
+        self.try_token(keyword.to_string());
+        matches!(self.current(), Token::Keyword(word) if word == keyword)
+    }
+
+    pub fn match_id(&self) -> bool {
+        matches!(self.current(), Token::Id(_))
+    }
+
+    pub fn at_end(&self) -> bool {
+        self.current() == &Token::Eof
+    }
+
+    pub fn eat_delim(&mut self, delim: char) -> Result<(), ParseError> {
+        if !self.match_delim(delim) {
+            return Err(self.unexpected(&format!("'{}'", delim)));
+        }
+        self.advance();
+        Ok(())
+    }
+
+    pub fn eat_int_constant(&mut self) -> Result<i32, ParseError> {
+        match self.current() {
+            Token::IntConstant(val) => {
+                let val = *val;
+                self.advance();
+                Ok(val)
+            }
+            _ => Err(self.unexpected("an integer")),
+        }
+    }
+
+    pub fn eat_long_constant(&mut self) -> Result<i64, ParseError> {
+        match self.current() {
+            Token::LongConstant(val) => {
+                let val = *val;
+                self.advance();
+                Ok(val)
+            }
+            _ => Err(self.unexpected("a long integer")),
+        }
+    }
+
+    pub fn eat_double_constant(&mut self) -> Result<f64, ParseError> {
+        match self.current() {
+            Token::DoubleConstant(val) => {
+                let val = *val;
+                self.advance();
+                Ok(val)
+            }
+            _ => Err(self.unexpected("a double")),
+        }
+    }
+
+    pub fn eat_string_constant(&mut self) -> Result<String, ParseError> {
+        match self.current() {
+            Token::StringConstant(val) => {
+                let val = val.clone();
+                self.advance();
+                Ok(val)
+            }
+            _ => Err(self.unexpected("a string")),
+        }
+    }
+
+    pub fn eat_blob_constant(&mut self) -> Result<Vec<u8>, ParseError> {
+        match self.current() {
+            Token::BlobConstant(val) => {
+                let val = val.clone();
+                self.advance();
+                Ok(val)
+            }
+            _ => Err(self.unexpected("a blob")),
+        }
+    }
+
+    pub fn eat_keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        if !self.match_keyword(keyword) {
+            return Err(self.unexpected(keyword));
+        }
+        self.advance();
+        Ok(())
+    }
+
+    pub fn eat_id(&mut self) -> Result<String, ParseError> {
+        match self.current() {
+            Token::Id(id) => {
+                let id = id.clone();
+                self.advance();
+                Ok(id)
+            }
+            _ => Err(self.unexpected("an identifier")),
+        }
+    }
+
+    // Returns a syntax error at the current token, expecting any of the
+    // delimiters and keywords looked for there, or else `expected`.
+    pub fn unexpected(&self, expected: &str) -> ParseError {
+        self.try_token(expected.to_string());
+        let kind = ParseErrorKind::UnexpectedToken { expected: self.tried.borrow().clone(), found: self.current().to_string() };
+        ParseError::new(kind, self.position())
+    }
+
+    // Skips the tokens of a statement with a syntax error, up to the next
+    // ';', which it consumes, or the next of the given keywords, which can
+    // start the following statement.
+    pub fn recover(&mut self, keywords: &[&str]) {
+        loop {
+            match self.current() {
+                Token::Eof => return,
+                Token::Keyword(word) if keywords.contains(&word.as_str()) => return,
+                Token::Delim(';') => {
+                    self.advance();
+                    return;
+                }
+                _ => self.advance(),
+            }
+        }
+    }
+
+    // Skips the current token, so that recovery always makes progress.
+    pub fn skip(&mut self) {
+        self.advance();
+    }
+
+    // The position of the current token, for errors the parser only detects
+    // once it has consumed the token.
+    pub fn position(&self) -> Position {
+        self.positions[self.pos]
+    }
+
+    fn current(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn advance(&mut self) {
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+            self.tried.borrow_mut().clear();
+        }
+    }
+
+    fn try_token(&self, token: String) {
+        let mut tried = self.tried.borrow_mut();
+        if !tried.contains(&token) {
+            tried.push(token);
+        }
+    }
+
+    fn tokenize(s: &str) -> Result<Vec<(Token, Position)>, ParseError> {
+        let chars: Vec<char> = s.chars().collect();
+        // The line and column of each character, and of the end of input.
+        let mut places = Vec::with_capacity(chars.len() + 1);
+        let (mut line, mut column) = (1, 1);
+        for c in chars.iter().chain(std::iter::once(&' ')) {
+            places.push((line, column));
+            (line, column) = if *c == '\n' { (line + 1, 1) } else { (line, column + 1) };
+        }
+        let span = |start: usize, end: usize| Position { line: places[start].0, column: places[start].1, len: end - start };
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let start = i;
+            if c.is_whitespace() {
+                i += 1;
+            } else if c.is_ascii_digit() || (c == '-' && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit())) {
+                i += 1;
+                i = Self::skip_digits(&chars, i);
+                let mut is_double = false;
+                if chars.get(i) == Some(&'.') && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()) {
+                    is_double = true;
+                    i = Self::skip_digits(&chars, i + 1);
+                }
+                if matches!(chars.get(i), Some('e' | 'E')) {
+                    let sign = usize::from(matches!(chars.get(i + 1), Some('+' | '-')));
+                    if chars.get(i + 1 + sign).is_some_and(|c| c.is_ascii_digit()) {
+                        is_double = true;
+                        i = Self::skip_digits(&chars, i + 1 + sign);
+                    }
+                }
+                let number: String = chars[start..i].iter().collect();
+                // A double too large to represent parses as infinity.
+                let token = if is_double {
+                    number.parse().ok().filter(|val: &f64| val.is_finite()).map(Token::DoubleConstant)
+                } else if let Ok(val) = number.parse() {
+                    Some(Token::IntConstant(val))
+                } else {
+                    number.parse().ok().map(Token::LongConstant)
+                };
+                let token = token.ok_or_else(|| ParseError::new(ParseErrorKind::InvalidNumber(number), span(start, i)))?;
+                tokens.push((token, span(start, i)));
+            } else if c == '\'' || (matches!(c, 'x' | 'X') && chars.get(i + 1) == Some(&'\'')) {
+                let is_blob = c != '\'';
+                let (string, end) = Self::quoted(&chars, i + usize::from(is_blob))
+                    .ok_or_else(|| ParseError::new(ParseErrorKind::UnterminatedString, span(start, chars.len())))?;
+                i = end;
+                let token = if is_blob {
+                    let blob = Self::hex(&string).ok_or_else(|| ParseError::new(ParseErrorKind::InvalidBlob(string), span(start, i)))?;
+                    Token::BlobConstant(blob)
+                } else {
+                    Token::StringConstant(string)
+                };
+                tokens.push((token, span(start, i)));
+            } else if c == '"' {
+                let (id, end) = Self::quoted(&chars, i)
+                    .ok_or_else(|| ParseError::new(ParseErrorKind::UnterminatedIdentifier, span(start, chars.len())))?;
+                i = end;
+                // A dot would make a qualified name ambiguous.
+                if id.is_empty() || id.contains('.') {
+                    return Err(ParseError::new(ParseErrorKind::InvalidIdentifier(id), span(start, i)));
+                }
+                tokens.push((Token::Id(id), span(start, i)));
+            } else if c.is_alphabetic() || c == '_' {
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect::<String>().to_lowercase();
+                let token = if Self::KEYWORDS.contains(&word.as_str()) { Token::Keyword(word) } else { Token::Id(word) };
+                tokens.push((token, span(start, i)));
+            } else {
+                i += 1;
+                tokens.push((Token::Delim(c), span(start, i)));
+            }
+        }
+        tokens.push((Token::Eof, span(chars.len(), chars.len() + 1)));
+        Ok(tokens)
+    }
+
+    // Returns the name as it is written in a statement: each part of a
+    // qualified name in double quotes if it would not read back as the same
+    // identifier otherwise, as when it has upper case letters or is a
+    // keyword. Statements the database writes itself, such as the text of a
+    // view, quote their names this way.
+    pub fn quote(name: &str) -> String {
+        let quote_part = |part: &str| {
+            let mut chars = part.chars();
+            let plain = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+                && chars.all(|c| c.is_alphanumeric() || c == '_')
+                && part.to_lowercase() == part
+                && !Self::KEYWORDS.contains(&part);
+            if plain { part.to_string() } else { format!("\"{}\"", part.replace('"', "\"\"")) }
+        };
+        name.split('.').map(quote_part).collect::<Vec<_>>().join(".")
+    }
+
+    // Reads the text between the quote at `start` and the matching closing
+    // quote, in which a doubled quote stands for one, as in 'it''s'.
+    // Returns the text and the position after the closing quote, or None if
+    // there is none.
+    fn quoted(chars: &[char], start: usize) -> Option<(String, usize)> {
+        let quote = chars[start];
+        let mut text = String::new();
+        let mut i = start + 1;
+        loop {
+            match (*chars.get(i)?, chars.get(i + 1)) {
+                (c, Some(next)) if c == quote && *next == quote => {
+                    text.push(quote);
+                    i += 2;
+                }
+                (c, _) if c == quote => return Some((text, i + 1)),
+                (c, _) => {
+                    text.push(c);
+                    i += 1;
+                }
+            }
+        }
+    }
+
+    // Decodes pairs of hexadecimal digits into bytes.
+    fn hex(s: &str) -> Option<Vec<u8>> {
+        if s.len() % 2 != 0 || !s.is_ascii() {
+            return None;
+        }
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok()).collect()
+    }
+
+    fn skip_digits(chars: &[char], mut i: usize) -> usize {
+        while i < chars.len() && chars[i].is_ascii_digit() {
+            i += 1;
+        }
+        i
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lexer() {
+        let mut lex = Lexer::new("SELECT a, B_1 from T where x = -12 and y='Hi there'").unwrap();
+        lex.eat_keyword("select").unwrap();
+        assert_eq!(lex.eat_id().unwrap(), "a");
+        lex.eat_delim(',').unwrap();
+        assert_eq!(lex.eat_id().unwrap(), "b_1");
+        assert!(lex.match_keyword("from"));
+        assert!(lex.eat_id().is_err());
+        lex.eat_keyword("from").unwrap();
+        assert_eq!(lex.eat_id().unwrap(), "t");
+        lex.eat_keyword("where").unwrap();
+        lex.eat_id().unwrap();
+        lex.eat_delim('=').unwrap();
+        assert_eq!(lex.eat_int_constant().unwrap(), -12);
+        lex.eat_keyword("and").unwrap();
+        lex.eat_id().unwrap();
+        lex.eat_delim('=').unwrap();
+        assert_eq!(lex.eat_string_constant().unwrap(), "Hi there");
+        assert!(lex.at_end());
+        let error = lex.eat_id().unwrap_err();
+        assert_eq!(error.kind(), &ParseErrorKind::UnexpectedToken { expected: vec!["an identifier".to_string()], found: "end of input".to_string() });
+        assert_eq!(error.position(), Position { line: 1, column: 52, len: 1 });
+    }
+
+    #[test]
+    fn test_lexer_errors() {
+        let kind = |s: &str| Lexer::new(s).err().map(|error| error.kind().clone());
+        assert_eq!(kind("select 'abc"), Some(ParseErrorKind::UnterminatedString));
+        assert_eq!(kind("x = 99999999999999999999"), Some(ParseErrorKind::InvalidNumber("99999999999999999999".to_string())));
+        assert_eq!(kind("x = 1e400"), Some(ParseErrorKind::InvalidNumber("1e400".to_string())));
+        assert_eq!(kind("x = 'it''s"), Some(ParseErrorKind::UnterminatedString));
+    }
+
+    #[test]
+    fn test_lexer_strings() {
+        let mut lex = Lexer::new("'it''s' '''' '' 'a''''b'").unwrap();
+        assert_eq!(lex.eat_string_constant().unwrap(), "it's");
+        assert_eq!(lex.eat_string_constant().unwrap(), "'");
+        assert_eq!(lex.eat_string_constant().unwrap(), "");
+        let error = lex.eat_id().unwrap_err();
+        assert_eq!(error.kind(), &ParseErrorKind::UnexpectedToken { expected: vec!["an identifier".to_string()], found: "'a''''b'".to_string() });
+        assert_eq!(lex.eat_string_constant().unwrap(), "a''b");
+        assert!(lex.at_end());
+    }
+
+    #[test]
+    fn test_lexer_positions() {
+        let mut lex = Lexer::new("select a,\n  bb\tfrom 'x y'").unwrap();
+        let mut positions = Vec::new();
+        while !lex.at_end() {
+            positions.push(lex.position());
+            lex.advance();
+        }
+        positions.push(lex.position());
+        let at = |line, column, len| Position { line, column, len };
+        assert_eq!(positions, [at(1, 1, 6), at(1, 8, 1), at(1, 9, 1), at(2, 3, 2), at(2, 6, 4), at(2, 11, 5), at(2, 16, 1)]);
+
+        let error = Lexer::new("select a\nfrom t where\n  b = 'open").err().unwrap();
+        assert_eq!(error.position(), Position { line: 3, column: 7, len: 5 });
+        assert_eq!(error.show("select a\nfrom t where\n  b = 'open"), "  b = 'open\n      ^^^^^\nsyntax error at line 3, column 7: unterminated string constant");
+        let error = Lexer::new("x = 1 + 99999999999999999999").err().unwrap();
+        assert_eq!(error.position(), Position { line: 1, column: 9, len: 20 });
+    }
+
+    #[test]
+    fn test_lexer_numbers() {
+        let mut lex = Lexer::new("7 99999999999 -2.5 1e3 6.02E+23 x.y").unwrap();
+        assert_eq!(lex.eat_int_constant().unwrap(), 7);
+        assert!(lex.match_long_constant());
+        assert_eq!(lex.eat_long_constant().unwrap(), 99999999999);
+        assert_eq!(lex.eat_double_constant().unwrap(), -2.5);
+        assert_eq!(lex.eat_double_constant().unwrap(), 1000.0);
+        assert_eq!(lex.eat_double_constant().unwrap(), 6.02e23);
+        assert_eq!(lex.eat_id().unwrap(), "x");
+        lex.eat_delim('.').unwrap();
+        assert_eq!(lex.eat_id().unwrap(), "y");
+    }
+
+    #[test]
+    fn test_lexer_quoted_ids() {
+        let mut lex = Lexer::new("MyTable \"MyTable\" \"select\" \"say \"\"hi\"\"\"").unwrap();
+        assert_eq!(lex.eat_id().unwrap(), "mytable");
+        assert_eq!(lex.eat_id().unwrap(), "MyTable");
+        assert_eq!(lex.eat_id().unwrap(), "select");
+        assert_eq!(lex.eat_id().unwrap(), "say \"hi\"");
+        assert!(lex.at_end());
+
+        let kind = |s: &str| Lexer::new(s).err().map(|error| error.kind().clone());
+        assert_eq!(kind("select \"abc"), Some(ParseErrorKind::UnterminatedIdentifier));
+        assert_eq!(kind("select \"\""), Some(ParseErrorKind::InvalidIdentifier("".to_string())));
+        assert_eq!(kind("select \"a.b\""), Some(ParseErrorKind::InvalidIdentifier("a.b".to_string())));
+
+        assert_eq!(Lexer::quote("my_table2"), "my_table2");
+        assert_eq!(Lexer::quote("app.t"), "app.t");
+        assert_eq!(Lexer::quote("App.t"), "\"App\".t");
+        assert_eq!(Lexer::quote("from"), "\"from\"");
+        assert_eq!(Lexer::quote("2nd"), "\"2nd\"");
+        assert_eq!(Lexer::quote("say \"hi\""), "\"say \"\"hi\"\"\"");
+        for name in ["MyTable", "select", "say \"hi\"", "a b"] {
+            assert_eq!(Lexer::new(&Lexer::quote(name)).unwrap().eat_id().unwrap(), name);
+        }
+    }
+
+    #[test]
+    fn test_lexer_blobs() {
+        let mut lex = Lexer::new("x'00aB' X'' x 'x'").unwrap();
+        assert!(lex.match_blob_constant());
+        assert_eq!(lex.eat_blob_constant().unwrap(), [0x00, 0xab]);
+        assert_eq!(lex.eat_blob_constant().unwrap(), []);
+        assert_eq!(lex.eat_id().unwrap(), "x");
+        assert_eq!(lex.eat_string_constant().unwrap(), "x");
+        let kind = |s: &str| Lexer::new(s).err().map(|error| error.kind().clone());
+        assert_eq!(kind("x'abc'"), Some(ParseErrorKind::InvalidBlob("abc".to_string())));
+        assert_eq!(kind("x'zz'"), Some(ParseErrorKind::InvalidBlob("zz".to_string())));
+        assert_eq!(kind("x'00"), Some(ParseErrorKind::UnterminatedString));
+    }
+}
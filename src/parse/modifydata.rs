@@ -0,0 +1,34 @@
+use crate::query::expression::Expression;
+use crate::query::predicate::Predicate;
+
+// The parsed form of an UPDATE statement, which sets a single field of the
+// matching records to the value of an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModifyData {
+    table: String,
+    field: String,
+    new_val: Expression,
+    pred: Predicate,
+}
+
+impl ModifyData {
+    pub fn new(table: String, field: String, new_val: Expression, pred: Predicate) -> ModifyData {
+        ModifyData { table, field, new_val, pred }
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn target_field(&self) -> &str {
+        &self.field
+    }
+
+    pub fn new_val(&self) -> &Expression {
+        &self.new_val
+    }
+
+    pub fn pred(&self) -> &Predicate {
+        &self.pred
+    }
+}
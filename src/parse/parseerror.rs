@@ -0,0 +1,98 @@
+use std::fmt::Display;
+
+// Where a token starts in a statement: its line and column, both counted
+// from 1 and in characters, and how many characters it spans.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    pub len: usize,
+}
+
+// What is wrong with a statement. `expected` lists the alternatives that
+// would have been valid, and `found` is the text of the offending token, or
+// "end of input".
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    UnexpectedToken { expected: Vec<String>, found: String },
+    InvalidNumber(String),
+    InvalidBlob(String),
+    InvalidIdentifier(String),
+    UnterminatedString,
+    UnterminatedIdentifier,
+}
+
+// Errors returned when a statement is not valid SQL, with the position of
+// the offending token so that a client can point at it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    pos: Position,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, pos: Position) -> ParseError {
+        ParseError { kind, pos }
+    }
+
+    pub fn kind(&self) -> &ParseErrorKind {
+        &self.kind
+    }
+
+    pub fn position(&self) -> Position {
+        self.pos
+    }
+
+    // Returns the line of the statement where the error is, with the
+    // offending token underlined, followed by the message:
+    //
+    //   select a form t
+    //            ^^^^
+    //   syntax error at line 1, column 10: expected from but found form
+    pub fn show(&self, statement: &str) -> String {
+        let line = statement.lines().nth(self.pos.line - 1).unwrap_or("");
+        let indent = " ".repeat(self.pos.column - 1);
+        let carets = "^".repeat(self.pos.len.max(1));
+        format!("{}\n{}{}\n{}", line, indent, carets, self)
+    }
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseErrorKind::UnexpectedToken { expected, found } if expected.len() == 1 => write!(f, "expected {} but found {}", expected[0], found),
+            ParseErrorKind::UnexpectedToken { expected, found } => write!(f, "expected one of: {} but found {}", expected.join(", "), found),
+            ParseErrorKind::InvalidNumber(number) => write!(f, "{} is not a valid number", number),
+            ParseErrorKind::InvalidBlob(blob) => write!(f, "x'{}' is not a valid hexadecimal string", blob),
+            ParseErrorKind::InvalidIdentifier(id) => write!(f, "\"{}\" is not a valid identifier", id),
+            ParseErrorKind::UnterminatedString => write!(f, "unterminated string constant"),
+            ParseErrorKind::UnterminatedIdentifier => write!(f, "unterminated quoted identifier"),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "syntax error at line {}, column {}: {}", self.pos.line, self.pos.column, self.kind)
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_show() {
+        let error = ParseError::new(
+            ParseErrorKind::UnexpectedToken { expected: vec!["from".to_string()], found: "form".to_string() },
+            Position { line: 2, column: 3, len: 4 },
+        );
+        assert_eq!(error.to_string(), "syntax error at line 2, column 3: expected from but found form");
+        assert_eq!(error.show("select a\n  form t"), "  form t\n  ^^^^\nsyntax error at line 2, column 3: expected from but found form");
+
+        let error = ParseError::new(
+            ParseErrorKind::UnexpectedToken { expected: vec!["','".to_string(), "from".to_string()], found: "form".to_string() },
+            Position { line: 1, column: 10, len: 4 },
+        );
+        assert_eq!(error.to_string(), "syntax error at line 1, column 10: expected one of: ',', from but found form");
+    }
+}
@@ -0,0 +1,811 @@
+use crate::index::index::IndexKind;
+use crate::parse::createindexdata::CreateIndexData;
+use crate::parse::createschemadata::CreateSchemaData;
+use crate::parse::createtabledata::CreateTableData;
+use crate::parse::createviewdata::CreateViewData;
+use crate::parse::deletedata::DeleteData;
+use crate::parse::dropdata::{DropData, DropKind};
+use crate::parse::exportdata::ExportData;
+use crate::parse::insertdata::InsertData;
+use crate::parse::lexer::Lexer;
+use crate::parse::modifydata::ModifyData;
+use crate::parse::parseerror::{ParseError, ParseErrorKind};
+use crate::parse::querydata::QueryData;
+use crate::parse::reindexdata::{ReindexData, ReindexKind};
+use crate::query::constant::Constant;
+use crate::query::date;
+use crate::query::expression::Expression;
+use crate::query::predicate::Predicate;
+use crate::query::term::Term;
+use crate::record::check::{Check, CompareOp};
+use crate::record::schema::{FieldType, Schema};
+
+// The parsed form of a statement that changes the database.
+#[derive(Clone)]
+pub enum UpdateCmd {
+    Insert(InsertData),
+    Delete(DeleteData),
+    Modify(ModifyData),
+    CreateTable(CreateTableData),
+    CreateView(CreateViewData),
+    CreateIndex(CreateIndexData),
+    CreateSchema(CreateSchemaData),
+    Drop(DropData),
+    Export(ExportData),
+    PinTable(String),
+    Kill(i32),
+    Checkpoint,
+    Reindex(ReindexData),
+}
+
+// The parser is a recursive-descent parser for the following grammar, with
+// one method per rule:
+//
+//   <Field>       := IdTok
+//   <Name>        := IdTok [ . IdTok ]
+//   <Constant>    := StrTok | IntTok | LongTok | DoubleTok | BlobTok | TRUE | FALSE
+//                   | NULL | DATE StrTok
+//   <Expression>  := <Field> | <Constant>
+//   <Term>        := <Expression> = <Expression> | <Expression> IS [ NOT ] NULL
+//                   | CONTAINS ( <Field> , StrTok )
+//   <Predicate>   := <Term> [ AND <Predicate> ]
+//   <Query>       := SELECT <SelectList> FROM <TableList> [ WHERE <Predicate> ]
+//   <SelectList>  := <Field> [ , <SelectList> ]
+//   <TableList>   := <Name> [ , <TableList> ]
+//   <UpdateCmd>   := <Insert> | <Delete> | <Modify> | <Create> | <Drop> | <Export>
+//                   | <PinTable> | <Kill> | CHECKPOINT | <Reindex>
+//   <Insert>      := INSERT INTO <Name> ( <FieldList> ) VALUES ( <ConstList> )
+//   <FieldList>   := <Field> [ , <FieldList> ]
+//   <ConstList>   := <Constant> [ , <ConstList> ]
+//   <Delete>      := DELETE FROM <Name> [ WHERE <Predicate> ]
+//   <Modify>      := UPDATE <Name> SET <Field> = <Expression> [ WHERE <Predicate> ]
+//   <Create>      := CREATE <CreateTable> | CREATE <CreateView> | CREATE <CreateIndex>
+//                   | CREATE <CreateSchema>
+//   <CreateTable> := TABLE [ <IfNotExists> ] <Name> ( <FieldDefs> )
+//   <FieldDefs>   := <FieldDef> [ , <FieldDefs> ]
+//   <FieldDef>    := IdTok <TypeDef> { <Constraint> }
+//   <TypeDef>     := INT | BIGINT | DOUBLE | FLOAT | BOOLEAN | DATE | TIMESTAMP
+//                   | BLOB | VARBINARY | VARCHAR ( IntTok )
+//   <Constraint>  := NOT NULL | CHECK ( <Field> <CompareOp> <Constant> )
+//   <CompareOp>   := = | <> | < | <= | > | >=
+//   <CreateView>  := VIEW [ <IfNotExists> ] <Name> AS <Query>
+//   <CreateIndex> := [ TEXT ] INDEX [ <IfNotExists> ] <Name> ON <Name> ( <Field> ) [ WHERE <Predicate> ]
+//   <CreateSchema> := SCHEMA [ <IfNotExists> ] IdTok
+//   <IfNotExists> := IF NOT EXISTS
+//   <Drop>        := DROP ( TABLE | VIEW | INDEX ) [ IF EXISTS ] <Name>
+//   <Export>      := EXPORT TABLE <Name> [ WHERE <Predicate> ] TO StrTok
+//   <PinTable>    := PIN TABLE <Name>
+//   <Kill>        := KILL IntTok
+//   <Reindex>     := REINDEX ( INDEX | TABLE ) <Name>
+//   <Script>      := <UpdateCmd> { ; <UpdateCmd> } [ ; ]
+//
+// The top-level `query` and `update_cmd` methods also require the statement
+// to end after the last token of the rule.
+pub struct Parser {
+    lex: Lexer,
+}
+
+impl Parser {
+    // The keywords that start an update command, where parsing a script
+    // resumes after a syntax error.
+    const STATEMENT_KEYWORDS: [&'static str; 10] =
+        ["insert", "delete", "update", "create", "drop", "export", "pin", "kill", "checkpoint", "reindex"];
+
+    pub fn new(s: &str) -> Result<Parser, ParseError> {
+        Ok(Parser { lex: Lexer::new(s)? })
+    }
+
+    pub fn field(&mut self) -> Result<String, ParseError> {
+        self.lex.eat_id()
+    }
+
+    // A name qualified with its schema is kept as one string, `schema.name`.
+    fn name(&mut self) -> Result<String, ParseError> {
+        let name = self.lex.eat_id()?;
+        if !self.lex.match_delim('.') {
+            return Ok(name);
+        }
+        self.lex.eat_delim('.')?;
+        Ok(format!("{}.{}", name, self.lex.eat_id()?))
+    }
+
+    pub fn constant(&mut self) -> Result<Constant, ParseError> {
+        if self.lex.match_string_constant() {
+            Ok(Constant::String(self.lex.eat_string_constant()?))
+        } else if self.lex.match_int_constant() {
+            Ok(Constant::Int(self.lex.eat_int_constant()?))
+        } else if self.lex.match_long_constant() {
+            Ok(Constant::Long(self.lex.eat_long_constant()?))
+        } else if self.lex.match_double_constant() {
+            Ok(Constant::Double(self.lex.eat_double_constant()?))
+        } else if self.lex.match_blob_constant() {
+            Ok(Constant::Blob(self.lex.eat_blob_constant()?))
+        } else if self.lex.match_keyword("true") || self.lex.match_keyword("false") {
+            let val = self.lex.match_keyword("true");
+            self.lex.eat_keyword(if val { "true" } else { "false" })?;
+            Ok(Constant::Bool(val))
+        } else if self.lex.match_keyword("null") {
+            self.lex.eat_keyword("null")?;
+            Ok(Constant::Null)
+        } else if self.lex.match_keyword("date") {
+            // A plain string also converts to a date where one is expected.
+            self.lex.eat_keyword("date")?;
+            let pos = self.lex.position();
+            let found = self.lex.eat_string_constant()?;
+            let date = date::parse(&found).ok_or_else(|| {
+                ParseError::new(ParseErrorKind::UnexpectedToken { expected: vec!["a date".to_string()], found: format!("'{}'", found) }, pos)
+            })?;
+            Ok(Constant::Date(date))
+        } else {
+            Err(self.lex.unexpected("a constant"))
+        }
+    }
+
+    pub fn expression(&mut self) -> Result<Expression, ParseError> {
+        if self.lex.match_id() {
+            Ok(Expression::Field(self.field()?))
+        } else {
+            Ok(Expression::Constant(self.constant()?))
+        }
+    }
+
+    pub fn term(&mut self) -> Result<Term, ParseError> {
+        if self.lex.match_keyword("contains") {
+            return self.contains();
+        }
+        let lhs = self.expression()?;
+        if self.lex.match_keyword("is") {
+            self.lex.eat_keyword("is")?;
+            let is_null = !self.lex.match_keyword("not");
+            if !is_null {
+                self.lex.eat_keyword("not")?;
+            }
+            self.lex.eat_keyword("null")?;
+            return Ok(Term::null_test(lhs, is_null));
+        }
+        self.lex.eat_delim('=')?;
+        let rhs = self.expression()?;
+        Ok(Term::new(lhs, rhs))
+    }
+
+    // The word a text field is searched for is a single word as a text
+    // index splits values into them, and matches in any case.
+    fn contains(&mut self) -> Result<Term, ParseError> {
+        self.lex.eat_keyword("contains")?;
+        self.lex.eat_delim('(')?;
+        let field = self.field()?;
+        self.lex.eat_delim(',')?;
+        let pos = self.lex.position();
+        let found = self.lex.eat_string_constant()?;
+        let word = found.to_lowercase();
+        if IndexKind::words(&found).into_iter().ne([word.clone()]) {
+            let kind = ParseErrorKind::UnexpectedToken { expected: vec!["a single word".to_string()], found: format!("'{}'", found) };
+            return Err(ParseError::new(kind, pos));
+        }
+        self.lex.eat_delim(')')?;
+        Ok(Term::contains(field, word))
+    }
+
+    pub fn predicate(&mut self) -> Result<Predicate, ParseError> {
+        let mut pred = Predicate::from_term(self.term()?);
+        if self.lex.match_keyword("and") {
+            self.lex.eat_keyword("and")?;
+            pred.conjoin_with(self.predicate()?);
+        }
+        Ok(pred)
+    }
+
+    pub fn query(&mut self) -> Result<QueryData, ParseError> {
+        self.lex.eat_keyword("select")?;
+        let fields = self.select_list()?;
+        self.lex.eat_keyword("from")?;
+        let tables = self.table_list()?;
+        let pred = self.optional_where()?;
+        self.end()?;
+        Ok(QueryData::new(fields, tables, pred))
+    }
+
+    pub fn update_cmd(&mut self) -> Result<UpdateCmd, ParseError> {
+        let cmd = self.statement()?;
+        self.end()?;
+        Ok(cmd)
+    }
+
+    // Parses a script of update commands separated by semicolons. After a
+    // syntax error the parser skips to the next semicolon or the keyword
+    // that starts the next command, so that every error in the script is
+    // reported, not only the first.
+    pub fn update_cmds(&mut self) -> Result<Vec<UpdateCmd>, Vec<ParseError>> {
+        let mut cmds = Vec::new();
+        let mut errors = Vec::new();
+        while !self.lex.at_end() {
+            let start = self.lex.position();
+            match self.statement().and_then(|cmd| self.end_of_statement().map(|_| cmd)) {
+                Ok(cmd) => cmds.push(cmd),
+                Err(error) => {
+                    errors.push(error);
+                    if self.lex.position() == start {
+                        self.lex.skip();
+                    }
+                    self.lex.recover(&Self::STATEMENT_KEYWORDS);
+                }
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(cmds)
+    }
+
+    fn statement(&mut self) -> Result<UpdateCmd, ParseError> {
+        let cmd = if self.lex.match_keyword("insert") {
+            UpdateCmd::Insert(self.insert()?)
+        } else if self.lex.match_keyword("delete") {
+            UpdateCmd::Delete(self.delete()?)
+        } else if self.lex.match_keyword("update") {
+            UpdateCmd::Modify(self.modify()?)
+        } else if self.lex.match_keyword("create") {
+            self.create()?
+        } else if self.lex.match_keyword("drop") {
+            UpdateCmd::Drop(self.drop()?)
+        } else if self.lex.match_keyword("export") {
+            UpdateCmd::Export(self.export()?)
+        } else if self.lex.match_keyword("pin") {
+            UpdateCmd::PinTable(self.pin_table()?)
+        } else if self.lex.match_keyword("kill") {
+            UpdateCmd::Kill(self.kill()?)
+        } else if self.lex.match_keyword("checkpoint") {
+            self.lex.eat_keyword("checkpoint")?;
+            UpdateCmd::Checkpoint
+        } else {
+            UpdateCmd::Reindex(self.reindex()?)
+        };
+        Ok(cmd)
+    }
+
+    fn select_list(&mut self) -> Result<Vec<String>, ParseError> {
+        let mut fields = vec![self.field()?];
+        while self.lex.match_delim(',') {
+            self.lex.eat_delim(',')?;
+            fields.push(self.field()?);
+        }
+        Ok(fields)
+    }
+
+    fn table_list(&mut self) -> Result<Vec<String>, ParseError> {
+        let mut tables = vec![self.name()?];
+        while self.lex.match_delim(',') {
+            self.lex.eat_delim(',')?;
+            tables.push(self.name()?);
+        }
+        Ok(tables)
+    }
+
+    fn optional_where(&mut self) -> Result<Predicate, ParseError> {
+        if !self.lex.match_keyword("where") {
+            return Ok(Predicate::new());
+        }
+        self.lex.eat_keyword("where")?;
+        self.predicate()
+    }
+
+    fn insert(&mut self) -> Result<InsertData, ParseError> {
+        self.lex.eat_keyword("insert")?;
+        self.lex.eat_keyword("into")?;
+        let table = self.name()?;
+        self.lex.eat_delim('(')?;
+        let fields = self.select_list()?;
+        self.lex.eat_delim(')')?;
+        self.lex.eat_keyword("values")?;
+        self.lex.eat_delim('(')?;
+        let mut vals = vec![self.constant()?];
+        while self.lex.match_delim(',') {
+            self.lex.eat_delim(',')?;
+            vals.push(self.constant()?);
+        }
+        self.lex.eat_delim(')')?;
+        Ok(InsertData::new(table, fields, vals))
+    }
+
+    fn delete(&mut self) -> Result<DeleteData, ParseError> {
+        self.lex.eat_keyword("delete")?;
+        self.lex.eat_keyword("from")?;
+        let table = self.name()?;
+        let pred = self.optional_where()?;
+        Ok(DeleteData::new(table, pred))
+    }
+
+    fn modify(&mut self) -> Result<ModifyData, ParseError> {
+        self.lex.eat_keyword("update")?;
+        let table = self.name()?;
+        self.lex.eat_keyword("set")?;
+        let field = self.field()?;
+        self.lex.eat_delim('=')?;
+        let new_val = self.expression()?;
+        let pred = self.optional_where()?;
+        Ok(ModifyData::new(table, field, new_val, pred))
+    }
+
+    fn create(&mut self) -> Result<UpdateCmd, ParseError> {
+        self.lex.eat_keyword("create")?;
+        if self.lex.match_keyword("view") {
+            Ok(UpdateCmd::CreateView(self.create_view()?))
+        } else if self.lex.match_keyword("index") {
+            Ok(UpdateCmd::CreateIndex(self.create_index(IndexKind::Hash)?))
+        } else if self.lex.match_keyword("text") {
+            self.lex.eat_keyword("text")?;
+            Ok(UpdateCmd::CreateIndex(self.create_index(IndexKind::Text)?))
+        } else if self.lex.match_keyword("schema") {
+            Ok(UpdateCmd::CreateSchema(self.create_schema()?))
+        } else {
+            Ok(UpdateCmd::CreateTable(self.create_table()?))
+        }
+    }
+
+    fn create_table(&mut self) -> Result<CreateTableData, ParseError> {
+        self.lex.eat_keyword("table")?;
+        let if_not_exists = self.if_not_exists()?;
+        let table = self.name()?;
+        self.lex.eat_delim('(')?;
+        let mut schema = Schema::new();
+        self.field_def(&mut schema)?;
+        while self.lex.match_delim(',') {
+            self.lex.eat_delim(',')?;
+            self.field_def(&mut schema)?;
+        }
+        self.lex.eat_delim(')')?;
+        Ok(CreateTableData::new(table, schema, if_not_exists))
+    }
+
+    fn field_def(&mut self, schema: &mut Schema) -> Result<(), ParseError> {
+        let field = self.field()?;
+        if self.lex.match_keyword("int") {
+            self.lex.eat_keyword("int")?;
+            schema.add_int_field(&field);
+        } else if self.lex.match_keyword("bigint") {
+            self.lex.eat_keyword("bigint")?;
+            schema.add_bigint_field(&field);
+        } else if self.lex.match_keyword("double") || self.lex.match_keyword("float") {
+            // FLOAT is a synonym; both are stored as 8-byte doubles.
+            self.lex.eat_keyword(if self.lex.match_keyword("double") { "double" } else { "float" })?;
+            schema.add_double_field(&field);
+        } else if self.lex.match_keyword("boolean") {
+            self.lex.eat_keyword("boolean")?;
+            schema.add_boolean_field(&field);
+        } else if self.lex.match_keyword("date") || self.lex.match_keyword("timestamp") {
+            // A DATE keeps its time of day, so TIMESTAMP is a synonym.
+            self.lex.eat_keyword(if self.lex.match_keyword("date") { "date" } else { "timestamp" })?;
+            schema.add_date_field(&field);
+        } else if self.lex.match_keyword("blob") || self.lex.match_keyword("varbinary") {
+            // Blobs have no maximum length, so VARBINARY takes none either.
+            self.lex.eat_keyword(if self.lex.match_keyword("blob") { "blob" } else { "varbinary" })?;
+            schema.add_blob_field(&field);
+        } else {
+            self.lex.eat_keyword("varchar")?;
+            self.lex.eat_delim('(')?;
+            let pos = self.lex.position();
+            let length = self.lex.eat_int_constant()?;
+            if length < 1 {
+                return Err(ParseError::new(ParseErrorKind::UnexpectedToken { expected: vec!["a positive length".to_string()], found: length.to_string() }, pos));
+            }
+            self.lex.eat_delim(')')?;
+            schema.add_string_field(&field, length);
+        }
+        loop {
+            if self.lex.match_keyword("not") {
+                self.lex.eat_keyword("not")?;
+                self.lex.eat_keyword("null")?;
+                schema.set_not_null(&field);
+            } else if self.lex.match_keyword("check") {
+                let check = self.check(&field, schema.ftype(&field))?;
+                schema.add_check(&field, check);
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    // The check is on the field being defined and compares it with a
+    // constant of its type, or one that converts to it.
+    fn check(&mut self, field: &str, ftype: i32) -> Result<Check, ParseError> {
+        self.lex.eat_keyword("check")?;
+        self.lex.eat_delim('(')?;
+        let pos = self.lex.position();
+        let found = self.field()?;
+        if found != field {
+            return Err(ParseError::new(ParseErrorKind::UnexpectedToken { expected: vec![format!("field {}", field)], found }, pos));
+        }
+        let op = self.compare_op()?;
+        let ftype = FieldType::try_from(ftype).unwrap();
+        let pos = self.lex.position();
+        let found = self.constant()?;
+        let val = found.coerce(ftype).filter(|val| !val.is_null()).ok_or_else(|| {
+            let kind = ParseErrorKind::UnexpectedToken { expected: vec![format!("a constant of type {:?}", ftype)], found: found.to_string() };
+            ParseError::new(kind, pos)
+        })?;
+        self.lex.eat_delim(')')?;
+        Ok(Check::new(op, val))
+    }
+
+    fn compare_op(&mut self) -> Result<CompareOp, ParseError> {
+        if self.lex.match_delim('=') {
+            self.lex.eat_delim('=')?;
+            return Ok(CompareOp::Eq);
+        }
+        let (op, or_equal) = if self.lex.match_delim('<') {
+            self.lex.eat_delim('<')?;
+            if self.lex.match_delim('>') {
+                self.lex.eat_delim('>')?;
+                return Ok(CompareOp::Ne);
+            }
+            (CompareOp::Lt, CompareOp::Le)
+        } else {
+            self.lex.eat_delim('>')?;
+            (CompareOp::Gt, CompareOp::Ge)
+        };
+        if self.lex.match_delim('=') {
+            self.lex.eat_delim('=')?;
+            return Ok(or_equal);
+        }
+        Ok(op)
+    }
+
+    fn create_view(&mut self) -> Result<CreateViewData, ParseError> {
+        self.lex.eat_keyword("view")?;
+        let if_not_exists = self.if_not_exists()?;
+        let view = self.name()?;
+        self.lex.eat_keyword("as")?;
+        Ok(CreateViewData::new(view, self.query()?, if_not_exists))
+    }
+
+    fn create_index(&mut self, kind: IndexKind) -> Result<CreateIndexData, ParseError> {
+        self.lex.eat_keyword("index")?;
+        let if_not_exists = self.if_not_exists()?;
+        let index = self.name()?;
+        self.lex.eat_keyword("on")?;
+        let table = self.name()?;
+        self.lex.eat_delim('(')?;
+        let field = self.field()?;
+        self.lex.eat_delim(')')?;
+        let pred = self.optional_where()?;
+        Ok(CreateIndexData::new(index, table, field, kind, pred, if_not_exists))
+    }
+
+    fn create_schema(&mut self) -> Result<CreateSchemaData, ParseError> {
+        self.lex.eat_keyword("schema")?;
+        let if_not_exists = self.if_not_exists()?;
+        Ok(CreateSchemaData::new(self.lex.eat_id()?, if_not_exists))
+    }
+
+    fn if_not_exists(&mut self) -> Result<bool, ParseError> {
+        if !self.lex.match_keyword("if") {
+            return Ok(false);
+        }
+        self.lex.eat_keyword("if")?;
+        self.lex.eat_keyword("not")?;
+        self.lex.eat_keyword("exists")?;
+        Ok(true)
+    }
+
+    fn drop(&mut self) -> Result<DropData, ParseError> {
+        self.lex.eat_keyword("drop")?;
+        let kind = if self.lex.match_keyword("view") {
+            self.lex.eat_keyword("view")?;
+            DropKind::View
+        } else if self.lex.match_keyword("index") {
+            self.lex.eat_keyword("index")?;
+            DropKind::Index
+        } else {
+            self.lex.eat_keyword("table")?;
+            DropKind::Table
+        };
+        let mut if_exists = false;
+        if self.lex.match_keyword("if") {
+            self.lex.eat_keyword("if")?;
+            self.lex.eat_keyword("exists")?;
+            if_exists = true;
+        }
+        Ok(DropData::new(kind, self.name()?, if_exists))
+    }
+
+    fn export(&mut self) -> Result<ExportData, ParseError> {
+        self.lex.eat_keyword("export")?;
+        self.lex.eat_keyword("table")?;
+        let table = self.name()?;
+        let pred = self.optional_where()?;
+        self.lex.eat_keyword("to")?;
+        let file = self.lex.eat_string_constant()?;
+        Ok(ExportData::new(table, pred, file))
+    }
+
+    fn pin_table(&mut self) -> Result<String, ParseError> {
+        self.lex.eat_keyword("pin")?;
+        self.lex.eat_keyword("table")?;
+        self.name()
+    }
+
+    fn kill(&mut self) -> Result<i32, ParseError> {
+        self.lex.eat_keyword("kill")?;
+        self.lex.eat_int_constant()
+    }
+
+    fn reindex(&mut self) -> Result<ReindexData, ParseError> {
+        self.lex.eat_keyword("reindex")?;
+        let kind = if self.lex.match_keyword("index") {
+            self.lex.eat_keyword("index")?;
+            ReindexKind::Index
+        } else {
+            self.lex.eat_keyword("table")?;
+            ReindexKind::Table
+        };
+        Ok(ReindexData::new(kind, self.name()?))
+    }
+
+    fn end(&self) -> Result<(), ParseError> {
+        if !self.lex.at_end() {
+            return Err(self.lex.unexpected("end of input"));
+        }
+        Ok(())
+    }
+
+    // A command in a script ends with a semicolon, except the last one.
+    fn end_of_statement(&mut self) -> Result<(), ParseError> {
+        if self.lex.match_delim(';') {
+            return self.lex.eat_delim(';');
+        }
+        self.end()
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query() {
+        let data = Parser::new("select A, b from T1, t2 where a = 3 and B = 'x' and c = d").unwrap().query().unwrap();
+        assert_eq!(data.fields(), ["a", "b"]);
+        assert_eq!(data.tables(), ["t1", "t2"]);
+        assert_eq!(data.pred().terms().len(), 3);
+        assert_eq!(data.pred().terms()[0].rhs(), &Expression::Constant(Constant::Int(3)));
+        assert_eq!(data.to_string(), "select a, b from t1, t2 where a=3 and b='x' and c=d");
+
+        let data = Parser::new("select a from t").unwrap().query().unwrap();
+        assert!(data.pred().is_empty());
+        assert_eq!(data.to_string(), "select a from t");
+
+        let data = Parser::new("select a from App.T, u").unwrap().query().unwrap();
+        assert_eq!(data.tables(), ["app.t", "u"]);
+        assert_eq!(data.to_string(), "select a from app.t, u");
+        assert!(Parser::new("select a from app.").unwrap().query().is_err());
+
+        let data = Parser::new("select a from t where a is null and b IS NOT NULL and c = null").unwrap().query().unwrap();
+        assert_eq!(data.to_string(), "select a from t where a is null and b is not null and c=null");
+        assert!(Parser::new("select a from t where a is 1").unwrap().query().is_err());
+
+        let data = Parser::new("select a from t where CONTAINS(b, 'Disk') and a = 1").unwrap().query().unwrap();
+        assert_eq!(data.to_string(), "select a from t where contains(b, 'disk') and a=1");
+        assert_eq!(data.pred().contains_word("b"), Some(&Constant::String("disk".to_string())));
+    }
+
+    #[test]
+    fn test_update_cmds() {
+        match Parser::new("insert into t (a, b) values (1, 'one')").unwrap().update_cmd().unwrap() {
+            UpdateCmd::Insert(data) => {
+                assert_eq!(data.table(), "t");
+                assert_eq!(data.fields(), ["a", "b"]);
+                assert_eq!(data.vals(), [Constant::Int(1), Constant::String("one".to_string())]);
+            }
+            _ => panic!("expected an insert"),
+        }
+        match Parser::new("delete from t where a = 1").unwrap().update_cmd().unwrap() {
+            UpdateCmd::Delete(data) => {
+                assert_eq!(data.table(), "t");
+                assert_eq!(data.pred().to_string(), "a=1");
+            }
+            _ => panic!("expected a delete"),
+        }
+        match Parser::new("update t set b = 'two' where a = 2").unwrap().update_cmd().unwrap() {
+            UpdateCmd::Modify(data) => {
+                assert_eq!(data.target_field(), "b");
+                assert_eq!(data.new_val(), &Expression::Constant(Constant::String("two".to_string())));
+                assert_eq!(data.pred().to_string(), "a=2");
+            }
+            _ => panic!("expected an update"),
+        }
+        match Parser::new("create table t (a int, b varchar(9))").unwrap().update_cmd().unwrap() {
+            UpdateCmd::CreateTable(data) => {
+                assert_eq!(data.table(), "t");
+                assert_eq!(data.schema().ftype("a"), 4);
+                assert_eq!(data.schema().ftype("b"), 12);
+                assert_eq!(data.schema().length("b"), 9);
+            }
+            _ => panic!("expected a create table"),
+        }
+        match Parser::new("create view v as select a from t where b = 1").unwrap().update_cmd().unwrap() {
+            UpdateCmd::CreateView(data) => {
+                assert_eq!(data.view(), "v");
+                assert_eq!(data.view_def(), "select a from t where b=1");
+            }
+            _ => panic!("expected a create view"),
+        }
+        match Parser::new("create index ta on t (a)").unwrap().update_cmd().unwrap() {
+            UpdateCmd::CreateIndex(data) => {
+                assert_eq!((data.index(), data.table(), data.field()), ("ta", "t", "a"));
+                assert!(!data.if_not_exists() && data.pred().is_empty());
+            }
+            _ => panic!("expected a create index"),
+        }
+        match Parser::new("create index tlive on t (a) where deleted = 0").unwrap().update_cmd().unwrap() {
+            UpdateCmd::CreateIndex(data) => assert_eq!((data.field(), data.pred().to_string()), ("a", "deleted=0".to_string())),
+            _ => panic!("expected a create index"),
+        }
+        match Parser::new("create text index tb on t (b)").unwrap().update_cmd().unwrap() {
+            UpdateCmd::CreateIndex(data) => assert_eq!((data.index(), data.kind()), ("tb", IndexKind::Text)),
+            _ => panic!("expected a create index"),
+        }
+        match Parser::new("create table if not exists t (a int)").unwrap().update_cmd().unwrap() {
+            UpdateCmd::CreateTable(data) => assert!(data.table() == "t" && data.if_not_exists()),
+            _ => panic!("expected a create table"),
+        }
+        match Parser::new("create view if not exists v as select a from t").unwrap().update_cmd().unwrap() {
+            UpdateCmd::CreateView(data) => assert!(data.view() == "v" && data.if_not_exists()),
+            _ => panic!("expected a create view"),
+        }
+        match Parser::new("create index if not exists ta on t (a)").unwrap().update_cmd().unwrap() {
+            UpdateCmd::CreateIndex(data) => assert!(data.index() == "ta" && data.if_not_exists()),
+            _ => panic!("expected a create index"),
+        }
+        match Parser::new("create table t (a int not null check (a >= 1) check (a<>5), b varchar(3) check (b < 'm'))").unwrap().update_cmd().unwrap() {
+            UpdateCmd::CreateTable(data) => {
+                assert!(data.schema().is_not_null("a") && !data.schema().is_not_null("b"));
+                assert_eq!(data.schema().checks("a"), [Check::new(CompareOp::Ge, Constant::Int(1)), Check::new(CompareOp::Ne, Constant::Int(5))]);
+                assert_eq!(data.schema().checks("b"), [Check::new(CompareOp::Lt, Constant::String("m".to_string()))]);
+            }
+            _ => panic!("expected a create table"),
+        }
+        match Parser::new("create table t (a bigint check (a > 0), b double, c float check (c < 1), d boolean)").unwrap().update_cmd().unwrap() {
+            UpdateCmd::CreateTable(data) => {
+                let ftypes: Vec<i32> = ["a", "b", "c", "d"].iter().map(|f| data.schema().ftype(f)).collect();
+                assert_eq!(ftypes, [FieldType::BIGINT as i32, FieldType::DOUBLE as i32, FieldType::DOUBLE as i32, FieldType::BOOLEAN as i32]);
+                assert_eq!(data.schema().checks("a"), [Check::new(CompareOp::Gt, Constant::Long(0))]);
+                assert_eq!(data.schema().checks("c"), [Check::new(CompareOp::Lt, Constant::Double(1.0))]);
+            }
+            _ => panic!("expected a create table"),
+        }
+        match Parser::new("insert into t (a, b, c, d) values (12345678901, 2.5, true, false)").unwrap().update_cmd().unwrap() {
+            UpdateCmd::Insert(data) => {
+                assert_eq!(data.vals(), [Constant::Long(12345678901), Constant::Double(2.5), Constant::Bool(true), Constant::Bool(false)]);
+            }
+            _ => panic!("expected an insert"),
+        }
+        match Parser::new("create table t (d date check (d >= '2000-01-01'), ts timestamp)").unwrap().update_cmd().unwrap() {
+            UpdateCmd::CreateTable(data) => {
+                assert_eq!(data.schema().ftype("ts"), FieldType::DATE as i32);
+                assert_eq!(data.schema().checks("d"), [Check::new(CompareOp::Ge, Constant::Date(946684800))]);
+            }
+            _ => panic!("expected a create table"),
+        }
+        match Parser::new("insert into t (d) values (date '1970-01-02')").unwrap().update_cmd().unwrap() {
+            UpdateCmd::Insert(data) => assert_eq!(data.vals(), [Constant::Date(86400)]),
+            _ => panic!("expected an insert"),
+        }
+        match Parser::new("create table t (b blob not null, v varbinary)").unwrap().update_cmd().unwrap() {
+            UpdateCmd::CreateTable(data) => {
+                assert_eq!(data.schema().ftype("b"), FieldType::BLOB as i32);
+                assert_eq!(data.schema().ftype("v"), FieldType::BLOB as i32);
+            }
+            _ => panic!("expected a create table"),
+        }
+        match Parser::new("insert into t (b) values (x'cafe')").unwrap().update_cmd().unwrap() {
+            UpdateCmd::Insert(data) => assert_eq!(data.vals(), [Constant::Blob(vec![0xca, 0xfe])]),
+            _ => panic!("expected an insert"),
+        }
+        match Parser::new("create schema if not exists app").unwrap().update_cmd().unwrap() {
+            UpdateCmd::CreateSchema(data) => assert!(data.schema() == "app" && data.if_not_exists()),
+            _ => panic!("expected a create schema"),
+        }
+        match Parser::new("insert into app.t (a) values (1)").unwrap().update_cmd().unwrap() {
+            UpdateCmd::Insert(data) => assert_eq!(data.table(), "app.t"),
+            _ => panic!("expected an insert"),
+        }
+        match Parser::new("drop table t").unwrap().update_cmd().unwrap() {
+            UpdateCmd::Drop(data) => assert!(data.kind() == DropKind::Table && data.name() == "t" && !data.if_exists()),
+            _ => panic!("expected a drop"),
+        }
+        match Parser::new("drop index if exists ta").unwrap().update_cmd().unwrap() {
+            UpdateCmd::Drop(data) => assert!(data.kind() == DropKind::Index && data.name() == "ta" && data.if_exists()),
+            _ => panic!("expected a drop"),
+        }
+        match Parser::new("export table t where a = 1 to 't.sql'").unwrap().update_cmd().unwrap() {
+            UpdateCmd::Export(data) => {
+                assert_eq!(data.table(), "t");
+                assert_eq!(data.pred().to_string(), "a=1");
+                assert_eq!(data.file(), "t.sql");
+            }
+            _ => panic!("expected an export"),
+        }
+        match Parser::new("pin table dept").unwrap().update_cmd().unwrap() {
+            UpdateCmd::PinTable(table) => assert_eq!(table, "dept"),
+            _ => panic!("expected a pin table"),
+        }
+        match Parser::new("kill 12").unwrap().update_cmd().unwrap() {
+            UpdateCmd::Kill(txnum) => assert_eq!(txnum, 12),
+            _ => panic!("expected a kill"),
+        }
+        match Parser::new("checkpoint").unwrap().update_cmd().unwrap() {
+            UpdateCmd::Checkpoint => {}
+            _ => panic!("expected a checkpoint"),
+        }
+        match Parser::new("reindex index app.ta").unwrap().update_cmd().unwrap() {
+            UpdateCmd::Reindex(data) => assert_eq!((data.kind(), data.name()), (ReindexKind::Index, "app.ta")),
+            _ => panic!("expected a reindex"),
+        }
+        match Parser::new("reindex table t").unwrap().update_cmd().unwrap() {
+            UpdateCmd::Reindex(data) => assert_eq!((data.kind(), data.name()), (ReindexKind::Table, "t")),
+            _ => panic!("expected a reindex"),
+        }
+        assert!(Parser::new("reindex t").unwrap().update_cmd().is_err());
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let error = Parser::new("select from t").unwrap().query().err().unwrap();
+        assert_eq!(error.kind(), &ParseErrorKind::UnexpectedToken { expected: vec!["an identifier".to_string()], found: "from".to_string() });
+        assert_eq!(error.to_string(), "syntax error at line 1, column 8: expected an identifier but found from");
+        let sql = "create table t (\n  a int,\n  b int check (a > 1)\n)";
+        let error = Parser::new(sql).unwrap().update_cmd().err().unwrap();
+        assert_eq!(error.show(sql), "  b int check (a > 1)\n               ^\nsyntax error at line 3, column 16: expected field b but found a");
+        assert!(Parser::new("select a from t where").unwrap().query().is_err());
+        assert!(Parser::new("select a from t extra").unwrap().query().is_err());
+        assert!(Parser::new("drop t").unwrap().update_cmd().is_err());
+        assert!(Parser::new("drop table if not exists t").unwrap().update_cmd().is_err());
+        assert!(Parser::new("insert into t (a) values (b)").unwrap().update_cmd().is_err());
+        assert!(Parser::new("create table if exists t (a int)").unwrap().update_cmd().is_err());
+        assert!(Parser::new("create table t (a int check (b > 1))").unwrap().update_cmd().is_err());
+        assert!(Parser::new("create table t (a int check (a > 'x'))").unwrap().update_cmd().is_err());
+        assert!(Parser::new("create table t (a int check (a > 1.5))").unwrap().update_cmd().is_err());
+        assert!(Parser::new("create table t (a boolean check (a = null))").unwrap().update_cmd().is_err());
+        assert!(Parser::new("create table t (a date check (a > '2024-02-30'))").unwrap().update_cmd().is_err());
+        assert!(Parser::new("select a from t where a = date 'today'").unwrap().query().is_err());
+        let error = Parser::new("create table t (a varchar(0))").unwrap().update_cmd().err().unwrap();
+        assert_eq!(error.to_string(), "syntax error at line 1, column 27: expected a positive length but found 0");
+        assert!(Parser::new("create table t (a varchar(-3))").unwrap().update_cmd().is_err());
+        let error = Parser::new("select a from t where contains(b, 'disk full')").unwrap().query().err().unwrap();
+        assert_eq!(error.to_string(), "syntax error at line 1, column 35: expected a single word but found 'disk full'");
+        assert!(Parser::new("select a from t where contains(b, '')").unwrap().query().is_err());
+        assert!(Parser::new("select a from t where contains('x', 'disk')").unwrap().query().is_err());
+
+        let error = Parser::new("select a form t").unwrap().query().err().unwrap();
+        assert_eq!(error.to_string(), "syntax error at line 1, column 10: expected one of: ',', from but found form");
+        let error = Parser::new("select a from t where a = 1 b").unwrap().query().err().unwrap();
+        assert_eq!(error.to_string(), "syntax error at line 1, column 29: expected one of: and, end of input but found b");
+        let error = Parser::new("vacuum t").unwrap().update_cmd().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "syntax error at line 1, column 1: expected one of: insert, delete, update, create, drop, export, pin, kill, checkpoint, reindex but found vacuum"
+        );
+    }
+
+    #[test]
+    fn test_script() {
+        let cmds = Parser::new("create table t (a int); insert into t (a) values (1);\ncheckpoint;").unwrap().update_cmds().unwrap();
+        assert_eq!(cmds.len(), 3);
+        assert!(matches!(cmds[2], UpdateCmd::Checkpoint));
+        assert!(Parser::new("").unwrap().update_cmds().unwrap().is_empty());
+
+        // Each bad command is reported, as is the missing semicolon after the
+        // delete, and parsing resumes with the next command.
+        let script = "insert into t (a) values (1 2);\ndelete from t where a = 1\ninsert t (a) values (3);\nvacuum t;\ncheckpoint";
+        let errors = Parser::new(script).unwrap().update_cmds().err().unwrap();
+        let errors: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            errors,
+            [
+                "syntax error at line 1, column 29: expected one of: ',', ')' but found 2",
+                "syntax error at line 3, column 1: expected one of: and, ';', end of input but found insert",
+                "syntax error at line 3, column 8: expected into but found t",
+                "syntax error at line 4, column 1: expected one of: insert, delete, update, create, drop, export, pin, kill, checkpoint, reindex but found vacuum",
+            ]
+        );
+    }
+}
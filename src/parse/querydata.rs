@@ -0,0 +1,42 @@
+use std::fmt::Display;
+use crate::parse::lexer::Lexer;
+use crate::query::predicate::Predicate;
+
+// The parsed form of a SELECT statement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryData {
+    fields: Vec<String>,
+    tables: Vec<String>,
+    pred: Predicate,
+}
+
+impl QueryData {
+    pub fn new(fields: Vec<String>, tables: Vec<String>, pred: Predicate) -> QueryData {
+        QueryData { fields, tables, pred }
+    }
+
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    pub fn tables(&self) -> &[String] {
+        &self.tables
+    }
+
+    pub fn pred(&self) -> &Predicate {
+        &self.pred
+    }
+}
+
+// Reconstructs the text of the query.
+impl Display for QueryData {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let fields: Vec<String> = self.fields.iter().map(|field| Lexer::quote(field)).collect();
+        let tables: Vec<String> = self.tables.iter().map(|table| Lexer::quote(table)).collect();
+        write!(f, "select {} from {}", fields.join(", "), tables.join(", "))?;
+        if !self.pred.is_empty() {
+            write!(f, " where {}", self.pred)?;
+        }
+        Ok(())
+    }
+}
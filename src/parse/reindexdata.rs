@@ -0,0 +1,27 @@
+// What a REINDEX statement rebuilds: an index, or every index of a table.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReindexKind {
+    Index,
+    Table,
+}
+
+// The parsed form of a REINDEX statement.
+#[derive(Clone)]
+pub struct ReindexData {
+    kind: ReindexKind,
+    name: String,
+}
+
+impl ReindexData {
+    pub fn new(kind: ReindexKind, name: String) -> ReindexData {
+        ReindexData { kind, name }
+    }
+
+    pub fn kind(&self) -> ReindexKind {
+        self.kind
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
@@ -0,0 +1,15 @@
+pub mod plan;
+pub mod tableplan;
+pub mod selectplan;
+pub mod projectplan;
+pub mod productplan;
+pub mod indexselectplan;
+pub mod indexjoinplan;
+pub mod bitmapandplan;
+pub mod lockeventsplan;
+pub mod tablesizesplan;
+pub mod transactionsplan;
+pub mod searchpath;
+pub mod basicqueryplanner;
+pub mod basicupdateplanner;
+pub mod indexupdateplanner;
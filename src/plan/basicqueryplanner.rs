@@ -0,0 +1,213 @@
+use std::sync::{Arc, Mutex};
+use crate::materialize::materializeplan::MaterializePlan;
+use crate::materialize::mergejoinplan::MergeJoinPlan;
+use crate::metadata::metadatamgr::MetadataMgr;
+use crate::parse::parser::Parser;
+use crate::parse::querydata::QueryData;
+use crate::plan::bitmapandplan::BitmapAndPlan;
+use crate::plan::indexjoinplan::IndexJoinPlan;
+use crate::plan::indexselectplan::IndexSelectPlan;
+use crate::plan::lockeventsplan::LockEventsPlan;
+use crate::plan::plan::Plan;
+use crate::plan::productplan::ProductPlan;
+use crate::plan::projectplan::ProjectPlan;
+use crate::plan::searchpath::SearchPath;
+use crate::plan::selectplan::SelectPlan;
+use crate::plan::tableplan::TablePlan;
+use crate::plan::tablesizesplan::TableSizesPlan;
+use crate::plan::transactionsplan::TransactionsPlan;
+use crate::query::predicate::Predicate;
+use crate::record::scanerror::ScanError;
+use crate::tx::transaction::Transaction;
+
+// The BasicQueryPlanner translates a query into relational algebra as
+// directly as possible: the product of its tables in the order they are
+// listed, a select on the predicate, and a projection on the selected
+// fields. It makes no attempt to find a cheaper plan, except that indexes
+// are used when the predicate allows: a table with an index on a field the
+// predicate equates with a constant is read through the index, and a table
+// with an index on a field the predicate equates with a field of the
+// tables before it is joined to them through the index instead of a
+// product. Without such an index, a table joined on a field is merge
+// joined to the tables before it when that reads fewer blocks than the
+// product. A view is planned by parsing its definition and planning that
+// query in place of the view. Table names are looked up on the planner's
+// search path.
+pub struct BasicQueryPlanner {
+    mdm: Arc<MetadataMgr>,
+    search_path: SearchPath,
+}
+
+impl BasicQueryPlanner {
+    pub fn new(mdm: Arc<MetadataMgr>) -> BasicQueryPlanner {
+        BasicQueryPlanner { mdm, search_path: SearchPath::default() }
+    }
+
+    pub fn set_search_path(&mut self, search_path: SearchPath) {
+        self.search_path = search_path;
+    }
+
+    pub fn create_plan(&self, data: &QueryData, tx: Arc<Mutex<Transaction>>) -> Result<Box<dyn Plan>, ScanError> {
+        let mut plan: Option<Box<dyn Plan>> = None;
+        for table in data.tables() {
+            let table = &Self::resolve(&self.mdm, &self.search_path, table, tx.clone())?;
+            plan = Some(match (plan.take(), self.mdm.view_def(table, tx.clone())?) {
+                (current, Some(def)) => {
+                    // The definition was written from a parsed query, so it parses.
+                    let view_data = Parser::new(&def).and_then(|mut parser| parser.query()).expect("a stored view definition parses");
+                    let next = self.create_plan(&view_data, tx.clone())?;
+                    match current {
+                        Some(current) => Box::new(ProductPlan::new(current, next)),
+                        None => next,
+                    }
+                }
+                (Some(current), None) => self.join_plan(current, table, data.pred(), tx.clone())?,
+                (None, None) => self.table_plan(table, data.pred(), tx.clone())?,
+            });
+        }
+        let mut plan = plan.expect("a query reads at least one table");
+
+        plan = Box::new(SelectPlan::new(plan, data.pred().clone()));
+        if let Some(field) = data.fields().iter().find(|field| !plan.schema().has_field(field)) {
+            return Err(ScanError::UnknownField(field.clone()));
+        }
+        Ok(Box::new(ProjectPlan::new(plan, data.fields())))
+    }
+
+    // Returns the catalogued name of the table or view. System tables
+    // belong to no schema and are visible whatever the search path.
+    pub(crate) fn resolve(mdm: &MetadataMgr, search_path: &SearchPath, table: &str, tx: Arc<Mutex<Transaction>>) -> Result<String, ScanError> {
+        if matches!(table, LockEventsPlan::TABLE | TableSizesPlan::TABLE | TableSizesPlan::RELATIONS | TransactionsPlan::TABLE) {
+            return Ok(table.to_string());
+        }
+        search_path.resolve(table, |name| mdm.relation_exists(name, tx.clone()))
+    }
+
+    // System tables are not in the catalog and have plans of their own.
+    pub(crate) fn system_table_plan(mdm: &MetadataMgr, table: &str, tx: Arc<Mutex<Transaction>>) -> Result<Option<Box<dyn Plan>>, ScanError> {
+        Ok(match table {
+            LockEventsPlan::TABLE => Some(Box::new(LockEventsPlan::new(tx.clone()))),
+            TableSizesPlan::TABLE => Some(Box::new(TableSizesPlan::new(mdm, false, tx)?)),
+            TableSizesPlan::RELATIONS => Some(Box::new(TableSizesPlan::new(mdm, true, tx)?)),
+            TransactionsPlan::TABLE => Some(Box::new(TransactionsPlan::new(tx.clone()))),
+            _ => None,
+        })
+    }
+
+    // The select on the whole predicate still follows, so the index only
+    // narrows down the records read. Of the indexes the predicate can use,
+    // which only include a partial index if it implies the index's own, the
+    // cheapest is taken, or all of them combined in a bitmap scan, unless
+    // fetching the records at random costs more than reading the whole
+    // table in order.
+    fn table_plan(&self, table: &str, pred: &Predicate, tx: Arc<Mutex<Transaction>>) -> Result<Box<dyn Plan>, ScanError> {
+        if let Some(plan) = Self::system_table_plan(&self.mdm, table, tx.clone())? {
+            return Ok(plan);
+        }
+        let mut best: Box<dyn Plan> = Box::new(TablePlan::new(tx.clone(), table, &self.mdm)?);
+        let indexes = self.mdm.usable_indexes(table, pred, tx.clone())?;
+        let text_indexes = self.mdm.usable_text_indexes(table, pred, tx.clone())?;
+        let lookups = BitmapAndPlan::lookups(indexes.values().chain(text_indexes.values()), pred);
+        for (ii, val) in &lookups {
+            let plan = IndexSelectPlan::new(TablePlan::new(tx.clone(), table, &self.mdm)?, ii.clone(), val.clone());
+            if plan.cost() < best.cost() {
+                best = Box::new(plan);
+            }
+        }
+        if lookups.len() >= 2 {
+            let plan = BitmapAndPlan::new(TablePlan::new(tx.clone(), table, &self.mdm)?, lookups);
+            if plan.cost() < best.cost() {
+                best = Box::new(plan);
+            }
+        }
+        Ok(best)
+    }
+
+    fn join_plan(&self, current: Box<dyn Plan>, table: &str, pred: &Predicate, tx: Arc<Mutex<Transaction>>) -> Result<Box<dyn Plan>, ScanError> {
+        for (field, ii) in self.mdm.usable_indexes(table, pred, tx.clone())? {
+            if let Some(join_field) = pred.equates_with_field(&field) && current.schema().has_field(join_field) {
+                let plan = TablePlan::new(tx, table, &self.mdm)?;
+                return Ok(Box::new(IndexJoinPlan::new(current, plan, ii, join_field)));
+            }
+        }
+        let plan = self.table_plan(table, pred, tx.clone())?;
+        let join_fields = plan.schema().fields().iter().find_map(|field| {
+            pred.equates_with_field(field).filter(|join_field| current.schema().has_field(join_field)).map(|join_field| (join_field.to_string(), field.clone()))
+        });
+        if let Some((field1, field2)) = join_fields {
+            // The costs of the ProductPlan and the MergeJoinPlan, worked out
+            // before either takes the plans.
+            let product_blocks = current.blocks_accessed() + current.records_output() * plan.blocks_accessed();
+            let merge_blocks = MaterializePlan::blocks(&tx, current.as_ref()) + MaterializePlan::blocks(&tx, plan.as_ref());
+            if merge_blocks < product_blocks {
+                return Ok(Box::new(MergeJoinPlan::new(tx, current, plan, &field1, &field2)));
+            }
+        }
+        Ok(Box::new(ProductPlan::new(current, plan)))
+    }
+}
+
+mod tests {
+    use std::fs;
+    use super::*;
+    use crate::parse::parser::Parser;
+    use crate::server::simpledb::SimpleDB;
+    use crate::record::schema::Schema;
+    use crate::query::scan::Scan;
+    use crate::record::tablescan::UpdateScan;
+
+    #[test]
+    fn test_basic_query_planner() {
+        let _ = fs::remove_dir_all("queryplannertestdb");
+        let db = SimpleDB::new("queryplannertestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+
+        let mut schema = Schema::new();
+        schema.add_int_field("sid");
+        schema.add_string_field("sname", 10);
+        schema.add_int_field("majorid");
+        mdm.create_table("student", &schema, tx.clone()).unwrap();
+        let mut schema = Schema::new();
+        schema.add_int_field("did");
+        schema.add_string_field("dname", 10);
+        mdm.create_table("dept", &schema, tx.clone()).unwrap();
+
+        let planner = BasicQueryPlanner::new(mdm.clone());
+        let mut ts = TablePlan::new(tx.clone(), "student", &mdm).unwrap().open_table().unwrap();
+        for (sid, sname, majorid) in [(1, "joe", 10), (2, "amy", 20), (3, "max", 10)] {
+            ts.insert().unwrap();
+            ts.set_int("sid", sid).unwrap();
+            ts.set_string("sname", sname).unwrap();
+            ts.set_int("majorid", majorid).unwrap();
+        }
+        ts.close();
+        let mut ts = TablePlan::new(tx.clone(), "dept", &mdm).unwrap().open_table().unwrap();
+        for (did, dname) in [(10, "compsci"), (20, "math")] {
+            ts.insert().unwrap();
+            ts.set_int("did", did).unwrap();
+            ts.set_string("dname", dname).unwrap();
+        }
+        ts.close();
+
+        let data = Parser::new("select sname, dname from student, dept where majorid = did and dname = 'compsci'").unwrap().query().unwrap();
+        let plan = planner.create_plan(&data, tx.clone()).unwrap();
+        assert_eq!(plan.schema().fields().len(), 2);
+        assert!(plan.records_output() <= 6);
+        let mut scan = plan.open().unwrap();
+        let mut names = Vec::new();
+        while scan.next().unwrap() {
+            names.push(scan.get_string("sname").unwrap());
+            assert_eq!(scan.get_string("dname").unwrap(), "compsci");
+        }
+        scan.close();
+        assert_eq!(names, ["joe", "max"]);
+
+        let data = Parser::new("select sname from nosuchtable").unwrap().query().unwrap();
+        assert_eq!(planner.create_plan(&data, tx.clone()).err(), Some(ScanError::UnknownTable("nosuchtable".to_string())));
+        let data = Parser::new("select nosuchfield from student").unwrap().query().unwrap();
+        assert_eq!(planner.create_plan(&data, tx.clone()).err(), Some(ScanError::UnknownField("nosuchfield".to_string())));
+
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
@@ -0,0 +1,831 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::iter;
+use std::sync::{Arc, Mutex};
+use crate::index::index::IndexKind;
+use crate::metadata::metadatamgr::MetadataMgr;
+use crate::metadata::schemamgr::SchemaMgr;
+use crate::parse::createindexdata::CreateIndexData;
+use crate::parse::createschemadata::CreateSchemaData;
+use crate::parse::createtabledata::CreateTableData;
+use crate::parse::createviewdata::CreateViewData;
+use crate::parse::deletedata::DeleteData;
+use crate::parse::dropdata::{DropData, DropKind};
+use crate::parse::exportdata::ExportData;
+use crate::parse::insertdata::InsertData;
+use crate::parse::lexer::Lexer;
+use crate::parse::modifydata::ModifyData;
+use crate::parse::parser::UpdateCmd;
+use crate::parse::querydata::QueryData;
+use crate::parse::reindexdata::{ReindexData, ReindexKind};
+use crate::plan::basicqueryplanner::BasicQueryPlanner;
+use crate::plan::searchpath::SearchPath;
+use crate::query::scan::Scan;
+use crate::query::selectscan::SelectScan;
+use crate::record::row::Row;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::FieldType;
+use crate::record::tablescan::{TableScan, UpdateScan};
+use crate::tx::transaction::Transaction;
+
+// The BasicUpdatePlanner executes the statements that change the database.
+// Updates and deletes run a select scan over the table and change each
+// record it outputs; each method returns the number of records affected.
+// If a statement fails halfway, the records it already changed stay
+// changed until the transaction is rolled back. Names are looked up, and
+// new objects created, on the planner's search path.
+pub struct BasicUpdatePlanner {
+    mdm: Arc<MetadataMgr>,
+    search_path: SearchPath,
+}
+
+impl BasicUpdatePlanner {
+    pub fn new(mdm: Arc<MetadataMgr>) -> BasicUpdatePlanner {
+        BasicUpdatePlanner { mdm, search_path: SearchPath::default() }
+    }
+
+    pub fn set_search_path(&mut self, search_path: SearchPath) {
+        self.search_path = search_path;
+    }
+
+    pub fn execute_update(&self, cmd: &UpdateCmd, tx: Arc<Mutex<Transaction>>) -> Result<usize, ScanError> {
+        match cmd {
+            UpdateCmd::Insert(data) => self.execute_insert(data, tx),
+            UpdateCmd::Delete(data) => self.execute_delete(data, tx),
+            UpdateCmd::Modify(data) => self.execute_modify(data, tx),
+            UpdateCmd::CreateTable(data) => self.execute_create_table(data, tx),
+            UpdateCmd::CreateView(data) => self.execute_create_view(data, tx),
+            UpdateCmd::CreateIndex(data) => self.execute_create_index(data, tx),
+            UpdateCmd::CreateSchema(data) => self.execute_create_schema(data, tx),
+            UpdateCmd::Drop(data) => self.execute_drop(data, tx),
+            UpdateCmd::Export(data) => self.execute_export(data, tx),
+            UpdateCmd::PinTable(table) => self.execute_pin_table(table, tx),
+            UpdateCmd::Kill(txnum) => self.execute_kill(*txnum, tx),
+            UpdateCmd::Checkpoint => self.execute_checkpoint(tx),
+            UpdateCmd::Reindex(data) => self.execute_reindex(data, tx),
+        }
+    }
+
+    pub fn execute_insert(&self, data: &InsertData, tx: Arc<Mutex<Transaction>>) -> Result<usize, ScanError> {
+        let mut row = Row::new();
+        for (field, val) in data.fields().iter().zip(data.vals()) {
+            row.set(field, val.clone());
+        }
+        let mut ts = self.open(data.table(), tx)?;
+        ts.insert_row(&row)?;
+        ts.close();
+        Ok(1)
+    }
+
+    pub fn execute_delete(&self, data: &DeleteData, tx: Arc<Mutex<Transaction>>) -> Result<usize, ScanError> {
+        let mut us = SelectScan::new(self.open(data.table(), tx)?, data.pred().clone());
+        let mut count = 0;
+        while us.next()? {
+            us.delete()?;
+            count += 1;
+        }
+        us.close();
+        Ok(count)
+    }
+
+    pub fn execute_modify(&self, data: &ModifyData, tx: Arc<Mutex<Transaction>>) -> Result<usize, ScanError> {
+        let mut us = SelectScan::new(self.open(data.table(), tx)?, data.pred().clone());
+        let mut count = 0;
+        while us.next()? {
+            let val = data.new_val().evaluate(&mut us)?;
+            us.set_val(data.target_field(), &val)?;
+            count += 1;
+        }
+        us.close();
+        Ok(count)
+    }
+
+    pub fn execute_create_table(&self, data: &CreateTableData, tx: Arc<Mutex<Transaction>>) -> Result<usize, ScanError> {
+        let table = &self.target(data.table(), tx.clone())?;
+        if Self::exists(self.mdm.relation_exists(table, tx.clone())?, data.table(), data.if_not_exists())? {
+            return Ok(0);
+        }
+        // The table is not in the catalog, so any file with its name was
+        // left behind by a drop that did not get to delete it, unless the
+        // drop is this transaction's own and the file is still needed if it
+        // rolls back.
+        let filename = format!("{}.tbl", table);
+        if tx.lock().unwrap().deletes_file(&filename) {
+            return Err(ScanError::AlreadyExists(data.table().to_string()));
+        }
+        tx.lock().unwrap().delete_stale_file(&filename)?;
+        self.mdm.create_table(table, data.schema(), tx)?;
+        Ok(0)
+    }
+
+    // The tables of the view's query are looked up now and stored by their
+    // catalogued names, so the view reads the same tables whatever the
+    // search path of the session using it.
+    pub fn execute_create_view(&self, data: &CreateViewData, tx: Arc<Mutex<Transaction>>) -> Result<usize, ScanError> {
+        let view = &self.target(data.view(), tx.clone())?;
+        if Self::exists(self.mdm.relation_exists(view, tx.clone())?, data.view(), data.if_not_exists())? {
+            return Ok(0);
+        }
+        let query = data.query();
+        let tables = query.tables().iter().map(|table| self.resolve(table, tx.clone())).collect::<Result<Vec<_>, _>>()?;
+        let def = QueryData::new(query.fields().to_vec(), tables, query.pred().clone());
+        self.mdm.create_view(view, &def.to_string(), tx)?;
+        Ok(0)
+    }
+
+    // Records the index in the catalog and fills it with the records the
+    // table already has. Nulls are left out of indexes, since no search can
+    // match them.
+    pub fn execute_create_index(&self, data: &CreateIndexData, tx: Arc<Mutex<Transaction>>) -> Result<usize, ScanError> {
+        let Some((index, table)) = &self.record_index(data, true, tx.clone())? else {
+            return Ok(0);
+        };
+        self.fill_index(index, table, tx)?;
+        Ok(0)
+    }
+
+    // Adds an index record for each record of the table the index holds.
+    fn fill_index(&self, index: &str, table: &str, tx: Arc<Mutex<Transaction>>) -> Result<(), ScanError> {
+        let layout = self.mdm.layout(table, tx.clone())?;
+        // The index info is looked up before the table scan is opened, since
+        // computing its statistics scans the table with the same transaction.
+        let ii = self.mdm.maintained_indexes(table, tx.clone())?.into_iter().find(|ii| ii.index_name() == index);
+        let ii = ii.ok_or_else(|| ScanError::UnknownIndex(index.to_string()))?;
+        let mut idx = ii.open();
+        let mut ts = TableScan::new(tx, layout, table)?;
+        while ts.next()? {
+            let rid = ts.rid().ok_or(ScanError::NoCurrentRecord)?;
+            if let Some(val) = ii.key(&mut ts)? {
+                idx.insert(&val, &rid)?;
+            }
+        }
+        idx.close();
+        ts.close();
+        self.mdm.forget_stats(index);
+        Ok(())
+    }
+
+    // Records the index of a CREATE INDEX statement in the catalog, empty,
+    // and returns the catalogued names of the index and its table, or None
+    // if the statement has nothing to do.
+    pub(crate) fn record_index(&self, data: &CreateIndexData, published: bool, tx: Arc<Mutex<Transaction>>) -> Result<Option<(String, String)>, ScanError> {
+        let index = self.target(data.index(), tx.clone())?;
+        if Self::exists(self.mdm.index_exists(&index, tx.clone())?, data.index(), data.if_not_exists())? {
+            return Ok(None);
+        }
+        let table = self.resolve(data.table(), tx.clone())?;
+        let layout = self.mdm.layout(&table, tx.clone())?;
+        let exprs = data.pred().terms().iter().flat_map(|term| [term.lhs(), term.rhs()]);
+        let mut fields = iter::once(data.field()).chain(exprs.filter_map(|expr| expr.as_field_name()));
+        if let Some(field) = fields.find(|field| !layout.schema().has_field(field)) {
+            return Err(ScanError::UnknownField(field.to_string()));
+        }
+        // A text index splits strings into words.
+        if data.kind() == IndexKind::Text && layout.schema().ftype(data.field()) != FieldType::VARCHAR as i32 {
+            return Err(ScanError::TypeMismatch { field: data.field().to_string(), expected: FieldType::VARCHAR });
+        }
+        match published {
+            true => self.mdm.create_index(&index, &table, data.field(), data.kind(), data.pred(), tx)?,
+            false => self.mdm.create_unpublished_index(&index, &table, data.field(), data.kind(), data.pred(), tx)?,
+        }
+        Ok(Some((index, table)))
+    }
+
+    pub fn execute_create_schema(&self, data: &CreateSchemaData, tx: Arc<Mutex<Transaction>>) -> Result<usize, ScanError> {
+        if Self::exists(self.mdm.schema_exists(data.schema(), tx.clone())?, data.schema(), data.if_not_exists())? {
+            return Ok(0);
+        }
+        self.mdm.create_schema(data.schema(), tx)?;
+        Ok(0)
+    }
+
+    // Removes the object from the catalog; the files of a table and its
+    // indexes are deleted when the transaction commits.
+    pub fn execute_drop(&self, data: &DropData, tx: Arc<Mutex<Transaction>>) -> Result<usize, ScanError> {
+        let dropped = match data.kind() {
+            DropKind::Table => match self.mdm.drop_table(&self.resolve(data.name(), tx.clone())?, tx) {
+                Err(ScanError::UnknownTable(_)) => false,
+                result => result.map(|_| true)?,
+            },
+            DropKind::View => self.mdm.drop_view(&self.resolve(data.name(), tx.clone())?, tx)?,
+            DropKind::Index => {
+                let index = self.search_path.resolve(data.name(), |name| self.mdm.index_exists(name, tx.clone()))?;
+                self.mdm.drop_index(&index, tx)?
+            }
+        };
+        match (dropped, data.if_exists(), data.kind()) {
+            (false, false, DropKind::Index) => Err(ScanError::UnknownIndex(data.name().to_string())),
+            (false, false, _) => Err(ScanError::UnknownTable(data.name().to_string())),
+            _ => Ok(0),
+        }
+    }
+
+    // Writes an INSERT statement for each record of the table that satisfies
+    // the predicate, one per line, and returns the number of records written.
+    // The parser takes one statement at a time without a terminator, so the
+    // file is replayed on another database line by line.
+    pub fn execute_export(&self, data: &ExportData, tx: Arc<Mutex<Transaction>>) -> Result<usize, ScanError> {
+        let table = &self.resolve(data.table(), tx.clone())?;
+        let layout = self.mdm.layout(table, tx.clone())?;
+        let fields: Vec<&str> = layout.schema().fields().iter().map(|field| field.as_ref()).collect();
+        let names: Vec<String> = fields.iter().map(|field| Lexer::quote(field)).collect();
+        let mut out = BufWriter::new(File::create(data.file())?);
+        let mut s = SelectScan::new(TableScan::new(tx, layout.clone(), table)?, data.pred().clone());
+        let mut count = 0;
+        while s.next()? {
+            let vals = fields.iter().map(|field| s.get_val(field).map(|val| val.to_string())).collect::<Result<Vec<_>, _>>()?;
+            writeln!(out, "insert into {} ({}) values ({})", Lexer::quote(data.table()), names.join(", "), vals.join(", "))?;
+            count += 1;
+        }
+        s.close();
+        out.flush()?;
+        Ok(count)
+    }
+
+    // Returns the catalogued name of the table or view the name refers to.
+    pub(crate) fn resolve(&self, name: &str, tx: Arc<Mutex<Transaction>>) -> Result<String, ScanError> {
+        BasicQueryPlanner::resolve(&self.mdm, &self.search_path, name, tx)
+    }
+
+    // Returns the catalogued name of a new object, whose schema must exist.
+    fn target(&self, name: &str, tx: Arc<Mutex<Transaction>>) -> Result<String, ScanError> {
+        let target = self.search_path.target(name);
+        let (schema, _) = SchemaMgr::split(&target);
+        if !self.mdm.schema_exists(schema, tx)? {
+            return Err(ScanError::UnknownSchema(schema.to_string()));
+        }
+        Ok(target)
+    }
+
+    // Whether a CREATE statement has nothing to do: creating something that
+    // already exists is an error, unless the statement said IF NOT EXISTS.
+    fn exists(exists: bool, name: &str, if_not_exists: bool) -> Result<bool, ScanError> {
+        match (exists, if_not_exists) {
+            (true, false) => Err(ScanError::AlreadyExists(name.to_string())),
+            (exists, _) => Ok(exists),
+        }
+    }
+
+    // Marks the blocks of the table to be kept in memory. The hint applies
+    // to the whole database and is not persisted, so it lasts until the
+    // database is closed.
+    pub fn execute_pin_table(&self, table: &str, tx: Arc<Mutex<Transaction>>) -> Result<usize, ScanError> {
+        let table = &self.resolve(table, tx.clone())?;
+        self.mdm.layout(table, tx.clone())?;
+        tx.lock().unwrap().keep_resident(&format!("{}.tbl", table));
+        Ok(0)
+    }
+
+    // Forces the rollback of an active transaction of the same database;
+    // see `TxRegistry::kill`.
+    pub fn execute_kill(&self, txnum: i32, tx: Arc<Mutex<Transaction>>) -> Result<usize, ScanError> {
+        let registry = tx.lock().unwrap().registry();
+        if !registry.kill(txnum) {
+            return Err(ScanError::UnknownTransaction(txnum));
+        }
+        Ok(0)
+    }
+
+    // Rebuilds the index, or every index of the table, from the records of
+    // the table: its index records are all deleted, and it is filled again
+    // as CREATE INDEX fills a new one. An index that lost index records, or
+    // kept ones it should not have, then matches the table again. Like any
+    // other statement, the rebuild is undone if the transaction rolls back.
+    // An index whose online build was cut short is published once rebuilt.
+    pub fn execute_reindex(&self, data: &ReindexData, tx: Arc<Mutex<Transaction>>) -> Result<usize, ScanError> {
+        let catalog = self.mdm.catalog(tx.clone())?;
+        let indexes: Vec<(String, String)> = match data.kind() {
+            ReindexKind::Index => {
+                let index = self.search_path.resolve(data.name(), |name| self.mdm.index_exists(name, tx.clone()))?;
+                let found = catalog.all_indexes().iter().flat_map(|(table, indexes)| indexes.iter().map(move |(name, _)| (name, table)));
+                found.filter(|(name, _)| **name == index).map(|(name, table)| (name.clone(), table.clone())).collect()
+            }
+            ReindexKind::Table => {
+                let table = self.resolve(data.name(), tx.clone())?;
+                self.mdm.layout(&table, tx.clone())?;
+                catalog.indexes(&table).iter().map(|(index, _)| (index.clone(), table.clone())).collect()
+            }
+        };
+        if data.kind() == ReindexKind::Index && indexes.is_empty() {
+            return Err(ScanError::UnknownIndex(data.name().to_string()));
+        }
+        for (index, table) in indexes {
+            let ii = self.mdm.maintained_indexes(&table, tx.clone())?.into_iter().find(|ii| ii.index_name() == index);
+            let mut idx = ii.ok_or_else(|| ScanError::UnknownIndex(index.clone()))?.open();
+            idx.clear()?;
+            idx.close();
+            self.fill_index(&index, &table, tx.clone())?;
+            if !catalog.is_published(&index) {
+                self.mdm.publish_index(&index, tx.clone())?;
+            }
+        }
+        Ok(0)
+    }
+
+    // The statement must come before any change in its transaction; see
+    // `Transaction::checkpoint`.
+    pub fn execute_checkpoint(&self, tx: Arc<Mutex<Transaction>>) -> Result<usize, ScanError> {
+        tx.lock().unwrap().checkpoint()?;
+        Ok(0)
+    }
+
+    fn open(&self, table: &str, tx: Arc<Mutex<Transaction>>) -> Result<TableScan, ScanError> {
+        let table = &self.resolve(table, tx.clone())?;
+        let layout = self.mdm.layout(table, tx.clone())?;
+        TableScan::new(tx, layout, table)
+    }
+}
+
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use super::*;
+    use crate::error::dberror::DbError;
+    use crate::parse::parser::Parser;
+    use crate::server::simpledb::SimpleDB;
+    use crate::plan::basicqueryplanner::BasicQueryPlanner;
+    use crate::query::constant::Constant;
+    use crate::record::schema::FieldType;
+    use crate::record::tablescan::RecordId;
+
+    #[test]
+    fn test_basic_update_planner() {
+        let _ = fs::remove_dir_all("updateplannertestdb");
+        let db = SimpleDB::new("updateplannertestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let planner = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone());
+
+        assert_eq!(update("create table t (a int, b varchar(10))"), Ok(0));
+        for i in 0..6 {
+            assert_eq!(update(&format!("insert into t (a, b) values ({}, 'rec{}')", i % 3, i)), Ok(1));
+        }
+        assert_eq!(update("update t set b = 'two' where a = 2"), Ok(2));
+        assert_eq!(update("update t set a = b where a = 1"), Err(ScanError::TypeMismatch { field: "a".to_string(), expected: FieldType::VARCHAR }));
+        assert_eq!(update("delete from t where a = 0"), Ok(2));
+        assert_eq!(update("insert into nosuchtable (a) values (1)"), Err(ScanError::UnknownTable("nosuchtable".to_string())));
+        assert_eq!(update("pin table nosuchtable"), Err(ScanError::UnknownTable("nosuchtable".to_string())));
+
+        let data = Parser::new("select a, b from t").unwrap().query().unwrap();
+        let mut scan = BasicQueryPlanner::new(mdm).create_plan(&data, tx.clone()).unwrap().open().unwrap();
+        let mut rows = Vec::new();
+        while scan.next().unwrap() {
+            rows.push((scan.get_int("a").unwrap(), scan.get_string("b").unwrap()));
+        }
+        scan.close();
+        rows.sort();
+        assert_eq!(rows, [(1, "rec1".to_string()), (1, "rec4".to_string()), (2, "two".to_string()), (2, "two".to_string())]);
+
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_export() {
+        let _ = fs::remove_dir_all("exporttestdb");
+        let db = SimpleDB::new("exporttestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let planner = BasicUpdatePlanner::new(db.metadata_mgr());
+        let update = |sql: &str| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone());
+
+        update("create table t (a int, b varchar(10))").unwrap();
+        for i in 0..4 {
+            update(&format!("insert into t (a, b) values ({}, 'rec{}')", i, i)).unwrap();
+        }
+        assert_eq!(update("export table t where b = 'rec2' to 'exporttestdb/t.sql'"), Ok(1));
+        let dump = fs::read_to_string("exporttestdb/t.sql").unwrap();
+        assert_eq!(dump, "insert into t (a, b) values (2, 'rec2')\n");
+
+        // The dump replays into another table with the same schema.
+        update("create table u (a int, b varchar(10))").unwrap();
+        assert_eq!(update(&dump.trim_end().replace("into t", "into u")), Ok(1));
+        assert_eq!(update("export table u to 'exporttestdb/u.sql'"), Ok(1));
+        assert_eq!(fs::read_to_string("exporttestdb/u.sql").unwrap(), dump.replace("into t", "into u"));
+
+        // Quotes in strings are doubled, so the dump reads back.
+        update("insert into u (a, b) values (9, 'it''s')").unwrap();
+        assert_eq!(update("export table u where a = 9 to 'exporttestdb/q.sql'"), Ok(1));
+        let dump = fs::read_to_string("exporttestdb/q.sql").unwrap();
+        assert_eq!(dump, "insert into u (a, b) values (9, 'it''s')\n");
+        assert_eq!(update("delete from u where b = 'it''s'"), Ok(1));
+        assert_eq!(update(dump.trim_end()), Ok(1));
+
+        assert!(matches!(update("export table t to 'nosuchdir/t.sql'"), Err(ScanError::Db(DbError::Io(_)))));
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_quoted_identifiers() {
+        let _ = fs::remove_dir_all("quotedidtestdb");
+        let db = SimpleDB::new("quotedidtestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let planner = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone());
+        let query = |sql: &str| -> Result<Vec<i32>, ScanError> {
+            let data = Parser::new(sql).unwrap().query().unwrap();
+            let mut scan = BasicQueryPlanner::new(mdm.clone()).create_plan(&data, tx.clone())?.open()?;
+            let mut vals = Vec::new();
+            while scan.next()? {
+                vals.push(scan.get_int(data.fields()[0].as_str())?);
+            }
+            scan.close();
+            Ok(vals)
+        };
+
+        // Unquoted names are folded to lower case; quoted ones are kept as
+        // they are, so MyTable and "MyTable" are two tables.
+        update("create table MyTable (Id int)").unwrap();
+        update("create table \"MyTable\" (\"Id\" int, \"from\" int)").unwrap();
+        update("insert into MYTABLE (ID) values (1)").unwrap();
+        update("insert into \"MyTable\" (\"Id\", \"from\") values (2, 3)").unwrap();
+        assert_eq!(query("select id from mytable"), Ok(vec![1]));
+        assert_eq!(query("select \"Id\" from \"MyTable\""), Ok(vec![2]));
+        assert_eq!(query("select Id from \"MyTable\""), Err(ScanError::UnknownField("id".to_string())));
+
+        // A view keeps the quotes its definition needs to read back the same.
+        update("create view \"V\" as select \"from\" from \"MyTable\" where \"Id\" = 2").unwrap();
+        assert_eq!(query("select \"from\" from \"V\""), Ok(vec![3]));
+
+        update("export table \"MyTable\" to 'quotedidtestdb/t.sql'").unwrap();
+        let dump = fs::read_to_string("quotedidtestdb/t.sql").unwrap();
+        assert_eq!(dump, "insert into \"MyTable\" (\"Id\", \"from\") values (2, 3)\n");
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_reindex() {
+        let db = SimpleDB::ephemeral().unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let planner = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone());
+        let count = |field: &str, key: i32| {
+            let mut idx = mdm.index_info("t", tx.clone()).unwrap()[field].open();
+            idx.before_first(&Constant::Int(key)).unwrap();
+            let mut count = 0;
+            while idx.next().unwrap() {
+                count += 1;
+            }
+            idx.close();
+            count
+        };
+        update("create table t (a int, b int)").unwrap();
+        update("create index ta on t (a)").unwrap();
+        update("create index tb on t (b)").unwrap();
+
+        // This planner does not keep indexes up to date, so the index misses
+        // the new records, and keeps one for a record that no longer exists.
+        update("insert into t (a, b) values (1, 1)").unwrap();
+        for i in 0..10 {
+            update(&format!("insert into t (a, b) values ({}, {})", i % 2, i)).unwrap();
+        }
+        let mut idx = mdm.index_info("t", tx.clone()).unwrap()["a"].open();
+        idx.insert(&Constant::Int(1), &RecordId { blocknum: 9, slot: 0 }).unwrap();
+        idx.close();
+        assert_eq!((count("a", 0), count("a", 1), count("b", 1)), (0, 1, 0));
+
+        update("reindex index ta").unwrap();
+        assert_eq!((count("a", 0), count("a", 1), count("b", 1)), (5, 6, 0));
+        update("reindex table t").unwrap();
+        assert_eq!((count("a", 0), count("a", 1), count("b", 1)), (5, 6, 2));
+
+        assert_eq!(update("reindex index tc"), Err(ScanError::UnknownIndex("tc".to_string())));
+        assert_eq!(update("reindex table u"), Err(ScanError::UnknownTable("u".to_string())));
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_if_not_exists() {
+        let _ = fs::remove_dir_all("ifnotexiststestdb");
+        let db = SimpleDB::new("ifnotexiststestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let planner = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone());
+
+        // The setup script can run any number of times.
+        let setup = [
+            "create table if not exists t (a int, b varchar(10))",
+            "create index if not exists ta on t (a)",
+            "create view if not exists v as select a from t",
+        ];
+        for _ in 0..2 {
+            for sql in setup {
+                assert_eq!(update(sql), Ok(0));
+            }
+        }
+        let exists = |name: &str| Err(ScanError::AlreadyExists(name.to_string()));
+        assert_eq!(update("create table t (c int)"), exists("t"));
+        assert_eq!(update("create table v (c int)"), exists("v"));
+        assert_eq!(update("create view t as select a from t"), exists("t"));
+        assert_eq!(update("create index ta on t (b)"), exists("ta"));
+        assert_eq!(mdm.layout("t", tx.clone()).unwrap().schema().fields().len(), 2);
+        assert_eq!(mdm.index_info("t", tx.clone()).unwrap().len(), 1);
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_drop() {
+        let _ = fs::remove_dir_all("droptestdb");
+        let db = SimpleDB::new("droptestdb", 400, 8).unwrap();
+        let mdm = db.metadata_mgr();
+        let planner = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str, tx: &Arc<Mutex<Transaction>>| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone());
+        let tx = db.new_tx().unwrap();
+        update("create table t (a int, b varchar(10))", &tx).unwrap();
+        for i in 0..40 {
+            update(&format!("insert into t (a, b) values ({}, 'rec{}')", i, i), &tx).unwrap();
+        }
+        update("create index ta on t (a)", &tx).unwrap();
+        update("create view v as select a from t", &tx).unwrap();
+        tx.lock().unwrap().commit().unwrap();
+        let exists = |file: &str| Path::new("droptestdb").join(file).exists();
+        assert!(exists("t.tbl") && exists("ta0.tbl"));
+
+        // A rolled back drop keeps the table, its index and its file.
+        let tx = db.new_tx().unwrap();
+        assert_eq!(update("drop table t", &tx), Ok(0));
+        assert_eq!(update("create table t (c int)", &tx), Err(ScanError::AlreadyExists("t".to_string())));
+        tx.lock().unwrap().rollback().unwrap();
+        let tx = db.new_tx().unwrap();
+        assert_eq!(mdm.index_info("t", tx.clone()).unwrap().len(), 1);
+        assert_eq!(mdm.stat_info("t", tx.clone()).unwrap().records_output(), 40);
+        assert!(exists("t.tbl"));
+
+        assert_eq!(update("drop view v", &tx), Ok(0));
+        assert_eq!(update("drop index ta", &tx), Ok(0));
+        assert_eq!(update("drop table t", &tx), Ok(0));
+        assert_eq!(mdm.layout("t", tx.clone()).err(), Some(ScanError::UnknownTable("t".to_string())));
+        assert_eq!(update("drop table t", &tx), Err(ScanError::UnknownTable("t".to_string())));
+        assert_eq!(update("drop view v", &tx), Err(ScanError::UnknownTable("v".to_string())));
+        assert_eq!(update("drop index ta", &tx), Err(ScanError::UnknownIndex("ta".to_string())));
+        assert_eq!(update("drop table if exists t", &tx), Ok(0));
+        assert_eq!(update("drop index if exists ta", &tx), Ok(0));
+        // The files stay until the drop commits.
+        assert!(exists("t.tbl"));
+        tx.lock().unwrap().commit().unwrap();
+        assert!(!exists("t.tbl") && !exists("ta0.tbl"));
+
+        // A table created with the same name starts out empty.
+        let tx = db.new_tx().unwrap();
+        update("create table t (a int, b varchar(10))", &tx).unwrap();
+        assert_eq!(mdm.stat_info("t", tx.clone()).unwrap().records_output(), 0);
+        assert!(mdm.index_info("t", tx.clone()).unwrap().is_empty());
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_schemas() {
+        let _ = fs::remove_dir_all("schematestdb");
+        let db = SimpleDB::new("schematestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let mut planner = BasicUpdatePlanner::new(mdm.clone());
+        let mut update = |sql: &str, path: &[&str]| {
+            planner.set_search_path(SearchPath::new(path));
+            planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone())
+        };
+        assert_eq!(update("create table app.users (id int)", &["public"]), Err(ScanError::UnknownSchema("app".to_string())));
+        assert_eq!(update("create schema app", &["public"]), Ok(0));
+        assert_eq!(update("create schema app", &["public"]), Err(ScanError::AlreadyExists("app".to_string())));
+        assert_eq!(update("create schema if not exists app", &["public"]), Ok(0));
+        assert_eq!(update("create schema billing", &["public"]), Ok(0));
+
+        // Each application creates its own users table through its path.
+        assert_eq!(update("create table users (id int)", &["app"]), Ok(0));
+        assert_eq!(update("create table users (id int, name varchar(10))", &["billing", "public"]), Ok(0));
+        assert_eq!(update("create table users (id int)", &["public"]), Ok(0));
+        assert_eq!(update("create index users_id on users (id)", &["app"]), Ok(0));
+        assert_eq!(update("insert into users (id) values (1)", &["app", "public"]), Ok(1));
+        assert_eq!(update("insert into public.users (id) values (2)", &["app"]), Ok(1));
+        assert_eq!(update("insert into billing.users (id, name) values (3, 'bill')", &["public"]), Ok(1));
+        assert_eq!(update("create view app_users as select id from users", &["app"]), Ok(0));
+        assert_eq!(mdm.view_def("app_users", tx.clone()), Ok(None));
+        assert_eq!(mdm.view_def("app.app_users", tx.clone()), Ok(Some("select id from app.users".to_string())));
+        assert!(mdm.index_exists("app.users_id", tx.clone()).unwrap());
+
+        let query = |sql: &str, path: &[&str]| {
+            let mut planner = BasicQueryPlanner::new(mdm.clone());
+            planner.set_search_path(SearchPath::new(path));
+            let data = Parser::new(sql).unwrap().query().unwrap();
+            let mut scan = planner.create_plan(&data, tx.clone())?.open()?;
+            let mut ids = Vec::new();
+            while scan.next()? {
+                ids.push(scan.get_int("id")?);
+            }
+            scan.close();
+            Ok::<_, ScanError>(ids)
+        };
+        assert_eq!(query("select id from users", &["app", "public"]), Ok(vec![1]));
+        assert_eq!(query("select id from users", &["public", "app"]), Ok(vec![2]));
+        assert_eq!(query("select id from users", &["billing"]), Ok(vec![3]));
+        // The view reads the table it was created on, whatever the path.
+        assert_eq!(query("select id from app.app_users", &["billing"]), Ok(vec![1]));
+        assert_eq!(query("select id from app_users", &["public"]), Err(ScanError::UnknownTable("app_users".to_string())));
+        assert_eq!(query("select id from nosuch.users", &["public"]), Err(ScanError::UnknownTable("nosuch.users".to_string())));
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_constraints() {
+        let _ = fs::remove_dir_all("constrainttestdb");
+        let db = SimpleDB::new("constrainttestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let planner = BasicUpdatePlanner::new(db.metadata_mgr());
+        let update = |sql: &str| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone());
+        let violation = |field: &str, constraint: &str| Err(ScanError::ConstraintViolation { field: field.to_string(), constraint: constraint.to_string() });
+        update("create table t (id int not null check (id > 0), qty int check (qty >= 0) check (qty < 100), b varchar(5) check (b <> 'bad'))").unwrap();
+
+        assert_eq!(update("insert into t (id, qty, b) values (1, 5, 'ok')"), Ok(1));
+        assert_eq!(update("insert into t (qty) values (5)"), violation("id", "not null"));
+        assert_eq!(update("insert into t (id, qty) values (0, 5)"), violation("id", "check (id > 0)"));
+        assert_eq!(update("insert into t (id, qty) values (2, 100)"), violation("qty", "check (qty < 100)"));
+        assert_eq!(update("insert into t (id, b) values (3, 'bad')"), violation("b", "check (b <> 'bad')"));
+        assert_eq!(update("insert into t (id) values (4)"), Ok(1));
+        assert_eq!(update("update t set qty = -1 where id = 1"), violation("qty", "check (qty >= 0)"));
+        assert_eq!(update("update t set qty = 200 where id = 1"), violation("qty", "check (qty < 100)"));
+        assert_eq!(update("update t set qty = 50 where id = 1"), Ok(1));
+        assert_eq!(update("update t set id = null where id = 4"), violation("id", "not null"));
+
+        // The failed inserts left no records behind.
+        let data = Parser::new("select id, qty from t").unwrap().query().unwrap();
+        let mut scan = BasicQueryPlanner::new(db.metadata_mgr()).create_plan(&data, tx.clone()).unwrap().open().unwrap();
+        let mut rows = Vec::new();
+        while scan.next().unwrap() {
+            rows.push((scan.get_int("id").unwrap(), scan.get_int_opt("qty").unwrap()));
+        }
+        scan.close();
+        assert_eq!(rows, [(1, Some(50)), (4, None)]);
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_nulls() {
+        let _ = fs::remove_dir_all("nulltestdb");
+        let db = SimpleDB::new("nulltestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let planner = BasicUpdatePlanner::new(db.metadata_mgr());
+        let update = |sql: &str| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone()).unwrap();
+        update("create table t (a int, b varchar(5))");
+        update("insert into t (a, b) values (1, 'x')");
+        update("insert into t (a) values (2)");
+        update("insert into t (a, b) values (null, 'z')");
+        update("insert into t (b) values (null)");
+
+        let query_planner = BasicQueryPlanner::new(db.metadata_mgr());
+        let query = |sql: &str| {
+            let data = Parser::new(sql).unwrap().query().unwrap();
+            let mut scan = query_planner.create_plan(&data, tx.clone()).unwrap().open().unwrap();
+            let mut rows = Vec::new();
+            while scan.next().unwrap() {
+                rows.push((scan.get_int_opt("a").unwrap(), scan.get_string_opt("b").unwrap()));
+            }
+            scan.close();
+            rows
+        };
+        let some = |b: &str| Some(b.to_string());
+        assert_eq!(query("select a, b from t"), [(Some(1), some("x")), (Some(2), None), (None, some("z")), (None, None)]);
+        assert_eq!(query("select a, b from t where b is null"), [(Some(2), None), (None, None)]);
+        assert_eq!(query("select a, b from t where a is not null and b is not null"), [(Some(1), some("x"))]);
+        // A comparison with a null is never true, not even with another null.
+        assert_eq!(query("select a, b from t where b = null"), []);
+        assert_eq!(query("select a, b from t where a = b"), []);
+
+        let data = Parser::new("select a from t where b = 'z'").unwrap().query().unwrap();
+        let mut scan = query_planner.create_plan(&data, tx.clone()).unwrap().open().unwrap();
+        assert!(scan.next().unwrap());
+        assert!(scan.is_null("a").unwrap());
+        assert_eq!(scan.get_val("a"), Ok(Constant::Null));
+        assert_eq!(scan.get_int("a"), Err(ScanError::NullValue("a".to_string())));
+        scan.close();
+
+        assert_eq!(update("update t set b = null where a = 1"), 1);
+        assert_eq!(update("update t set a = 3 where a is null and b = 'z'"), 1);
+        assert_eq!(query("select a, b from t where b is null"), [(Some(1), None), (Some(2), None), (None, None)]);
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_field_types() {
+        let _ = fs::remove_dir_all("fieldtypestestdb");
+        let db = SimpleDB::new("fieldtypestestdb", 400, 8).unwrap();
+        let planner = BasicUpdatePlanner::new(db.metadata_mgr());
+        let update = |sql: &str, tx: &Arc<Mutex<Transaction>>| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone());
+        let query_planner = BasicQueryPlanner::new(db.metadata_mgr());
+        let query = |sql: &str, tx: &Arc<Mutex<Transaction>>| {
+            let data = Parser::new(sql).unwrap().query().unwrap();
+            let mut scan = query_planner.create_plan(&data, tx.clone()).unwrap().open().unwrap();
+            let mut rows = Vec::new();
+            while scan.next().unwrap() {
+                rows.push((scan.get_long("big").unwrap(), scan.get_double("d").unwrap(), scan.get_bool("flag").unwrap()));
+            }
+            scan.close();
+            rows
+        };
+        let tx = db.new_tx().unwrap();
+        update("create table t (big bigint check (big >= 0), d double, f float, flag boolean)", &tx).unwrap();
+        update("insert into t (big, d, f, flag) values (12345678901, 2.5, 1e-3, true)", &tx).unwrap();
+        // Integer literals widen to the BIGINT and DOUBLE fields.
+        update("insert into t (big, d, f, flag) values (3, 4, 0.5, false)", &tx).unwrap();
+        update("create index bigidx on t (big)", &tx).unwrap();
+        tx.lock().unwrap().commit().unwrap();
+
+        let tx = db.new_tx().unwrap();
+        assert_eq!(query("select big, d, flag from t", &tx), [(12345678901, 2.5, true), (3, 4.0, false)]);
+        assert_eq!(query("select big, d, flag from t where big = 3", &tx), [(3, 4.0, false)]);
+        assert_eq!(query("select big, d, flag from t where d = 4 and flag = false", &tx), [(3, 4.0, false)]);
+        assert_eq!(query("select big, d, flag from t where flag = true", &tx), [(12345678901, 2.5, true)]);
+        assert_eq!(update("insert into t (big) values (-1)", &tx), Err(ScanError::ConstraintViolation { field: "big".to_string(), constraint: "check (big >= 0)".to_string() }));
+        assert_eq!(update("insert into t (flag) values (1)", &tx), Err(ScanError::TypeMismatch { field: "flag".to_string(), expected: FieldType::INTEGER }));
+        assert_eq!(update("update t set d = flag", &tx), Err(ScanError::TypeMismatch { field: "d".to_string(), expected: FieldType::BOOLEAN }));
+
+        // The new values are logged, and undone by a rollback.
+        update("update t set d = -0.25 where big = 3", &tx).unwrap();
+        update("update t set flag = true where big = 3", &tx).unwrap();
+        update("update t set big = 12345678902 where big = 12345678901", &tx).unwrap();
+        assert_eq!(query("select big, d, flag from t where flag = true", &tx), [(12345678902, 2.5, true), (3, -0.25, true)]);
+        tx.lock().unwrap().rollback().unwrap();
+        let tx = db.new_tx().unwrap();
+        assert_eq!(query("select big, d, flag from t where big = 3", &tx), [(3, 4.0, false)]);
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_dates() {
+        let _ = fs::remove_dir_all("datetestdb");
+        let db = SimpleDB::new("datetestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let planner = BasicUpdatePlanner::new(db.metadata_mgr());
+        let update = |sql: &str| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone());
+        let query_planner = BasicQueryPlanner::new(db.metadata_mgr());
+        let query = |sql: &str| {
+            let data = Parser::new(sql).unwrap().query().unwrap();
+            let mut scan = query_planner.create_plan(&data, tx.clone()).unwrap().open().unwrap();
+            let mut rows = Vec::new();
+            while scan.next().unwrap() {
+                rows.push((scan.get_int("id").unwrap(), scan.get_val("hired").unwrap().to_string()));
+            }
+            scan.close();
+            rows
+        };
+        update("create table emp (id int, hired date check (hired >= '2000-01-01'))").unwrap();
+        update("insert into emp (id, hired) values (1, '2024-01-15')").unwrap();
+        update("insert into emp (id, hired) values (2, '2023-06-30 08:00:00')").unwrap();
+        update("insert into emp (id, hired) values (3, date '2024-01-15')").unwrap();
+        update("create index hiredidx on emp (hired)").unwrap();
+
+        let date = |s: &str| s.to_string();
+        assert_eq!(query("select id, hired from emp where hired = '2024-01-15'"), [(1, date("'2024-01-15'")), (3, date("'2024-01-15'"))]);
+        assert_eq!(query("select id, hired from emp where id = 2"), [(2, date("'2023-06-30 08:00:00'"))]);
+        let data = Parser::new("select hired from emp where id = 1").unwrap().query().unwrap();
+        let mut scan = query_planner.create_plan(&data, tx.clone()).unwrap().open().unwrap();
+        assert!(scan.next().unwrap());
+        assert_eq!(scan.get_date("hired"), Ok(1705276800));
+        scan.close();
+
+        assert_eq!(update("insert into emp (id, hired) values (4, '1999-12-31')"), Err(ScanError::ConstraintViolation { field: "hired".to_string(), constraint: "check (hired >= '2000-01-01')".to_string() }));
+        assert_eq!(update("insert into emp (id, hired) values (4, 'someday')"), Err(ScanError::TypeMismatch { field: "hired".to_string(), expected: FieldType::VARCHAR }));
+        assert_eq!(update("update emp set hired = '2025-03-01 09:30:00' where id = 2"), Ok(1));
+        assert_eq!(query("select id, hired from emp where id = 2"), [(2, date("'2025-03-01 09:30:00'"))]);
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_views() {
+        let _ = fs::remove_dir_all("viewtestdb");
+        let db = SimpleDB::new("viewtestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let planner = BasicUpdatePlanner::new(db.metadata_mgr());
+        let update = |sql: &str| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone());
+        let query = |sql: &str| {
+            let data = Parser::new(sql).unwrap().query().unwrap();
+            let mut scan = BasicQueryPlanner::new(db.metadata_mgr()).create_plan(&data, tx.clone())?.open()?;
+            let mut vals = Vec::new();
+            while scan.next()? {
+                vals.push(scan.get_int("a")?);
+            }
+            scan.close();
+            Ok::<_, ScanError>(vals)
+        };
+
+        update("create table t (a int, b varchar(10))").unwrap();
+        for i in 0..6 {
+            update(&format!("insert into t (a, b) values ({}, 'rec{}')", i, i % 2)).unwrap();
+        }
+        assert_eq!(update("create view odd as select a from t where b = 'rec1'"), Ok(0));
+
+        // The view is planned from its definition, and sees later changes.
+        assert_eq!(query("select a from odd"), Ok(vec![1, 3, 5]));
+        update("insert into t (a, b) values (7, 'rec1')").unwrap();
+        assert_eq!(query("select a from odd where a = 7"), Ok(vec![7]));
+        assert_eq!(query("select b from odd"), Err(ScanError::UnknownField("b".to_string())));
+
+        // A definition that does not fit in the catalog is rejected.
+        let long = format!("create view big as select a from t where b = '{}'", "x".repeat(100));
+        assert!(matches!(update(&long), Err(ScanError::ValueTooLong { .. })));
+        assert_eq!(query("select a from big"), Err(ScanError::UnknownTable("big".to_string())));
+
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::BTreeSet;
+use crate::metadata::indexinfo::IndexInfo;
+use crate::plan::plan::Plan;
+use crate::plan::tableplan::TablePlan;
+use crate::query::bitmapscan::BitmapScan;
+use crate::query::constant::Constant;
+use crate::query::predicate::Predicate;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::Schema;
+use crate::record::tablescan::RecordId;
+
+// The BitmapAndPlan selects the records of a table whose indexed fields
+// equal constants, or contain words, through several indexes at once, rather than through the
+// single most selective one. It collects the RecordIds each index holds
+// for its constant into a set sorted in file order, the bitmap, keeps
+// those found by every index, and only then fetches the records, in block
+// order.
+pub struct BitmapAndPlan {
+    plan: TablePlan,
+    lookups: Vec<(IndexInfo, Constant)>,
+}
+
+impl BitmapAndPlan {
+    pub fn new(plan: TablePlan, lookups: Vec<(IndexInfo, Constant)>) -> BitmapAndPlan {
+        debug_assert!(lookups.len() >= 2, "a bitmap scan combines two indexes or more");
+        BitmapAndPlan { plan, lookups }
+    }
+
+    // The indexes the predicate gives a search key for, with their keys, in
+    // the order of their fields and then their names. A planner combines
+    // them if there are two or more.
+    pub(crate) fn lookups<'a>(indexes: impl IntoIterator<Item = &'a IndexInfo>, pred: &Predicate) -> Vec<(IndexInfo, Constant)> {
+        let mut lookups: Vec<(IndexInfo, Constant)> = indexes.into_iter()
+            .filter_map(|ii| ii.search_key(pred).map(|val| (ii.clone(), val.clone())))
+            .collect();
+        lookups.sort_by(|(ii1, _), (ii2, _)| (ii1.field(), ii1.index_name()).cmp(&(ii2.field(), ii2.index_name())));
+        lookups
+    }
+
+    // The RecordIds the index holds for the value.
+    fn bitmap(ii: &IndexInfo, val: &Constant) -> Result<BTreeSet<RecordId>, ScanError> {
+        let mut idx = ii.open();
+        let mut rids = BTreeSet::new();
+        idx.before_first(val)?;
+        while idx.next()? {
+            rids.insert(idx.data_rid()?);
+        }
+        idx.close();
+        Ok(rids)
+    }
+}
+
+impl Plan for BitmapAndPlan {
+    fn open(&self) -> Result<Box<dyn Scan>, ScanError> {
+        let mut rids = Self::bitmap(&self.lookups[0].0, &self.lookups[0].1)?;
+        for (ii, val) in &self.lookups[1..] {
+            let other = Self::bitmap(ii, val)?;
+            rids.retain(|rid| other.contains(rid));
+        }
+        Ok(Box::new(BitmapScan::new(self.plan.open_table()?, rids.into_iter().collect())))
+    }
+
+    // Each index is searched once, and each block holding a matching
+    // record is read once.
+    fn blocks_accessed(&self) -> usize {
+        self.lookups.iter().map(|(ii, _)| ii.blocks_accessed()).sum::<usize>() + self.rand_blocks()
+    }
+
+    fn rand_blocks(&self) -> usize {
+        self.records_output().min(self.plan.blocks_accessed())
+    }
+
+    // The fields are assumed to be independent, so each index keeps the
+    // same share of the records that the others leave.
+    fn records_output(&self) -> usize {
+        let records = self.plan.records_output().max(1);
+        self.lookups.iter().fold(records, |output, (ii, _)| output * ii.records_output() / records).max(1)
+    }
+
+    fn distinct_values(&self, field: &str) -> usize {
+        if self.lookups.iter().any(|(ii, _)| ii.distinct_values(field) == 1) {
+            1
+        } else {
+            self.plan.distinct_values(field).min(self.records_output())
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        self.plan.schema()
+    }
+
+    fn describe(&self) -> String {
+        let lookups: Vec<String> = self.lookups.iter().map(|(ii, val)| format!("{} with {}", ii.describe_search(val), ii.index_name())).collect();
+        format!("BitmapAnd on {}: {}", self.plan.table(), lookups.join(", "))
+    }
+}
+
+mod tests {
+    use std::fs;
+    use crate::parse::parser::Parser;
+    use crate::plan::basicqueryplanner::BasicQueryPlanner;
+    use crate::plan::basicupdateplanner::BasicUpdatePlanner;
+    use crate::opt::heuristicqueryplanner::HeuristicQueryPlanner;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_bitmap_and() {
+        let _ = fs::remove_dir_all("bitmapandtestdb");
+        let db = SimpleDB::new("bitmapandtestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let updater = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| updater.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone()).unwrap();
+        update("create table t (id int, a int, b int)");
+        for i in 0..1000 {
+            update(&format!("insert into t (id, a, b) values ({}, {}, {})", i, i % 50, i % 40));
+        }
+        update("create index a_idx on t (a)");
+        update("create index b_idx on t (b)");
+
+        // Each index alone matches a few dozen records fetched at random,
+        // but only the five in both are fetched.
+        let basic = BasicQueryPlanner::new(mdm.clone());
+        let heuristic = HeuristicQueryPlanner::new(mdm.clone());
+        let data = Parser::new("select id from t where b = 7 and a = 17").unwrap().query().unwrap();
+        for plan in [basic.create_plan(&data, tx.clone()).unwrap(), heuristic.create_plan(&data, tx.clone()).unwrap()] {
+            assert!(plan.explain().contains("BitmapAnd on t: a=17 with a_idx, b=7 with b_idx"), "{}", plan.explain());
+            let mut scan = plan.open().unwrap();
+            let mut ids = Vec::new();
+            while scan.next().unwrap() {
+                ids.push(scan.get_int("id").unwrap());
+            }
+            scan.close();
+            assert_eq!(ids, [167, 367, 567, 767, 967]);
+        }
+
+        // An index on a field with a single matching record is cheaper
+        // alone.
+        update("create index id_idx on t (id)");
+        let data = Parser::new("select id from t where b = 7 and a = 17 and id = 367").unwrap().query().unwrap();
+        let plan = basic.create_plan(&data, tx.clone()).unwrap();
+        assert!(plan.explain().contains("IndexSelect id=367"), "{}", plan.explain());
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
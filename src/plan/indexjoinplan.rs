@@ -0,0 +1,116 @@
+use crate::metadata::indexinfo::IndexInfo;
+use crate::plan::plan::Plan;
+use crate::plan::tableplan::TablePlan;
+use crate::query::indexjoinscan::IndexJoinScan;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::Schema;
+
+// The IndexJoinPlan joins the first plan with a table through an index on
+// the table: for each record of the first plan, it searches the index for
+// the value of the join field, so the table is never read in full.
+pub struct IndexJoinPlan {
+    p1: Box<dyn Plan>,
+    p2: TablePlan,
+    ii: IndexInfo,
+    join_field: String,
+    schema: Schema,
+}
+
+impl IndexJoinPlan {
+    pub fn new(p1: Box<dyn Plan>, p2: TablePlan, ii: IndexInfo, join_field: &str) -> IndexJoinPlan {
+        let mut schema = Schema::new();
+        schema.add_all(p1.schema());
+        schema.add_all(p2.schema());
+        IndexJoinPlan { p1, p2, ii, join_field: join_field.to_string(), schema }
+    }
+}
+
+impl Plan for IndexJoinPlan {
+    fn open(&self) -> Result<Box<dyn Scan>, ScanError> {
+        let lhs = self.p1.open()?;
+        Ok(Box::new(IndexJoinScan::new(lhs, self.ii.open(), &self.join_field, self.p2.open_table()?)?))
+    }
+
+    // The index is searched once for each record of the first plan, and
+    // each matching record costs one more block access.
+    fn blocks_accessed(&self) -> usize {
+        self.p1.blocks_accessed() + self.p1.records_output() * self.ii.blocks_accessed() + self.records_output()
+    }
+
+    // As for an IndexSelectPlan, the matching records are fetched at
+    // random.
+    fn rand_blocks(&self) -> usize {
+        self.p1.rand_blocks() + self.records_output()
+    }
+
+    fn records_output(&self) -> usize {
+        self.p1.records_output() * self.ii.records_output()
+    }
+
+    fn distinct_values(&self, field: &str) -> usize {
+        if self.p2.schema().has_field(field) {
+            self.p2.distinct_values(field)
+        } else {
+            self.p1.distinct_values(field)
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn describe(&self) -> String {
+        format!("IndexJoin {}={} on {} with {}", self.ii.field(), self.join_field, self.p2.table(), self.ii.index_name())
+    }
+
+    fn inputs(&self) -> Vec<&dyn Plan> {
+        vec![self.p1.as_ref()]
+    }
+}
+
+mod tests {
+    use std::fs;
+    use crate::parse::parser::Parser;
+    use crate::plan::basicqueryplanner::BasicQueryPlanner;
+    use crate::plan::basicupdateplanner::BasicUpdatePlanner;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_index_join() {
+        let _ = fs::remove_dir_all("indexjointestdb");
+        let db = SimpleDB::new("indexjointestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let updater = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| updater.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone()).unwrap();
+        update("create table dept (did int, dname varchar(10))");
+        update("create table student (sid int, sname varchar(10), majorid int)");
+        for (did, dname) in [(10, "compsci"), (20, "math"), (30, "drama")] {
+            update(&format!("insert into dept (did, dname) values ({}, '{}')", did, dname));
+        }
+        for i in 0..40 {
+            update(&format!("insert into student (sid, sname, majorid) values ({}, 'rec{}', {})", i, i, 10 + 10 * (i % 2)));
+        }
+        update("create index majorid_idx on student (majorid)");
+
+        // The student table is joined to dept through its index on majorid.
+        let planner = BasicQueryPlanner::new(mdm.clone());
+        let data = Parser::new("select dname, sid from dept, student where did = majorid and dname = 'math'").unwrap().query().unwrap();
+        let plan = planner.create_plan(&data, tx.clone()).unwrap();
+        let ii = &mdm.index_info("student", tx.clone()).unwrap()["majorid"];
+        let dept = mdm.stat_info("dept", tx.clone()).unwrap();
+        let records = dept.records_output() * ii.records_output();
+        assert_eq!(plan.blocks_accessed(), dept.blocks_accessed() + dept.records_output() * ii.blocks_accessed() + records);
+        let mut scan = plan.open().unwrap();
+        let mut sids = Vec::new();
+        while scan.next().unwrap() {
+            assert_eq!(scan.get_string("dname").unwrap(), "math");
+            sids.push(scan.get_int("sid").unwrap());
+        }
+        scan.close();
+        sids.sort();
+        assert_eq!(sids, (0..20).map(|i| 2 * i + 1).collect::<Vec<_>>());
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
@@ -0,0 +1,110 @@
+use crate::metadata::indexinfo::IndexInfo;
+use crate::plan::plan::Plan;
+use crate::plan::tableplan::TablePlan;
+use crate::query::constant::Constant;
+use crate::query::indexselectscan::IndexSelectScan;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::Schema;
+
+// The IndexSelectPlan selects the records of a table whose indexed field
+// equals a constant, or contains a word for a text index, through the
+// index. It reads the index records of the search key and then one data
+// block per matching record, instead of every block of the table.
+pub struct IndexSelectPlan {
+    plan: TablePlan,
+    ii: IndexInfo,
+    val: Constant,
+}
+
+impl IndexSelectPlan {
+    pub fn new(plan: TablePlan, ii: IndexInfo, val: Constant) -> IndexSelectPlan {
+        IndexSelectPlan { plan, ii, val }
+    }
+}
+
+impl Plan for IndexSelectPlan {
+    fn open(&self) -> Result<Box<dyn Scan>, ScanError> {
+        Ok(Box::new(IndexSelectScan::new(self.plan.open_table()?, self.ii.open(), self.val.clone())?))
+    }
+
+    fn blocks_accessed(&self) -> usize {
+        self.ii.blocks_accessed() + self.records_output()
+    }
+
+    // The index bucket is read in order, but each matching record is
+    // fetched from wherever its block is.
+    fn rand_blocks(&self) -> usize {
+        self.records_output()
+    }
+
+    fn records_output(&self) -> usize {
+        self.ii.records_output()
+    }
+
+    fn distinct_values(&self, field: &str) -> usize {
+        self.ii.distinct_values(field)
+    }
+
+    fn schema(&self) -> &Schema {
+        self.plan.schema()
+    }
+
+    fn describe(&self) -> String {
+        format!("IndexSelect {} on {} with {}", self.ii.describe_search(&self.val), self.plan.table(), self.ii.index_name())
+    }
+}
+
+mod tests {
+    use std::fs;
+    use crate::parse::parser::Parser;
+    use crate::plan::basicqueryplanner::BasicQueryPlanner;
+    use crate::plan::basicupdateplanner::BasicUpdatePlanner;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_index_select() {
+        let _ = fs::remove_dir_all("indexselecttestdb");
+        let db = SimpleDB::new("indexselecttestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let updater = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| updater.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone()).unwrap();
+        update("create table student (sid int, sname varchar(10), majorid int)");
+        for i in 0..100 {
+            update(&format!("insert into student (sid, sname, majorid) values ({}, 'rec{}', {})", i, i, i % 10));
+        }
+        update("create index majorid_idx on student (majorid)");
+        update("create index sid_idx on student (sid)");
+
+        let planner = BasicQueryPlanner::new(mdm.clone());
+        let query = |sql: &str| planner.create_plan(&Parser::new(sql).unwrap().query().unwrap(), tx.clone()).unwrap();
+        // The table is read through the more selective index.
+        let ii = &mdm.index_info("student", tx.clone()).unwrap()["sid"];
+        let plan = query("select sid from student where majorid = 3 and sid = 23");
+        assert_eq!(plan.blocks_accessed(), ii.blocks_accessed() + ii.records_output());
+        assert_eq!(plan.rand_blocks(), 1);
+        let mut scan = plan.open().unwrap();
+        let mut sids = Vec::new();
+        while scan.next().unwrap() {
+            sids.push(scan.get_int("sid").unwrap());
+        }
+        scan.close();
+        assert_eq!(sids, [23]);
+
+        // A tenth of the table fetched at random costs more than reading
+        // all of it in order, so the index on majorid is not used.
+        let table = mdm.stat_info("student", tx.clone()).unwrap();
+        let plan = query("select sid from student where majorid = 7");
+        assert_eq!((plan.seq_blocks(), plan.rand_blocks()), (table.blocks_accessed(), 0));
+        let mut scan = plan.open().unwrap();
+        let mut sids = Vec::new();
+        while scan.next().unwrap() {
+            sids.push(scan.get_int("sid").unwrap());
+        }
+        scan.close();
+        sids.sort();
+        assert_eq!(sids, (0..10).map(|i| i * 10 + 7).collect::<Vec<_>>());
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
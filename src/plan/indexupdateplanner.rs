@@ -0,0 +1,298 @@
+use std::sync::{Arc, Mutex};
+use crate::index::index::Index;
+use crate::metadata::indexinfo::IndexInfo;
+use crate::metadata::metadatamgr::MetadataMgr;
+use crate::parse::deletedata::DeleteData;
+use crate::parse::insertdata::InsertData;
+use crate::parse::modifydata::ModifyData;
+use crate::parse::parser::UpdateCmd;
+use crate::plan::basicupdateplanner::BasicUpdatePlanner;
+use crate::plan::searchpath::SearchPath;
+use crate::query::constant::Constant;
+use crate::query::scan::Scan;
+use crate::query::selectscan::SelectScan;
+use crate::record::row::Row;
+use crate::record::scanerror::ScanError;
+use crate::record::tablescan::{RecordId, TableScan, UpdateScan};
+use crate::tx::transaction::Transaction;
+
+// The IndexUpdatePlanner executes the same statements as the
+// BasicUpdatePlanner, and also keeps the indexes of the table up to date:
+// the index records of every record inserted, deleted or modified are
+// inserted, deleted or moved along with it. Statements that do not change
+// records are left to the BasicUpdatePlanner.
+pub struct IndexUpdatePlanner {
+    mdm: Arc<MetadataMgr>,
+    basic: BasicUpdatePlanner,
+}
+
+impl IndexUpdatePlanner {
+    pub fn new(mdm: Arc<MetadataMgr>) -> IndexUpdatePlanner {
+        IndexUpdatePlanner { basic: BasicUpdatePlanner::new(mdm.clone()), mdm }
+    }
+
+    pub fn set_search_path(&mut self, search_path: SearchPath) {
+        self.basic.set_search_path(search_path);
+    }
+
+    pub fn execute_update(&self, cmd: &UpdateCmd, tx: Arc<Mutex<Transaction>>) -> Result<usize, ScanError> {
+        match cmd {
+            UpdateCmd::Insert(data) => self.execute_insert(data, tx),
+            UpdateCmd::Delete(data) => self.execute_delete(data, tx),
+            UpdateCmd::Modify(data) => self.execute_modify(data, tx),
+            cmd => self.basic.execute_update(cmd, tx),
+        }
+    }
+
+    // Fields left out of the statement are null. Null values are not
+    // indexed, nor are records a partial index leaves out, as in
+    // `execute_create_index`.
+    pub fn execute_insert(&self, data: &InsertData, tx: Arc<Mutex<Transaction>>) -> Result<usize, ScanError> {
+        let mut row = Row::new();
+        for (field, val) in data.fields().iter().zip(data.vals()) {
+            row.set(field, val.clone());
+        }
+        let table = &self.basic.resolve(data.table(), tx.clone())?;
+        let mut indexes = self.open_indexes(table, tx.clone())?;
+        let mut ts = self.open(table, tx)?;
+        ts.insert_row(&row)?;
+        let rid = ts.rid().ok_or(ScanError::NoCurrentRecord)?;
+        for (ii, idx) in indexes.iter_mut() {
+            if let Some(val) = ii.key(&mut ts)? {
+                idx.insert(&val, &rid)?;
+            }
+        }
+        ts.close();
+        Self::close_indexes(indexes);
+        Ok(1)
+    }
+
+    pub fn execute_delete(&self, data: &DeleteData, tx: Arc<Mutex<Transaction>>) -> Result<usize, ScanError> {
+        let table = &self.basic.resolve(data.table(), tx.clone())?;
+        let mut indexes = self.open_indexes(table, tx.clone())?;
+        let mut us = SelectScan::new(self.open(table, tx)?, data.pred().clone());
+        let mut count = 0;
+        while us.next()? {
+            let rid = Self::rid(&us)?;
+            for (ii, idx) in indexes.iter_mut() {
+                if let Some(val) = ii.key(&mut us)? {
+                    idx.delete(&val, &rid)?;
+                }
+            }
+            us.delete()?;
+            count += 1;
+        }
+        us.close();
+        Self::close_indexes(indexes);
+        Ok(count)
+    }
+
+    pub fn execute_modify(&self, data: &ModifyData, tx: Arc<Mutex<Transaction>>) -> Result<usize, ScanError> {
+        let table = &self.basic.resolve(data.table(), tx.clone())?;
+        let mut indexes = self.open_indexes(table, tx.clone())?;
+        let mut us = SelectScan::new(self.open(table, tx)?, data.pred().clone());
+        let mut count = 0;
+        while us.next()? {
+            let val = data.new_val().evaluate(&mut us)?;
+            let rid = Self::rid(&us)?;
+            // The change may move the record in an index on the field, and
+            // in or out of a partial index whose predicate reads it, so the
+            // keys are compared before and after. A record whose key does
+            // not change keeps its index record. Otherwise only the index
+            // record of this record moves; those of the others with the old
+            // value stay.
+            let old = Self::keys(&indexes, &mut us)?;
+            us.set_val(data.target_field(), &val)?;
+            let new = Self::keys(&indexes, &mut us)?;
+            for ((_, idx), (old, new)) in indexes.iter_mut().zip(old.into_iter().zip(new)) {
+                if old == new {
+                    continue;
+                }
+                if let Some(old) = old {
+                    idx.delete(&old, &rid)?;
+                }
+                if let Some(new) = new {
+                    idx.insert(&new, &rid)?;
+                }
+            }
+            count += 1;
+        }
+        us.close();
+        Self::close_indexes(indexes);
+        Ok(count)
+    }
+
+    // Opens every index of the table, each with its info, including those
+    // being built online. The indexes are opened before the table scan,
+    // since looking them up computes the table's statistics with the same
+    // transaction.
+    fn open_indexes(&self, table: &str, tx: Arc<Mutex<Transaction>>) -> Result<Vec<(IndexInfo, Box<dyn Index>)>, ScanError> {
+        Ok(self.mdm.maintained_indexes(table, tx)?.into_iter().map(|ii| {
+            let idx = ii.open();
+            (ii, idx)
+        }).collect())
+    }
+
+    // The key of the current record in each index, or None where the index
+    // does not hold it.
+    fn keys(indexes: &[(IndexInfo, Box<dyn Index>)], us: &mut SelectScan<TableScan>) -> Result<Vec<Option<Constant>>, ScanError> {
+        indexes.iter().map(|(ii, _)| ii.key(us)).collect()
+    }
+
+    fn close_indexes(indexes: Vec<(IndexInfo, Box<dyn Index>)>) {
+        indexes.into_iter().for_each(|(_, mut idx)| idx.close());
+    }
+
+    fn open(&self, table: &str, tx: Arc<Mutex<Transaction>>) -> Result<TableScan, ScanError> {
+        let layout = self.mdm.layout(table, tx.clone())?;
+        TableScan::new(tx, layout, table)
+    }
+
+    fn rid(us: &SelectScan<TableScan>) -> Result<RecordId, ScanError> {
+        us.rid().ok_or(ScanError::NoCurrentRecord)
+    }
+}
+
+mod tests {
+    use std::fs;
+    use super::*;
+    use crate::parse::parser::Parser;
+    use crate::query::constant::Constant;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_index_update_planner() {
+        let _ = fs::remove_dir_all("indexupdatetestdb");
+        let db = SimpleDB::new("indexupdatetestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let planner = IndexUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone()).unwrap();
+        update("create table t (a int, b varchar(10))");
+        update("create index ta on t (a)");
+        update("create index tb on t (b)");
+        for i in 0..20 {
+            update(&format!("insert into t (a, b) values ({}, 'rec{}')", i % 4, i % 5));
+        }
+        update("insert into t (b) values ('rec0')");
+
+        let indexes = mdm.index_info("t", tx.clone()).unwrap();
+        let count = |field: &str, val: Constant| {
+            let mut idx = indexes[field].open();
+            idx.before_first(&val).unwrap();
+            let mut count = 0;
+            while idx.next().unwrap() {
+                count += 1;
+            }
+            idx.close();
+            count
+        };
+        assert_eq!(count("a", Constant::Int(1)), 5);
+        // The record without a value for a is null there, and not indexed.
+        assert_eq!(count("a", Constant::Int(0)), 5);
+        assert_eq!(count("a", Constant::Null), 0);
+        assert_eq!(count("b", Constant::String("rec0".to_string())), 5);
+
+        assert_eq!(update("delete from t where a = 1"), 5);
+        assert_eq!(count("a", Constant::Int(1)), 0);
+        assert_eq!(count("b", Constant::String("rec1".to_string())), 3);
+
+        assert_eq!(update("update t set a = 7 where b = 'rec0'"), 4);
+        assert_eq!(count("a", Constant::Int(7)), 4);
+        assert_eq!(count("a", Constant::Int(0)), 4);
+        assert_eq!(update("update t set a = null where a = 7"), 4);
+        assert_eq!(count("a", Constant::Int(7)), 0);
+        assert_eq!(update("delete from t where a is null"), 4);
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let db = SimpleDB::ephemeral().unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let planner = IndexUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone()).unwrap();
+        update("create table t (a int, b int)");
+        update("create index ta on t (a)");
+        for b in 0..6 {
+            update(&format!("insert into t (a, b) values (1, {})", b));
+        }
+
+        // The values of b of the records the index finds for the key.
+        let layout = mdm.layout("t", tx.clone()).unwrap();
+        let find = |key: i32| {
+            let mut idx = mdm.index_info("t", tx.clone()).unwrap()["a"].open();
+            let mut ts = TableScan::new(tx.clone(), layout.clone(), "t").unwrap();
+            idx.before_first(&Constant::Int(key)).unwrap();
+            let mut found = Vec::new();
+            while idx.next().unwrap() {
+                ts.move_to_rid(&idx.data_rid().unwrap()).unwrap();
+                found.push(ts.get_int("b").unwrap());
+            }
+            idx.close();
+            ts.close();
+            found.sort();
+            found
+        };
+
+        // Moving one record off a shared key leaves the others indexed.
+        assert_eq!(update("update t set a = 2 where b = 3"), 1);
+        assert_eq!(find(1), [0, 1, 2, 4, 5]);
+        assert_eq!(find(2), [3]);
+        assert_eq!(update("update t set a = 1 where a = 1"), 5);
+        assert_eq!(find(1), [0, 1, 2, 4, 5]);
+        assert_eq!(update("delete from t where b = 0"), 1);
+        assert_eq!(find(1), [1, 2, 4, 5]);
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_partial_index() {
+        let db = SimpleDB::ephemeral().unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let planner = IndexUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone());
+        let pred = |sql: &str| Parser::new(sql).unwrap().predicate().unwrap();
+        update("create table t (a int, deleted int)").unwrap();
+        for i in 0..12 {
+            update(&format!("insert into t (a, deleted) values ({}, {})", i % 3, i % 2)).unwrap();
+        }
+        update("create index tlive on t (a) where deleted = 0").unwrap();
+        assert_eq!(update("create index tbad on t (a) where c = 0"), Err(ScanError::UnknownField("c".to_string())));
+
+        // Only a query that selects live records may use the index.
+        assert!(mdm.index_info("t", tx.clone()).unwrap().is_empty());
+        assert!(mdm.usable_indexes("t", &pred("a = 1"), tx.clone()).unwrap().is_empty());
+        let indexes = mdm.usable_indexes("t", &pred("a = 1 and deleted = 0"), tx.clone()).unwrap();
+        let count = |key: i32| {
+            let mut idx = indexes["a"].open();
+            idx.before_first(&Constant::Int(key)).unwrap();
+            let mut count = 0;
+            while idx.next().unwrap() {
+                count += 1;
+            }
+            idx.close();
+            count
+        };
+        assert_eq!([count(0), count(1), count(2)], [2, 2, 2]);
+
+        // Records enter and leave the index as they come to satisfy its
+        // predicate or stop doing so.
+        update("insert into t (a, deleted) values (1, 0)").unwrap();
+        update("insert into t (a, deleted) values (1, 1)").unwrap();
+        assert_eq!(count(1), 3);
+        assert_eq!(update("update t set deleted = 1 where a = 0"), Ok(4));
+        assert_eq!(count(0), 0);
+        assert_eq!(update("update t set deleted = 0 where a = 2"), Ok(4));
+        assert_eq!(count(2), 4);
+        assert_eq!(update("update t set a = 2 where a = 0"), Ok(4));
+        assert_eq!(count(2), 4);
+        assert_eq!(update("update t set a = 0 where a = 1"), Ok(6));
+        assert_eq!([count(0), count(1)], [3, 0]);
+        assert_eq!(update("delete from t where deleted = 0"), Ok(7));
+        assert_eq!([count(0), count(2)], [0, 0]);
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
@@ -0,0 +1,109 @@
+use crate::plan::plan::Plan;
+use crate::query::constant::Constant;
+use crate::query::rowscan::RowScan;
+use crate::query::scan::Scan;
+use crate::record::row::Row;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::Schema;
+use std::sync::{Arc, Mutex};
+use crate::tx::transaction::Transaction;
+use crate::tx::concurrency::concurrencymgr::ConcurrencyMgr;
+use crate::tx::concurrency::lockevent::LockEventKind;
+
+// The LockEventsPlan reads the sys_lock_events system table, which lists
+// the lock events recorded by the lock table, oldest first. The table is
+// not stored: its rows are taken from the lock table when it is opened.
+// The lock table is shared by every database in the process; only the
+// events on the files of this database are listed, as the blocks of
+// another database, or of a file deleted since, have no name here.
+pub struct LockEventsPlan {
+    tx: Arc<Mutex<Transaction>>,
+    schema: Schema,
+}
+
+impl LockEventsPlan {
+    pub const TABLE: &'static str = "sys_lock_events";
+
+    pub fn new(tx: Arc<Mutex<Transaction>>) -> LockEventsPlan {
+        let mut schema = Schema::new();
+        schema.add_string_field("kind", 7);
+        schema.add_string_field("filename", 32);
+        schema.add_int_field("blocknum");
+        schema.add_string_field("mode", 9);
+        schema.add_int_field("holders");
+        schema.add_int_field("waited");
+        LockEventsPlan { tx, schema }
+    }
+}
+
+impl Plan for LockEventsPlan {
+    fn open(&self) -> Result<Box<dyn Scan>, ScanError> {
+        let tx = self.tx.lock().unwrap();
+        let rows = ConcurrencyMgr::lock_events().into_iter().filter_map(|event| {
+            let filename = tx.filename(event.block.file)?;
+            let mut row = Row::new();
+            let kind = match event.kind {
+                LockEventKind::Wait => "wait",
+                LockEventKind::Timeout => "timeout",
+            };
+            row.set("kind", Constant::String(kind.to_string()));
+            row.set("filename", Constant::String(filename.to_string()));
+            row.set("blocknum", Constant::Int(event.block.number() as i32));
+            row.set("mode", Constant::String(if event.exclusive { "exclusive" } else { "shared" }.to_string()));
+            row.set("holders", Constant::Int(event.holders));
+            row.set("waited", Constant::Int(event.waited.min(i32::MAX as u128) as i32));
+            Some(row)
+        }).collect();
+        Ok(Box::new(RowScan::new(self.schema.clone(), rows)))
+    }
+
+    fn blocks_accessed(&self) -> usize {
+        0
+    }
+
+    fn records_output(&self) -> usize {
+        ConcurrencyMgr::lock_events().len()
+    }
+
+    fn distinct_values(&self, _field: &str) -> usize {
+        self.records_output().max(1)
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn describe(&self) -> String {
+        format!("SystemTable {}", Self::TABLE)
+    }
+}
+
+mod tests {
+    use std::fs;
+    use crate::parse::parser::Parser;
+    use crate::plan::basicqueryplanner::BasicQueryPlanner;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_sys_lock_events() {
+        let _ = fs::remove_dir_all("lockeventstestdb");
+        let db = SimpleDB::new("lockeventstestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let planner = BasicQueryPlanner::new(db.metadata_mgr());
+
+        // Other tests may leave events on this database's catalog, so only
+        // their shape is checked here; the lock table tests check the
+        // contents.
+        let data = Parser::new("select kind, filename, blocknum, mode, holders, waited from sys_lock_events").unwrap().query().unwrap();
+        let plan = planner.create_plan(&data, tx.clone()).unwrap();
+        assert_eq!(plan.schema().fields().len(), 6);
+        let mut scan = plan.open().unwrap();
+        while scan.next().unwrap() {
+            assert!(["wait", "timeout"].contains(&scan.get_string("kind").unwrap().as_str()));
+            assert!(["shared", "exclusive"].contains(&scan.get_string("mode").unwrap().as_str()));
+            assert!(scan.get_int("waited").unwrap() >= 0);
+        }
+        scan.close();
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
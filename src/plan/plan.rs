@@ -0,0 +1,144 @@
+use std::sync::Arc;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::Schema;
+
+// A block read at random costs this many sequential reads: reading a file
+// in order lets the disk read ahead, while every random read waits for a
+// seek.
+pub const RANDOM_BLOCK_COST: usize = 4;
+
+// A Plan is the node of a query tree: it knows how to open the scan that
+// evaluates its part of the query, and estimates the cost of doing so
+// without opening it. Planners compare plans by these estimates.
+pub trait Plan {
+    fn open(&self) -> Result<Box<dyn Scan>, ScanError>;
+
+    // The estimated number of block accesses needed to read every record
+    // of the scan.
+    fn blocks_accessed(&self) -> usize;
+
+    // The part of `blocks_accessed` read one block at a time in no
+    // particular order, such as the data blocks fetched through an index.
+    fn rand_blocks(&self) -> usize {
+        0
+    }
+
+    // The part of `blocks_accessed` read in file order.
+    fn seq_blocks(&self) -> usize {
+        self.blocks_accessed().saturating_sub(self.rand_blocks())
+    }
+
+    // The estimated cost of the scan in sequential block reads, which
+    // planners compare plans by.
+    fn cost(&self) -> usize {
+        self.seq_blocks() + RANDOM_BLOCK_COST * self.rand_blocks()
+    }
+
+    // The estimated number of records the scan outputs.
+    fn records_output(&self) -> usize;
+
+    // The estimated number of distinct values of the field in the output.
+    fn distinct_values(&self, field: &str) -> usize;
+
+    fn schema(&self) -> &Schema;
+
+    // What the plan does, in one line and without the plans it reads from.
+    fn describe(&self) -> String;
+
+    // The plans this plan reads from.
+    fn inputs(&self) -> Vec<&dyn Plan> {
+        Vec::new()
+    }
+
+    // Describes the tree of plans, one per line with its estimates and
+    // indented under the plan that reads from it.
+    fn explain(&self) -> String {
+        let mut out = String::new();
+        self.explain_at(0, &mut out);
+        out
+    }
+
+    fn explain_at(&self, depth: usize, out: &mut String) {
+        out.push_str(&format!("{}{} (blocks: {}, records: {})\n", "  ".repeat(depth), self.describe(), self.blocks_accessed(), self.records_output()));
+        for input in self.inputs() {
+            input.explain_at(depth + 1, out);
+        }
+    }
+}
+
+// A plan can be shared by several plans built on it, as when a planner
+// builds the candidate ways of joining a table to the plan so far and
+// keeps the cheapest.
+impl<P: Plan + ?Sized> Plan for Arc<P> {
+    fn open(&self) -> Result<Box<dyn Scan>, ScanError> {
+        (**self).open()
+    }
+
+    fn blocks_accessed(&self) -> usize {
+        (**self).blocks_accessed()
+    }
+
+    fn rand_blocks(&self) -> usize {
+        (**self).rand_blocks()
+    }
+
+    fn records_output(&self) -> usize {
+        (**self).records_output()
+    }
+
+    fn distinct_values(&self, field: &str) -> usize {
+        (**self).distinct_values(field)
+    }
+
+    fn schema(&self) -> &Schema {
+        (**self).schema()
+    }
+
+    fn describe(&self) -> String {
+        (**self).describe()
+    }
+
+    fn inputs(&self) -> Vec<&dyn Plan> {
+        (**self).inputs()
+    }
+}
+
+mod tests {
+    use std::fs;
+    use crate::parse::parser::Parser;
+    use crate::plan::basicqueryplanner::BasicQueryPlanner;
+    use crate::plan::basicupdateplanner::BasicUpdatePlanner;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_explain() {
+        let _ = fs::remove_dir_all("explaintestdb");
+        let db = SimpleDB::new("explaintestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let updater = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| updater.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone()).unwrap();
+        update("create table dept (did int, dname varchar(10))");
+        update("create table student (sid int, sname varchar(10), majorid int)");
+        for (did, dname) in [(10, "compsci"), (20, "math")] {
+            update(&format!("insert into dept (did, dname) values ({}, '{}')", did, dname));
+        }
+        for i in 0..40 {
+            update(&format!("insert into student (sid, sname, majorid) values ({}, 'rec{}', {})", i, i, 10 + 10 * (i % 2)));
+        }
+        update("create index majorid_idx on student (majorid)");
+
+        let planner = BasicQueryPlanner::new(mdm.clone());
+        let data = Parser::new("select sname, dname from dept, student where did = majorid and dname = 'math'").unwrap().query().unwrap();
+        let plan = planner.create_plan(&data, tx.clone()).unwrap();
+        assert_eq!(plan.explain(), [
+            "Project sname, dname (blocks: 45, records: 10)",
+            "  Select did=majorid and dname='math' (blocks: 45, records: 10)",
+            "    IndexJoin majorid=did on student with majorid_idx (blocks: 45, records: 40)",
+            "      Table dept (blocks: 1, records: 2)",
+            "",
+        ].join("\n"));
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
@@ -0,0 +1,60 @@
+use crate::plan::plan::Plan;
+use crate::query::productscan::ProductScan;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::Schema;
+
+// The ProductPlan outputs every combination of the records of its two
+// plans. The second plan is read once for each record of the first.
+pub struct ProductPlan {
+    p1: Box<dyn Plan>,
+    p2: Box<dyn Plan>,
+    schema: Schema,
+}
+
+impl ProductPlan {
+    pub fn new(p1: Box<dyn Plan>, p2: Box<dyn Plan>) -> ProductPlan {
+        let mut schema = Schema::new();
+        schema.add_all(p1.schema());
+        schema.add_all(p2.schema());
+        ProductPlan { p1, p2, schema }
+    }
+}
+
+impl Plan for ProductPlan {
+    fn open(&self) -> Result<Box<dyn Scan>, ScanError> {
+        Ok(Box::new(ProductScan::new(self.p1.open()?, self.p2.open()?)?))
+    }
+
+    fn blocks_accessed(&self) -> usize {
+        self.p1.blocks_accessed() + self.p1.records_output() * self.p2.blocks_accessed()
+    }
+
+    fn rand_blocks(&self) -> usize {
+        self.p1.rand_blocks() + self.p1.records_output() * self.p2.rand_blocks()
+    }
+
+    fn records_output(&self) -> usize {
+        self.p1.records_output() * self.p2.records_output()
+    }
+
+    fn distinct_values(&self, field: &str) -> usize {
+        if self.p1.schema().has_field(field) {
+            self.p1.distinct_values(field)
+        } else {
+            self.p2.distinct_values(field)
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn describe(&self) -> String {
+        "Product".to_string()
+    }
+
+    fn inputs(&self) -> Vec<&dyn Plan> {
+        vec![self.p1.as_ref(), self.p2.as_ref()]
+    }
+}
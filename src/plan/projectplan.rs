@@ -0,0 +1,58 @@
+use crate::plan::plan::Plan;
+use crate::query::projectscan::ProjectScan;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::Schema;
+
+// The ProjectPlan keeps only the specified fields of the underlying plan.
+// It reads and outputs the same records, so its estimates are those of the
+// underlying plan.
+pub struct ProjectPlan {
+    plan: Box<dyn Plan>,
+    schema: Schema,
+}
+
+impl ProjectPlan {
+    pub fn new(plan: Box<dyn Plan>, fields: &[String]) -> ProjectPlan {
+        let mut schema = Schema::new();
+        for field in fields {
+            schema.add(field, plan.schema());
+        }
+        ProjectPlan { plan, schema }
+    }
+}
+
+impl Plan for ProjectPlan {
+    fn open(&self) -> Result<Box<dyn Scan>, ScanError> {
+        let fields = self.schema.fields().iter().map(|field| field.to_string()).collect();
+        Ok(Box::new(ProjectScan::new(self.plan.open()?, fields)))
+    }
+
+    fn blocks_accessed(&self) -> usize {
+        self.plan.blocks_accessed()
+    }
+
+    fn rand_blocks(&self) -> usize {
+        self.plan.rand_blocks()
+    }
+
+    fn records_output(&self) -> usize {
+        self.plan.records_output()
+    }
+
+    fn distinct_values(&self, field: &str) -> usize {
+        self.plan.distinct_values(field)
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn describe(&self) -> String {
+        format!("Project {}", self.schema.fields().join(", "))
+    }
+
+    fn inputs(&self) -> Vec<&dyn Plan> {
+        vec![self.plan.as_ref()]
+    }
+}
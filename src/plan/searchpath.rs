@@ -0,0 +1,55 @@
+use crate::metadata::schemamgr::SchemaMgr;
+use crate::record::scanerror::ScanError;
+
+// The SearchPath is the list of schemas a session looks up unqualified
+// names in: a name refers to the object in the first schema on the path
+// that has one by that name, and a new object is created in the first
+// schema on the path. A qualified name, `schema.name`, is taken as written.
+// Each planner has its own path, so sessions sharing a database can see
+// different schemas under the same names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchPath {
+    schemas: Vec<String>,
+}
+
+impl SearchPath {
+    pub fn new(schemas: &[&str]) -> SearchPath {
+        assert!(!schemas.is_empty(), "a search path needs a schema to create objects in");
+        SearchPath { schemas: schemas.iter().map(|schema| schema.to_string()).collect() }
+    }
+
+    pub fn schemas(&self) -> &[String] {
+        &self.schemas
+    }
+
+    // Returns the catalogued name of the object the name refers to. When
+    // no schema on the path has it, the name it would have in the first
+    // schema is returned, so that the lookup that follows reports it
+    // unknown.
+    pub(crate) fn resolve(&self, name: &str, mut exists: impl FnMut(&str) -> Result<bool, ScanError>) -> Result<String, ScanError> {
+        if !name.contains('.') {
+            for schema in &self.schemas {
+                let qualified = SchemaMgr::qualify(schema, name);
+                if exists(&qualified)? {
+                    return Ok(qualified);
+                }
+            }
+        }
+        Ok(self.target(name))
+    }
+
+    // Returns the catalogued name of a new object with the name.
+    pub(crate) fn target(&self, name: &str) -> String {
+        let (schema, name) = match name.contains('.') {
+            true => SchemaMgr::split(name),
+            false => (self.schemas[0].as_str(), name),
+        };
+        SchemaMgr::qualify(schema, name)
+    }
+}
+
+impl Default for SearchPath {
+    fn default() -> SearchPath {
+        SearchPath::new(&[SchemaMgr::DEFAULT])
+    }
+}
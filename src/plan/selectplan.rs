@@ -0,0 +1,62 @@
+use crate::plan::plan::Plan;
+use crate::query::predicate::Predicate;
+use crate::query::scan::Scan;
+use crate::query::selectscan::SelectScan;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::Schema;
+
+// The SelectPlan reads every record of the underlying plan and outputs the
+// ones that satisfy the predicate, reduced by its estimated reduction
+// factor.
+pub struct SelectPlan {
+    plan: Box<dyn Plan>,
+    pred: Predicate,
+}
+
+impl SelectPlan {
+    pub fn new(plan: Box<dyn Plan>, pred: Predicate) -> SelectPlan {
+        SelectPlan { plan, pred }
+    }
+}
+
+impl Plan for SelectPlan {
+    fn open(&self) -> Result<Box<dyn Scan>, ScanError> {
+        Ok(Box::new(SelectScan::new(self.plan.open()?, self.pred.clone())))
+    }
+
+    fn blocks_accessed(&self) -> usize {
+        self.plan.blocks_accessed()
+    }
+
+    fn rand_blocks(&self) -> usize {
+        self.plan.rand_blocks()
+    }
+
+    fn records_output(&self) -> usize {
+        self.plan.records_output() / self.pred.reduction_factor(&|field| self.plan.distinct_values(field))
+    }
+
+    // A field equated with a constant has a single value; a field equated
+    // with another field has at most as many values as the other.
+    fn distinct_values(&self, field: &str) -> usize {
+        if self.pred.equates_with_constant(field).is_some() {
+            return 1;
+        }
+        match self.pred.equates_with_field(field) {
+            Some(other) => self.plan.distinct_values(field).min(self.plan.distinct_values(other)),
+            None => self.plan.distinct_values(field),
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        self.plan.schema()
+    }
+
+    fn describe(&self) -> String {
+        format!("Select {}", self.pred)
+    }
+
+    fn inputs(&self) -> Vec<&dyn Plan> {
+        vec![self.plan.as_ref()]
+    }
+}
@@ -0,0 +1,62 @@
+use std::sync::{Arc, Mutex};
+use crate::metadata::metadatamgr::MetadataMgr;
+use crate::metadata::statmgr::StatInfo;
+use crate::plan::plan::Plan;
+use crate::query::scan::Scan;
+use crate::record::layout::Layout;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::Schema;
+use crate::record::tablescan::TableScan;
+use crate::tx::transaction::Transaction;
+
+// The TablePlan reads a stored table. Its estimates are the table's
+// statistics as kept by the StatMgr.
+pub struct TablePlan {
+    tx: Arc<Mutex<Transaction>>,
+    table: String,
+    layout: Arc<Layout>,
+    info: StatInfo,
+}
+
+impl TablePlan {
+    pub fn new(tx: Arc<Mutex<Transaction>>, table: &str, mdm: &MetadataMgr) -> Result<TablePlan, ScanError> {
+        let layout = mdm.layout(table, tx.clone())?;
+        let info = mdm.stat_info(table, tx.clone())?;
+        Ok(TablePlan { tx, table: table.to_string(), layout, info })
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    // Opens the table scan itself, for callers that need to update it.
+    pub(crate) fn open_table(&self) -> Result<TableScan, ScanError> {
+        TableScan::new(self.tx.clone(), self.layout.clone(), &self.table)
+    }
+}
+
+impl Plan for TablePlan {
+    fn open(&self) -> Result<Box<dyn Scan>, ScanError> {
+        Ok(Box::new(self.open_table()?))
+    }
+
+    fn blocks_accessed(&self) -> usize {
+        self.info.blocks_accessed()
+    }
+
+    fn records_output(&self) -> usize {
+        self.info.records_output()
+    }
+
+    fn distinct_values(&self, field: &str) -> usize {
+        self.info.distinct_values(field)
+    }
+
+    fn schema(&self) -> &Schema {
+        self.layout.schema()
+    }
+
+    fn describe(&self) -> String {
+        format!("Table {}", self.table)
+    }
+}
@@ -0,0 +1,137 @@
+use std::sync::{Arc, Mutex};
+use crate::metadata::metadatamgr::MetadataMgr;
+use crate::plan::plan::Plan;
+use crate::query::constant::Constant;
+use crate::query::rowscan::RowScan;
+use crate::query::scan::Scan;
+use crate::record::row::Row;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::Schema;
+use crate::tx::transaction::Transaction;
+
+// The TableSizesPlan reads the sys_table_sizes system table, which lists
+// the disk usage of every table in the catalog along with its quota, or
+// -1 if the table may grow without limit. It also reads the
+// sys_relation_sizes system table, which lists each table followed by its
+// indexes, an index with its name in index_name, which is null for a
+// table. The sizes are measured when the plan is created, since measuring
+// them scans every table and index.
+pub struct TableSizesPlan {
+    name: &'static str,
+    schema: Schema,
+    rows: Vec<Row>,
+}
+
+impl TableSizesPlan {
+    pub const TABLE: &'static str = "sys_table_sizes";
+    pub const RELATIONS: &'static str = "sys_relation_sizes";
+
+    pub fn new(mdm: &MetadataMgr, with_indexes: bool, tx: Arc<Mutex<Transaction>>) -> Result<TableSizesPlan, ScanError> {
+        let mut schema = Schema::new();
+        schema.add_string_field("table_name", 16);
+        if with_indexes {
+            schema.add_string_field("index_name", 16);
+        }
+        schema.add_int_field("blocks");
+        schema.add_int_field("bytes");
+        schema.add_int_field("records");
+        schema.add_int_field("free_slots");
+        schema.add_int_field("quota");
+        let usage = if with_indexes { mdm.disk_usage(tx)? } else { mdm.table_mgr().disk_usage(tx)? };
+        let rows = usage.into_iter().map(|size| {
+            let mut row = Row::new();
+            row.set("table_name", Constant::String(size.table));
+            if with_indexes {
+                row.set("index_name", size.index.map_or(Constant::Null, Constant::String));
+            }
+            row.set("blocks", Constant::Int(size.blocks as i32));
+            row.set("bytes", Constant::Int(size.bytes as i32));
+            row.set("records", Constant::Int(size.records as i32));
+            row.set("free_slots", Constant::Int(size.free_slots as i32));
+            row.set("quota", Constant::Int(size.quota.map_or(-1, |quota| quota as i32)));
+            row
+        }).collect();
+        let name = if with_indexes { Self::RELATIONS } else { Self::TABLE };
+        Ok(TableSizesPlan { name, schema, rows })
+    }
+}
+
+impl Plan for TableSizesPlan {
+    fn open(&self) -> Result<Box<dyn Scan>, ScanError> {
+        Ok(Box::new(RowScan::new(self.schema.clone(), self.rows.clone())))
+    }
+
+    fn blocks_accessed(&self) -> usize {
+        0
+    }
+
+    fn records_output(&self) -> usize {
+        self.rows.len()
+    }
+
+    fn distinct_values(&self, _field: &str) -> usize {
+        self.records_output().max(1)
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn describe(&self) -> String {
+        format!("SystemTable {}", self.name)
+    }
+}
+
+mod tests {
+    use std::fs;
+    use crate::error::dberror::DbError;
+    use crate::parse::parser::Parser;
+    use crate::query::constant::Constant;
+    use crate::plan::basicqueryplanner::BasicQueryPlanner;
+    use crate::plan::basicupdateplanner::BasicUpdatePlanner;
+    use crate::record::scanerror::ScanError;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_table_quota() {
+        let _ = fs::remove_dir_all("tablequotatestdb");
+        let db = SimpleDB::new("tablequotatestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let updater = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| updater.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone());
+        update("create table t (a int, b varchar(10))").unwrap();
+        db.file_mgr().set_table_quota("t", Some(2));
+
+        // 15 records fit in a block, so the 31st needs a third block.
+        for i in 0..30 {
+            update(&format!("insert into t (a, b) values ({}, 'rec{}')", i, i)).unwrap();
+        }
+        let error = update("insert into t (a, b) values (30, 'rec30')").unwrap_err();
+        assert_eq!(error, ScanError::Db(DbError::TableQuotaExceeded { table: "t".to_string(), blocks: 2 }));
+
+        let planner = BasicQueryPlanner::new(mdm.clone());
+        let data = Parser::new("select table_name, blocks, records, quota from sys_table_sizes").unwrap().query().unwrap();
+        let mut scan = planner.create_plan(&data, tx.clone()).unwrap().open().unwrap();
+        let mut sizes = Vec::new();
+        while scan.next().unwrap() {
+            let size = (scan.get_int("blocks").unwrap(), scan.get_int("records").unwrap(), scan.get_int("quota").unwrap());
+            sizes.push((scan.get_string("table_name").unwrap(), size));
+        }
+        scan.close();
+        assert!(sizes.contains(&("t".to_string(), (2, 30, 2))));
+        assert!(sizes.iter().any(|(table, (_, _, quota))| table == "tblcat" && *quota == -1));
+
+        // sys_relation_sizes also lists the indexes, after their table.
+        update("create index ta on t (a)").unwrap();
+        let data = Parser::new("select table_name, index_name, records from sys_relation_sizes where table_name = 't'").unwrap().query().unwrap();
+        let mut scan = planner.create_plan(&data, tx.clone()).unwrap().open().unwrap();
+        let mut sizes = Vec::new();
+        while scan.next().unwrap() {
+            sizes.push((scan.get_val("index_name").unwrap(), scan.get_int("records").unwrap()));
+        }
+        scan.close();
+        assert_eq!(sizes, [(Constant::Null, 30), (Constant::String("ta".to_string()), 30)]);
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
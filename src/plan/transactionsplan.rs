@@ -0,0 +1,119 @@
+use crate::plan::plan::Plan;
+use crate::query::constant::Constant;
+use crate::query::rowscan::RowScan;
+use crate::query::scan::Scan;
+use crate::record::row::Row;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::Schema;
+use std::sync::{Arc, Mutex};
+use crate::tx::transaction::Transaction;
+use crate::tx::txstatus::TxRegistry;
+
+// The TransactionsPlan reads the sys_transactions system table, which lists
+// the active transactions of the database, oldest first, with the number of
+// blocks each has locked and of buffers it has pinned. Like
+// sys_lock_events, the table is not stored.
+pub struct TransactionsPlan {
+    registry: Arc<TxRegistry>,
+    schema: Schema,
+}
+
+impl TransactionsPlan {
+    pub const TABLE: &'static str = "sys_transactions";
+
+    pub fn new(tx: Arc<Mutex<Transaction>>) -> TransactionsPlan {
+        let mut schema = Schema::new();
+        schema.add_int_field("txnum");
+        schema.add_date_field("started");
+        schema.add_string_field("state", 12);
+        schema.add_int_field("locks");
+        schema.add_int_field("pins");
+        TransactionsPlan { registry: tx.lock().unwrap().registry(), schema }
+    }
+}
+
+impl Plan for TransactionsPlan {
+    fn open(&self) -> Result<Box<dyn Scan>, ScanError> {
+        let rows = self.registry.active().into_iter().map(|info| {
+            let mut row = Row::new();
+            row.set("txnum", Constant::Int(info.txnum));
+            row.set("started", Constant::Date(info.started));
+            row.set("state", Constant::String(info.state.name().to_string()));
+            row.set("locks", Constant::Int(info.locks as i32));
+            row.set("pins", Constant::Int(info.pins as i32));
+            row
+        }).collect();
+        Ok(Box::new(RowScan::new(self.schema.clone(), rows)))
+    }
+
+    fn blocks_accessed(&self) -> usize {
+        0
+    }
+
+    fn records_output(&self) -> usize {
+        self.registry.active().len()
+    }
+
+    fn distinct_values(&self, _field: &str) -> usize {
+        self.records_output().max(1)
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn describe(&self) -> String {
+        format!("SystemTable {}", Self::TABLE)
+    }
+}
+
+mod tests {
+    use std::fs;
+    use crate::parse::parser::Parser;
+    use crate::plan::basicqueryplanner::BasicQueryPlanner;
+    use crate::plan::basicupdateplanner::BasicUpdatePlanner;
+    use crate::query::constant::Constant;
+    use crate::error::dberror::DbError;
+    use crate::record::scanerror::ScanError;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_sys_transactions() {
+        let _ = fs::remove_dir_all("transactionstestdb");
+        let db = SimpleDB::new("transactionstestdb", 400, 8).unwrap();
+        let mdm = db.metadata_mgr();
+        let updater = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str, tx| updater.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx);
+        let tx1 = db.new_tx().unwrap();
+        update("create table killed (a int)", tx1.clone()).unwrap();
+        update("insert into killed (a) values (1)", tx1.clone()).unwrap();
+        let txnum1 = tx1.lock().unwrap().txnum();
+
+        // A transaction of another database is not listed.
+        let _ = fs::remove_dir_all("transactionsothertestdb");
+        let other = SimpleDB::new("transactionsothertestdb", 400, 8).unwrap();
+        let _other_tx = other.new_tx().unwrap();
+        let tx2 = db.new_tx().unwrap();
+        let txnum2 = tx2.lock().unwrap().txnum();
+        let planner = BasicQueryPlanner::new(mdm.clone());
+        let data = Parser::new("select txnum, started, state, locks, pins from sys_transactions").unwrap().query().unwrap();
+        let plan = planner.create_plan(&data, tx2.clone()).unwrap();
+        let mut scan = plan.open().unwrap();
+        let mut seen = Vec::new();
+        while scan.next().unwrap() {
+            assert_eq!(scan.get_string("state").unwrap(), "active");
+            assert!(matches!(scan.get_val("started").unwrap(), Constant::Date(ms) if ms > 0));
+            seen.push((scan.get_int("txnum").unwrap(), scan.get_int("locks").unwrap() > 0, scan.get_int("pins").unwrap()));
+        }
+        scan.close();
+        assert_eq!(seen, vec![(txnum1, true, 0), (txnum2, false, 0)]);
+
+        // Killing the idle transaction rolls it back right away, and it
+        // fails from then on.
+        assert_eq!(update(&format!("kill {}", txnum1), tx2.clone()), Ok(0));
+        assert_eq!(tx1.lock().unwrap().commit(), Err(DbError::Killed(txnum1)));
+        assert_eq!(update(&format!("kill {}", txnum1), tx2.clone()), Err(ScanError::UnknownTransaction(txnum1)));
+        assert_eq!(update("insert into killed (a) values (2)", tx2.clone()), Err(ScanError::UnknownTable("killed".to_string())));
+        tx2.lock().unwrap().commit().unwrap();
+    }
+}
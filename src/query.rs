@@ -0,0 +1,14 @@
+pub mod constant;
+pub mod date;
+pub mod expression;
+pub mod term;
+pub mod predicate;
+pub mod scan;
+pub mod selectscan;
+pub mod projectscan;
+pub mod productscan;
+pub mod indexselectscan;
+pub mod indexjoinscan;
+pub mod rowscan;
+pub mod bitmapscan;
+pub mod resultset;
@@ -0,0 +1,56 @@
+use crate::query::constant::Constant;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+use crate::record::tablescan::{RecordId, TableScan, UpdateScan};
+
+// The BitmapScan reads the records of a table with the given RecordIds,
+// which are sorted, so that the table is read in block order and each
+// block is visited once however many of its records are wanted.
+pub struct BitmapScan {
+    ts: TableScan,
+    rids: Vec<RecordId>,
+    pos: usize,
+}
+
+impl BitmapScan {
+    pub(crate) fn new(ts: TableScan, rids: Vec<RecordId>) -> BitmapScan {
+        debug_assert!(rids.is_sorted(), "the RecordIds of a bitmap scan are sorted");
+        BitmapScan { ts, rids, pos: 0 }
+    }
+}
+
+impl Scan for BitmapScan {
+    fn before_first(&mut self) -> Result<(), ScanError> {
+        self.pos = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<bool, ScanError> {
+        let Some(rid) = self.rids.get(self.pos) else {
+            return Ok(false);
+        };
+        self.ts.move_to_rid(rid)?;
+        self.pos += 1;
+        Ok(true)
+    }
+
+    fn get_int(&mut self, field: &str) -> Result<i32, ScanError> {
+        self.ts.get_int(field)
+    }
+
+    fn get_string(&mut self, field: &str) -> Result<String, ScanError> {
+        self.ts.get_string(field)
+    }
+
+    fn get_val(&mut self, field: &str) -> Result<Constant, ScanError> {
+        self.ts.get_val(field)
+    }
+
+    fn has_field(&self, field: &str) -> bool {
+        self.ts.has_field(field)
+    }
+
+    fn close(&mut self) {
+        self.ts.close();
+    }
+}
@@ -0,0 +1,315 @@
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use crate::query::date;
+use crate::record::schema::FieldType;
+
+// A Constant is a single field value read from or written to a record,
+// tagged with the type of the field it belongs to. A date is the number of
+// seconds since the epoch, written as a string; see `query::date`. A blob
+// is a string of bytes of any length. Null stands for a field
+// without a value, whatever its type; it orders after every other value.
+// Doubles are ordered, compared and hashed by `f64::total_cmp` and their
+// bits, so that constants can be sorted and used as keys.
+#[derive(Debug, Clone)]
+pub enum Constant {
+    Int(i32),
+    Long(i64),
+    Double(f64),
+    Bool(bool),
+    Date(i64),
+    String(String),
+    Blob(Vec<u8>),
+    Null,
+}
+
+impl Constant {
+    pub fn is_null(&self) -> bool {
+        *self == Constant::Null
+    }
+
+    pub fn as_int(&self) -> Option<i32> {
+        match self {
+            Constant::Int(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn as_long(&self) -> Option<i64> {
+        match self {
+            Constant::Long(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn as_double(&self) -> Option<f64> {
+        match self {
+            Constant::Double(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Constant::Bool(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn as_date(&self) -> Option<i64> {
+        match self {
+            Constant::Date(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn as_string(&self) -> Option<&str> {
+        match self {
+            Constant::String(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    pub fn as_blob(&self) -> Option<&[u8]> {
+        match self {
+            Constant::Blob(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    // The type of field the value belongs in; a null belongs in any.
+    pub fn ftype(&self) -> Option<FieldType> {
+        match self {
+            Constant::Int(_) => Some(FieldType::INTEGER),
+            Constant::Long(_) => Some(FieldType::BIGINT),
+            Constant::Double(_) => Some(FieldType::DOUBLE),
+            Constant::Bool(_) => Some(FieldType::BOOLEAN),
+            Constant::Date(_) => Some(FieldType::DATE),
+            Constant::String(_) => Some(FieldType::VARCHAR),
+            Constant::Blob(_) => Some(FieldType::BLOB),
+            Constant::Null => None,
+        }
+    }
+
+    // Converts the value to the type of a field. Integers widen to BIGINT
+    // and DOUBLE, a BIGINT narrows to INTEGER if it fits, and a string that
+    // holds a date converts to DATE; any other change of type returns None.
+    // Null converts to every type.
+    pub fn coerce(&self, ftype: FieldType) -> Option<Constant> {
+        match (self, ftype) {
+            (Constant::Null, _) => Some(Constant::Null),
+            (Constant::Int(_), FieldType::INTEGER) => Some(self.clone()),
+            (Constant::Long(val), FieldType::INTEGER) => i32::try_from(*val).ok().map(Constant::Int),
+            (Constant::Int(val), FieldType::BIGINT) => Some(Constant::Long(*val as i64)),
+            (Constant::Long(_), FieldType::BIGINT) => Some(self.clone()),
+            (Constant::Int(val), FieldType::DOUBLE) => Some(Constant::Double(*val as f64)),
+            // A long above 2^53 may have no double of the same value.
+            (Constant::Long(val), FieldType::DOUBLE) => {
+                let double = *val as f64;
+                (double as i128 == *val as i128).then_some(Constant::Double(double))
+            }
+            (Constant::Double(_), FieldType::DOUBLE) => Some(self.clone()),
+            (Constant::Bool(_), FieldType::BOOLEAN) => Some(self.clone()),
+            (Constant::Date(_), FieldType::DATE) => Some(self.clone()),
+            (Constant::String(val), FieldType::DATE) => date::parse(val).map(Constant::Date),
+            (Constant::String(_), FieldType::VARCHAR) => Some(self.clone()),
+            (Constant::Blob(_), FieldType::BLOB) => Some(self.clone()),
+            _ => None,
+        }
+    }
+
+    // Compares two values the way a predicate does: numbers of different
+    // types are compared by value, as doubles if either one is, and a date
+    // compared with a string is compared with the date the string holds.
+    // Other values compare as `Ord` does.
+    pub fn compare(&self, other: &Constant) -> Ordering {
+        match (self, other) {
+            (Constant::Date(_), Constant::String(_)) => if let Some(other) = other.coerce(FieldType::DATE) {
+                return self.cmp(&other);
+            },
+            (Constant::String(_), Constant::Date(_)) => if let Some(val) = self.coerce(FieldType::DATE) {
+                return val.cmp(other);
+            },
+            _ => {}
+        }
+        let numbers = match (self, other) {
+            (Constant::Double(_), _) | (_, Constant::Double(_)) => self.to_f64().zip(other.to_f64()).map(|(lhs, rhs)| lhs.total_cmp(&rhs)),
+            _ => self.to_i64().zip(other.to_i64()).map(|(lhs, rhs)| lhs.cmp(&rhs)),
+        };
+        numbers.unwrap_or_else(|| self.cmp(other))
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        match self {
+            Constant::Int(val) => Some(*val as i64),
+            Constant::Long(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    fn to_f64(&self) -> Option<f64> {
+        match self {
+            Constant::Double(val) => Some(*val),
+            _ => self.to_i64().map(|val| val as f64),
+        }
+    }
+
+    // The position of the variant in the order of constants of different
+    // types.
+    fn rank(&self) -> u8 {
+        match self {
+            Constant::Int(_) => 0,
+            Constant::Long(_) => 1,
+            Constant::Double(_) => 2,
+            Constant::Bool(_) => 3,
+            Constant::Date(_) => 4,
+            Constant::String(_) => 5,
+            Constant::Blob(_) => 6,
+            Constant::Null => 7,
+        }
+    }
+}
+
+impl PartialEq for Constant {
+    fn eq(&self, other: &Constant) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Constant {}
+
+impl PartialOrd for Constant {
+    fn partial_cmp(&self, other: &Constant) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Constant {
+    fn cmp(&self, other: &Constant) -> Ordering {
+        match (self, other) {
+            (Constant::Int(lhs), Constant::Int(rhs)) => lhs.cmp(rhs),
+            (Constant::Long(lhs), Constant::Long(rhs)) => lhs.cmp(rhs),
+            (Constant::Double(lhs), Constant::Double(rhs)) => lhs.total_cmp(rhs),
+            (Constant::Bool(lhs), Constant::Bool(rhs)) => lhs.cmp(rhs),
+            (Constant::Date(lhs), Constant::Date(rhs)) => lhs.cmp(rhs),
+            (Constant::String(lhs), Constant::String(rhs)) => lhs.cmp(rhs),
+            (Constant::Blob(lhs), Constant::Blob(rhs)) => lhs.cmp(rhs),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl Hash for Constant {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+        match self {
+            Constant::Int(val) => val.hash(state),
+            Constant::Long(val) => val.hash(state),
+            Constant::Double(val) => val.to_bits().hash(state),
+            Constant::Bool(val) => val.hash(state),
+            Constant::Date(val) => val.hash(state),
+            Constant::String(val) => val.hash(state),
+            Constant::Blob(val) => val.hash(state),
+            Constant::Null => {}
+        }
+    }
+}
+
+// Doubles are written in Rust's debug format, which always has a decimal
+// point or an exponent, so that they parse back as doubles. Quotes in
+// strings are doubled, as the lexer expects. Dates are
+// written as string literals, which convert back to dates, and blobs as
+// hexadecimal literals.
+impl Display for Constant {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Constant::Int(val) => write!(f, "{}", val),
+            Constant::Long(val) => write!(f, "{}", val),
+            Constant::Double(val) => write!(f, "{:?}", val),
+            Constant::Bool(val) => write!(f, "{}", val),
+            Constant::Date(val) => write!(f, "'{}'", date::format(*val)),
+            Constant::String(val) => write!(f, "'{}'", val.replace('\'', "''")),
+            Constant::Blob(val) => {
+                write!(f, "x'")?;
+                val.iter().try_for_each(|byte| write!(f, "{:02x}", byte))?;
+                write!(f, "'")
+            }
+            Constant::Null => write!(f, "null"),
+        }
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constant() {
+        let int = Constant::Int(42);
+        let string = Constant::String("abc".to_string());
+        assert_eq!(int.as_int(), Some(42));
+        assert_eq!(int.as_string(), None);
+        assert_eq!(string.as_string(), Some("abc"));
+        assert_eq!(int.to_string(), "42");
+        assert_eq!(string.to_string(), "'abc'");
+        assert!(Constant::Int(1) < Constant::Int(2));
+        assert!(Constant::Null.is_null() && !int.is_null());
+        assert_eq!(Constant::Null.as_int(), None);
+        assert_eq!(Constant::Null.to_string(), "null");
+        assert!(Constant::String("zzz".to_string()) < Constant::Null);
+    }
+
+    #[test]
+    fn test_numeric_types() {
+        assert_eq!(Constant::Double(2.0).to_string(), "2.0");
+        assert_eq!(Constant::Bool(true).to_string(), "true");
+        assert_eq!(Constant::Long(1 << 40).to_string(), "1099511627776");
+        assert!(Constant::Double(-1.5) < Constant::Double(0.25));
+        assert_eq!(Constant::Double(f64::NAN), Constant::Double(f64::NAN));
+
+        assert_eq!(Constant::Int(3).coerce(FieldType::BIGINT), Some(Constant::Long(3)));
+        assert_eq!(Constant::Int(3).coerce(FieldType::DOUBLE), Some(Constant::Double(3.0)));
+        assert_eq!(Constant::Long(3).coerce(FieldType::INTEGER), Some(Constant::Int(3)));
+        assert_eq!(Constant::Long(1 << 40).coerce(FieldType::INTEGER), None);
+        assert_eq!(Constant::Long(1 << 53).coerce(FieldType::DOUBLE), Some(Constant::Double(9007199254740992.0)));
+        assert_eq!(Constant::Long((1 << 53) + 1).coerce(FieldType::DOUBLE), None);
+        assert_eq!(Constant::Long(i64::MAX).coerce(FieldType::DOUBLE), None);
+        assert_eq!(Constant::String("it's".to_string()).to_string(), "'it''s'");
+        assert_eq!(Constant::Double(3.0).coerce(FieldType::INTEGER), None);
+        assert_eq!(Constant::Bool(true).coerce(FieldType::VARCHAR), None);
+        assert_eq!(Constant::Null.coerce(FieldType::BOOLEAN), Some(Constant::Null));
+
+        // Numbers of different types compare by value.
+        assert_eq!(Constant::Int(3).compare(&Constant::Long(3)), Ordering::Equal);
+        assert_eq!(Constant::Long(4).compare(&Constant::Double(3.5)), Ordering::Greater);
+        assert_eq!(Constant::Double(3.5).compare(&Constant::Int(4)), Ordering::Less);
+        assert_eq!(Constant::Int(3).compare(&Constant::Int(4)), Ordering::Less);
+        assert_ne!(Constant::Int(3), Constant::Long(3));
+    }
+
+    #[test]
+    fn test_dates() {
+        let date = Constant::Date(86400);
+        assert_eq!(date.to_string(), "'1970-01-02'");
+        assert_eq!(date.as_date(), Some(86400));
+        assert_eq!(Constant::String("1970-01-02".to_string()).coerce(FieldType::DATE), Some(date.clone()));
+        assert_eq!(Constant::String("tomorrow".to_string()).coerce(FieldType::DATE), None);
+        assert_eq!(Constant::Int(86400).coerce(FieldType::DATE), None);
+
+        // A date compares with a string holding a date as with that date.
+        assert_eq!(date.compare(&Constant::String("1970-01-02".to_string())), Ordering::Equal);
+        assert_eq!(Constant::String("1970-01-01 12:00:00".to_string()).compare(&date), Ordering::Less);
+        assert_ne!(date, Constant::String("1970-01-02".to_string()));
+    }
+
+    #[test]
+    fn test_blobs() {
+        let blob = Constant::Blob(vec![0x00, 0xab, 0x10]);
+        assert_eq!(blob.to_string(), "x'00ab10'");
+        assert_eq!(blob.as_blob(), Some(&[0x00, 0xab, 0x10][..]));
+        assert_eq!(blob.coerce(FieldType::BLOB), Some(blob.clone()));
+        assert_eq!(blob.coerce(FieldType::VARCHAR), None);
+        assert_eq!(Constant::String("abc".to_string()).coerce(FieldType::BLOB), None);
+        assert!(Constant::Blob(vec![1]) < Constant::Blob(vec![1, 0]));
+        assert!(blob < Constant::Null);
+    }
+}
@@ -0,0 +1,103 @@
+// Dates are stored as the number of seconds since 1970-01-01 00:00:00 UTC.
+// They are written in SQL as strings, either `YYYY-MM-DD` for midnight or
+// `YYYY-MM-DD HH:MM:SS`, with a `T` also allowed between date and time.
+const SECONDS_PER_DAY: i64 = 86400;
+
+// Returns the seconds since the epoch of a date string, or None if it is
+// not a valid date.
+pub fn parse(s: &str) -> Option<i64> {
+    let (date, time) = match s.split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (s, None),
+    };
+    let [year, month, day] = fields(date, '-')?;
+    if date.len() != 10 || !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    let seconds = match time {
+        Some(time) => {
+            let [hour, minute, second] = fields(time, ':')?;
+            if time.len() != 8 || hour > 23 || minute > 59 || second > 59 {
+                return None;
+            }
+            hour * 3600 + minute * 60 + second
+        }
+        None => 0,
+    };
+    Some(days_from_civil(year, month, day) * SECONDS_PER_DAY + seconds)
+}
+
+// Writes the date the way `parse` reads it, leaving out the time at
+// midnight.
+pub fn format(seconds: i64) -> String {
+    let (year, month, day) = civil_from_days(seconds.div_euclid(SECONDS_PER_DAY));
+    let time = seconds.rem_euclid(SECONDS_PER_DAY);
+    if time == 0 {
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    } else {
+        format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, time / 3600, time / 60 % 60, time % 60)
+    }
+}
+
+fn fields(s: &str, separator: char) -> Option<[i64; 3]> {
+    let mut parts = s.split(separator).map(|part| part.parse::<u32>().ok().map(i64::from));
+    let fields = [parts.next()??, parts.next()??, parts.next()??];
+    parts.next().is_none().then_some(fields)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// The conversions between days since the epoch and the proleptic Gregorian
+// calendar are Howard Hinnant's `days_from_civil` and `civil_from_days`.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date() {
+        assert_eq!(parse("1970-01-01"), Some(0));
+        assert_eq!(parse("1970-01-02 00:00:01"), Some(86401));
+        assert_eq!(parse("2024-01-01"), Some(1704067200));
+        assert_eq!(parse("2024-02-29T12:30:00"), Some(1709209800));
+        assert_eq!(parse("1969-12-31 23:59:59"), Some(-1));
+        assert_eq!(parse("2023-02-29"), None);
+        assert_eq!(parse("2024-13-01"), None);
+        assert_eq!(parse("2024-1-1"), None);
+        assert_eq!(parse("2024-01-01 24:00:00"), None);
+        assert_eq!(parse("hello"), None);
+
+        assert_eq!(format(0), "1970-01-01");
+        assert_eq!(format(-1), "1969-12-31 23:59:59");
+        assert_eq!(format(1709209800), "2024-02-29 12:30:00");
+        for s in ["1600-03-01", "2000-02-29", "2100-12-31 01:02:03"] {
+            assert_eq!(format(parse(s).unwrap()), s);
+        }
+    }
+}
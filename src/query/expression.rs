@@ -0,0 +1,59 @@
+use std::fmt::Display;
+use crate::parse::lexer::Lexer;
+use crate::query::constant::Constant;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::Schema;
+
+// An Expression is either a constant or the name of a field, the two kinds
+// of operand a term can compare.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Constant(Constant),
+    Field(String),
+}
+
+impl Expression {
+    pub fn is_field_name(&self) -> bool {
+        matches!(self, Expression::Field(_))
+    }
+
+    pub fn as_constant(&self) -> Option<&Constant> {
+        match self {
+            Expression::Constant(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    pub fn as_field_name(&self) -> Option<&str> {
+        match self {
+            Expression::Field(field) => Some(field),
+            _ => None,
+        }
+    }
+
+    // Returns true if every field the expression mentions is in the schema.
+    pub fn applies_to(&self, schema: &Schema) -> bool {
+        match self {
+            Expression::Constant(_) => true,
+            Expression::Field(field) => schema.has_field(field),
+        }
+    }
+
+    // Returns the value of the expression for the current record of the scan.
+    pub fn evaluate(&self, scan: &mut dyn Scan) -> Result<Constant, ScanError> {
+        match self {
+            Expression::Constant(val) => Ok(val.clone()),
+            Expression::Field(field) => scan.get_val(field),
+        }
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Expression::Constant(val) => write!(f, "{}", val),
+            Expression::Field(field) => write!(f, "{}", Lexer::quote(field)),
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::index::index::Index;
+use crate::query::constant::Constant;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+use crate::record::tablescan::{TableScan, UpdateScan};
+
+// The IndexJoinScan joins the records of the first scan with the records
+// of a table whose indexed field equals the join field of the first scan.
+// Instead of reading the whole table for each record of the first scan, as
+// a product would, it searches the index for the value of the join field
+// and moves the table scan to each matching record.
+pub struct IndexJoinScan<S: Scan> {
+    lhs: S,
+    idx: Box<dyn Index>,
+    join_field: String,
+    rhs: TableScan,
+    // Whether the first scan is positioned on a record, which is false once
+    // it runs out of records or if it had none to begin with.
+    on_lhs: bool,
+}
+
+impl<S: Scan> IndexJoinScan<S> {
+    pub(crate) fn new(lhs: S, idx: Box<dyn Index>, join_field: &str, rhs: TableScan) -> Result<IndexJoinScan<S>, ScanError> {
+        let mut scan = IndexJoinScan { lhs, idx, join_field: join_field.to_string(), rhs, on_lhs: false };
+        scan.before_first()?;
+        Ok(scan)
+    }
+
+    // Moves the first scan to its next record and positions the index on
+    // the value of its join field.
+    fn next_lhs(&mut self) -> Result<(), ScanError> {
+        self.on_lhs = self.lhs.next()?;
+        if self.on_lhs {
+            let search_key = self.lhs.get_val(&self.join_field)?;
+            self.idx.before_first(&search_key)?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: Scan> Scan for IndexJoinScan<S> {
+    fn before_first(&mut self) -> Result<(), ScanError> {
+        self.lhs.before_first()?;
+        self.next_lhs()
+    }
+
+    fn next(&mut self) -> Result<bool, ScanError> {
+        while self.on_lhs {
+            if self.idx.next()? {
+                let rid = self.idx.data_rid()?;
+                self.rhs.move_to_rid(&rid)?;
+                return Ok(true);
+            }
+            self.next_lhs()?;
+        }
+        Ok(false)
+    }
+
+    fn get_int(&mut self, field: &str) -> Result<i32, ScanError> {
+        if self.rhs.has_field(field) {
+            self.rhs.get_int(field)
+        } else {
+            self.lhs.get_int(field)
+        }
+    }
+
+    fn get_string(&mut self, field: &str) -> Result<String, ScanError> {
+        if self.rhs.has_field(field) {
+            self.rhs.get_string(field)
+        } else {
+            self.lhs.get_string(field)
+        }
+    }
+
+    fn get_val(&mut self, field: &str) -> Result<Constant, ScanError> {
+        if self.rhs.has_field(field) {
+            self.rhs.get_val(field)
+        } else {
+            self.lhs.get_val(field)
+        }
+    }
+
+    fn has_field(&self, field: &str) -> bool {
+        self.rhs.has_field(field) || self.lhs.has_field(field)
+    }
+
+    fn close(&mut self) {
+        self.lhs.close();
+        self.idx.close();
+        self.rhs.close();
+    }
+}
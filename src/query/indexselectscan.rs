@@ -0,0 +1,59 @@
+use crate::index::index::Index;
+use crate::query::constant::Constant;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+use crate::record::tablescan::{TableScan, UpdateScan};
+
+// The IndexSelectScan implements a select on a field equated with a
+// constant by searching an index on the field instead of reading the whole
+// table: for each index record with the constant, it moves the table scan
+// to the data record the index record points to.
+pub struct IndexSelectScan {
+    ts: TableScan,
+    idx: Box<dyn Index>,
+    val: Constant,
+}
+
+impl IndexSelectScan {
+    pub(crate) fn new(ts: TableScan, idx: Box<dyn Index>, val: Constant) -> Result<IndexSelectScan, ScanError> {
+        let mut scan = IndexSelectScan { ts, idx, val };
+        scan.before_first()?;
+        Ok(scan)
+    }
+}
+
+impl Scan for IndexSelectScan {
+    fn before_first(&mut self) -> Result<(), ScanError> {
+        self.idx.before_first(&self.val)
+    }
+
+    fn next(&mut self) -> Result<bool, ScanError> {
+        if !self.idx.next()? {
+            return Ok(false);
+        }
+        let rid = self.idx.data_rid()?;
+        self.ts.move_to_rid(&rid)?;
+        Ok(true)
+    }
+
+    fn get_int(&mut self, field: &str) -> Result<i32, ScanError> {
+        self.ts.get_int(field)
+    }
+
+    fn get_string(&mut self, field: &str) -> Result<String, ScanError> {
+        self.ts.get_string(field)
+    }
+
+    fn get_val(&mut self, field: &str) -> Result<Constant, ScanError> {
+        self.ts.get_val(field)
+    }
+
+    fn has_field(&self, field: &str) -> bool {
+        self.ts.has_field(field)
+    }
+
+    fn close(&mut self) {
+        self.idx.close();
+        self.ts.close();
+    }
+}
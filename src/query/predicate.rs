@@ -0,0 +1,164 @@
+use std::fmt::Display;
+use crate::query::scan::Scan;
+use crate::query::term::Term;
+use crate::query::constant::Constant;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::Schema;
+
+// A Predicate is a conjunction of terms. The empty predicate places no
+// condition on the records, so it is what a statement without a WHERE
+// clause gets.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Predicate {
+    terms: Vec<Term>,
+}
+
+impl Predicate {
+    pub fn new() -> Predicate {
+        Predicate { terms: Vec::new() }
+    }
+
+    pub fn from_term(term: Term) -> Predicate {
+        Predicate { terms: vec![term] }
+    }
+
+    // Appends the terms of the other predicate to this one.
+    pub fn conjoin_with(&mut self, pred: Predicate) {
+        self.terms.extend(pred.terms);
+    }
+
+    pub fn terms(&self) -> &[Term] {
+        &self.terms
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    // Returns the constant the field is equated with, if any of the terms
+    // has the form `field = constant`.
+    pub fn equates_with_constant(&self, field: &str) -> Option<&Constant> {
+        self.terms.iter().find_map(|term| term.equates_with_constant(field))
+    }
+
+    // Returns the word the field is searched for, if any of the terms has
+    // the form `contains(field, word)`.
+    pub fn contains_word(&self, field: &str) -> Option<&Constant> {
+        self.terms.iter().find_map(|term| term.contains_word(field))
+    }
+
+    // Returns the field the specified field is equated with, if any of the
+    // terms has the form `field = other`.
+    pub fn equates_with_field(&self, field: &str) -> Option<&str> {
+        self.terms.iter().find_map(|term| term.equates_with_field(field))
+    }
+
+    // Returns true if every record that satisfies this predicate satisfies
+    // the other: each of its terms is implied by one of ours. Every
+    // predicate implies the empty one.
+    pub fn implies(&self, other: &Predicate) -> bool {
+        other.terms.iter().all(|term| self.terms.iter().any(|ours| ours.implies(term)))
+    }
+
+    // Returns the terms that apply to the schema, which a select over a scan
+    // with that schema can evaluate, or None if there are none.
+    pub fn select_sub_pred(&self, schema: &Schema) -> Option<Predicate> {
+        let terms: Vec<Term> = self.terms.iter().filter(|term| term.applies_to(schema)).cloned().collect();
+        if terms.is_empty() {
+            return None;
+        }
+        Some(Predicate { terms })
+    }
+
+    // Returns the terms that apply to the join of the two schemas but to
+    // neither of them alone, or None if there are none.
+    pub fn join_sub_pred(&self, schema1: &Schema, schema2: &Schema) -> Option<Predicate> {
+        let mut joined = schema1.clone();
+        joined.add_all(schema2);
+        let terms: Vec<Term> = self.terms.iter()
+            .filter(|term| !term.applies_to(schema1) && !term.applies_to(schema2) && term.applies_to(&joined))
+            .cloned()
+            .collect();
+        if terms.is_empty() {
+            return None;
+        }
+        Some(Predicate { terms })
+    }
+
+    // Estimates by what factor the predicate reduces the number of records
+    // output by a query: the product of the factors of its terms. See
+    // `Term::reduction_factor` for the distinct values hook.
+    pub fn reduction_factor(&self, distinct_values: &dyn Fn(&str) -> usize) -> usize {
+        self.terms.iter().fold(1, |factor, term| factor.saturating_mul(term.reduction_factor(distinct_values)))
+    }
+
+    // Returns true if every term is satisfied by the current record of the
+    // scan. The empty predicate is satisfied by every record.
+    pub fn is_satisfied(&self, scan: &mut dyn Scan) -> Result<bool, ScanError> {
+        for term in &self.terms {
+            if !term.is_satisfied(scan)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl Display for Predicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let terms: Vec<String> = self.terms.iter().map(|term| term.to_string()).collect();
+        write!(f, "{}", terms.join(" and "))
+    }
+}
+
+mod tests {
+    use super::*;
+    use crate::parse::parser::Parser;
+
+    #[test]
+    fn test_sub_predicates() {
+        let pred = Parser::new("a = 1 and b = c and 'x' = d and c = 2").unwrap().predicate().unwrap();
+        assert_eq!(pred.equates_with_constant("a"), Some(&Constant::Int(1)));
+        assert_eq!(pred.equates_with_constant("d"), Some(&Constant::String("x".to_string())));
+        assert_eq!(pred.equates_with_constant("b"), None);
+        assert_eq!(pred.equates_with_field("c"), Some("b"));
+        assert_eq!(pred.equates_with_field("a"), None);
+
+        let mut schema1 = Schema::new();
+        schema1.add_int_field("a");
+        schema1.add_int_field("b");
+        let mut schema2 = Schema::new();
+        schema2.add_int_field("c");
+        schema2.add_string_field("d", 5);
+        assert_eq!(pred.select_sub_pred(&schema1).unwrap().to_string(), "a=1");
+        assert_eq!(pred.select_sub_pred(&schema2).unwrap().to_string(), "'x'=d and c=2");
+        assert_eq!(pred.join_sub_pred(&schema1, &schema2).unwrap().to_string(), "b=c");
+        assert!(pred.join_sub_pred(&schema1, &Schema::new()).is_none());
+    }
+
+    #[test]
+    fn test_implies() {
+        let pred = |sql: &str| Parser::new(sql).unwrap().predicate().unwrap();
+        let query = pred("a = 1 and deleted = 0 and b = c");
+        assert!(query.implies(&pred("deleted = 0")));
+        assert!(query.implies(&pred("0 = deleted and c = b")));
+        assert!(query.implies(&pred("a is not null")));
+        assert!(query.implies(&Predicate::new()));
+        assert!(!query.implies(&pred("deleted = 1")));
+        assert!(!query.implies(&pred("deleted = 0 and d = 2")));
+        assert!(!query.implies(&pred("a is null")));
+        assert!(!Predicate::new().implies(&pred("deleted = 0")));
+    }
+
+    #[test]
+    fn test_reduction_factor() {
+        let distinct_values = |field: &str| if field == "a" { 10 } else { 4 };
+        let pred = Parser::new("a = 1 and a = b").unwrap().predicate().unwrap();
+        assert_eq!(pred.reduction_factor(&distinct_values), 100);
+        assert_eq!(Predicate::new().reduction_factor(&distinct_values), 1);
+        let pred = Parser::new("1 = 1").unwrap().predicate().unwrap();
+        assert_eq!(pred.reduction_factor(&distinct_values), 1);
+        let pred = Parser::new("1 = 2").unwrap().predicate().unwrap();
+        assert_eq!(pred.reduction_factor(&distinct_values), usize::MAX);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::query::constant::Constant;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+
+// The ProductScan implements the product operator: it outputs every
+// combination of a record from the first scan with a record from the
+// second. The second scan is iterated in full for each record of the
+// first, which is positioned on its first record up front.
+pub struct ProductScan<S1: Scan, S2: Scan> {
+    s1: S1,
+    s2: S2,
+}
+
+impl<S1: Scan, S2: Scan> ProductScan<S1, S2> {
+    pub fn new(s1: S1, s2: S2) -> Result<ProductScan<S1, S2>, ScanError> {
+        let mut scan = ProductScan { s1, s2 };
+        scan.before_first()?;
+        Ok(scan)
+    }
+}
+
+impl<S1: Scan, S2: Scan> Scan for ProductScan<S1, S2> {
+    fn before_first(&mut self) -> Result<(), ScanError> {
+        self.s1.before_first()?;
+        self.s1.next()?;
+        self.s2.before_first()
+    }
+
+    fn next(&mut self) -> Result<bool, ScanError> {
+        if self.s2.next()? {
+            return Ok(true);
+        }
+        self.s2.before_first()?;
+        Ok(self.s2.next()? && self.s1.next()?)
+    }
+
+    fn get_int(&mut self, field: &str) -> Result<i32, ScanError> {
+        if self.s1.has_field(field) {
+            self.s1.get_int(field)
+        } else {
+            self.s2.get_int(field)
+        }
+    }
+
+    fn get_string(&mut self, field: &str) -> Result<String, ScanError> {
+        if self.s1.has_field(field) {
+            self.s1.get_string(field)
+        } else {
+            self.s2.get_string(field)
+        }
+    }
+
+    fn get_val(&mut self, field: &str) -> Result<Constant, ScanError> {
+        if self.s1.has_field(field) {
+            self.s1.get_val(field)
+        } else {
+            self.s2.get_val(field)
+        }
+    }
+
+    fn has_field(&self, field: &str) -> bool {
+        self.s1.has_field(field) || self.s2.has_field(field)
+    }
+
+    fn close(&mut self) {
+        self.s1.close();
+        self.s2.close();
+    }
+}
+
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+    use super::*;
+    use crate::buffer::buffermgr::BufferMgr;
+    use crate::file::filemgr::FileMgr;
+    use crate::log::logmgr::LogMgr;
+    use crate::tx::admission::AdmissionCtl;
+    use crate::record::layout::{Layout, Schema};
+    use crate::record::tablescan::{TableScan, UpdateScan};
+    use crate::tx::transaction::Transaction;
+
+    #[test]
+    fn test_product_scan() {
+        let fm = Arc::new(FileMgr::new(PathBuf::from("productscantestdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
+        let bm = Arc::new(Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 8)));
+        let tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap()));
+
+        let mut schema1 = Schema::new();
+        schema1.add_int_field("A");
+        let layout1 = Arc::new(Layout::new(schema1));
+        let mut schema2 = Schema::new();
+        schema2.add_int_field("C");
+        let layout2 = Arc::new(Layout::new(schema2));
+
+        let mut ts1 = TableScan::new(tx.clone(), layout1.clone(), "T1").unwrap();
+        let mut ts2 = TableScan::new(tx.clone(), layout2.clone(), "T2").unwrap();
+        for i in 0..3 {
+            ts1.insert().unwrap();
+            ts1.set_int("A", i).unwrap();
+        }
+        for i in 0..4 {
+            ts2.insert().unwrap();
+            ts2.set_int("C", 10 + i).unwrap();
+        }
+        ts1.close();
+        ts2.close();
+
+        let s1: Box<dyn Scan> = Box::new(TableScan::new(tx.clone(), layout1, "T1").unwrap());
+        let s2: Box<dyn Scan> = Box::new(TableScan::new(tx.clone(), layout2, "T2").unwrap());
+        let mut product = ProductScan::new(s1, s2).unwrap();
+        let mut pairs = Vec::new();
+        while product.next().unwrap() {
+            pairs.push((product.get_int("A").unwrap(), product.get_val("C").unwrap()));
+        }
+        product.close();
+
+        assert_eq!(pairs.len(), 12);
+        assert_eq!(pairs[0], (0, Constant::Int(10)));
+        assert_eq!(pairs[11], (2, Constant::Int(13)));
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
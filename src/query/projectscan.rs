@@ -0,0 +1,57 @@
+use crate::query::constant::Constant;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+
+// The ProjectScan implements the project operator: it outputs the records
+// of the underlying scan restricted to the specified fields. Asking for any
+// other field is an error, even if the underlying scan has it.
+pub struct ProjectScan<S: Scan> {
+    scan: S,
+    fields: Vec<String>,
+}
+
+impl<S: Scan> ProjectScan<S> {
+    pub fn new(scan: S, fields: Vec<String>) -> ProjectScan<S> {
+        ProjectScan { scan, fields }
+    }
+
+    fn check_field(&self, field: &str) -> Result<(), ScanError> {
+        if !self.has_field(field) {
+            return Err(ScanError::UnknownField(field.to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl<S: Scan> Scan for ProjectScan<S> {
+    fn before_first(&mut self) -> Result<(), ScanError> {
+        self.scan.before_first()
+    }
+
+    fn next(&mut self) -> Result<bool, ScanError> {
+        self.scan.next()
+    }
+
+    fn get_int(&mut self, field: &str) -> Result<i32, ScanError> {
+        self.check_field(field)?;
+        self.scan.get_int(field)
+    }
+
+    fn get_string(&mut self, field: &str) -> Result<String, ScanError> {
+        self.check_field(field)?;
+        self.scan.get_string(field)
+    }
+
+    fn get_val(&mut self, field: &str) -> Result<Constant, ScanError> {
+        self.check_field(field)?;
+        self.scan.get_val(field)
+    }
+
+    fn has_field(&self, field: &str) -> bool {
+        self.fields.iter().any(|f| f == field)
+    }
+
+    fn close(&mut self) {
+        self.scan.close()
+    }
+}
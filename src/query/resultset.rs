@@ -0,0 +1,129 @@
+use crate::query::constant::Constant;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::FieldType;
+
+// A ResultSet is how a client reads the output of a query: a scan with a
+// typed getter for each field type that, as in JDBC, reads a null as the
+// type's zero value and lets the client tell it apart with `was_null`
+// afterwards. Reading a field as the wrong type is a TypeMismatch error.
+pub struct ResultSet {
+    scan: Box<dyn Scan>,
+    was_null: bool,
+}
+
+impl ResultSet {
+    pub fn new(scan: Box<dyn Scan>) -> ResultSet {
+        ResultSet { scan, was_null: false }
+    }
+
+    pub fn before_first(&mut self) -> Result<(), ScanError> {
+        self.scan.before_first()
+    }
+
+    pub fn next(&mut self) -> Result<bool, ScanError> {
+        self.scan.next()
+    }
+
+    // Returns true if the value the last getter read was null.
+    pub fn was_null(&self) -> bool {
+        self.was_null
+    }
+
+    // The value of the field, or None if it is null. Every other getter
+    // reads through this one.
+    pub fn get_val(&mut self, field: &str) -> Result<Option<Constant>, ScanError> {
+        let val = self.scan.get_val(field)?;
+        self.was_null = val.is_null();
+        Ok(Some(val).filter(|val| !val.is_null()))
+    }
+
+    pub fn get_int(&mut self, field: &str) -> Result<i32, ScanError> {
+        self.get(field, FieldType::INTEGER, Constant::as_int)
+    }
+
+    pub fn get_long(&mut self, field: &str) -> Result<i64, ScanError> {
+        self.get(field, FieldType::BIGINT, Constant::as_long)
+    }
+
+    pub fn get_double(&mut self, field: &str) -> Result<f64, ScanError> {
+        self.get(field, FieldType::DOUBLE, Constant::as_double)
+    }
+
+    pub fn get_bool(&mut self, field: &str) -> Result<bool, ScanError> {
+        self.get(field, FieldType::BOOLEAN, Constant::as_bool)
+    }
+
+    // Returns the date as seconds since the epoch; see `query::date`.
+    pub fn get_date(&mut self, field: &str) -> Result<i64, ScanError> {
+        self.get(field, FieldType::DATE, Constant::as_date)
+    }
+
+    pub fn get_string(&mut self, field: &str) -> Result<String, ScanError> {
+        self.get(field, FieldType::VARCHAR, |val| val.as_string().map(str::to_string))
+    }
+
+    pub fn get_blob(&mut self, field: &str) -> Result<Vec<u8>, ScanError> {
+        self.get(field, FieldType::BLOB, |val| val.as_blob().map(<[u8]>::to_vec))
+    }
+
+    pub fn close(&mut self) {
+        self.scan.close();
+    }
+
+    fn get<T: Default>(&mut self, field: &str, expected: FieldType, convert: impl Fn(&Constant) -> Option<T>) -> Result<T, ScanError> {
+        match self.get_val(field)? {
+            None => Ok(T::default()),
+            Some(val) => convert(&val).ok_or_else(|| ScanError::TypeMismatch { field: field.to_string(), expected }),
+        }
+    }
+}
+
+mod tests {
+    use super::*;
+    use crate::parse::parser::Parser;
+    use crate::plan::basicqueryplanner::BasicQueryPlanner;
+    use crate::plan::basicupdateplanner::BasicUpdatePlanner;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_result_set() {
+        let db = SimpleDB::ephemeral().unwrap();
+        let tx = db.new_tx().unwrap();
+        let mdm = db.metadata_mgr();
+        let updater = BasicUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str| updater.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone()).unwrap();
+        update("create table t (id int, big bigint, d double, flag boolean, day date, name varchar(9), photo blob)");
+        update("insert into t (id, big, d, flag, day, name, photo) values (1, 12345678901, 2.5, true, date '2024-02-29', 'one', x'cafe')");
+        update("insert into t (id) values (2)");
+
+        let data = Parser::new("select id, big, d, flag, day, name, photo from t").unwrap().query().unwrap();
+        let plan = BasicQueryPlanner::new(mdm.clone()).create_plan(&data, tx.clone()).unwrap();
+        let mut rs = ResultSet::new(plan.open().unwrap());
+        assert!(rs.next().unwrap());
+        assert_eq!(rs.get_int("id").unwrap(), 1);
+        assert_eq!(rs.get_long("big").unwrap(), 12345678901);
+        assert_eq!(rs.get_double("d").unwrap(), 2.5);
+        assert!(rs.get_bool("flag").unwrap());
+        assert_eq!(rs.get_date("day").unwrap(), 1709164800);
+        assert_eq!(rs.get_string("name").unwrap(), "one");
+        assert_eq!(rs.get_blob("photo").unwrap(), [0xca, 0xfe]);
+        assert!(!rs.was_null());
+        assert_eq!(rs.get_long("id"), Err(ScanError::TypeMismatch { field: "id".to_string(), expected: FieldType::BIGINT }));
+        assert_eq!(rs.get_string("flag").unwrap_err().to_string(), "field flag is not of type VARCHAR");
+        assert!(matches!(rs.get_int("missing"), Err(ScanError::UnknownField(_))));
+
+        // A null reads as the zero value, and was_null tells it apart.
+        assert!(rs.next().unwrap());
+        assert_eq!(rs.get_long("big").unwrap(), 0);
+        assert!(rs.was_null());
+        assert_eq!(rs.get_int("id").unwrap(), 2);
+        assert!(!rs.was_null());
+        assert_eq!(rs.get_string("name").unwrap(), "");
+        assert!(rs.was_null());
+        assert_eq!(rs.get_val("d").unwrap(), None);
+        assert!(!rs.next().unwrap());
+        rs.close();
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
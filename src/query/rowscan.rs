@@ -0,0 +1,66 @@
+use crate::query::constant::Constant;
+use crate::query::scan::Scan;
+use crate::record::row::Row;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::{FieldType, Schema};
+
+// The RowScan outputs rows held in memory rather than records read from a
+// table. It backs the system tables, whose contents are produced by the
+// engine when the scan is opened.
+pub struct RowScan {
+    schema: Schema,
+    rows: Vec<Row>,
+    current: Option<usize>,
+}
+
+impl RowScan {
+    pub fn new(schema: Schema, rows: Vec<Row>) -> RowScan {
+        RowScan { schema, rows, current: None }
+    }
+
+    fn current_row(&self) -> Result<&Row, ScanError> {
+        self.current.and_then(|idx| self.rows.get(idx)).ok_or(ScanError::NoCurrentRecord)
+    }
+}
+
+impl Scan for RowScan {
+    fn before_first(&mut self) -> Result<(), ScanError> {
+        self.current = None;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<bool, ScanError> {
+        let next = self.current.map_or(0, |idx| idx + 1).min(self.rows.len());
+        self.current = Some(next);
+        Ok(next < self.rows.len())
+    }
+
+    fn get_int(&mut self, field: &str) -> Result<i32, ScanError> {
+        match self.get_val(field)? {
+            Constant::Int(val) => Ok(val),
+            Constant::Null => Err(ScanError::NullValue(field.to_string())),
+            _ => Err(ScanError::TypeMismatch { field: field.to_string(), expected: FieldType::INTEGER }),
+        }
+    }
+
+    fn get_string(&mut self, field: &str) -> Result<String, ScanError> {
+        match self.get_val(field)? {
+            Constant::String(val) => Ok(val),
+            Constant::Null => Err(ScanError::NullValue(field.to_string())),
+            _ => Err(ScanError::TypeMismatch { field: field.to_string(), expected: FieldType::VARCHAR }),
+        }
+    }
+
+    fn get_val(&mut self, field: &str) -> Result<Constant, ScanError> {
+        if !self.has_field(field) {
+            return Err(ScanError::UnknownField(field.to_string()));
+        }
+        self.current_row()?.get(field).cloned().ok_or(ScanError::UnknownField(field.to_string()))
+    }
+
+    fn has_field(&self, field: &str) -> bool {
+        self.schema.has_field(field)
+    }
+
+    fn close(&mut self) {}
+}
@@ -0,0 +1,116 @@
+use crate::query::constant::Constant;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::FieldType;
+
+// A Scan iterates over the output records of a relational algebra
+// operator. Table scans read records from a table; the other scans wrap
+// one or two underlying scans and transform their output, so that scans
+// can be composed into a pipeline that evaluates a query.
+pub trait Scan {
+    // Positions the scan before its first record.
+    fn before_first(&mut self) -> Result<(), ScanError>;
+
+    // Moves to the next record, returning false when there are no more.
+    fn next(&mut self) -> Result<bool, ScanError>;
+
+    fn get_int(&mut self, field: &str) -> Result<i32, ScanError>;
+
+    fn get_string(&mut self, field: &str) -> Result<String, ScanError>;
+
+    fn get_val(&mut self, field: &str) -> Result<Constant, ScanError>;
+
+    // The getters of the other types read the value through `get_val`.
+    fn get_long(&mut self, field: &str) -> Result<i64, ScanError> {
+        let val = self.get_val(field)?;
+        val.as_long().ok_or_else(|| unexpected(field, &val, FieldType::BIGINT))
+    }
+
+    fn get_double(&mut self, field: &str) -> Result<f64, ScanError> {
+        let val = self.get_val(field)?;
+        val.as_double().ok_or_else(|| unexpected(field, &val, FieldType::DOUBLE))
+    }
+
+    fn get_bool(&mut self, field: &str) -> Result<bool, ScanError> {
+        let val = self.get_val(field)?;
+        val.as_bool().ok_or_else(|| unexpected(field, &val, FieldType::BOOLEAN))
+    }
+
+    // Returns the date as seconds since the epoch; see `query::date`.
+    fn get_date(&mut self, field: &str) -> Result<i64, ScanError> {
+        let val = self.get_val(field)?;
+        val.as_date().ok_or_else(|| unexpected(field, &val, FieldType::DATE))
+    }
+
+    fn get_blob(&mut self, field: &str) -> Result<Vec<u8>, ScanError> {
+        let val = self.get_val(field)?;
+        val.as_blob().map(<[u8]>::to_vec).ok_or_else(|| unexpected(field, &val, FieldType::BLOB))
+    }
+
+    fn has_field(&self, field: &str) -> bool;
+
+    fn is_null(&mut self, field: &str) -> Result<bool, ScanError> {
+        Ok(self.get_val(field)?.is_null())
+    }
+
+    // `get_int` and `get_string` fail on a null field; these return None
+    // instead.
+    fn get_int_opt(&mut self, field: &str) -> Result<Option<i32>, ScanError> {
+        if self.is_null(field)? {
+            return Ok(None);
+        }
+        self.get_int(field).map(Some)
+    }
+
+    fn get_string_opt(&mut self, field: &str) -> Result<Option<String>, ScanError> {
+        if self.is_null(field)? {
+            return Ok(None);
+        }
+        self.get_string(field).map(Some)
+    }
+
+    // Releases the resources held by the scan and its underlying scans.
+    fn close(&mut self);
+}
+
+// The error for a value read as the wrong type.
+fn unexpected(field: &str, val: &Constant, expected: FieldType) -> ScanError {
+    match val {
+        Constant::Null => ScanError::NullValue(field.to_string()),
+        _ => ScanError::TypeMismatch { field: field.to_string(), expected },
+    }
+}
+
+// Scans are often built at runtime, so a boxed scan is a scan too.
+impl<S: Scan + ?Sized> Scan for Box<S> {
+    fn before_first(&mut self) -> Result<(), ScanError> {
+        (**self).before_first()
+    }
+
+    fn next(&mut self) -> Result<bool, ScanError> {
+        (**self).next()
+    }
+
+    fn get_int(&mut self, field: &str) -> Result<i32, ScanError> {
+        (**self).get_int(field)
+    }
+
+    fn get_string(&mut self, field: &str) -> Result<String, ScanError> {
+        (**self).get_string(field)
+    }
+
+    fn get_val(&mut self, field: &str) -> Result<Constant, ScanError> {
+        (**self).get_val(field)
+    }
+
+    fn has_field(&self, field: &str) -> bool {
+        (**self).has_field(field)
+    }
+
+    fn is_null(&mut self, field: &str) -> Result<bool, ScanError> {
+        (**self).is_null(field)
+    }
+
+    fn close(&mut self) {
+        (**self).close()
+    }
+}
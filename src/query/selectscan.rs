@@ -0,0 +1,151 @@
+use crate::query::constant::Constant;
+use crate::query::predicate::Predicate;
+use crate::query::scan::Scan;
+use crate::record::row::Row;
+use crate::record::scanerror::ScanError;
+use crate::record::tablescan::{RecordId, UpdateScan};
+
+// The SelectScan implements the select operator: it outputs the records of
+// the underlying scan that satisfy the predicate. Since it outputs records
+// unchanged, it is updatable whenever the underlying scan is.
+pub struct SelectScan<S: Scan> {
+    scan: S,
+    pred: Predicate,
+}
+
+impl<S: Scan> SelectScan<S> {
+    pub fn new(scan: S, pred: Predicate) -> SelectScan<S> {
+        SelectScan { scan, pred }
+    }
+}
+
+impl<S: Scan> Scan for SelectScan<S> {
+    fn before_first(&mut self) -> Result<(), ScanError> {
+        self.scan.before_first()
+    }
+
+    fn next(&mut self) -> Result<bool, ScanError> {
+        while self.scan.next()? {
+            if self.pred.is_satisfied(&mut self.scan)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn get_int(&mut self, field: &str) -> Result<i32, ScanError> {
+        self.scan.get_int(field)
+    }
+
+    fn get_string(&mut self, field: &str) -> Result<String, ScanError> {
+        self.scan.get_string(field)
+    }
+
+    fn get_val(&mut self, field: &str) -> Result<Constant, ScanError> {
+        self.scan.get_val(field)
+    }
+
+    fn has_field(&self, field: &str) -> bool {
+        self.scan.has_field(field)
+    }
+
+    fn close(&mut self) {
+        self.scan.close()
+    }
+}
+
+impl<S: UpdateScan> UpdateScan for SelectScan<S> {
+    fn set_int(&mut self, field: &str, val: i32) -> Result<(), ScanError> {
+        self.scan.set_int(field, val)
+    }
+
+    fn set_string(&mut self, field: &str, val: &str) -> Result<(), ScanError> {
+        self.scan.set_string(field, val)
+    }
+
+    fn set_val(&mut self, field: &str, val: &Constant) -> Result<(), ScanError> {
+        self.scan.set_val(field, val)
+    }
+
+    fn set_null(&mut self, field: &str) -> Result<(), ScanError> {
+        self.scan.set_null(field)
+    }
+
+    fn set_row(&mut self, row: &Row) -> Result<(), ScanError> {
+        self.scan.set_row(row)
+    }
+
+    fn insert(&mut self) -> Result<(), ScanError> {
+        self.scan.insert()
+    }
+
+    fn delete(&mut self) -> Result<(), ScanError> {
+        self.scan.delete()
+    }
+
+    fn rid(&self) -> Option<RecordId> {
+        self.scan.rid()
+    }
+
+    fn move_to_rid(&mut self, rid: &RecordId) -> Result<(), ScanError> {
+        self.scan.move_to_rid(rid)
+    }
+}
+
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+    use super::*;
+    use crate::buffer::buffermgr::BufferMgr;
+    use crate::file::filemgr::FileMgr;
+    use crate::log::logmgr::LogMgr;
+    use crate::tx::admission::AdmissionCtl;
+    use crate::query::expression::Expression;
+    use crate::query::projectscan::ProjectScan;
+    use crate::query::term::Term;
+    use crate::record::layout::{Layout, Schema};
+    use crate::record::tablescan::TableScan;
+    use crate::tx::transaction::Transaction;
+
+    #[test]
+    fn test_select_and_project() {
+        let fm = Arc::new(FileMgr::new(PathBuf::from("selectscantestdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
+        let bm = Arc::new(Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 8)));
+        let tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap()));
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 9);
+        let layout = Arc::new(Layout::new(schema));
+
+        let mut ts = TableScan::new(tx.clone(), layout.clone(), "T").unwrap();
+        for i in 0..20 {
+            ts.insert().unwrap();
+            ts.set_int("A", i % 5).unwrap();
+            ts.set_string("B", &format!("rec{}", i)).unwrap();
+        }
+        ts.close();
+
+        // Updating through the select changes only the matching records.
+        let pred = Predicate::from_term(Term::new(Expression::Field("A".to_string()), Expression::Constant(Constant::Int(3))));
+        let mut us = SelectScan::new(TableScan::new(tx.clone(), layout.clone(), "T").unwrap(), pred.clone());
+        while us.next().unwrap() {
+            us.set_string("B", "three").unwrap();
+        }
+        us.close();
+
+        let select = SelectScan::new(TableScan::new(tx.clone(), layout, "T").unwrap(), pred);
+        let mut project = ProjectScan::new(select, vec!["B".to_string()]);
+        let mut count = 0;
+        while project.next().unwrap() {
+            assert_eq!(project.get_string("B").unwrap(), "three");
+            assert_eq!(project.get_int("A"), Err(ScanError::UnknownField("A".to_string())));
+            count += 1;
+        }
+        assert_eq!(count, 4);
+        project.close();
+
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
@@ -0,0 +1,160 @@
+use std::cmp::Ordering;
+use std::fmt::Display;
+use crate::index::index::IndexKind;
+use crate::query::expression::Expression;
+use crate::query::scan::Scan;
+use crate::query::constant::Constant;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::Schema;
+
+// What a term says about its expressions: that they are equal, that the
+// left-hand one is or is not null, in which case the right-hand one is the
+// null constant, or that the left-hand field holds text containing the
+// right-hand word.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TermOp {
+    Equals,
+    IsNull,
+    IsNotNull,
+    Contains,
+}
+
+// A Term compares two expressions for equality, such as `a = 3` or `a = b`,
+// tests an expression for null, such as `a is null`, or searches a text
+// field for a word, such as `contains(a, 'disk')`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    lhs: Expression,
+    rhs: Expression,
+    op: TermOp,
+}
+
+impl Term {
+    pub fn new(lhs: Expression, rhs: Expression) -> Term {
+        Term { lhs, rhs, op: TermOp::Equals }
+    }
+
+    pub fn null_test(lhs: Expression, is_null: bool) -> Term {
+        let op = if is_null { TermOp::IsNull } else { TermOp::IsNotNull };
+        Term { lhs, rhs: Expression::Constant(Constant::Null), op }
+    }
+
+    // The word is in lower case, as a text index keys its words.
+    pub fn contains(field: String, word: String) -> Term {
+        Term { lhs: Expression::Field(field), rhs: Expression::Constant(Constant::String(word)), op: TermOp::Contains }
+    }
+
+    pub fn op(&self) -> TermOp {
+        self.op
+    }
+
+    pub fn lhs(&self) -> &Expression {
+        &self.lhs
+    }
+
+    pub fn rhs(&self) -> &Expression {
+        &self.rhs
+    }
+
+    // If the term has the form `field = constant` (in either order), returns
+    // the constant.
+    pub fn equates_with_constant(&self, field: &str) -> Option<&Constant> {
+        if self.op != TermOp::Equals {
+            return None;
+        }
+        match (&self.lhs, &self.rhs) {
+            (Expression::Field(f), Expression::Constant(val)) if f == field => Some(val),
+            (Expression::Constant(val), Expression::Field(f)) if f == field => Some(val),
+            _ => None,
+        }
+    }
+
+    // If the term has the form `field = other` (in either order) for another
+    // field, returns the other field.
+    pub fn equates_with_field(&self, field: &str) -> Option<&str> {
+        if self.op != TermOp::Equals {
+            return None;
+        }
+        match (&self.lhs, &self.rhs) {
+            (Expression::Field(f), Expression::Field(other)) if f == field => Some(other),
+            (Expression::Field(other), Expression::Field(f)) if f == field => Some(other),
+            _ => None,
+        }
+    }
+
+    // If the term has the form `contains(field, word)`, returns the word.
+    pub fn contains_word(&self, field: &str) -> Option<&Constant> {
+        match (&self.lhs, &self.rhs) {
+            (Expression::Field(f), Expression::Constant(word)) if self.op == TermOp::Contains && f == field => Some(word),
+            _ => None,
+        }
+    }
+
+    pub fn applies_to(&self, schema: &Schema) -> bool {
+        self.lhs.applies_to(schema) && self.rhs.applies_to(schema)
+    }
+
+    // Returns true if every record that satisfies this term satisfies the
+    // other: they are the same term, possibly with the sides of an equality
+    // swapped, or the other tests for not null an expression this one
+    // equates with something, since an equality never holds for a null.
+    pub fn implies(&self, other: &Term) -> bool {
+        if self == other {
+            return true;
+        }
+        match (self.op, other.op) {
+            (TermOp::Equals, TermOp::Equals) => self.lhs == other.rhs && self.rhs == other.lhs,
+            (TermOp::Equals, TermOp::IsNotNull) => self.lhs == other.lhs || self.rhs == other.lhs,
+            _ => false,
+        }
+    }
+
+    // Estimates by what factor the term reduces the number of records output
+    // by a query. The caller supplies the number of distinct values of a field
+    // in its input, typically taken from the statistics of a plan. Comparing
+    // two fields keeps one record in max(distinct values) of them; comparing
+    // a field with a constant keeps one in the field's distinct values, and
+    // so does testing it for null or searching it for a word. Testing for not
+    // null is assumed to keep every record.
+    pub fn reduction_factor(&self, distinct_values: &dyn Fn(&str) -> usize) -> usize {
+        if self.op == TermOp::IsNotNull {
+            return 1;
+        }
+        match (&self.lhs, &self.rhs) {
+            (Expression::Field(lhs), Expression::Field(rhs)) => distinct_values(lhs).max(distinct_values(rhs)),
+            (Expression::Field(field), _) | (_, Expression::Field(field)) => distinct_values(field),
+            (Expression::Constant(lhs), Expression::Constant(rhs)) if lhs == rhs => 1,
+            _ => usize::MAX,
+        }
+    }
+
+    // Returns true if both expressions evaluate to the same value for the
+    // current record of the scan. As in SQL, comparing with a null is never
+    // true, not even comparing two nulls; only a null test can match them.
+    pub fn is_satisfied(&self, scan: &mut dyn Scan) -> Result<bool, ScanError> {
+        let lhs = self.lhs.evaluate(scan)?;
+        match self.op {
+            TermOp::IsNull => Ok(lhs.is_null()),
+            TermOp::IsNotNull => Ok(!lhs.is_null()),
+            TermOp::Equals => {
+                let rhs = self.rhs.evaluate(scan)?;
+                Ok(!lhs.is_null() && lhs.compare(&rhs) == Ordering::Equal)
+            }
+            TermOp::Contains => match (lhs, self.rhs.evaluate(scan)?) {
+                (Constant::String(text), Constant::String(word)) => Ok(IndexKind::words(&text).contains(&word)),
+                _ => Ok(false),
+            },
+        }
+    }
+}
+
+impl Display for Term {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.op {
+            TermOp::Equals => write!(f, "{}={}", self.lhs, self.rhs),
+            TermOp::IsNull => write!(f, "{} is null", self.lhs),
+            TermOp::IsNotNull => write!(f, "{} is not null", self.lhs),
+            TermOp::Contains => write!(f, "contains({}, {})", self.lhs, self.rhs),
+        }
+    }
+}
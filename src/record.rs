@@ -1,4 +1,9 @@
-mod schema;
+pub(crate) mod schema;
+pub(crate) mod check;
 pub(crate) mod layout;
-mod recordpage;
-pub(crate) mod tablescan;
\ No newline at end of file
+pub(crate) mod recordpage;
+mod slottedpage;
+pub(crate) mod overflow;
+pub(crate) mod tablescan;
+pub(crate) mod scanerror;
+pub(crate) mod row;
\ No newline at end of file
@@ -0,0 +1,111 @@
+use std::cmp::Ordering;
+use std::fmt::Display;
+use crate::query::constant::Constant;
+
+// The comparison a CHECK constraint makes between the value of a field and
+// a constant. The codes are how the operators are stored in the catalog.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq = 1,
+    Ne = 2,
+    Lt = 3,
+    Le = 4,
+    Gt = 5,
+    Ge = 6,
+}
+
+impl CompareOp {
+    pub fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            CompareOp::Eq => ordering == Ordering::Equal,
+            CompareOp::Ne => ordering != Ordering::Equal,
+            CompareOp::Lt => ordering == Ordering::Less,
+            CompareOp::Le => ordering != Ordering::Greater,
+            CompareOp::Gt => ordering == Ordering::Greater,
+            CompareOp::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+impl TryFrom<i32> for CompareOp {
+    type Error = i32;
+
+    fn try_from(code: i32) -> Result<CompareOp, i32> {
+        match code {
+            1 => Ok(CompareOp::Eq),
+            2 => Ok(CompareOp::Ne),
+            3 => Ok(CompareOp::Lt),
+            4 => Ok(CompareOp::Le),
+            5 => Ok(CompareOp::Gt),
+            6 => Ok(CompareOp::Ge),
+            _ => Err(code),
+        }
+    }
+}
+
+impl Display for CompareOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let op = match self {
+            CompareOp::Eq => "=",
+            CompareOp::Ne => "<>",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+// A CHECK constraint on a field: every value stored in the field must
+// compare with the constant as the operator says, such as `> 0`. The
+// constant has the type of the field. As in SQL, a null passes every check;
+// keeping nulls out is up to NOT NULL.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    op: CompareOp,
+    val: Constant,
+}
+
+impl Check {
+    pub fn new(op: CompareOp, val: Constant) -> Check {
+        Check { op, val }
+    }
+
+    pub fn op(&self) -> CompareOp {
+        self.op
+    }
+
+    pub fn val(&self) -> &Constant {
+        &self.val
+    }
+
+    pub fn is_satisfied(&self, val: &Constant) -> bool {
+        val.is_null() || self.op.holds(val.compare(&self.val))
+    }
+}
+
+impl Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {}", self.op, self.val)
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let positive = Check::new(CompareOp::Gt, Constant::Int(0));
+        assert!(positive.is_satisfied(&Constant::Int(1)));
+        assert!(!positive.is_satisfied(&Constant::Int(0)));
+        assert!(positive.is_satisfied(&Constant::Null));
+        let not_empty = Check::new(CompareOp::Ne, Constant::String(String::new()));
+        assert!(not_empty.is_satisfied(&Constant::String("a".to_string())));
+        assert_eq!(not_empty.to_string(), "<> ''");
+        for code in 1..=6 {
+            assert_eq!(CompareOp::try_from(code).map(|op| op as i32), Ok(code));
+        }
+        assert_eq!(CompareOp::try_from(7), Err(7));
+    }
+}
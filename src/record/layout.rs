@@ -1,52 +1,188 @@
 use crate::file::page::Page;
-pub(crate) use crate::record::schema::Schema;
-use std::collections::HashMap;
+use crate::record::recordpage::RecordPage;
+pub(crate) use crate::record::schema::{FieldType, Schema};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+// How the records of a table are laid out in its pages: in slots of the
+// fixed size of the layout, or packed one after the other at their own
+// length, see `SlottedPage`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordFormat {
+    Fixed = 0,
+    Variable = 1,
+}
+
+impl TryFrom<i32> for RecordFormat {
+    type Error = i32;
+
+    fn try_from(format: i32) -> Result<RecordFormat, i32> {
+        match format {
+            0 => Ok(RecordFormat::Fixed),
+            1 => Ok(RecordFormat::Variable),
+            _ => Err(format),
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Layout {
-    schema: Schema,
-    offsets: HashMap<String, i32>,
+    schema: Arc<Schema>,
+    offsets: HashMap<Arc<str>, i32>,
+    null_bits: HashMap<Arc<str>, i32>,
+    spilled: HashSet<Arc<str>>,
     slot_size: i32,
+    fill_factor: i32,
+    format: RecordFormat,
 }
 
 // The Layout struct holds additional physical information about the record. It computes
 // the field and slot sizes, and the field offsets within a slot. When a table is created
 // this constructor is called to create to compute the layout information of the schema.
+// Layouts are immutable once built, so scans and record pages share them through an Arc.
+// Each slot starts with the flag, followed by a null bitmap with one bit per field, in
+// 4-byte integers, and then the fields themselves. A VARCHAR field that is
+// spilled, see `fit`, only holds the 8-byte reference to its value in the
+// table's overflow blocks, like a blob.
 impl Layout {
+    pub const DEFAULT_FILL_FACTOR: i32 = 100;
+    pub(crate) const NULL_BITMAP_OFFSET: i32 = 4;
+
     pub fn new(schema: Schema) -> Layout {
+        Self::spilling(schema, HashSet::new())
+    }
+
+    fn spilling(schema: Schema, spilled: HashSet<Arc<str>>) -> Layout {
         let mut offsets = HashMap::new();
-        let mut pos = 4; // 4 bytes for the flag.
+        let mut pos = Self::NULL_BITMAP_OFFSET + Self::null_bitmap_size(schema.fields().len());
         for field in schema.fields() {
             offsets.insert(field.clone(), pos);
-            let length_in_bytes = match schema.ftype(&field) {
-                4 => 4,
-                12 => Page::max_length(schema.length(&field) as usize) as i32,
-                _ => panic!("Unexpected field type: {}", schema.ftype(&field)),
+            let length_in_bytes = match FieldType::try_from(schema.ftype(field)) {
+                Ok(FieldType::INTEGER) => 4,
+                Ok(FieldType::BIGINT) | Ok(FieldType::DOUBLE) | Ok(FieldType::DATE) => 8,
+                // The record only holds a reference to the blob's overflow
+                // blocks, see `Overflow`.
+                Ok(FieldType::BLOB) => 8,
+                Ok(FieldType::VARCHAR) if spilled.contains(field) => 8,
+                Ok(FieldType::BOOLEAN) => 1,
+                Ok(FieldType::VARCHAR) => Page::max_length(schema.length(field) as usize) as i32,
+                Err(ftype) => panic!("Unexpected field type: {}", ftype),
             };
             pos += length_in_bytes;
         }
+        let null_bits = Self::null_bits(&offsets);
         Layout {
-            schema,
+            schema: Arc::new(schema),
             offsets,
+            null_bits,
+            spilled,
             slot_size: pos,
+            fill_factor: Self::DEFAULT_FILL_FACTOR,
+            format: RecordFormat::Fixed,
+        }
+    }
+
+    pub fn from(schema: Schema, offsets: HashMap<String, i32>, slot_size: i32, spilled: HashSet<String>) -> Layout {
+        let offsets = schema.fields().iter().map(|field| (field.clone(), offsets[&**field])).collect();
+        let null_bits = Self::null_bits(&offsets);
+        let spilled = schema.fields().iter().filter(|field| spilled.contains(&***field)).cloned().collect();
+        Layout { schema: Arc::new(schema), offsets, null_bits, spilled, slot_size, fill_factor: Self::DEFAULT_FILL_FACTOR, format: RecordFormat::Fixed }
+    }
+
+    fn null_bitmap_size(fields: usize) -> i32 {
+        4 * fields.div_ceil(32) as i32
+    }
+
+    // Fields get their null bits in the order of their offsets, which does
+    // not depend on the order the catalog returns them in.
+    fn null_bits(offsets: &HashMap<Arc<str>, i32>) -> HashMap<Arc<str>, i32> {
+        let mut fields: Vec<&Arc<str>> = offsets.keys().collect();
+        fields.sort_by_key(|field| offsets[*field]);
+        fields.into_iter().enumerate().map(|(bit, field)| (field.clone(), bit as i32)).collect()
+    }
+
+    // Sets the percentage of a page's slots that inserts may fill. The rest
+    // is left free for the records already on the page to grow into.
+    pub fn with_fill_factor(mut self, fill_factor: i32) -> Layout {
+        assert!((1..=100).contains(&fill_factor), "fill factor must be between 1 and 100, got {}", fill_factor);
+        self.fill_factor = fill_factor;
+        self
+    }
+
+    // Sets the format of the table's pages. The offsets and the slot size
+    // are kept either way: a variable-length record stores its fields in
+    // the order of their offsets, and is never longer than a slot.
+    pub fn with_format(mut self, format: RecordFormat) -> Layout {
+        self.format = format;
+        self
+    }
+
+    // Spills the widest VARCHAR fields, first by offset among equals, until
+    // a record fits in a block of the size. The layout is returned as it is
+    // once it fits, or when no field is left that would get shorter.
+    pub fn fit(self, block_size: usize) -> Layout {
+        let mut layout = self;
+        while !RecordPage::fits(&layout, block_size) {
+            let schema = layout.schema.clone();
+            let widest = schema.fields().iter()
+                .filter(|field| schema.ftype(field) == FieldType::VARCHAR as i32 && !layout.is_spilled(field))
+                .filter(|field| Page::max_length(schema.length(field) as usize) > 8)
+                .min_by_key(|field| (-schema.length(field), layout.offset(field)));
+            let Some(widest) = widest else {
+                break;
+            };
+            let mut spilled = layout.spilled.clone();
+            spilled.insert(widest.clone());
+            layout = Self::spilling((*schema).clone(), spilled).with_fill_factor(layout.fill_factor).with_format(layout.format);
         }
+        layout
     }
 
-    pub fn from(schema: Schema, offsets: HashMap<String, i32>, slot_size: i32) -> Layout {
-        Layout { schema, offsets, slot_size }
+    pub fn is_spilled(&self, field: &str) -> bool {
+        self.spilled.contains(field)
     }
 
     pub fn offset(&self, field: &str) -> i32 {
         self.offsets[field]
     }
 
+    // Returns the offset of the bitmap integer that holds the field's null
+    // bit, and the mask of the bit within it.
+    pub fn null_bit(&self, field: &str) -> (i32, i32) {
+        let bit = self.null_bits[field];
+        (Self::NULL_BITMAP_OFFSET + 4 * (bit / 32), 1 << (bit % 32))
+    }
+
+    // Returns the offset of each integer of the null bitmap, with the mask
+    // of all the fields' bits in it.
+    pub fn null_bitmap(&self) -> Vec<(i32, i32)> {
+        let mut words: Vec<(i32, i32)> = Vec::new();
+        for field in self.null_bits.keys() {
+            let (offset, mask) = self.null_bit(field);
+            match words.iter_mut().find(|(word, _)| *word == offset) {
+                Some((_, bits)) => *bits |= mask,
+                None => words.push((offset, mask)),
+            }
+        }
+        words.sort();
+        words
+    }
+
     // The slot size is the sum of the field lengths plus 4 bytes for
-    // an integer-sized empty/used flag.
+    // an integer-sized empty/used flag and the null bitmap.
     pub fn slot_size(&self) -> i32 {
         self.slot_size
     }
 
-    pub fn schema(&self) -> &Schema {
+    pub fn fill_factor(&self) -> i32 {
+        self.fill_factor
+    }
+
+    pub fn format(&self) -> RecordFormat {
+        self.format
+    }
+
+    pub fn schema(&self) -> &Arc<Schema> {
         &self.schema
     }
 }
@@ -62,9 +198,54 @@ mod tests {
         schema.add_int_field("C");
         let layout = Layout::new(schema);
 
-        assert_eq!(layout.offset("A"), 4);
-        assert_eq!(layout.offset("B"), 8);
-        assert_eq!(layout.offset("C"), 21);
+        assert_eq!(layout.offset("A"), 8);
+        assert_eq!(layout.offset("B"), 12);
+        assert_eq!(layout.offset("C"), 25);
+        assert_eq!(layout.slot_size(), 29);
+        assert_eq!(layout.null_bit("A"), (4, 1));
+        assert_eq!(layout.null_bit("C"), (4, 4));
+        assert_eq!(layout.null_bitmap(), [(4, 7)]);
+
+        let mut wide = Schema::new();
+        for i in 0..40 {
+            wide.add_int_field(&format!("f{}", i));
+        }
+        let layout = Layout::new(wide);
+        assert_eq!(layout.offset("f0"), 12);
+        assert_eq!(layout.null_bit("f33"), (8, 2));
+        assert_eq!(layout.null_bitmap(), [(4, -1), (8, 0xff)]);
+
+        let mut schema = Schema::new();
+        schema.add_bigint_field("A");
+        schema.add_boolean_field("B");
+        schema.add_double_field("C");
+        let layout = Layout::new(schema);
+        assert_eq!((layout.offset("A"), layout.offset("B"), layout.offset("C")), (8, 16, 17));
         assert_eq!(layout.slot_size(), 25);
     }
+
+    #[test]
+    fn test_fit() {
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 150);
+        schema.add_string_field("C", 200);
+        schema.add_string_field("D", 150);
+        let layout = Layout::new(schema.clone());
+        assert_eq!(layout.slot_size(), 524);
+
+        // C is spilled first, then B, the first of the two left.
+        let layout = Layout::new(schema.clone()).fit(400);
+        assert_eq!(layout.slot_size(), 328);
+        assert!(layout.is_spilled("C") && !layout.is_spilled("B") && !layout.is_spilled("D"));
+        let layout = Layout::new(schema.clone()).fit(200);
+        assert!(layout.is_spilled("B") && layout.is_spilled("C") && !layout.is_spilled("D"));
+        assert_eq!((layout.offset("B"), layout.offset("C"), layout.offset("D")), (12, 20, 28));
+        let layout = Layout::new(schema).fit(20);
+        assert_eq!(layout.slot_size(), 36);
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        assert_eq!(Layout::new(schema).fit(400).slot_size(), 12);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,242 @@
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use crate::error::dberror::DbError;
+use crate::file::blockid::{BlockId, FileId};
+use crate::file::page::Page;
+use crate::query::constant::Constant;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::FieldType;
+use crate::tx::transaction::Transaction;
+
+// The Overflow stores the blobs of a table, and the values of its spilled
+// VARCHAR fields, in the table's overflow file, `<table>.blob`, outside the
+// fixed-size slots of its records. A blob is
+// split into chunks, each in a block of its own, and the blocks are chained
+// through the number of the next block, 0 ending the chain. Block 0 of the
+// file is a header holding the first block of the free list, the chain of
+// blocks freed by deleted or overwritten blobs, which are reused before the
+// file grows.
+//
+// The record refers to its blob with a reference that packs the first
+// block of the chain in its high 32 bits and the blob's length in its low
+// ones; an empty blob takes no block and is stored as a reference to
+// block 0. Every change to the overflow blocks is logged, so a rollback
+// restores the blobs and the free list, although blocks appended to the
+// file stay there.
+pub(crate) struct Overflow {
+    tx: Arc<Mutex<Transaction>>,
+    filename: String,
+    file: FileId,
+}
+
+impl Overflow {
+    const FREE_LIST_POS: usize = Page::HEADER_SIZE;
+    const NEXT_POS: usize = Page::HEADER_SIZE;
+    const CHUNK_POS: usize = Page::HEADER_SIZE + 4;
+
+    // The longest blob that can be stored, as its length must fit in the
+    // reference.
+    pub(crate) const MAX_LENGTH: usize = i32::MAX as usize;
+
+    pub(crate) fn new(tx: Arc<Mutex<Transaction>>, table: &str) -> Overflow {
+        let filename = format!("{}.blob", table);
+        let file = tx.lock().unwrap().file_id(&filename);
+        Overflow { tx, filename, file }
+    }
+
+    // The number of bytes of a blob stored in each block. Writing a chunk
+    // logs the chunk it overwrites, so it is kept to less than half a block
+    // for the log record to fit in a log block.
+    fn chunk_size(&self) -> usize {
+        (self.tx.lock().unwrap().block_size() - Self::CHUNK_POS - 4) / 2
+    }
+
+    // Writes the bytes read from the source to a new chain of blocks and
+    // returns its reference, or None if the source holds more than
+    // `MAX_LENGTH` bytes, in which case nothing is kept.
+    pub(crate) fn write(&self, src: &mut impl Read) -> Result<Option<i64>, ScanError> {
+        let mut chunk = vec![0; self.chunk_size()];
+        let mut first = 0;
+        let mut last: Option<BlockId> = None;
+        let mut length = 0;
+        loop {
+            let len = Self::fill(src, &mut chunk)?;
+            if len == 0 {
+                break;
+            }
+            length += len;
+            if length > Self::MAX_LENGTH {
+                self.free(Self::reference(first, length - len))?;
+                return Ok(None);
+            }
+            let blk = self.allocate()?;
+            let mut tx = self.tx.lock().unwrap();
+            tx.pin(&blk)?;
+            tx.set_values(&blk, &[(Self::NEXT_POS, Constant::Int(0)), (Self::CHUNK_POS, Constant::Blob(chunk[..len].to_vec()))], true)?;
+            tx.unpin(&blk);
+            match last {
+                Some(last) => Self::set_next(&mut tx, &last, blk.number())?,
+                None => first = blk.number(),
+            }
+            last = Some(blk);
+            if len < chunk.len() {
+                break;
+            }
+        }
+        Ok(Some(Self::reference(first, length)))
+    }
+
+    // Returns a reader over the blob.
+    pub(crate) fn reader(&self, reference: i64) -> BlobReader {
+        let (first, length) = Self::unpack(reference);
+        BlobReader { tx: self.tx.clone(), file: self.file, next: first, remaining: length, chunk: Vec::new(), pos: 0 }
+    }
+
+    // Reads the whole blob.
+    pub(crate) fn read(&self, reference: i64) -> Result<Vec<u8>, DbError> {
+        let mut reader = self.reader(reference);
+        let mut blob = Vec::with_capacity(reader.remaining);
+        while let Some(chunk) = reader.next_chunk()? {
+            blob.extend_from_slice(&chunk);
+        }
+        Ok(blob)
+    }
+
+    // Puts the chain of the blob in front of the free list.
+    pub(crate) fn free(&self, reference: i64) -> Result<(), DbError> {
+        let (first, _) = Self::unpack(reference);
+        if first == 0 {
+            return Ok(());
+        }
+        let header = BlockId::new(self.file, 0);
+        let mut tx = self.tx.lock().unwrap();
+        let mut last = BlockId::new(self.file, first);
+        while let Some(next) = Self::next(&mut tx, &last)? {
+            last = BlockId::new(self.file, next);
+        }
+        tx.pin(&header)?;
+        let head = tx.get_int(&header, Self::FREE_LIST_POS)?.unwrap();
+        tx.set_int(&header, Self::FREE_LIST_POS, first as i32, true)?;
+        tx.unpin(&header);
+        Self::set_next(&mut tx, &last, head as usize)
+    }
+
+    // Takes the first block of the free list, or appends one to the file
+    // if the list is empty. The header is created along with the file.
+    fn allocate(&self) -> Result<BlockId, DbError> {
+        let mut tx = self.tx.lock().unwrap();
+        if tx.size(&self.filename)? == 0 {
+            tx.append(&self.filename)?;
+        }
+        let header = BlockId::new(self.file, 0);
+        tx.pin(&header)?;
+        let head = tx.get_int(&header, Self::FREE_LIST_POS)?.unwrap() as usize;
+        let result = match head {
+            0 => tx.append(&self.filename),
+            head => {
+                let blk = BlockId::new(self.file, head);
+                Self::next(&mut tx, &blk)
+                    .and_then(|next| tx.set_int(&header, Self::FREE_LIST_POS, next.unwrap_or(0) as i32, true))
+                    .map(|_| blk)
+            }
+        };
+        tx.unpin(&header);
+        result
+    }
+
+    fn next(tx: &mut Transaction, blk: &BlockId) -> Result<Option<usize>, DbError> {
+        tx.pin(blk)?;
+        let next = tx.get_int(blk, Self::NEXT_POS);
+        tx.unpin(blk);
+        Ok(next?.map(|next| next as usize).filter(|next| *next != 0))
+    }
+
+    fn set_next(tx: &mut Transaction, blk: &BlockId, next: usize) -> Result<(), DbError> {
+        tx.pin(blk)?;
+        let result = tx.set_int(blk, Self::NEXT_POS, next as i32, true);
+        tx.unpin(blk);
+        result
+    }
+
+    // Reads from the source until the buffer is full or the source ends.
+    fn fill(src: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut len = 0;
+        while len < buf.len() {
+            match src.read(&mut buf[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(len)
+    }
+
+    fn reference(first: usize, length: usize) -> i64 {
+        ((first as i64) << 32) | length as i64
+    }
+
+    fn unpack(reference: i64) -> (usize, usize) {
+        ((reference >> 32) as usize, (reference & 0xffff_ffff) as usize)
+    }
+
+    // The length of the blob, without reading it.
+    pub(crate) fn length(reference: i64) -> usize {
+        Self::unpack(reference).1
+    }
+}
+
+// A BlobReader streams a blob one chunk at a time, so that a blob is never
+// held in memory whole. It reads through the transaction that opened it.
+pub struct BlobReader {
+    tx: Arc<Mutex<Transaction>>,
+    file: FileId,
+    next: usize,
+    remaining: usize,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl BlobReader {
+    // The number of bytes of the blob not read yet.
+    pub fn remaining(&self) -> usize {
+        self.remaining + self.chunk.len() - self.pos
+    }
+
+    fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, DbError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        let blk = BlockId::new(self.file, self.next);
+        let mut tx = self.tx.lock().unwrap();
+        tx.pin(&blk)?;
+        let values = tx.get_values(&blk, &[(Overflow::NEXT_POS, FieldType::INTEGER), (Overflow::CHUNK_POS, FieldType::BLOB)]);
+        tx.unpin(&blk);
+        let mut values = values?.unwrap();
+        let Some(Constant::Blob(chunk)) = values.pop() else {
+            unreachable!("chunks are read as blobs");
+        };
+        if chunk.is_empty() || chunk.len() > self.remaining {
+            return Err(DbError::Corruption(format!("blob chain is broken at {}", blk)));
+        }
+        self.next = values[0].as_int().unwrap() as usize;
+        self.remaining -= chunk.len();
+        Ok(Some(chunk))
+    }
+}
+
+impl Read for BlobReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos == self.chunk.len() {
+            match self.next_chunk().map_err(|error| std::io::Error::other(error.to_string()))? {
+                Some(chunk) => self.chunk = chunk,
+                None => return Ok(0),
+            }
+            self.pos = 0;
+        }
+        let len = buf.len().min(self.chunk.len() - self.pos);
+        buf[..len].copy_from_slice(&self.chunk[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
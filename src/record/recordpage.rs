@@ -1,6 +1,12 @@
 use std::sync::{Arc, Mutex};
+use crate::error::dberror::DbError;
 use crate::file::blockid::BlockId;
-use crate::record::layout::Layout;
+use crate::file::page::Page;
+use crate::query::constant::Constant;
+use crate::record::layout::{Layout, RecordFormat};
+use crate::record::row::Row;
+use crate::record::schema::FieldType;
+use crate::record::slottedpage::SlottedPage;
 use crate::tx::transaction::Transaction;
 
 #[derive(Clone, Copy)]
@@ -12,96 +18,254 @@ enum Slot {
 pub(crate) struct RecordPage {
     tx: Arc<Mutex<Transaction>>,
     block_id: BlockId,
-    layout: Layout,
+    layout: Arc<Layout>,
 }
 
 // The RecordPage manages the records within a page. It provides methods for reading and writing
 // records, as well as for navigating the page. The RecordPage is responsible for maintaining the
 // slot array, which keeps track of which slots are in use -- it implements the slotted-page structure
 // where the empty/used flags are implemented as 4-byte integers instead of single bytes.
+// A field whose bit is set in the slot's null bitmap is null, whatever its bytes hold.
+// A blob field, or a spilled VARCHAR, holds the reference to its value's
+// overflow blocks, which is read and written as a BIGINT; the TableScan
+// resolves it.
+// The pages of a table in the variable-length format are handed to a
+// SlottedPage instead, which keeps the same slot numbers for its records.
 impl RecordPage {
-    pub fn new(tx: Arc<Mutex<Transaction>>, block_id: BlockId, layout: Layout) -> RecordPage {
-        tx.lock().unwrap().pin(&block_id);
-        RecordPage { tx, block_id, layout }
+    pub fn new(tx: Arc<Mutex<Transaction>>, block_id: BlockId, layout: Arc<Layout>) -> Result<RecordPage, DbError> {
+        tx.lock().unwrap().pin(&block_id)?;
+        Ok(RecordPage { tx, block_id, layout })
     }
 
-    pub(crate) fn get_int(&mut self, slot: i32, field: &str) -> i32 {
+    pub(crate) fn get_int(&mut self, slot: i32, field: &str) -> Result<i32, DbError> {
+        if let Some(page) = self.slotted() {
+            return Ok(page.get_val(slot, field)?.as_int().unwrap());
+        }
         let fpos = self.offset(slot) + self.layout.offset(field);
-        self.tx.lock().unwrap().get_int(&self.block_id, fpos as usize).unwrap()
+        Ok(self.tx.lock().unwrap().get_int(&self.block_id, fpos as usize)?.unwrap())
     }
 
-    pub(crate) fn set_int(&mut self, slot: i32, field: &str, val: i32) {
-        let fpos = self.offset(slot) + self.layout.offset(field);
-        self.tx.lock().unwrap().set_int(&self.block_id, fpos as usize, val, true);
+    pub(crate) fn set_int(&mut self, slot: i32, field: &str, val: i32) -> Result<(), DbError> {
+        self.set_val(slot, field, Constant::Int(val))
     }
 
-    pub(crate) fn get_string(&mut self, slot: i32, field: &str) -> String {
+    pub(crate) fn get_string(&mut self, slot: i32, field: &str) -> Result<String, DbError> {
+        if let Some(page) = self.slotted() {
+            return Ok(page.get_val(slot, field)?.as_string().unwrap().to_string());
+        }
         let fpos = self.offset(slot) + self.layout.offset(field);
-        self.tx.lock().unwrap().get_string(&self.block_id, fpos as usize).unwrap()
+        Ok(self.tx.lock().unwrap().get_string(&self.block_id, fpos as usize)?.unwrap())
     }
 
-    pub(crate) fn set_string(&mut self, slot: i32, field: &str, val: &str) {
-        let fpos = self.offset(slot) + self.layout.offset(field);
-        self.tx.lock().unwrap().set_string(&self.block_id, fpos as usize, val, true);
+    pub(crate) fn set_string(&mut self, slot: i32, field: &str, val: &str) -> Result<(), DbError> {
+        self.set_val(slot, field, Constant::String(val.to_string()))
+    }
+
+    // Reads the field together with its null bit, returning Null if the
+    // bit is set.
+    pub(crate) fn get_val(&mut self, slot: i32, field: &str) -> Result<Constant, DbError> {
+        if let Some(page) = self.slotted() {
+            return page.get_val(slot, field);
+        }
+        let (word, mask) = self.null_bit(slot, field);
+        let specs = [(word, FieldType::INTEGER), (self.field_pos(slot, field), Self::stored_type(&self.layout, field))];
+        let mut values = self.tx.lock().unwrap().get_values(&self.block_id, &specs)?.unwrap();
+        let val = values.pop().unwrap();
+        Ok(if Self::bits(&values[0]) & mask != 0 { Constant::Null } else { val })
+    }
+
+    pub(crate) fn is_null(&mut self, slot: i32, field: &str) -> Result<bool, DbError> {
+        if let Some(page) = self.slotted() {
+            return page.is_null(slot, field);
+        }
+        let (word, mask) = self.null_bit(slot, field);
+        Ok(self.tx.lock().unwrap().get_int(&self.block_id, word)?.unwrap() & mask != 0)
+    }
+
+    pub(crate) fn set_null(&mut self, slot: i32, field: &str) -> Result<(), DbError> {
+        if let Some(page) = self.slotted() {
+            return page.set_null(slot, field);
+        }
+        let (word, mask) = self.null_bit(slot, field);
+        let mut tx = self.tx.lock().unwrap();
+        let bits = tx.get_int(&self.block_id, word)?.unwrap();
+        if bits & mask == 0 {
+            tx.set_int(&self.block_id, word, bits | mask, true)?;
+        }
+        Ok(())
     }
 
-    pub fn next_after(&mut self, slot: Option<i32>) -> Option<i32> {
+    // Writes the value, which must have the type of the field, and clears
+    // the field's null bit if it is set.
+    pub(crate) fn set_val(&mut self, slot: i32, field: &str, val: Constant) -> Result<(), DbError> {
+        if let Some(page) = self.slotted() {
+            return page.set_val(slot, field, val);
+        }
+        let (word, mask) = self.null_bit(slot, field);
+        let mut values = vec![(self.field_pos(slot, field), val)];
+        let mut tx = self.tx.lock().unwrap();
+        let bits = tx.get_int(&self.block_id, word)?.unwrap();
+        if bits & mask != 0 {
+            values.push((word, Constant::Int(bits & !mask)));
+        }
+        tx.set_values(&self.block_id, &values, true)
+    }
+
+    // Reads every field of the record in the slot with a single call to the
+    // transaction, rather than one lock round-trip per field.
+    pub(crate) fn get_row(&mut self, slot: i32) -> Result<Row, DbError> {
+        if let Some(page) = self.slotted() {
+            return page.get_row(slot);
+        }
+        let schema = self.layout.schema();
+        let fields = schema.fields();
+        let bitmap = self.null_bitmap(slot);
+        let specs: Vec<(usize, FieldType)> = bitmap.iter().map(|(word, _)| (*word, FieldType::INTEGER))
+            .chain(fields.iter().map(|field| (self.field_pos(slot, field), Self::stored_type(&self.layout, field))))
+            .collect();
+        let mut values = self.tx.lock().unwrap().get_values(&self.block_id, &specs)?.unwrap();
+        let field_values = values.split_off(bitmap.len());
+        let words: Vec<(usize, i32)> = bitmap.iter().zip(&values).map(|((word, _), bits)| (*word, Self::bits(bits))).collect();
+        let mut row = Row::new();
+        for (field, val) in fields.iter().zip(field_values) {
+            let (word, mask) = self.null_bit(slot, field);
+            let is_null = words.iter().any(|(w, bits)| *w == word && bits & mask != 0);
+            row.set(field, if is_null { Constant::Null } else { val });
+        }
+        Ok(row)
+    }
+
+    // Writes the values in the row to the record in the slot with a single
+    // call to the transaction, setting the null bits of the null values and
+    // clearing those of the others. Fields missing from the row are left as
+    // is.
+    pub(crate) fn set_row(&mut self, slot: i32, row: &Row) -> Result<(), DbError> {
+        if let Some(page) = self.slotted() {
+            return page.set_row(slot, row);
+        }
+        let bitmap = self.null_bitmap(slot);
+        let specs: Vec<(usize, FieldType)> = bitmap.iter().map(|(word, _)| (*word, FieldType::INTEGER)).collect();
+        let mut tx = self.tx.lock().unwrap();
+        let old: Vec<i32> = tx.get_values(&self.block_id, &specs)?.unwrap().iter().map(Self::bits).collect();
+        let mut words = old.clone();
+        let mut values = Vec::new();
+        for (field, val) in row.iter() {
+            let (word, mask) = self.null_bit(slot, field);
+            let idx = bitmap.iter().position(|(w, _)| *w == word).unwrap();
+            if val.is_null() {
+                words[idx] |= mask;
+            } else {
+                words[idx] &= !mask;
+                values.push((self.field_pos(slot, field), val.clone()));
+            }
+        }
+        for ((word, _), (bits, old)) in bitmap.iter().zip(words.into_iter().zip(old)) {
+            if bits != old {
+                values.push((*word, Constant::Int(bits)));
+            }
+        }
+        tx.set_values(&self.block_id, &values, true)
+    }
+
+    pub fn next_after(&mut self, slot: Option<i32>) -> Result<Option<i32>, DbError> {
+        if let Some(page) = self.slotted() {
+            return page.next_after(slot);
+        }
         self.search_after(slot, Slot::Used)
     }
 
-    pub fn insert_after(&mut self, slot: Option<i32>) -> Option<i32> {
-        let new_slot = self.search_after(slot, Slot::Empty);
+    // Finds an empty slot after the specified one and marks it as used, with
+    // every field of the new record null. Once the page holds as many records
+    // as the layout's fill factor allows, no slot is returned and the caller
+    // moves on to another page.
+    pub fn insert_after(&mut self, slot: Option<i32>) -> Result<Option<i32>, DbError> {
+        if let Some(page) = self.slotted() {
+            return page.insert_after(slot);
+        }
+        if self.is_filled()? {
+            return Ok(None);
+        }
+        let new_slot = self.search_after(slot, Slot::Empty)?;
         if let Some(new_slot) = new_slot {
-            self.set_flag(new_slot, Slot::Used);
+            let mut values = vec![(self.offset(new_slot) as usize, Constant::Int(Slot::Used as i32))];
+            values.extend(self.null_bitmap(new_slot).into_iter().map(|(word, mask)| (word, Constant::Int(mask))));
+            self.tx.lock().unwrap().set_values(&self.block_id, &values, true)?;
         }
-        new_slot
+        Ok(new_slot)
     }
 
-    pub fn delete(&mut self, slot: i32) {
-        self.set_flag(slot, Slot::Empty);
+    pub fn delete(&mut self, slot: i32) -> Result<(), DbError> {
+        if let Some(page) = self.slotted() {
+            return page.delete(slot);
+        }
+        self.set_flag(slot, Slot::Empty)
     }
 
-    // Formats the page by setting all slots to empty.
-    pub fn format(&mut self) {
+    // Formats the page by setting all slots to empty. The whole page
+    // is written with a single call to the transaction.
+    pub fn format(&mut self) -> Result<(), DbError> {
+        if let Some(page) = self.slotted() {
+            return page.format();
+        }
+        let mut values = Vec::new();
         let mut slot = 0;
         while self.is_valid_slot(slot) {
-            self.tx.lock().unwrap().set_int(&self.block_id, self.offset(slot) as usize, Slot::Empty as i32, false);
+            values.push((self.offset(slot) as usize, Constant::Int(Slot::Empty as i32)));
+            values.extend(self.null_bitmap(slot).into_iter().map(|(word, _)| (word, Constant::Int(0))));
             for field in self.layout.schema().fields() {
-                let fpos = self.offset(slot) + self.layout.offset(&field);
-                if self.layout.schema().ftype(&field) == 4 {
-                    self.tx.lock().unwrap().set_int(&self.block_id, fpos as usize, 0, false);
-                }
-                // TODO: Add support for VARCHAR fields.
+                let empty = match Self::stored_type(&self.layout, field) {
+                    FieldType::INTEGER => Constant::Int(0),
+                    FieldType::BIGINT | FieldType::BLOB => Constant::Long(0),
+                    FieldType::DOUBLE => Constant::Double(0.0),
+                    FieldType::BOOLEAN => Constant::Bool(false),
+                    FieldType::DATE => Constant::Date(0),
+                    FieldType::VARCHAR => Constant::String(String::new()),
+                };
+                values.push((self.field_pos(slot, field), empty));
             }
             slot += 1;
         }
+        self.tx.lock().unwrap().set_values(&self.block_id, &values, false)
+    }
+
+    // Whether a record of the layout fits in a block of the size. Otherwise
+    // no record could ever be inserted.
+    pub(crate) fn fits(layout: &Layout, block_size: usize) -> bool {
+        match layout.format() {
+            RecordFormat::Fixed => layout.slot_size() as usize <= block_size - Page::HEADER_SIZE,
+            RecordFormat::Variable => SlottedPage::fits(layout, block_size),
+        }
     }
 
     pub fn block_id(&self) -> &BlockId {
         &self.block_id
     }
 
-    fn set_flag(&mut self, slot: i32, flag: Slot) {
-        self.tx.lock().unwrap().set_int(&self.block_id, self.offset(slot) as usize, flag as i32, true);
+    fn slotted(&self) -> Option<SlottedPage<'_>> {
+        (self.layout.format() == RecordFormat::Variable).then(|| SlottedPage::new(&self.tx, &self.block_id, &self.layout))
+    }
+
+    fn set_flag(&mut self, slot: i32, flag: Slot) -> Result<(), DbError> {
+        self.tx.lock().unwrap().set_int(&self.block_id, self.offset(slot) as usize, flag as i32, true)
     }
 
     // Finds the next empty or used slot after the specified slot.
     // If the slot is None, then the search starts at the beginning of the page.
     // The search continues until a slot is found with the specified flag.
     // If no slot is found, then None is returned.
-    fn search_after(&mut self, slot: Option<i32>, flag: Slot) -> Option<i32> {
+    fn search_after(&mut self, slot: Option<i32>, flag: Slot) -> Result<Option<i32>, DbError> {
         let mut new_slot = 0;
         if let Some(slot) = slot {
             new_slot = slot + 1;
         }
         while self.is_valid_slot(new_slot) {
-            let x = self.tx.lock().unwrap().get_int(&self.block_id, self.offset(new_slot) as usize).unwrap();
+            let x = self.tx.lock().unwrap().get_int(&self.block_id, self.offset(new_slot) as usize)?.unwrap();
             if x == flag as i32 {
-                return Some(new_slot)
+                return Ok(Some(new_slot))
             }
             new_slot += 1;
         }
-        None
+        Ok(None)
     }
 
     // The slot is valid if it fits within the layout size and the file block size.
@@ -114,8 +278,56 @@ impl RecordPage {
         self.offset(slot + 1) <= self.tx.lock().unwrap().block_size() as i32
     }
 
+    // Whether the page already holds as many records as the fill factor
+    // allows. The used slots are only counted up to that number.
+    fn is_filled(&mut self) -> Result<bool, DbError> {
+        let fill_factor = self.layout.fill_factor();
+        if fill_factor >= 100 {
+            return Ok(false);
+        }
+        let slots = (self.tx.lock().unwrap().block_size() as i32 - Page::HEADER_SIZE as i32) / self.layout.slot_size();
+        let max_used = (slots * fill_factor / 100).max(1);
+        let mut used = 0;
+        let mut slot = self.next_after(None)?;
+        while let Some(s) = slot {
+            used += 1;
+            if used >= max_used {
+                return Ok(true);
+            }
+            slot = self.next_after(Some(s))?;
+        }
+        Ok(false)
+    }
+
+    // Slots start right after the page header.
     fn offset(&self, slot: i32) -> i32 {
-        slot * self.layout.slot_size()
+        Page::HEADER_SIZE as i32 + slot * self.layout.slot_size()
+    }
+
+    // The type of the bytes of the field in the slot.
+    pub(super) fn stored_type(layout: &Layout, field: &str) -> FieldType {
+        match FieldType::try_from(layout.schema().ftype(field)).unwrap() {
+            FieldType::BLOB => FieldType::BIGINT,
+            FieldType::VARCHAR if layout.is_spilled(field) => FieldType::BIGINT,
+            ftype => ftype,
+        }
+    }
+
+    fn field_pos(&self, slot: i32, field: &str) -> usize {
+        (self.offset(slot) + self.layout.offset(field)) as usize
+    }
+
+    fn null_bit(&self, slot: i32, field: &str) -> (usize, i32) {
+        let (offset, mask) = self.layout.null_bit(field);
+        ((self.offset(slot) + offset) as usize, mask)
+    }
+
+    fn null_bitmap(&self, slot: i32) -> Vec<(usize, i32)> {
+        self.layout.null_bitmap().into_iter().map(|(offset, mask)| ((self.offset(slot) + offset) as usize, mask)).collect()
+    }
+
+    fn bits(word: &Constant) -> i32 {
+        word.as_int().unwrap()
     }
 }
 
@@ -128,59 +340,64 @@ mod tests {
     use rand::Rng;
     use crate::buffer::buffermgr::BufferMgr;
     use crate::log::logmgr::LogMgr;
+    use crate::tx::admission::AdmissionCtl;
 
     #[test]
     fn test_record_page() {
-        let fm = Arc::new(FileMgr::new(PathBuf::from("recoverytestdb"), 400));
-        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log")));
+        let fm = Arc::new(FileMgr::new(PathBuf::from("recordpagetestdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
         let bm = Arc::new(Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 3)));
-        let mut tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone())));
+        let mut tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap()));
 
         let mut schema = Schema::new();
         schema.add_int_field("A");
         schema.add_string_field("B", 9);
-        let layout = Layout::new(schema);
+        let layout = Arc::new(Layout::new(schema));
         for fname in layout.schema().fields() {
-            let offset = layout.offset(&fname);
+            let offset = layout.offset(fname);
             println!("Field {} starts at offset {}", fname, offset);
         }
 
-        let block = tx.lock().unwrap().append("testfile");
-        tx.lock().unwrap().pin(&block);
+        let block = tx.lock().unwrap().append("testfile").unwrap();
+        tx.lock().unwrap().pin(&block).unwrap();
 
-        let mut rp = RecordPage::new(tx.clone(), block.clone(), layout);
-        rp.format();
+        let mut rp = RecordPage::new(tx.clone(), block, layout).unwrap();
+        rp.format().unwrap();
+        assert_eq!(rp.get_int(0, "A").unwrap(), 0);
+        assert_eq!(rp.get_string(0, "B").unwrap(), "");
 
         println!("Filling the page with random records...");
-        let mut slot = rp.insert_after(None);
+        let mut slot = rp.insert_after(None).unwrap();
         while slot.is_some() {
             let num = rand::rng().random_range(0..50);
-            rp.set_int(slot.unwrap(), "A", num);
-            rp.set_int(slot.unwrap(), "B", num);
-            println!("Inserting into slot {}: ({}, {})", slot.unwrap(), num, num);
-            slot = rp.insert_after(slot);
+            rp.set_int(slot.unwrap(), "A", num).unwrap();
+            rp.set_string(slot.unwrap(), "B", &format!("rec{}", num)).unwrap();
+            println!("Inserting into slot {}: ({}, rec{})", slot.unwrap(), num, num);
+            slot = rp.insert_after(slot).unwrap();
         }
 
         println!("Deleting records with A < 25...");
         let mut count = 0;
-        slot = rp.next_after(None);
+        slot = rp.next_after(None).unwrap();
         while slot.is_some() {
-            if rp.get_int(slot.unwrap(), "A") < 25 {
-                rp.delete(slot.unwrap());
+            let a = rp.get_int(slot.unwrap(), "A").unwrap();
+            assert_eq!(rp.get_string(slot.unwrap(), "B").unwrap(), format!("rec{}", a));
+            if a < 25 {
+                rp.delete(slot.unwrap()).unwrap();
                 count += 1;
             }
-            slot = rp.next_after(slot);
+            slot = rp.next_after(slot).unwrap();
         }
         println!("Deleted {} records", count);
 
         println!("Here are the remaining records:");
-        slot = rp.next_after(None);
+        slot = rp.next_after(None).unwrap();
         while slot.is_some() {
-            println!("Slot {}: ({}, {})", slot.unwrap(), rp.get_int(slot.unwrap(), "A"), rp.get_int(slot.unwrap(), "B"));
-            slot = rp.next_after(slot);
+            println!("Slot {}: ({}, {})", slot.unwrap(), rp.get_int(slot.unwrap(), "A").unwrap(), rp.get_string(slot.unwrap(), "B").unwrap());
+            slot = rp.next_after(slot).unwrap();
         }
 
         tx.lock().unwrap().unpin(&block);
-        tx.lock().unwrap().commit();
+        tx.lock().unwrap().commit().unwrap();
     }
 }
\ No newline at end of file
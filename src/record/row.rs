@@ -0,0 +1,59 @@
+use crate::query::constant::Constant;
+
+// A Row holds the values of a whole record, keyed by field name in schema
+// order. It lets a record be read or written with a single round-trip to
+// the transaction instead of one per field.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Row {
+    values: Vec<(String, Constant)>,
+}
+
+impl Row {
+    pub fn new() -> Row {
+        Row { values: Vec::new() }
+    }
+
+    pub fn get(&self, field: &str) -> Option<&Constant> {
+        self.values.iter().find(|(name, _)| name == field).map(|(_, val)| val)
+    }
+
+    // Sets the value of the field, replacing any previous value.
+    pub fn set(&mut self, field: &str, val: Constant) {
+        match self.values.iter_mut().find(|(name, _)| name == field) {
+            Some((_, old)) => *old = val,
+            None => self.values.push((field.to_string(), val)),
+        }
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.values.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Constant)> {
+        self.values.iter().map(|(name, val)| (name.as_str(), val))
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_row() {
+        let mut row = Row::new();
+        row.set("A", Constant::Int(1));
+        row.set("B", Constant::String("b".to_string()));
+        row.set("A", Constant::Int(2));
+        assert_eq!(row.len(), 2);
+        assert_eq!(row.get("A"), Some(&Constant::Int(2)));
+        assert_eq!(row.get("C"), None);
+        assert_eq!(row.fields().collect::<Vec<_>>(), vec!["A", "B"]);
+    }
+}
@@ -0,0 +1,69 @@
+use std::fmt::Display;
+use crate::error::dberror::DbError;
+use crate::parse::parseerror::ParseError;
+use crate::record::schema::FieldType;
+
+// Errors returned when accessing the fields of a scan. Asking for a field
+// that is not in the schema, reading it as the wrong type, or reading while
+// the scan is not positioned on a record are caller bugs that would
+// otherwise look like valid data.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScanError {
+    UnknownField(String),
+    UnknownTable(String),
+    UnknownIndex(String),
+    UnknownSchema(String),
+    UnknownTransaction(i32),
+    AlreadyExists(String),
+    TypeMismatch { field: String, expected: FieldType },
+    ValueTooLong { field: String, max: i32 },
+    ConstraintViolation { field: String, constraint: String },
+    NullValue(String),
+    NoCurrentRecord,
+    // The records of the table do not fit in a block, even with their
+    // VARCHAR fields spilled to overflow blocks.
+    RecordTooWide { table: String, size: i32 },
+    // A statement read from a file, rather than parsed by the caller, is
+    // not valid SQL.
+    Syntax(ParseError),
+    Db(DbError),
+}
+
+impl From<DbError> for ScanError {
+    fn from(error: DbError) -> ScanError {
+        ScanError::Db(error)
+    }
+}
+
+impl From<ParseError> for ScanError {
+    fn from(error: ParseError) -> ScanError {
+        ScanError::Syntax(error)
+    }
+}
+
+impl From<std::io::Error> for ScanError {
+    fn from(error: std::io::Error) -> ScanError {
+        ScanError::Db(error.into())
+    }
+}
+
+impl Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ScanError::UnknownField(field) => write!(f, "unknown field {}", field),
+            ScanError::UnknownTable(table) => write!(f, "unknown table {}", table),
+            ScanError::UnknownIndex(index) => write!(f, "unknown index {}", index),
+            ScanError::UnknownSchema(schema) => write!(f, "unknown schema {}", schema),
+            ScanError::UnknownTransaction(txnum) => write!(f, "unknown transaction {}", txnum),
+            ScanError::AlreadyExists(name) => write!(f, "{} already exists", name),
+            ScanError::TypeMismatch { field, expected } => write!(f, "field {} is not of type {:?}", field, expected),
+            ScanError::ValueTooLong { field, max } => write!(f, "value of field {} is longer than {} characters", field, max),
+            ScanError::ConstraintViolation { field, constraint } => write!(f, "value of field {} violates {}", field, constraint),
+            ScanError::NullValue(field) => write!(f, "field {} is null", field),
+            ScanError::NoCurrentRecord => write!(f, "the scan is not positioned on a record"),
+            ScanError::RecordTooWide { table, size } => write!(f, "records of table {} take {} bytes, more than a block holds", table, size),
+            ScanError::Syntax(error) => write!(f, "{}", error),
+            ScanError::Db(error) => write!(f, "{}", error),
+        }
+    }
+}
@@ -1,25 +1,58 @@
 use std::collections::HashMap;
+use std::sync::Arc;
+use crate::query::constant::Constant;
+use crate::record::check::Check;
+use crate::record::scanerror::ScanError;
 
+// The type codes are those of java.sql.Types, as in the original SimpleDB,
+// and are how the types are stored in the catalog.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FieldType {
     INTEGER = 4,
     VARCHAR = 12,
+    BIGINT = -5,
+    DOUBLE = 8,
+    BOOLEAN = 16,
+    DATE = 91,
+    BLOB = 2004,
+}
+
+impl TryFrom<i32> for FieldType {
+    type Error = i32;
+
+    fn try_from(ftype: i32) -> Result<FieldType, i32> {
+        match ftype {
+            4 => Ok(FieldType::INTEGER),
+            12 => Ok(FieldType::VARCHAR),
+            -5 => Ok(FieldType::BIGINT),
+            8 => Ok(FieldType::DOUBLE),
+            16 => Ok(FieldType::BOOLEAN),
+            91 => Ok(FieldType::DATE),
+            2004 => Ok(FieldType::BLOB),
+            _ => Err(ftype),
+        }
+    }
 }
 
 #[derive(Clone)]
 struct FieldInfo {
     ftype: i32,
     length: i32,
+    not_null: bool,
+    checks: Vec<Check>,
 }
 
 #[derive(Clone)]
 pub struct Schema {
-    fields: Vec<String>,
-    info: HashMap<String, FieldInfo>
+    fields: Vec<Arc<str>>,
+    info: HashMap<Arc<str>, FieldInfo>
 }
 
 // The Schema struct holds a record's _schema_, the name and type of each field, and the
 // length of each field. A schema can be thought of as a list of triples, each consisting of
-// a field name, a field type, and a field length.
+// a field name, a field type, and a field length. Field names are interned: the
+// field list and the info map share the same allocation, which a Layout built
+// from the schema reuses as well.
 impl Schema {
     pub fn new() -> Schema {
         Schema {
@@ -29,8 +62,9 @@ impl Schema {
     }
 
     pub fn add_field(&mut self, field: &str, ftype: i32, length: i32) {
-        self.fields.push(String::from(field));
-        self.info.insert(String::from(field), FieldInfo { ftype, length });
+        let field: Arc<str> = Arc::from(field);
+        self.fields.push(field.clone());
+        self.info.insert(field, FieldInfo { ftype, length, not_null: false, checks: Vec::new() });
     }
 
     pub fn add_int_field(&mut self, field: &str) {
@@ -41,6 +75,26 @@ impl Schema {
         self.add_field(field, FieldType::VARCHAR as i32, length);
     }
 
+    pub fn add_bigint_field(&mut self, field: &str) {
+        self.add_field(field, FieldType::BIGINT as i32, 0);
+    }
+
+    pub fn add_double_field(&mut self, field: &str) {
+        self.add_field(field, FieldType::DOUBLE as i32, 0);
+    }
+
+    pub fn add_boolean_field(&mut self, field: &str) {
+        self.add_field(field, FieldType::BOOLEAN as i32, 0);
+    }
+
+    pub fn add_date_field(&mut self, field: &str) {
+        self.add_field(field, FieldType::DATE as i32, 0);
+    }
+
+    pub fn add_blob_field(&mut self, field: &str) {
+        self.add_field(field, FieldType::BLOB as i32, 0);
+    }
+
     pub fn add(&mut self, field: &str, schema: &Schema) {
         let ftype = schema.ftype(field);
         let length = schema.length(field);
@@ -53,8 +107,8 @@ impl Schema {
         }
     }
 
-    pub fn fields(&self) -> Vec<String> {
-        self.fields.clone()
+    pub fn fields(&self) -> &[Arc<str>] {
+        &self.fields
     }
 
     pub fn has_field(&self, field: &str) -> bool {
@@ -68,4 +122,34 @@ impl Schema {
     pub fn length(&self, field: &str) -> i32 {
         self.info.get(field).unwrap().length
     }
+
+    pub fn set_not_null(&mut self, field: &str) {
+        self.info.get_mut(field).unwrap().not_null = true;
+    }
+
+    pub fn is_not_null(&self, field: &str) -> bool {
+        self.info.get(field).unwrap().not_null
+    }
+
+    pub fn add_check(&mut self, field: &str, check: Check) {
+        self.info.get_mut(field).unwrap().checks.push(check);
+    }
+
+    pub fn checks(&self, field: &str) -> &[Check] {
+        &self.info.get(field).unwrap().checks
+    }
+
+    // Fails if the value is null and the field is NOT NULL, or with the
+    // first CHECK constraint of the field the value breaks. Constraints are
+    // not carried over by `add`, so the schemas of plans and temporary
+    // tables have none.
+    pub fn validate(&self, field: &str, val: &Constant) -> Result<(), ScanError> {
+        if val.is_null() && self.is_not_null(field) {
+            return Err(ScanError::ConstraintViolation { field: field.to_string(), constraint: "not null".to_string() });
+        }
+        match self.checks(field).iter().find(|check| !check.is_satisfied(val)) {
+            Some(check) => Err(ScanError::ConstraintViolation { field: field.to_string(), constraint: format!("check ({} {})", field, check) }),
+            None => Ok(()),
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,455 @@
+use std::sync::{Arc, Mutex};
+use crate::error::dberror::DbError;
+use crate::file::blockid::BlockId;
+use crate::file::page::Page;
+use crate::query::constant::Constant;
+use crate::record::layout::Layout;
+use crate::record::recordpage::RecordPage;
+use crate::record::row::Row;
+use crate::record::schema::FieldType;
+use crate::tx::transaction::Transaction;
+
+// The SlottedPage manages the records of a page in the variable-length
+// format, where each record takes only the bytes of its values. After the
+// page header come the number of slots and the offset where the record
+// data starts, then the slot directory, one (offset, length) entry per
+// slot, growing towards the end of the block. The records are packed from
+// the end of the block backwards. An entry with offset 0 is an empty slot.
+//
+// A record starts with its null bitmap, as in a fixed slot, followed by
+// the values of its non-null fields in the order of their offsets in the
+// layout; null fields take no bytes. A record that grows is moved to the
+// free space between the directory and the data, and the page is compacted
+// first if that space is too small, so that the slots, and the RecordIds,
+// never change. The page is only written through byte ranges.
+pub(crate) struct SlottedPage<'a> {
+    tx: &'a Arc<Mutex<Transaction>>,
+    blk: &'a BlockId,
+    layout: &'a Layout,
+}
+
+impl<'a> SlottedPage<'a> {
+    const SLOT_COUNT_POS: usize = Page::HEADER_SIZE;
+    const DATA_START_POS: usize = Page::HEADER_SIZE + 4;
+    const DIRECTORY_POS: usize = Page::HEADER_SIZE + 8;
+    const ENTRY_SIZE: usize = 8;
+
+    pub(crate) fn new(tx: &'a Arc<Mutex<Transaction>>, blk: &'a BlockId, layout: &'a Layout) -> SlottedPage<'a> {
+        SlottedPage { tx, blk, layout }
+    }
+
+    pub(crate) fn get_val(&self, slot: i32, field: &str) -> Result<Constant, DbError> {
+        Ok(self.get_row(slot)?.get(field).cloned().unwrap_or(Constant::Null))
+    }
+
+    pub(crate) fn is_null(&self, slot: i32, field: &str) -> Result<bool, DbError> {
+        Ok(self.get_val(slot, field)?.is_null())
+    }
+
+    pub(crate) fn set_val(&self, slot: i32, field: &str, val: Constant) -> Result<(), DbError> {
+        let mut row = Row::new();
+        row.set(field, val);
+        self.set_row(slot, &row)
+    }
+
+    pub(crate) fn set_null(&self, slot: i32, field: &str) -> Result<(), DbError> {
+        self.set_val(slot, field, Constant::Null)
+    }
+
+    pub(crate) fn get_row(&self, slot: i32) -> Result<Row, DbError> {
+        let (offset, len) = self.entry(slot)?;
+        Ok(self.decode(self.read(offset, len)?))
+    }
+
+    // Overlays the values in the row on the record and writes it back.
+    // Fields missing from the row are left as is.
+    pub(crate) fn set_row(&self, slot: i32, row: &Row) -> Result<(), DbError> {
+        let mut record = self.get_row(slot)?;
+        for (field, val) in row.iter() {
+            record.set(field, val.clone());
+        }
+        if self.write_record(slot, &self.encode(&record))? {
+            Ok(())
+        } else {
+            Err(DbError::RecordTooLarge(*self.blk))
+        }
+    }
+
+    pub(crate) fn next_after(&self, slot: Option<i32>) -> Result<Option<i32>, DbError> {
+        let directory = self.directory()?;
+        let start = slot.map_or(0, |slot| slot as usize + 1);
+        Ok((start..directory.len()).find(|slot| directory[*slot].0 != 0).map(|slot| slot as i32))
+    }
+
+    // Takes an empty slot after the specified one, or adds a slot at the end
+    // of the directory, and writes a record with every field null to it. A
+    // record is only inserted if the page has room for it to grow to the
+    // size of a fixed slot, and, below a fill factor of 100, while the used
+    // part of the page is under the fill factor.
+    pub(crate) fn insert_after(&self, slot: Option<i32>) -> Result<Option<i32>, DbError> {
+        let directory = self.directory()?;
+        let start = slot.map_or(0, |slot| slot as usize + 1);
+        let reuse = (start..directory.len()).find(|slot| directory[*slot].0 == 0);
+        let capacity = self.block_size() - Self::DIRECTORY_POS;
+        let used = directory.len() * Self::ENTRY_SIZE + directory.iter().map(|(_, len)| len).sum::<usize>();
+        let needed = Self::max_record_size(self.layout) + if reuse.is_some() { 0 } else { Self::ENTRY_SIZE };
+        let fill_factor = self.layout.fill_factor() as usize;
+        let filled = fill_factor < 100 && directory.iter().any(|(offset, _)| *offset != 0) && used >= capacity * fill_factor / 100;
+        if used + needed > capacity || filled {
+            return Ok(None);
+        }
+        let new_slot = match reuse {
+            Some(slot) => slot,
+            None => {
+                if self.data_start()? < self.directory_end(directory.len() + 1) {
+                    self.compact(None)?;
+                }
+                let mut entry = Vec::new();
+                entry.extend_from_slice(&0i32.to_be_bytes());
+                entry.extend_from_slice(&0i32.to_be_bytes());
+                self.write(self.entry_pos(directory.len()), &entry)?;
+                self.write(Self::SLOT_COUNT_POS, &(directory.len() as i32 + 1).to_be_bytes())?;
+                directory.len()
+            }
+        };
+        let written = self.write_record(new_slot as i32, &self.encode(&Row::new()))?;
+        debug_assert!(written, "the space of a new record was checked");
+        Ok(Some(new_slot as i32))
+    }
+
+    pub(crate) fn delete(&self, slot: i32) -> Result<(), DbError> {
+        self.set_entry(slot as usize, 0, 0)
+    }
+
+    // Formats the page with an empty directory and no data.
+    pub(crate) fn format(&self) -> Result<(), DbError> {
+        let mut header = Vec::new();
+        header.extend_from_slice(&0i32.to_be_bytes());
+        header.extend_from_slice(&(self.block_size() as i32).to_be_bytes());
+        self.tx.lock().unwrap().set_range(self.blk, Self::SLOT_COUNT_POS, &header, false)
+    }
+
+    // Writes the record to the slot, in place if it is not longer than the
+    // record already there, and otherwise at the start of the data,
+    // compacting the page if needed. Returns false, leaving the page as it
+    // is, if the block does not have the room for it.
+    fn write_record(&self, slot: i32, record: &[u8]) -> Result<bool, DbError> {
+        let slot = slot as usize;
+        let directory = self.directory()?;
+        let (offset, len) = directory[slot];
+        if offset != 0 && record.len() <= len {
+            self.write(offset, record)?;
+            return self.set_entry(slot, offset, record.len()).map(|_| true);
+        }
+        let others: usize = directory.iter().enumerate().filter(|(s, _)| *s != slot).map(|(_, (_, len))| len).sum();
+        let directory_end = self.directory_end(directory.len());
+        if directory_end + others + record.len() > self.block_size() {
+            return Ok(false);
+        }
+        let mut data_start = self.data_start()?;
+        if data_start - directory_end < record.len() {
+            data_start = self.compact(Some(slot))?;
+        }
+        let offset = data_start - record.len();
+        self.write(offset, record)?;
+        self.set_entry(slot, offset, record.len())?;
+        self.write(Self::DATA_START_POS, &(offset as i32).to_be_bytes())?;
+        Ok(true)
+    }
+
+    // Packs the records against the end of the block, keeping their order,
+    // and returns the new start of the data. The record of the slot to skip,
+    // which is about to be rewritten, is dropped.
+    fn compact(&self, skip: Option<usize>) -> Result<usize, DbError> {
+        let block_size = self.block_size();
+        let mut directory = self.directory()?;
+        let page = self.read(0, block_size)?;
+        let mut live: Vec<usize> = (0..directory.len()).filter(|slot| directory[*slot].0 != 0 && Some(*slot) != skip).collect();
+        live.sort_by_key(|slot| std::cmp::Reverse(directory[*slot].0));
+        let mut packed = vec![0; block_size];
+        let mut data_start = block_size;
+        for slot in live {
+            let (offset, len) = directory[slot];
+            data_start -= len;
+            packed[data_start..data_start + len].copy_from_slice(&page[offset..offset + len]);
+            directory[slot] = (data_start, len);
+        }
+        if let Some(slot) = skip {
+            directory[slot] = (0, 0);
+        }
+        self.write(data_start, &packed[data_start..])?;
+        let entries: Vec<u8> = directory.iter()
+            .flat_map(|(offset, len)| (*offset as i32).to_be_bytes().into_iter().chain((*len as i32).to_be_bytes()))
+            .collect();
+        self.write(Self::DIRECTORY_POS, &entries)?;
+        self.write(Self::DATA_START_POS, &(data_start as i32).to_be_bytes())?;
+        Ok(data_start)
+    }
+
+    // Encodes the values of the row, fields missing from it being null.
+    fn encode(&self, row: &Row) -> Vec<u8> {
+        let bitmap = self.layout.null_bitmap();
+        let mut page = Page::new(Self::max_record_size(self.layout));
+        let mut words = vec![0; bitmap.len()];
+        let mut pos = 4 * bitmap.len();
+        for field in self.fields() {
+            let (word, mask) = self.layout.null_bit(&field);
+            let word = Self::word(word);
+            match row.get(&field).unwrap_or(&Constant::Null) {
+                Constant::Null => {
+                    words[word] |= mask;
+                    continue;
+                }
+                Constant::Int(val) => page.set_int(pos, *val),
+                Constant::Long(val) | Constant::Date(val) => page.set_long(pos, *val),
+                Constant::Double(val) => page.set_double(pos, *val),
+                Constant::Bool(val) => page.set_bool(pos, *val),
+                Constant::String(val) => page.set_string(pos, val),
+                Constant::Blob(_) => unreachable!("records hold references to blobs"),
+            }
+            pos += Self::length(&page, pos, RecordPage::stored_type(self.layout, &field));
+        }
+        for (word, bits) in words.into_iter().enumerate() {
+            page.set_int(4 * word, bits);
+        }
+        page.bytebuffer.truncate(pos);
+        page.bytebuffer
+    }
+
+    fn decode(&self, record: Vec<u8>) -> Row {
+        let page = Page::wrap(record);
+        let mut row = Row::new();
+        let mut pos = 4 * self.layout.null_bitmap().len();
+        for field in self.fields() {
+            let (word, mask) = self.layout.null_bit(&field);
+            if page.get_int(4 * Self::word(word)) & mask != 0 {
+                row.set(&field, Constant::Null);
+                continue;
+            }
+            let ftype = RecordPage::stored_type(self.layout, &field);
+            let val = match ftype {
+                FieldType::INTEGER => Constant::Int(page.get_int(pos)),
+                FieldType::BIGINT | FieldType::BLOB => Constant::Long(page.get_long(pos)),
+                FieldType::DATE => Constant::Date(page.get_long(pos)),
+                FieldType::DOUBLE => Constant::Double(page.get_double(pos)),
+                FieldType::BOOLEAN => Constant::Bool(page.get_bool(pos)),
+                FieldType::VARCHAR => Constant::String(page.get_string(pos)),
+            };
+            pos += Self::length(&page, pos, ftype);
+            row.set(&field, val);
+        }
+        row
+    }
+
+    // The number of bytes of the value at the position.
+    fn length(page: &Page, pos: usize, ftype: FieldType) -> usize {
+        match ftype {
+            FieldType::INTEGER => 4,
+            FieldType::BIGINT | FieldType::DOUBLE | FieldType::DATE | FieldType::BLOB => 8,
+            FieldType::BOOLEAN => 1,
+            FieldType::VARCHAR => 4 + page.get_bytes(pos).len(),
+        }
+    }
+
+    // The fields in the order of their offsets.
+    fn fields(&self) -> Vec<Arc<str>> {
+        let mut fields = self.layout.schema().fields().to_vec();
+        fields.sort_by_key(|field| self.layout.offset(field));
+        fields
+    }
+
+    // The index of the bitmap integer at the offset in a fixed slot.
+    fn word(offset: i32) -> usize {
+        (offset - Layout::NULL_BITMAP_OFFSET) as usize / 4
+    }
+
+    // The longest a record gets: a fixed slot without its flag.
+    fn max_record_size(layout: &Layout) -> usize {
+        (layout.slot_size() - Layout::NULL_BITMAP_OFFSET) as usize
+    }
+
+    // Whether the longest record, with its directory entry, fits in an
+    // empty page.
+    pub(crate) fn fits(layout: &Layout, block_size: usize) -> bool {
+        Self::DIRECTORY_POS + Self::ENTRY_SIZE + Self::max_record_size(layout) <= block_size
+    }
+
+    fn directory(&self) -> Result<Vec<(usize, usize)>, DbError> {
+        let count = self.read_int(Self::SLOT_COUNT_POS)? as usize;
+        let page = Page::wrap(self.read(Self::DIRECTORY_POS, count * Self::ENTRY_SIZE)?);
+        Ok((0..count).map(|slot| {
+            let pos = slot * Self::ENTRY_SIZE;
+            (page.get_int(pos) as usize, page.get_int(pos + 4) as usize)
+        }).collect())
+    }
+
+    fn entry(&self, slot: i32) -> Result<(usize, usize), DbError> {
+        let page = Page::wrap(self.read(self.entry_pos(slot as usize), Self::ENTRY_SIZE)?);
+        Ok((page.get_int(0) as usize, page.get_int(4) as usize))
+    }
+
+    fn set_entry(&self, slot: usize, offset: usize, len: usize) -> Result<(), DbError> {
+        let mut page = Page::new(Self::ENTRY_SIZE);
+        page.set_int(0, offset as i32);
+        page.set_int(4, len as i32);
+        self.write(self.entry_pos(slot), &page.bytebuffer)
+    }
+
+    fn entry_pos(&self, slot: usize) -> usize {
+        Self::DIRECTORY_POS + slot * Self::ENTRY_SIZE
+    }
+
+    fn directory_end(&self, slots: usize) -> usize {
+        self.entry_pos(slots)
+    }
+
+    fn data_start(&self) -> Result<usize, DbError> {
+        Ok(self.read_int(Self::DATA_START_POS)? as usize)
+    }
+
+    fn read_int(&self, offset: usize) -> Result<i32, DbError> {
+        Ok(Page::wrap(self.read(offset, 4)?).get_int(0))
+    }
+
+    fn read(&self, offset: usize, len: usize) -> Result<Vec<u8>, DbError> {
+        Ok(self.tx.lock().unwrap().get_range(self.blk, offset, len)?.unwrap())
+    }
+
+    fn write(&self, offset: usize, bytes: &[u8]) -> Result<(), DbError> {
+        self.tx.lock().unwrap().set_range(self.blk, offset, bytes, true)
+    }
+
+    fn block_size(&self) -> usize {
+        self.tx.lock().unwrap().block_size()
+    }
+}
+
+mod tests {
+    use std::path::PathBuf;
+    use super::*;
+    use crate::buffer::buffermgr::BufferMgr;
+    use crate::file::filemgr::FileMgr;
+    use crate::log::logmgr::LogMgr;
+    use crate::tx::admission::AdmissionCtl;
+    use crate::query::scan::Scan;
+    use crate::record::layout::RecordFormat;
+    use crate::record::scanerror::ScanError;
+    use crate::record::schema::Schema;
+    use crate::record::tablescan::{TableScan, UpdateScan};
+
+    fn layout() -> Arc<Layout> {
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 60);
+        Arc::new(Layout::new(schema).with_format(RecordFormat::Variable))
+    }
+
+    #[test]
+    fn test_slotted_page() {
+        let _ = std::fs::remove_dir_all("slottedpagetestdb");
+        let fm = Arc::new(FileMgr::new(PathBuf::from("slottedpagetestdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
+        let bm = Arc::new(Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 8)));
+        let tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap()));
+        let layout = layout();
+
+        let mut ts = TableScan::new(tx.clone(), layout.clone(), "slotted").unwrap();
+        for i in 0..30 {
+            ts.insert().unwrap();
+            ts.set_int("A", i).unwrap();
+            ts.set_string("B", &format!("r{}", i)).unwrap();
+        }
+        // Short strings take a fraction of their 76-byte fixed slot, of
+        // which a block holds 5.
+        let mut in_first = Vec::new();
+        ts.before_first().unwrap();
+        while ts.next().unwrap() {
+            let rid = ts.rid().unwrap();
+            assert_eq!(ts.get_string("B").unwrap(), format!("r{}", ts.get_int("A").unwrap()));
+            if rid.blocknum == 0 {
+                in_first.push(rid);
+            }
+        }
+        assert!(in_first.len() > 10, "{} records in the first block", in_first.len());
+
+        // Records grow until the block is out of space.
+        let long = |i: i32| format!("{}{}", "x".repeat(30), i);
+        let mut grown = 0;
+        let error = loop {
+            ts.move_to_rid(&in_first[grown]).unwrap();
+            match ts.set_string("B", &long(grown as i32)) {
+                Ok(()) => grown += 1,
+                Err(error) => break error,
+            }
+        };
+        assert_eq!(error, ScanError::Db(DbError::RecordTooLarge(fm.block("slotted.tbl", 0))));
+        assert_eq!(ts.get_string("B").unwrap(), format!("r{}", grown));
+
+        // Freeing the grown records leaves holes that only a compaction
+        // puts together. The other records keep their slots.
+        for rid in &in_first[..grown] {
+            ts.move_to_rid(rid).unwrap();
+            ts.delete().unwrap();
+        }
+        ts.move_to_rid(&in_first[grown]).unwrap();
+        ts.set_string("B", &long(grown as i32)).unwrap();
+        for (i, rid) in in_first.iter().enumerate().skip(grown) {
+            ts.move_to_rid(rid).unwrap();
+            let expected = if i == grown { long(i as i32) } else { format!("r{}", i) };
+            assert_eq!((ts.get_int("A").unwrap(), ts.get_string("B").unwrap()), (i as i32, expected));
+        }
+        ts.move_to_rid(&in_first[grown]).unwrap();
+        ts.set_null("B").unwrap();
+        assert_eq!((ts.get_int("A").unwrap(), ts.get_val("B").unwrap()), (grown as i32, Constant::Null));
+
+        ts.close();
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_slotted_page_rollback() {
+        let _ = std::fs::remove_dir_all("slottedrollbacktestdb");
+        let fm = Arc::new(FileMgr::new(PathBuf::from("slottedrollbacktestdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
+        let bm = Arc::new(Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 8)));
+        let layout = layout();
+        let read = |tx: &Arc<Mutex<Transaction>>| {
+            let mut ts = TableScan::new(tx.clone(), layout.clone(), "slottedrollback").unwrap();
+            let mut rows = Vec::new();
+            while ts.next().unwrap() {
+                rows.push((ts.rid().unwrap(), ts.get_int("A").unwrap(), ts.get_val("B").unwrap()));
+            }
+            ts.close();
+            rows
+        };
+
+        let tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap()));
+        let mut ts = TableScan::new(tx.clone(), layout.clone(), "slottedrollback").unwrap();
+        for i in 0..3 {
+            ts.insert().unwrap();
+            ts.set_int("A", i).unwrap();
+            ts.set_string("B", &format!("r{}", i)).unwrap();
+        }
+        ts.close();
+        let committed = read(&tx);
+        tx.lock().unwrap().commit().unwrap();
+
+        // A deleted slot is reused by the next insert after it.
+        let tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap()));
+        let mut ts = TableScan::new(tx.clone(), layout.clone(), "slottedrollback").unwrap();
+        ts.move_to_rid(&committed[1].0).unwrap();
+        ts.delete().unwrap();
+        ts.move_to_rid(&committed[0].0).unwrap();
+        ts.set_string("B", &"y".repeat(60)).unwrap();
+        ts.insert().unwrap();
+        assert_eq!(ts.rid(), Some(committed[1].0.clone()));
+        ts.set_int("A", 7).unwrap();
+        ts.close();
+        assert_eq!(read(&tx).iter().map(|(_, a, _)| *a).collect::<Vec<_>>(), [0, 7, 2]);
+        tx.lock().unwrap().rollback().unwrap();
+
+        let tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap()));
+        assert_eq!(read(&tx), committed);
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
@@ -1,170 +1,459 @@
+use std::io::Read;
 use std::sync::{Arc, Mutex};
-use crate::file::blockid::BlockId;
+use crate::error::dberror::DbError;
+use crate::file::blockid::{BlockId, FileId};
 use crate::record::layout::Layout;
+use crate::record::overflow::{BlobReader, Overflow};
 use crate::record::recordpage::RecordPage;
+use crate::record::row::Row;
+use crate::query::constant::Constant;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::FieldType;
+use crate::tx::rowchange::ChangeOp;
 use crate::tx::transaction::Transaction;
 
-#[derive(Debug)]
-struct RecordId {
-    blocknum: i32,
-    slot: i32,
+// RecordIds order by block, then slot, which is the order of the records
+// in the file.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RecordId {
+    pub(crate) blocknum: i32,
+    pub(crate) slot: i32,
 }
 
-pub trait UpdateScan {
-    fn set_int(&mut self, field: &str, val: i32);
-    fn set_string(&mut self, field: &str, val: &str);
-    fn insert(&mut self);
-    fn delete(&mut self);
+pub trait UpdateScan: Scan {
+    fn set_int(&mut self, field: &str, val: i32) -> Result<(), ScanError>;
+    fn set_string(&mut self, field: &str, val: &str) -> Result<(), ScanError>;
+    fn set_val(&mut self, field: &str, val: &Constant) -> Result<(), ScanError>;
+    fn set_long(&mut self, field: &str, val: i64) -> Result<(), ScanError> {
+        self.set_val(field, &Constant::Long(val))
+    }
+    fn set_double(&mut self, field: &str, val: f64) -> Result<(), ScanError> {
+        self.set_val(field, &Constant::Double(val))
+    }
+    fn set_bool(&mut self, field: &str, val: bool) -> Result<(), ScanError> {
+        self.set_val(field, &Constant::Bool(val))
+    }
+    fn set_date(&mut self, field: &str, val: i64) -> Result<(), ScanError> {
+        self.set_val(field, &Constant::Date(val))
+    }
+    fn set_blob(&mut self, field: &str, val: &[u8]) -> Result<(), ScanError> {
+        self.set_val(field, &Constant::Blob(val.to_vec()))
+    }
+    fn set_null(&mut self, field: &str) -> Result<(), ScanError>;
+    fn set_row(&mut self, row: &Row) -> Result<(), ScanError>;
+    fn insert(&mut self) -> Result<(), ScanError>;
+    fn delete(&mut self) -> Result<(), ScanError>;
     fn rid(&self) -> Option<RecordId>;
-    fn move_to_rid(&mut self, rid: &RecordId);
+    fn move_to_rid(&mut self, rid: &RecordId) -> Result<(), ScanError>;
 }
 
 pub(crate) struct TableScan {
     tx: Arc<Mutex<Transaction>>,
-    layout: Layout,
+    layout: Arc<Layout>,
     rp: Option<RecordPage>,
     filename: String,
+    file: FileId,
     current_slot: Option<i32>,
+    overflow: Overflow,
 }
 
 // The TableScan keeps track of the current record, allowing the client to change the record
 // and access its fields. It hides the block structure from the client. The client will not know,
 // or even care, that the table is stored in blocks.
+// Blobs are kept in the table's overflow blocks, and the record only holds a reference to them:
+// the scan follows the reference when a blob is read, writes a new chain of blocks when one is
+// set, and frees the chain of a blob that is overwritten, set to null or deleted. VARCHAR fields
+// spilled by the layout are kept the same way, and read back as strings.
 impl TableScan {
-    pub fn new(tx: Arc<Mutex<Transaction>>, layout: Layout, table: &str) -> TableScan {
+    pub fn new(tx: Arc<Mutex<Transaction>>, layout: Arc<Layout>, table: &str) -> Result<TableScan, ScanError> {
         let filename = format!("{}.tbl", table);
-        let mut ts = TableScan { tx: tx.clone(), layout, rp: None, filename: filename.clone(), current_slot: None };
-        if tx.lock().unwrap().size(&filename) == 0 {
-            ts.move_to_new_block();
+        let overflow = Overflow::new(tx.clone(), table);
+        let file = tx.lock().unwrap().file_id(&filename);
+        let mut ts = TableScan { tx: tx.clone(), layout, rp: None, filename: filename.clone(), file, current_slot: None, overflow };
+        let size = tx.lock().unwrap().size(&filename)?;
+        if size == 0 {
+            ts.move_to_new_block()?;
+        } else {
+            ts.move_to_block(0)?;
+        }
+        Ok(ts)
+    }
+
+    // Inserts a record with the values of the row. The fields left out are
+    // null, so every NOT NULL field must have a value in the row; if a value
+    // breaks a constraint, the record is deleted again and the violation
+    // returned.
+    pub(crate) fn insert_row(&mut self, row: &Row) -> Result<(), ScanError> {
+        let schema = self.layout.schema().clone();
+        if let Some(field) = schema.fields().iter().find(|field| row.get(field).is_none() && schema.is_not_null(field)) {
+            return Err(ScanError::ConstraintViolation { field: field.to_string(), constraint: "not null".to_string() });
+        }
+        self.insert()?;
+        let result = self.set_row(row);
+        if result.is_err() {
+            self.delete()?;
+        }
+        result
+    }
+
+    // Positions the scan before the first record of the block, for a scan
+    // of part of the table.
+    pub(crate) fn before_block(&mut self, block_num: usize) -> Result<(), ScanError> {
+        self.move_to_block(block_num as i32)
+    }
+
+    // Returns all the fields of the current record at once.
+    pub(crate) fn get_row(&mut self) -> Result<Row, ScanError> {
+        let (rp, slot) = self.current_record()?;
+        let mut row = rp.get_row(slot)?;
+        for field in self.overflow_fields() {
+            if let Some(Constant::Long(reference)) = row.get(&field) {
+                let val = self.read_overflow(&field, *reference)?;
+                row.set(&field, val);
+            }
+        }
+        Ok(row)
+    }
+
+    // Returns a reader that streams the blob in the field of the current
+    // record, rather than reading it whole as `get_val` does. The reader
+    // goes through the scan's transaction, and the blob must not be changed
+    // while it is read.
+    pub(crate) fn blob_reader(&mut self, field: &str) -> Result<BlobReader, ScanError> {
+        self.check_field(field, FieldType::BLOB)?;
+        let (rp, slot) = self.current_record()?;
+        match rp.get_val(slot, field)? {
+            Constant::Long(reference) => Ok(self.overflow.reader(reference)),
+            _ => Err(ScanError::NullValue(field.to_string())),
+        }
+    }
+
+    // Writes the bytes read from the source to the blob field of the
+    // current record, without holding them in memory whole, and returns
+    // their number.
+    pub(crate) fn write_blob(&mut self, field: &str, src: &mut impl Read) -> Result<usize, ScanError> {
+        self.check_field(field, FieldType::BLOB)?;
+        self.current_record()?;
+        let reference = self.store_blob(field, src)?;
+        let (rp, slot) = self.current_record()?;
+        rp.set_val(slot, field, Constant::Long(reference))?;
+        self.record_change(ChangeOp::Modify);
+        Ok(Overflow::length(reference))
+    }
+
+    // Writes the blob to a new chain of overflow blocks and frees the chain
+    // of the field's current value, returning the reference to the new one.
+    // The old chain is kept until the new one is written, so a failed write
+    // leaves the field as it was.
+    fn store_blob(&mut self, field: &str, src: &mut impl Read) -> Result<i64, ScanError> {
+        let too_long = || ScanError::ValueTooLong { field: field.to_string(), max: Overflow::MAX_LENGTH as i32 };
+        let reference = self.overflow.write(src)?.ok_or_else(too_long)?;
+        self.free_blob(field)?;
+        Ok(reference)
+    }
+
+    fn free_blob(&mut self, field: &str) -> Result<(), ScanError> {
+        let (rp, slot) = self.current_record()?;
+        if let Constant::Long(reference) = rp.get_val(slot, field)? {
+            self.overflow.free(reference)?;
+        }
+        Ok(())
+    }
+
+    // Reads the value of a blob or spilled field from its overflow blocks.
+    fn read_overflow(&self, field: &str, reference: i64) -> Result<Constant, ScanError> {
+        let bytes = self.overflow.read(reference)?;
+        if self.layout.is_spilled(field) {
+            let corrupt = |_| DbError::Corruption(format!("spilled value of field {} is not UTF-8", field));
+            Ok(Constant::String(String::from_utf8(bytes).map_err(corrupt)?))
         } else {
-            ts.move_to_block(0);
+            Ok(Constant::Blob(bytes))
         }
-        ts
     }
 
-    fn before_first(&mut self) {
-        self.move_to_block(0);
+    // Whether the field's value is kept in the overflow blocks: a blob, or
+    // a VARCHAR spilled from a record too wide for a block.
+    fn in_overflow(&self, field: &str) -> bool {
+        self.layout.schema().ftype(field) == FieldType::BLOB as i32 || self.layout.is_spilled(field)
+    }
+
+    fn overflow_fields(&self) -> Vec<Arc<str>> {
+        self.layout.schema().fields().iter().filter(|field| self.in_overflow(field)).cloned().collect()
+    }
+
+    // Tells the transaction that the current record changed, so that the
+    // observers of the table hear of it once the transaction commits.
+    fn record_change(&self, op: ChangeOp) {
+        if let Some(rid) = self.rid() {
+            let table = self.filename.trim_end_matches(".tbl");
+            self.tx.lock().unwrap().record_change(table, op, rid);
+        }
+    }
+
+    // Checks that the field is part of the schema and has the expected type.
+    fn check_field(&self, field: &str, expected: FieldType) -> Result<(), ScanError> {
+        if !self.has_field(field) {
+            return Err(ScanError::UnknownField(field.to_string()));
+        }
+        if self.layout.schema().ftype(field) != expected as i32 {
+            return Err(ScanError::TypeMismatch { field: field.to_string(), expected });
+        }
+        Ok(())
     }
 
-    pub(crate) fn next(&mut self) -> bool {
+    // Checks that the value can be stored in the field and converts it to
+    // the field's type, as `Constant::coerce` allows; the mismatch error
+    // names the type of the value. The converted value must also fit in the
+    // field and satisfy its constraints.
+    fn prepare(&self, field: &str, val: &Constant) -> Result<Constant, ScanError> {
+        if !self.has_field(field) {
+            return Err(ScanError::UnknownField(field.to_string()));
+        }
+        let ftype = FieldType::try_from(self.layout.schema().ftype(field)).unwrap();
+        let expected = val.ftype().unwrap_or(ftype);
+        let val = val.coerce(ftype).ok_or(ScanError::TypeMismatch { field: field.to_string(), expected })?;
+        if let Constant::String(s) = &val {
+            self.check_length(field, s)?;
+        }
+        self.layout.schema().validate(field, &val)?;
+        Ok(val)
+    }
+
+    // Checks that the string fits in the field; a longer one would spill
+    // into the next field or slot.
+    fn check_length(&self, field: &str, val: &str) -> Result<(), ScanError> {
+        let max = self.layout.schema().length(field);
+        if val.len() > max as usize {
+            return Err(ScanError::ValueTooLong { field: field.to_string(), max });
+        }
+        Ok(())
+    }
+
+    fn current_record(&mut self) -> Result<(&mut RecordPage, i32), ScanError> {
+        match (&mut self.rp, self.current_slot) {
+            (Some(rp), Some(slot)) => Ok((rp, slot)),
+            _ => Err(ScanError::NoCurrentRecord),
+        }
+    }
+
+    fn move_to_block(&mut self, block_num: i32) -> Result<(), ScanError> {
+        self.close();
+        let blk = BlockId::new(self.file, block_num as usize);
+        self.rp = Some(RecordPage::new(self.tx.clone(), blk, self.layout.clone())?);
+        self.current_slot = None;
+        Ok(())
+    }
+
+    fn move_to_new_block(&mut self) -> Result<(), ScanError> {
+        self.close();
+        let blk = self.tx.lock().unwrap().append(&self.filename)?;
+        let mut rp = RecordPage::new(self.tx.clone(), blk, self.layout.clone())?;
+        rp.format()?;
+        self.rp = Some(rp);
+        self.current_slot = None;
+        Ok(())
+    }
+
+    fn at_last_block(&self) -> Result<bool, ScanError> {
+        match &self.rp {
+            Some(rp) => Ok(rp.block_id().number == self.tx.lock().unwrap().size(&self.filename)? - 1),
+            None => Ok(false),
+        }
+    }
+}
+
+impl Scan for TableScan {
+    fn before_first(&mut self) -> Result<(), ScanError> {
+        self.move_to_block(0)
+    }
+
+    fn next(&mut self) -> Result<bool, ScanError> {
         if let Some(rp) = &mut self.rp {
-            self.current_slot = rp.next_after(self.current_slot);
+            self.current_slot = rp.next_after(self.current_slot)?;
         }
         while self.current_slot.is_none() {
-            if self.at_last_block() {
-                return false;
+            if self.at_last_block()? {
+                return Ok(false);
             }
             let mut blk = None;
             if let Some(rp) = &self.rp {
                 blk = Some(rp.block_id().number + 1);
             }
             if let Some(blk) = blk {
-                self.move_to_block(blk as i32);
+                self.move_to_block(blk as i32)?;
             }
             if let Some(rp) = &mut self.rp {
-                self.current_slot = rp.next_after(self.current_slot);
+                self.current_slot = rp.next_after(self.current_slot)?;
             }
         }
-        true
+        Ok(true)
     }
 
-    pub(crate) fn get_int(&mut self, field: &str) -> i32 {
-        if let Some(rp) = &mut self.rp {
-            if let Some(slot) = self.current_slot {
-                return rp.get_int(slot, field)
-            }
+    fn get_int(&mut self, field: &str) -> Result<i32, ScanError> {
+        self.check_field(field, FieldType::INTEGER)?;
+        match self.get_val(field)? {
+            Constant::Int(val) => Ok(val),
+            _ => Err(ScanError::NullValue(field.to_string())),
         }
-        -1
     }
 
-    pub(crate) fn get_string(&mut self, field: &str) -> String {
-        if let Some(rp) = &mut self.rp {
-            if let Some(slot) = self.current_slot {
-                return rp.get_string(slot, field)
-            }
+    fn get_string(&mut self, field: &str) -> Result<String, ScanError> {
+        self.check_field(field, FieldType::VARCHAR)?;
+        match self.get_val(field)? {
+            Constant::String(val) => Ok(val),
+            _ => Err(ScanError::NullValue(field.to_string())),
+        }
+    }
+
+    // Returns the value of the field in the current record, typed according
+    // to the layout's schema, or Null.
+    fn get_val(&mut self, field: &str) -> Result<Constant, ScanError> {
+        if !self.has_field(field) {
+            return Err(ScanError::UnknownField(field.to_string()));
+        }
+        let overflow = self.in_overflow(field);
+        let (rp, slot) = self.current_record()?;
+        match rp.get_val(slot, field)? {
+            Constant::Long(reference) if overflow => self.read_overflow(field, reference),
+            val => Ok(val),
         }
-        "".to_string()
     }
 
     fn has_field(&self, field: &str) -> bool {
         self.layout.schema().has_field(field)
     }
 
-    pub fn close(&self) {
+    fn is_null(&mut self, field: &str) -> Result<bool, ScanError> {
+        if !self.has_field(field) {
+            return Err(ScanError::UnknownField(field.to_string()));
+        }
+        let (rp, slot) = self.current_record()?;
+        Ok(rp.is_null(slot, field)?)
+    }
+
+    fn close(&mut self) {
         if let Some(rp) = &self.rp {
             self.tx.lock().unwrap().unpin(&rp.block_id());
         }
     }
+}
 
-    fn move_to_block(&mut self, block_num: i32) {
-        self.close();
-        let blk = BlockId::new(&self.filename, block_num as usize);
-        self.rp = Some(RecordPage::new(self.tx.clone(), blk, self.layout.clone()));
-        self.current_slot = None;
+impl UpdateScan for TableScan {
+    fn set_int(&mut self, field: &str, val: i32) -> Result<(), ScanError> {
+        self.set_val(field, &Constant::Int(val))
     }
 
-    fn move_to_new_block(&mut self) {
-        self.close();
-        let blk = self.tx.lock().unwrap().append(&self.filename);
-        let mut rp = RecordPage::new(self.tx.clone(), blk, self.layout.clone());
-        rp.format();
-        self.rp = Some(rp);
-        self.current_slot = None;
+    fn set_string(&mut self, field: &str, val: &str) -> Result<(), ScanError> {
+        self.set_val(field, &Constant::String(val.to_string()))
     }
 
-    fn at_last_block(&self) -> bool {
-        self.rp.as_ref().map_or(false, |rp| rp.block_id().number == self.tx.lock().unwrap().size(&self.filename) - 1)
+    fn set_val(&mut self, field: &str, val: &Constant) -> Result<(), ScanError> {
+        if val.is_null() {
+            return self.set_null(field);
+        }
+        match self.prepare(field, val)? {
+            Constant::Blob(val) => self.write_blob(field, &mut val.as_slice()).map(|_| ()),
+            Constant::String(val) if self.layout.is_spilled(field) => {
+                let reference = self.store_blob(field, &mut val.as_bytes())?;
+                let (rp, slot) = self.current_record()?;
+                rp.set_val(slot, field, Constant::Long(reference))?;
+                self.record_change(ChangeOp::Modify);
+                Ok(())
+            }
+            val => {
+                let (rp, slot) = self.current_record()?;
+                rp.set_val(slot, field, val)?;
+                self.record_change(ChangeOp::Modify);
+                Ok(())
+            }
+        }
     }
-}
 
-impl UpdateScan for TableScan {
-     fn set_int(&mut self, field: &str, val: i32) {
-        if let Some(rp) = &mut self.rp {
-            if let Some(slot) = self.current_slot {
-                rp.set_int(slot, field, val);
-            }
+    fn set_null(&mut self, field: &str) -> Result<(), ScanError> {
+        if !self.has_field(field) {
+            return Err(ScanError::UnknownField(field.to_string()));
         }
+        self.layout.schema().validate(field, &Constant::Null)?;
+        if self.in_overflow(field) {
+            self.free_blob(field)?;
+        }
+        let (rp, slot) = self.current_record()?;
+        rp.set_null(slot, field)?;
+        self.record_change(ChangeOp::Modify);
+        Ok(())
     }
 
-     fn set_string(&mut self, field: &str, val: &str) {
-        if let Some(rp) = &mut self.rp {
-            if let Some(slot) = self.current_slot {
-                rp.set_string(slot, field, val);
+    fn set_row(&mut self, row: &Row) -> Result<(), ScanError> {
+        let mut prepared = Row::new();
+        for (field, val) in row.iter() {
+            prepared.set(field, self.prepare(field, val)?);
+        }
+        for field in self.overflow_fields() {
+            match prepared.get(&field) {
+                Some(Constant::Blob(val)) => {
+                    let reference = self.store_blob(&field, &mut val.as_slice())?;
+                    prepared.set(&field, Constant::Long(reference));
+                }
+                Some(Constant::String(val)) => {
+                    let reference = self.store_blob(&field, &mut val.as_bytes())?;
+                    prepared.set(&field, Constant::Long(reference));
+                }
+                Some(Constant::Null) => self.free_blob(&field)?,
+                _ => {}
             }
         }
+        let (rp, slot) = self.current_record()?;
+        rp.set_row(slot, &prepared)?;
+        self.record_change(ChangeOp::Modify);
+        Ok(())
     }
 
     // The insert method tries to insert a new record starting after the current record.
-    // If the block is full, it moves to the next one and continues until it finds an empty slot.
+    // If no slot is found and the whole block was searched, the block is recorded as full
+    // in the free-space map, which then points at the first block that may still have room.
     // If all blocks are full, it appends a new block to the file and inserts the record there.
-    fn insert(&mut self) {
+    fn insert(&mut self) -> Result<(), ScanError> {
+        if !RecordPage::fits(&self.layout, self.tx.lock().unwrap().block_size()) {
+            let table = self.filename.trim_end_matches(".tbl").to_string();
+            return Err(ScanError::RecordTooWide { table, size: self.layout.slot_size() });
+        }
+        let mut searched_block = self.current_slot.is_none();
         if let Some(rp) = &mut self.rp {
-            self.current_slot = rp.insert_after(self.current_slot);
+            self.current_slot = rp.insert_after(self.current_slot)?;
         }
         while self.current_slot.is_none() {
-            if self.at_last_block() {
-                self.move_to_new_block();
-            } else {
-                let mut blk = None;
-                if let Some(rp) = &self.rp {
-                    blk = Some(rp.block_id().number + 1);
-                }
-                if let Some(blk) = blk {
-                    self.move_to_block(blk as i32);
-                }
+            if let Some(rp) = &self.rp && searched_block {
+                self.tx.lock().unwrap().free_space().mark_full(rp.block_id());
+            }
+            let first_free = {
+                let mut tx = self.tx.lock().unwrap();
+                let size = tx.size(&self.filename)?;
+                tx.free_space().first_free(self.file, size)
+            };
+            match first_free {
+                Some(blk) => self.move_to_block(blk as i32)?,
+                None => self.move_to_new_block()?,
             }
+            searched_block = true;
             if let Some(rp) = &mut self.rp {
-                self.current_slot = rp.insert_after(self.current_slot);
+                self.current_slot = rp.insert_after(self.current_slot)?;
             }
         }
+        self.record_change(ChangeOp::Insert);
+        Ok(())
     }
 
-    fn delete(&mut self) {
-        if let Some(rp) = &mut self.rp {
-            if let Some(slot) = self.current_slot {
-                rp.delete(slot);
-            }
+    fn delete(&mut self) -> Result<(), ScanError> {
+        for field in self.overflow_fields() {
+            self.free_blob(&field)?;
         }
+        let (rp, slot) = self.current_record()?;
+        rp.delete(slot)?;
+        let blk = *rp.block_id();
+        self.tx.lock().unwrap().free_space().mark_free(&blk);
+        self.record_change(ChangeOp::Delete);
+        Ok(())
     }
 
     fn rid(&self) -> Option<RecordId>{
@@ -176,11 +465,12 @@ impl UpdateScan for TableScan {
         None
     }
 
-    fn move_to_rid(&mut self, rid: &RecordId) {
+    fn move_to_rid(&mut self, rid: &RecordId) -> Result<(), ScanError> {
         self.close();
-        let blk = BlockId::new(&self.filename, rid.blocknum as usize);
-        self.rp = Some(RecordPage::new(self.tx.clone(), blk, self.layout.clone()));
+        let blk = BlockId::new(self.file, rid.blocknum as usize);
+        self.rp = Some(RecordPage::new(self.tx.clone(), blk, self.layout.clone())?);
         self.current_slot = Some(rid.slot);
+        Ok(())
     }
 }
 
@@ -190,54 +480,198 @@ mod tests {
     use crate::buffer::buffermgr::BufferMgr;
     use crate::file::filemgr::FileMgr;
     use crate::log::logmgr::LogMgr;
+    use crate::tx::admission::AdmissionCtl;
     use super::*;
     use crate::record::schema::Schema;
 
     #[test]
     fn test_table_scan() {
-        let fm = Arc::new(FileMgr::new(PathBuf::from("tablescantestdb"), 400));
-        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log")));
+        let fm = Arc::new(FileMgr::new(PathBuf::from("tablescantestdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
         let bm = Arc::new(Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 8)));
-        let mut tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone())));
+        let mut tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap()));
 
         let mut schema = Schema::new();
         schema.add_int_field("A");
         schema.add_int_field("B");
-        let layout = Layout::new(schema);
+        let layout = Arc::new(Layout::new(schema));
         for fname in layout.schema().fields() {
-            let offset = layout.offset(&fname);
+            let offset = layout.offset(fname);
             println!("Field {} starts at offset {}", fname, offset);
         }
         println!("The slot size is {}", layout.slot_size());
 
         println!("Filling the table with 50 random records...");
-        let mut ts = TableScan::new(tx.clone(), layout, "T1");
+        let mut ts = TableScan::new(tx.clone(), layout, "T1").unwrap();
         for i in 0..50 {
-            ts.insert();
+            ts.insert().unwrap();
             let num = rand::rng().random_range(0..50);
-            ts.set_int("A", num);
-            ts.set_int("B", num);
+            ts.set_int("A", num).unwrap();
+            ts.set_int("B", num).unwrap();
             println!("Inserting into slot {:?}: ({}, {})", ts.rid().unwrap(), num, num);
         }
 
         println!("Deleting records with A < 25...");
         let mut count = 0;
-        ts.before_first();
-        while ts.next() {
-            if ts.get_int("A") < 25 {
-                ts.delete();
+        ts.before_first().unwrap();
+        while ts.next().unwrap() {
+            if ts.get_int("A").unwrap() < 25 {
+                ts.delete().unwrap();
                 count += 1;
             }
         }
         println!("Deleted {} records", count);
 
         println!("Here are the remaining records:");
-        ts.before_first();
-        while ts.next() {
-            println!("Slot {:?}: ({}, {})", ts.rid().unwrap(), ts.get_int("A"), ts.get_int( "B"));
+        ts.before_first().unwrap();
+        while ts.next().unwrap() {
+            println!("Slot {:?}: ({}, {})", ts.rid().unwrap(), ts.get_int("A").unwrap(), ts.get_int( "B").unwrap());
         }
 
         ts.close();
-        tx.lock().unwrap().commit();
+        tx.lock().unwrap().commit().unwrap();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_table_scan_field_errors() {
+        let fm = Arc::new(FileMgr::new(PathBuf::from("tablescantestdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
+        let bm = Arc::new(Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 8)));
+        let tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap()));
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_string_field("B", 9);
+        let layout = Arc::new(Layout::new(schema));
+
+        let mut ts = TableScan::new(tx.clone(), layout, "T2").unwrap();
+        assert_eq!(ts.get_int("A"), Err(ScanError::NoCurrentRecord));
+
+        ts.insert().unwrap();
+        ts.set_int("A", 7).unwrap();
+        ts.set_val("B", &Constant::String("seven".to_string())).unwrap();
+        assert_eq!(ts.get_val("A"), Ok(Constant::Int(7)));
+        assert_eq!(ts.get_val("B"), Ok(Constant::String("seven".to_string())));
+        assert_eq!(ts.get_int("C"), Err(ScanError::UnknownField("C".to_string())));
+        assert_eq!(ts.get_int("B"), Err(ScanError::TypeMismatch { field: "B".to_string(), expected: FieldType::INTEGER }));
+        assert_eq!(ts.set_string("A", "x"), Err(ScanError::TypeMismatch { field: "A".to_string(), expected: FieldType::VARCHAR }));
+
+        let mut row = Row::new();
+        row.set("A", Constant::Int(8));
+        row.set("B", Constant::String("eight".to_string()));
+        ts.set_row(&row).unwrap();
+        assert_eq!(ts.get_row(), Ok(row));
+
+        ts.close();
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_insert_reuses_freed_block() {
+        let fm = Arc::new(FileMgr::new(PathBuf::from("freespacetestdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
+        let bm = Arc::new(Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 8)));
+        let tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap()));
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        let layout = Arc::new(Layout::new(schema));
+
+        // 33 records fill the first block, the rest go into a second one.
+        let mut ts = TableScan::new(tx.clone(), layout, "T").unwrap();
+        for i in 0..50 {
+            ts.insert().unwrap();
+            ts.set_int("A", i).unwrap();
+        }
+        assert_eq!(ts.rid().unwrap().blocknum, 1);
+
+        ts.before_first().unwrap();
+        while ts.next().unwrap() {
+            if ts.get_int("A").unwrap() == 7 {
+                ts.delete().unwrap();
+            }
+        }
+
+        // Once the second block is full, the next insert jumps back to the hole.
+        let mut inserted = 0;
+        loop {
+            ts.insert().unwrap();
+            inserted += 1;
+            if ts.rid().unwrap().blocknum == 0 {
+                break;
+            }
+        }
+        assert_eq!(inserted, 17);
+        assert_eq!(tx.lock().unwrap().size("T.tbl").unwrap(), 2);
+
+        ts.close();
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_blobs() {
+        let _ = std::fs::remove_dir_all("blobtestdb");
+        let fm = Arc::new(FileMgr::new(PathBuf::from("blobtestdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
+        let bm = Arc::new(Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 8)));
+        let tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap()));
+
+        let mut schema = Schema::new();
+        schema.add_int_field("A");
+        schema.add_blob_field("B");
+        let layout = Arc::new(Layout::new(schema));
+        let blob: Vec<u8> = (0..1000).map(|i| (i * 7) as u8).collect();
+
+        // 194 bytes fit in each overflow block, so the blob takes 6 blocks
+        // after the header.
+        let mut ts = TableScan::new(tx.clone(), layout.clone(), "T").unwrap();
+        ts.insert().unwrap();
+        ts.set_int("A", 1).unwrap();
+        assert_eq!(ts.write_blob("B", &mut blob.as_slice()), Ok(1000));
+        assert_eq!(tx.lock().unwrap().size("T.blob").unwrap(), 7);
+        assert_eq!(ts.get_val("B"), Ok(Constant::Blob(blob.clone())));
+        let mut reader = ts.blob_reader("B").unwrap();
+        assert_eq!(reader.remaining(), 1000);
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).unwrap();
+        assert_eq!(read, blob);
+        assert_eq!(ts.write_blob("A", &mut blob.as_slice()), Err(ScanError::TypeMismatch { field: "A".to_string(), expected: FieldType::BLOB }));
+
+        // Overwritten and deleted blobs give their blocks back for reuse.
+        // The old chain is only freed once the new one is written, so the
+        // short blob takes a new block.
+        ts.set_blob("B", b"short").unwrap();
+        assert_eq!(ts.get_blob("B"), Ok(b"short".to_vec()));
+        ts.delete().unwrap();
+        ts.insert().unwrap();
+        let mut row = Row::new();
+        row.set("A", Constant::Int(2));
+        row.set("B", Constant::Blob(blob.clone()));
+        ts.set_row(&row).unwrap();
+        assert_eq!(ts.get_row(), Ok(row));
+        assert_eq!(tx.lock().unwrap().size("T.blob").unwrap(), 8);
+        let rid = ts.rid().unwrap();
+        ts.close();
+        tx.lock().unwrap().commit().unwrap();
+
+        // A rollback restores the blob and the free list.
+        let tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap()));
+        let mut ts = TableScan::new(tx.clone(), layout.clone(), "T").unwrap();
+        ts.move_to_rid(&rid).unwrap();
+        ts.set_blob("B", &[9; 500]).unwrap();
+        ts.set_null("B").unwrap();
+        assert_eq!(ts.get_val("B"), Ok(Constant::Null));
+        assert_eq!(ts.blob_reader("B").err(), Some(ScanError::NullValue("B".to_string())));
+        ts.close();
+        tx.lock().unwrap().rollback().unwrap();
+
+        let tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap()));
+        let mut ts = TableScan::new(tx.clone(), layout, "T").unwrap();
+        ts.move_to_rid(&rid).unwrap();
+        assert_eq!(ts.get_blob("B"), Ok(blob));
+        ts.set_blob("B", &[]).unwrap();
+        assert_eq!(ts.get_blob("B"), Ok(Vec::new()));
+        ts.close();
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
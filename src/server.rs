@@ -0,0 +1,4 @@
+pub mod backup;
+pub mod dump;
+pub mod indexbuild;
+pub mod simpledb;
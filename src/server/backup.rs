@@ -0,0 +1,235 @@
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use crate::error::dberror::DbError;
+use crate::file::doublewrite::DoubleWrite;
+use crate::file::filemgr::FileMgr;
+use crate::file::page::Page;
+use crate::file::superblock::Superblock;
+use crate::log::logmgr::LogMgr;
+use crate::server::simpledb::SimpleDB;
+
+// An incremental backup holds the blocks changed since an earlier backup,
+// full or incremental, identified by the LSN of the checkpoint it was taken
+// at. Every logged change stamps its LSN in the page header, and a new
+// block starts out with the LSN of the log on disk, so the blocks changed
+// since the checkpoint are those whose LSN is no lower than its own; the
+// blocks of the log carry the LSN of their last record in their header
+// instead. Restoring copies a full backup and layers the incrementals over
+// it in order.
+//
+// The backup starts with a header of five integers: a magic number, the
+// block size, the LSN it was taken since and that of its own checkpoint,
+// and the number of files. Each file follows, as the length of its name,
+// its number of blocks and its name, then each changed block as its number
+// and checksum followed by its contents, and a block number of -1. Every
+// file of the database is listed, changed or not, so that restoring also
+// removes the files deleted since and truncates the shrunk ones.
+const MAGIC: i32 = 0x53444249;
+const HEADER_SIZE: usize = 20;
+
+// Writes the blocks of the database changed since the LSN, and returns
+// how many. The caller holds off every other transaction, right after a
+// checkpoint at `lsn`; see `Transaction::write_incremental`.
+pub(crate) fn write_incremental(fm: &FileMgr, since: i32, lsn: i32, out: &mut dyn Write) -> Result<usize, DbError> {
+    let filenames = backed_up(FileMgr::filenames(fm.db_dir())?);
+    let mut header = Page::new(HEADER_SIZE);
+    header.set_int(0, MAGIC);
+    header.set_int(4, fm.block_size() as i32);
+    header.set_int(8, since);
+    header.set_int(12, lsn);
+    header.set_int(16, filenames.len() as i32);
+    out.write_all(&header.bytebuffer)?;
+    let mut page = Page::new(fm.block_size());
+    let mut changed = 0;
+    for filename in &filenames {
+        let blocks = fm.length(filename)? as usize;
+        out.write_all(&ints(&[filename.len() as i32, blocks as i32]).bytebuffer)?;
+        out.write_all(filename.as_bytes())?;
+        for number in 0..blocks {
+            fm.read(&fm.block(filename, number), &mut page)?;
+            let page_lsn = if filename == SimpleDB::LOG_FILE { LogMgr::block_lsn(&page) } else { page.lsn() };
+            if page_lsn >= since {
+                out.write_all(&ints(&[number as i32, DoubleWrite::checksum(&page.bytebuffer)]).bytebuffer)?;
+                out.write_all(&page.bytebuffer)?;
+                changed += 1;
+            }
+        }
+        out.write_all(&ints(&[-1, 0]).bytebuffer)?;
+    }
+    Ok(changed)
+}
+
+// Copies the full backup in `base` into `dest`, which must not exist yet,
+// and layers the incrementals over it in order. Each must have been taken
+// since an LSN no later than that of the backup before it, and not before
+// it. If any fails to apply, `dest` is removed.
+pub(crate) fn restore(base: &Path, incrementals: &mut [&mut dyn Read], dest: &Path) -> Result<(), DbError> {
+    fs::create_dir(dest)?;
+    let result = copy_and_layer(base, incrementals, dest);
+    if result.is_err() {
+        let _ = fs::remove_dir_all(dest);
+    }
+    result
+}
+
+fn copy_and_layer(base: &Path, incrementals: &mut [&mut dyn Read], dest: &Path) -> Result<(), DbError> {
+    for filename in FileMgr::filenames(base)? {
+        fs::copy(base.join(&filename), dest.join(&filename))?;
+    }
+    for incremental in incrementals.iter_mut() {
+        layer(dest, *incremental)?;
+    }
+    for filename in FileMgr::filenames(dest)? {
+        OpenOptions::new().write(true).open(dest.join(filename))?.sync_all()?;
+    }
+    Ok(())
+}
+
+fn layer(dest: &Path, reader: &mut dyn Read) -> Result<(), DbError> {
+    let header = read_ints(reader, 5)?;
+    if header.get_int(0) != MAGIC {
+        return Err(DbError::Corruption("not an incremental backup".to_string()));
+    }
+    let block_size = header.get_int(4) as usize;
+    Superblock::open(dest, block_size, false)?;
+    let (since, lsn) = (header.get_int(8), header.get_int(12));
+    let base_lsn = log_lsn(dest, block_size)?;
+    if since > base_lsn || lsn < base_lsn {
+        return Err(DbError::Corruption(format!("incremental backup of LSNs {} to {} does not follow the backup at LSN {}", since, lsn, base_lsn)));
+    }
+
+    let mut listed = Vec::new();
+    let mut page = Page::new(block_size);
+    for _ in 0..header.get_int(16) {
+        let file = read_ints(reader, 2)?;
+        let mut name = vec![0; file.get_int(0) as usize];
+        reader.read_exact(&mut name)?;
+        let filename = String::from_utf8(name).map_err(|_| DbError::Corruption("incremental backup has an invalid file name".to_string()))?;
+        let mut out = OpenOptions::new().write(true).create(true).truncate(false).open(dest.join(&filename))?;
+        out.set_len((file.get_int(4) as usize * block_size) as u64)?;
+        loop {
+            let entry = read_ints(reader, 2)?;
+            let number = entry.get_int(0);
+            if number < 0 {
+                break;
+            }
+            reader.read_exact(&mut page.bytebuffer)?;
+            if entry.get_int(4) != DoubleWrite::checksum(&page.bytebuffer) {
+                return Err(DbError::Corruption(format!("block {} of {} in the incremental backup does not match its checksum", number, filename)));
+            }
+            out.seek(SeekFrom::Start((number as usize * block_size) as u64))?;
+            out.write_all(&page.bytebuffer)?;
+        }
+        listed.push(filename);
+    }
+    for filename in backed_up(FileMgr::filenames(dest)?) {
+        if !listed.contains(&filename) {
+            fs::remove_file(dest.join(filename))?;
+        }
+    }
+    Ok(())
+}
+
+// The files a backup covers: the superblock does not change, and the
+// double-write area is emptied whenever the database opens.
+fn backed_up(filenames: Vec<String>) -> Vec<String> {
+    filenames.into_iter().filter(|name| name != Superblock::FILENAME && name != DoubleWrite::FILENAME).collect()
+}
+
+// The LSN of the last record of the log in the directory.
+fn log_lsn(dir: &Path, block_size: usize) -> Result<i32, DbError> {
+    let log = fs::read(dir.join(SimpleDB::LOG_FILE))?;
+    if log.len() < block_size {
+        return Err(DbError::Corruption("backup has no log".to_string()));
+    }
+    Ok(LogMgr::block_lsn(&Page::wrap(log[log.len() - block_size..].to_vec())))
+}
+
+fn ints(vals: &[i32]) -> Page {
+    let mut page = Page::new(4 * vals.len());
+    vals.iter().enumerate().for_each(|(i, val)| page.set_int(4 * i, *val));
+    page
+}
+
+fn read_ints(reader: &mut dyn Read, count: usize) -> Result<Page, DbError> {
+    let mut page = Page::new(4 * count);
+    reader.read_exact(&mut page.bytebuffer)?;
+    Ok(page)
+}
+
+mod tests {
+    use std::fs;
+    use crate::error::dberror::DbError;
+    use crate::parse::parser::Parser;
+    use crate::plan::basicqueryplanner::BasicQueryPlanner;
+    use crate::plan::indexupdateplanner::IndexUpdatePlanner;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_incremental_backup() {
+        for dir in ["backupsourcetestdb", "backupbasetestdb", "backuprestoretestdb"] {
+            let _ = fs::remove_dir_all(dir);
+        }
+        let db = SimpleDB::new("backupsourcetestdb", 400, 8).unwrap();
+        let update = |sql: &str| {
+            let tx = db.new_tx().unwrap();
+            IndexUpdatePlanner::new(db.metadata_mgr()).execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone()).unwrap();
+            tx.lock().unwrap().commit().unwrap();
+        };
+        let query = |db: &SimpleDB, sql: &str| {
+            let tx = db.new_tx().unwrap();
+            let data = Parser::new(sql).unwrap().query().unwrap();
+            let mut scan = BasicQueryPlanner::new(db.metadata_mgr()).create_plan(&data, tx.clone()).unwrap().open().unwrap();
+            let mut rows = Vec::new();
+            while scan.next().unwrap() {
+                rows.push((scan.get_int("id").unwrap(), scan.get_string("name").unwrap()));
+            }
+            scan.close();
+            tx.lock().unwrap().commit().unwrap();
+            rows.sort();
+            rows
+        };
+        update("create table t (id int, name varchar(20))");
+        update("create table gone (a int)");
+        for i in 0..200 {
+            update(&format!("insert into t (id, name) values ({}, 'row{}')", i, i));
+        }
+        update("create index t_id on t (id)");
+        let base_lsn = db.clone_to("backupbasetestdb").unwrap();
+
+        // The first incremental has one changed record, a new table and
+        // a dropped one, not the whole table.
+        update("update t set name = 'changed' where id = 5");
+        update("drop table gone");
+        update("create table added (id int, name varchar(20))");
+        update("insert into added (id, name) values (1, 'new')");
+        let mut first = Vec::new();
+        let first_lsn = db.backup_incremental(base_lsn, &mut first).unwrap();
+        assert!(first_lsn > base_lsn);
+        let full_size: u64 = fs::read_dir("backupsourcetestdb").unwrap().map(|entry| entry.unwrap().metadata().unwrap().len()).sum();
+        assert!((first.len() as u64) < full_size / 4, "{} of {}", first.len(), full_size);
+
+        for i in 100..110 {
+            update(&format!("delete from t where id = {}", i));
+        }
+        update("insert into added (id, name) values (2, 'newer')");
+        let mut second = Vec::new();
+        db.backup_incremental(first_lsn, &mut second).unwrap();
+
+        // Skipping the first incremental leaves a gap, and the restore
+        // leaves nothing behind.
+        let error = SimpleDB::restore("backupbasetestdb", &mut [&mut second.as_slice()], "backuprestoretestdb").unwrap_err();
+        assert!(matches!(error, DbError::Corruption(_)), "{:?}", error);
+        assert!(!fs::exists("backuprestoretestdb").unwrap());
+
+        SimpleDB::restore("backupbasetestdb", &mut [&mut first.as_slice(), &mut second.as_slice()], "backuprestoretestdb").unwrap();
+        let restored = SimpleDB::new("backuprestoretestdb", 400, 8).unwrap();
+        for sql in ["select id, name from t", "select id, name from t where id = 5", "select id, name from added"] {
+            assert_eq!(query(&restored, sql), query(&db, sql), "{}", sql);
+        }
+        assert_eq!(query(&restored, "select id, name from t").len(), 190);
+        assert!(!fs::exists("backuprestoretestdb/gone.tbl").unwrap());
+    }
+}
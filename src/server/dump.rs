@@ -0,0 +1,148 @@
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+use std::sync::{Arc, Mutex};
+use crate::index::index::IndexKind;
+use crate::metadata::metadatamgr::MetadataMgr;
+use crate::parse::lexer::Lexer;
+use crate::parse::parseerror::ParseErrorKind;
+use crate::parse::parser::Parser;
+use crate::plan::indexupdateplanner::IndexUpdatePlanner;
+use crate::query::scan::Scan;
+use crate::record::layout::Schema;
+use crate::record::scanerror::ScanError;
+use crate::record::schema::FieldType;
+use crate::record::tablescan::TableScan;
+use crate::tx::transaction::Transaction;
+
+// The tables every database has, which a dump leaves out.
+const CATALOG_TABLES: [&str; 6] = ["tblcat", "fldcat", "constraintcat", "viewcat", "idxcat", "schemacat"];
+
+// Writes the statements that rebuild the database, one per line: its
+// schemas, its tables with their constraints, an INSERT for each record,
+// its indexes, which are built once the records are in, and its views, each
+// after the views it reads. Objects are written in the order of their names,
+// so that dumps of the same database compare equal. Tables are recreated
+// with the default fill factor and record format, which SQL cannot state.
+// Returns the number of statements written.
+pub(crate) fn dump(mdm: &MetadataMgr, out: &mut dyn Write, tx: Arc<Mutex<Transaction>>) -> Result<usize, ScanError> {
+    let catalog = mdm.catalog(tx.clone())?;
+    let mut count = 0;
+    let mut schemas: Vec<&String> = catalog.schemas().iter().collect();
+    schemas.sort();
+    for schema in schemas {
+        writeln!(out, "create schema {}", Lexer::quote(schema))?;
+        count += 1;
+    }
+
+    let mut tables: Vec<&String> = catalog.layouts().keys().filter(|table| !CATALOG_TABLES.contains(&table.as_str())).collect();
+    tables.sort();
+    for table in &tables {
+        let schema = catalog.layouts()[*table].schema();
+        let fields: Vec<String> = schema.fields().iter().map(|field| field_def(schema, field)).collect();
+        writeln!(out, "create table {} ({})", Lexer::quote(table), fields.join(", "))?;
+        count += 1;
+    }
+    for table in &tables {
+        // A table that was never written to has no file, and opening a
+        // scan on it would allocate its first block.
+        if tx.lock().unwrap().size(&format!("{}.tbl", table))? == 0 {
+            continue;
+        }
+        let layout = catalog.layouts()[*table].clone();
+        let fields: Vec<String> = layout.schema().fields().iter().map(|field| Lexer::quote(field)).collect();
+        let mut ts = TableScan::new(tx.clone(), layout.clone(), table)?;
+        while ts.next()? {
+            let vals = layout.schema().fields().iter().map(|field| ts.get_val(field).map(|val| val.to_string())).collect::<Result<Vec<_>, _>>()?;
+            writeln!(out, "insert into {} ({}) values ({})", Lexer::quote(table), fields.join(", "), vals.join(", "))?;
+            count += 1;
+        }
+        ts.close();
+    }
+    for table in &tables {
+        // An index whose online build did not finish is left out.
+        let mut indexes: Vec<(String, String)> = catalog.indexes(table).iter().filter(|(index, _)| catalog.is_published(index)).cloned().collect();
+        indexes.sort();
+        for (index, field) in indexes {
+            let kind = match catalog.kind(&index) {
+                IndexKind::Hash => "",
+                IndexKind::Text => "text ",
+            };
+            let mut def = format!("create {}index {} on {} ({})", kind, Lexer::quote(&index), Lexer::quote(table), Lexer::quote(&field));
+            if let Some(pred) = catalog.predicate(&index) {
+                def.push_str(&format!(" where {}", pred));
+            }
+            writeln!(out, "{}", def)?;
+            count += 1;
+        }
+    }
+
+    // The next view is the first one whose definition only reads what is
+    // written already. A view whose tables were dropped since it was
+    // created has none, and is written when nothing else is left.
+    let mut views: Vec<(&String, Vec<String>)> = catalog.view_defs().keys().map(|view| {
+        let def = &catalog.view_defs()[view];
+        let query = Parser::new(def).and_then(|mut parser| parser.query()).expect("a stored view definition parses");
+        (view, query.tables().to_vec())
+    }).collect();
+    views.sort();
+    let mut written: HashSet<&str> = tables.iter().map(|table| table.as_str()).collect();
+    while !views.is_empty() {
+        let next = views.iter().position(|(_, reads)| reads.iter().all(|table| written.contains(table.as_str()))).unwrap_or(0);
+        let (view, _) = views.remove(next);
+        writeln!(out, "create view {} as {}", Lexer::quote(view), catalog.view_defs()[view])?;
+        written.insert(view);
+        count += 1;
+    }
+    out.flush()?;
+    Ok(count)
+}
+
+// Runs the statements of a dump, one per line, and returns how many ran.
+// A string value may hold a line break, so a line that ends inside a string
+// is joined with the next. Blank lines are skipped.
+pub(crate) fn load(planner: &IndexUpdatePlanner, input: &mut dyn BufRead, tx: Arc<Mutex<Transaction>>) -> Result<usize, ScanError> {
+    let mut count = 0;
+    let mut statement = String::new();
+    for line in input.lines() {
+        if !statement.is_empty() {
+            statement.push('\n');
+        }
+        statement.push_str(&line?);
+        if statement.trim().is_empty() {
+            statement.clear();
+            continue;
+        }
+        let cmd = match Parser::new(&statement).and_then(|mut parser| parser.update_cmd()) {
+            Err(error) if error.kind() == &ParseErrorKind::UnterminatedString => continue,
+            cmd => cmd?,
+        };
+        planner.execute_update(&cmd, tx.clone())?;
+        statement.clear();
+        count += 1;
+    }
+    if !statement.is_empty() {
+        Parser::new(&statement)?;
+    }
+    Ok(count)
+}
+
+// The definition of a field in a CREATE TABLE statement.
+fn field_def(schema: &Schema, field: &str) -> String {
+    let ftype = match FieldType::try_from(schema.ftype(field)).expect("a catalogued field type") {
+        FieldType::INTEGER => "int".to_string(),
+        FieldType::VARCHAR => format!("varchar({})", schema.length(field)),
+        FieldType::BIGINT => "bigint".to_string(),
+        FieldType::DOUBLE => "double".to_string(),
+        FieldType::BOOLEAN => "boolean".to_string(),
+        FieldType::DATE => "date".to_string(),
+        FieldType::BLOB => "blob".to_string(),
+    };
+    let mut def = format!("{} {}", Lexer::quote(field), ftype);
+    if schema.is_not_null(field) {
+        def.push_str(" not null");
+    }
+    for check in schema.checks(field) {
+        def.push_str(&format!(" check ({} {})", Lexer::quote(field), check));
+    }
+    def
+}
@@ -0,0 +1,191 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use crate::error::dberror::DbError;
+use crate::index::hashindex::HashIndex;
+use crate::index::index::Index;
+use crate::metadata::indexinfo::IndexInfo;
+use crate::metadata::metadatamgr::MetadataMgr;
+use crate::parse::createindexdata::CreateIndexData;
+use crate::plan::basicupdateplanner::BasicUpdatePlanner;
+use crate::query::constant::Constant;
+use crate::query::scan::Scan;
+use crate::record::scanerror::ScanError;
+use crate::record::tablescan::{RecordId, TableScan, UpdateScan};
+use crate::server::simpledb::SimpleDB;
+use crate::tx::transaction::Transaction;
+
+// How many times a step that timed out on a lock is tried again before the
+// build gives up. The build and the updates of the table lock the blocks
+// of the table and of the index in different orders, so they may deadlock.
+const RETRIES: usize = 3;
+
+// Builds the index of a CREATE INDEX statement while the table stays open
+// to writers. CREATE INDEX fills the index in the transaction that creates
+// it, which keeps every block of the table locked until it commits. Here
+// each step runs in a transaction of its own:
+//
+// 1. The index is recorded unpublished. From then on, updates of the table
+//    keep it up to date as they do any other index, but queries do not use
+//    it.
+// 2. The build waits for the transactions that were active by then, which
+//    may have looked up the indexes of the table before it had this one.
+// 3. The records of the table are added to the index one block at a time,
+//    so that a writer waits at most for the block being read. A record that
+//    an update indexed since step 1 is not added again. The index records
+//    are written to the buckets directly, under each of the keys the kind
+//    of index holds the record under.
+// 4. The index is published, in a single catalog change.
+//
+// If a step fails, the index is dropped. A build cut short by a crash
+// leaves the index unpublished until it is dropped, or rebuilt with
+// REINDEX.
+pub(crate) fn build_index(db: &SimpleDB, planner: &BasicUpdatePlanner, data: &CreateIndexData) -> Result<(), ScanError> {
+    let Some((index, table)) = db.run(|tx| planner.record_index(data, false, tx))? else {
+        return Ok(());
+    };
+    let result = fill(db, &index, &table, data).and_then(|()| {
+        db.run(|tx| db.metadata_mgr().publish_index(&index, tx))?;
+        Ok(())
+    });
+    if result.is_err() {
+        let _ = db.run(|tx| db.metadata_mgr().drop_index(&index, tx));
+    }
+    db.metadata_mgr().forget_stats(&index);
+    result
+}
+
+fn fill(db: &SimpleDB, index: &str, table: &str, data: &CreateIndexData) -> Result<(), ScanError> {
+    let registry = db.tx_registry();
+    let older: Vec<i32> = registry.active().iter().map(|info| info.txnum).collect();
+    while registry.active().iter().any(|info| older.contains(&info.txnum)) {
+        thread::sleep(Duration::from_millis(10));
+    }
+    let mut block = 0;
+    let mut retries = 0;
+    loop {
+        match db.run(|tx| fill_block(&db.metadata_mgr(), index, table, data, block, tx)) {
+            Ok(true) => {
+                block += 1;
+                retries = 0;
+            }
+            Ok(false) => return Ok(()),
+            Err(ScanError::Db(DbError::LockTimeout(_))) if retries < RETRIES => retries += 1,
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+// Adds the records of the block of the table that the index holds but has
+// no index record of yet. Returns false if the table has no such block.
+fn fill_block(mdm: &MetadataMgr, index: &str, table: &str, data: &CreateIndexData, block: usize, tx: Arc<Mutex<Transaction>>) -> Result<bool, ScanError> {
+    let field = data.field();
+    if block >= tx.lock().unwrap().size(&format!("{}.tbl", table))? {
+        return Ok(false);
+    }
+    let layout = mdm.layout(table, tx.clone())?;
+    let mut idx = HashIndex::new(tx.clone(), index, Arc::new(IndexInfo::index_layout(field, layout.schema())));
+    let mut ts = TableScan::new(tx, layout, table)?;
+    ts.before_block(block)?;
+    while ts.next()? {
+        let rid = ts.rid().ok_or(ScanError::NoCurrentRecord)?;
+        if rid.blocknum as usize != block {
+            break;
+        }
+        let val = ts.get_val(field)?;
+        if val.is_null() || !data.pred().is_satisfied(&mut ts)? {
+            continue;
+        }
+        for key in data.kind().keys(&val) {
+            if !contains(&mut idx, &key, &rid)? {
+                idx.insert(&key, &rid)?;
+            }
+        }
+    }
+    idx.close();
+    ts.close();
+    Ok(true)
+}
+
+fn contains(idx: &mut HashIndex, val: &Constant, rid: &RecordId) -> Result<bool, ScanError> {
+    idx.before_first(val)?;
+    while idx.next()? {
+        if &idx.data_rid()? == rid {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+mod tests {
+    use std::thread;
+    use super::*;
+    use crate::index::index::IndexKind;
+    use crate::parse::parser::Parser;
+    use crate::plan::indexupdateplanner::IndexUpdatePlanner;
+    use crate::query::predicate::Predicate;
+
+    #[test]
+    fn test_build_index() {
+        let db = SimpleDB::ephemeral().unwrap();
+        let mdm = db.metadata_mgr();
+        let planner = IndexUpdatePlanner::new(mdm.clone());
+        let update = |sql: &str, tx: &Arc<Mutex<Transaction>>| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone()).unwrap();
+        let tx = db.new_tx().unwrap();
+        update("create table t (a int, b varchar(10))", &tx);
+        for i in 0..200 {
+            update(&format!("insert into t (a, b) values ({}, 'rec{}')", i % 20, i), &tx);
+        }
+        tx.lock().unwrap().commit().unwrap();
+        let recorded = || {
+            let tx = db.new_tx().unwrap();
+            let recorded = mdm.index_exists("ta", tx.clone()).unwrap();
+            tx.lock().unwrap().commit().unwrap();
+            recorded
+        };
+        // The lookup reads the catalog snapshot, which the writer would
+        // otherwise read with locks that hold off the build's catalog change.
+        assert!(!recorded());
+
+        // A writer that was active when the build started holds it up, and
+        // its statements keep the index up to date once it is recorded.
+        let writer = db.new_tx().unwrap();
+        update("insert into t (a, b) values (100, 'old')", &writer);
+        let data = CreateIndexData::new("ta".to_string(), "t".to_string(), "a".to_string(), IndexKind::Hash, Predicate::new(), false);
+        thread::scope(|scope| {
+            let build = scope.spawn(|| db.create_index_online(&data));
+            while !recorded() && !build.is_finished() {
+                thread::yield_now();
+            }
+
+            // Queries do not use the index before it is published.
+            let tx = db.new_tx().unwrap();
+            assert!(mdm.index_info("t", tx.clone()).unwrap().is_empty());
+            tx.lock().unwrap().commit().unwrap();
+
+            update("insert into t (a, b) values (101, 'new')", &writer);
+            update("update t set a = 102 where b = 'rec5'", &writer);
+            writer.lock().unwrap().commit().unwrap();
+            build.join().unwrap().unwrap();
+        });
+
+        let tx = db.new_tx().unwrap();
+        let mut idx = mdm.index_info("t", tx.clone()).unwrap()["a"].open();
+        let mut count = |key: i32| {
+            idx.before_first(&Constant::Int(key)).unwrap();
+            let mut count = 0;
+            while idx.next().unwrap() {
+                count += 1;
+            }
+            count
+        };
+        assert_eq!([count(0), count(5), count(100), count(101), count(102)], [10, 9, 1, 1, 1]);
+        idx.close();
+        tx.lock().unwrap().commit().unwrap();
+
+        // A statement with nothing to build does nothing.
+        assert_eq!(db.create_index_online(&CreateIndexData::new("ta".to_string(), "t".to_string(), "a".to_string(), IndexKind::Hash, Predicate::new(), true)), Ok(()));
+        let unknown = CreateIndexData::new("tc".to_string(), "t".to_string(), "c".to_string(), IndexKind::Hash, Predicate::new(), false);
+        assert_eq!(db.create_index_online(&unknown), Err(ScanError::UnknownField("c".to_string())));
+    }
+}
@@ -0,0 +1,598 @@
+use std::io::{BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::{env, fs, process};
+use crate::buffer::buffermgr::BufferMgr;
+use crate::error::dberror::DbError;
+use crate::file::filemgr::FileMgr;
+use crate::log::logmgr::LogMgr;
+use crate::metadata::metadatamgr::MetadataMgr;
+use crate::metadata::tablemgr::RelationSize;
+use crate::plan::indexupdateplanner::IndexUpdatePlanner;
+use crate::record::scanerror::ScanError;
+use crate::parse::createindexdata::CreateIndexData;
+use crate::plan::basicupdateplanner::BasicUpdatePlanner;
+use crate::server::{backup, dump, indexbuild};
+use crate::tx::admission::AdmissionCtl;
+use crate::tx::rawtx::RawTx;
+use crate::tx::transaction::Transaction;
+use crate::tx::txstatus::TxRegistry;
+use crate::tx::journal;
+use crate::tx::rowchange::{ChangeObservers, RowChange};
+
+// The SimpleDB struct is the entry point of the engine: it opens the
+// database in a directory and owns the managers every transaction shares.
+// A new database gets an empty catalog. If the log has records, the
+// database may not have been shut down cleanly, so it is recovered before
+// anything else runs: the changes of transactions that neither committed
+// nor rolled back are undone and a checkpoint is written. Only then is the
+// catalog opened and `new_tx` handed out.
+pub struct SimpleDB {
+    fm: Arc<FileMgr>,
+    lm: Arc<Mutex<LogMgr>>,
+    bm: Arc<Mutex<BufferMgr>>,
+    mdm: Arc<MetadataMgr>,
+    observers: Arc<ChangeObservers>,
+    admission: Arc<AdmissionCtl>,
+    registry: Arc<TxRegistry>,
+    // Whether new transactions run optimistically; see `set_optimistic`.
+    optimistic: AtomicBool,
+    // The directory of an ephemeral database, removed when it is dropped.
+    ephemeral_dir: Option<PathBuf>,
+}
+
+static EPHEMERAL_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+impl SimpleDB {
+    pub const LOG_FILE: &'static str = "simpledb.log";
+    // The block sizes a database is meant to run with, and the default one.
+    // Any other size works too: the tests mostly use the 400 bytes of the
+    // textbook, which make records and logs cross block boundaries often.
+    pub const BLOCK_SIZES: [usize; 3] = [4096, 8192, 16384];
+    pub const BLOCK_SIZE: usize = Self::BLOCK_SIZES[0];
+    pub const BUFFER_COUNT: usize = 8;
+
+    pub fn new(db_dir: &str, block_size: usize, buffer_count: usize) -> Result<SimpleDB, DbError> {
+        journal::install_panic_hook();
+        let fm = Arc::new(FileMgr::new(PathBuf::from(db_dir), block_size)?);
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), Self::LOG_FILE)?));
+        let bm = Arc::new(Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), buffer_count)));
+        let admission = AdmissionCtl::unlimited();
+        let registry = Arc::new(TxRegistry::new());
+
+        if !lm.lock().unwrap().is_empty() {
+            println!("Recovering existing database");
+            Transaction::new(fm.clone(), bm.clone(), lm.clone(), admission.clone(), registry.clone())?.recover()?;
+        }
+        let tx = Arc::new(Mutex::new(Transaction::new(fm.clone(), bm.clone(), lm.clone(), admission.clone(), registry.clone())?));
+        // The catalog tables are always well formed, so the only errors
+        // opening them can raise come from the layers below the scans.
+        let mdm = Arc::new(MetadataMgr::new(fm.is_new(), tx.clone()).map_err(|error| match error {
+            ScanError::Db(error) => error,
+            error => DbError::Corruption(error.to_string()),
+        })?);
+        tx.lock().unwrap().commit()?;
+
+        Ok(SimpleDB { fm, lm, bm, mdm, observers: Arc::new(ChangeObservers::new()), admission, registry, optimistic: AtomicBool::new(false), ephemeral_dir: None })
+    }
+
+    // Opens a new database in a directory of its own under the system's
+    // temporary directory, which is removed when the database is dropped.
+    // Tests that each use one can run in parallel without sharing files.
+    pub fn ephemeral() -> Result<SimpleDB, DbError> {
+        let dir = loop {
+            let count = EPHEMERAL_COUNT.fetch_add(1, Ordering::Relaxed);
+            let dir = env::temp_dir().join(format!("simpledb-{}-{}", process::id(), count));
+            if !fs::exists(&dir)? {
+                break dir;
+            }
+        };
+        match SimpleDB::new(&dir.to_string_lossy(), Self::BLOCK_SIZE, Self::BUFFER_COUNT) {
+            Ok(mut db) => {
+                db.ephemeral_dir = Some(dir);
+                Ok(db)
+            }
+            Err(error) => {
+                let _ = fs::remove_dir_all(&dir);
+                Err(error)
+            }
+        }
+    }
+
+    pub fn new_tx(&self) -> Result<Arc<Mutex<Transaction>>, DbError> {
+        let mut tx = Transaction::new(self.fm.clone(), self.bm.clone(), self.lm.clone(), self.admission.clone(), self.registry.clone())?;
+        tx.notify(self.observers.clone());
+        if self.optimistic.load(Ordering::SeqCst) {
+            tx.run_optimistically();
+        }
+        let tx = Arc::new(Mutex::new(tx));
+        tx.lock().unwrap().set_handle(Arc::downgrade(&tx));
+        Ok(tx)
+    }
+
+    // Starts a transaction that reads and writes blocks directly, for
+    // storage structures of their own built on the buffer and transaction
+    // managers; see `RawTx`.
+    pub fn raw(&self) -> Result<RawTx, DbError> {
+        Ok(RawTx::new(self.new_tx()?))
+    }
+
+    // Sets the maximum number of transactions of the database that may be
+    // active at once. There is no cap by default.
+    pub fn set_max_active(&self, max_active: usize) {
+        self.admission.set_max_active(max_active);
+    }
+
+    // Sets how many buffers a sort may fill with records before it spills
+    // them to a temporary table, and how many it may use to merge them. It
+    // defaults to a quarter of the buffer pool.
+    pub fn set_work_mem(&self, buffers: usize) {
+        self.bm.lock().unwrap().set_work_mem(buffers);
+    }
+
+    // Makes every transaction of the database started from now on run
+    // optimistically: its reads take no locks and are validated when it
+    // commits, see `ConcurrencyMgr`. It suits read-mostly workloads, where
+    // readers would otherwise wait on, and time out behind, the locks of
+    // writers. A transaction that conflicts with another is rolled back and
+    // fails with `DbError::Conflict`, and is up to the caller to retry.
+    pub fn set_optimistic(&self, on: bool) {
+        self.optimistic.store(on, Ordering::SeqCst);
+    }
+
+    // Writes a checkpoint, so that recovery after a restart only reads the
+    // log written since. It waits for the active transactions to complete
+    // and holds off new ones while it is written.
+    pub fn checkpoint(&self) -> Result<(), DbError> {
+        self.run(|tx| tx.lock().unwrap().checkpoint())
+    }
+
+    // Copies the database into a new directory, which then opens as an
+    // independent database with the same block size. The copy is taken at
+    // a checkpoint, so it has the changes of the transactions that
+    // committed before it and none of the others. Like a checkpoint, it
+    // waits for the active transactions to complete, and new ones wait
+    // while the files are copied. Returns the LSN of the checkpoint, which
+    // an incremental backup of the changes since the copy starts from.
+    pub fn clone_to(&self, dest_dir: &str) -> Result<i32, DbError> {
+        self.run(|tx| tx.lock().unwrap().copy_database(Path::new(dest_dir)))
+    }
+
+    // Writes the blocks changed since the LSN of an earlier backup, taken
+    // at a checkpoint as `clone_to` is, and returns the LSN the next
+    // incremental backup starts from; see `backup`.
+    pub fn backup_incremental(&self, since: i32, out: &mut dyn Write) -> Result<i32, DbError> {
+        self.run(|tx| tx.lock().unwrap().write_incremental(since, out))
+    }
+
+    // Restores a database into a new directory from a copy made by
+    // `clone_to` and the incremental backups taken after it, in order.
+    pub fn restore(base_dir: &str, incrementals: &mut [&mut dyn Read], dest_dir: &str) -> Result<(), DbError> {
+        backup::restore(Path::new(base_dir), incrementals, Path::new(dest_dir))
+    }
+
+    // Writes the database out as SQL statements that rebuild it, and returns
+    // how many were written; see `dump::dump`. The statements do not depend
+    // on the file format, so `load` reads them into a database of any
+    // version or block size. The dump runs in a transaction of its own, and
+    // is a consistent copy of the database as of the time it ends.
+    pub fn dump(&self, out: &mut dyn Write) -> Result<usize, ScanError> {
+        self.run(|tx| dump::dump(&self.mdm, out, tx))
+    }
+
+    // Runs the statements of a dump in one transaction, so that either all
+    // of them take effect or none does, and returns how many ran.
+    pub fn load(&self, input: &mut dyn BufRead) -> Result<usize, ScanError> {
+        self.run(|tx| dump::load(&IndexUpdatePlanner::new(self.mdm.clone()), input, tx))
+    }
+
+    // Reports the disk usage of every table, each followed by its indexes,
+    // as the sys_relation_sizes system table lists it. Measuring scans
+    // every table and index, in a transaction of its own.
+    pub fn disk_usage(&self) -> Result<Vec<RelationSize>, ScanError> {
+        self.run(|tx| self.mdm.disk_usage(tx))
+    }
+
+    // Creates the index of a CREATE INDEX statement without holding off the
+    // writers of the table while it is filled, as the statement would; see
+    // `indexbuild::build_index`. Queries start using the index once it is
+    // complete. The build runs in transactions of its own and waits for the
+    // transactions active when it starts, so the caller must not have one
+    // open.
+    pub fn create_index_online(&self, data: &CreateIndexData) -> Result<(), ScanError> {
+        indexbuild::build_index(self, &BasicUpdatePlanner::new(self.mdm.clone()), data)
+    }
+
+    // Runs the operation in a new transaction, which is committed if the
+    // operation succeeds and rolled back if it fails.
+    pub(crate) fn run<T, E: From<DbError>>(&self, op: impl FnOnce(Arc<Mutex<Transaction>>) -> Result<T, E>) -> Result<T, E> {
+        let tx = self.new_tx()?;
+        let result = op(tx.clone());
+        let mut tx = tx.lock().unwrap();
+        match result {
+            Ok(val) => {
+                tx.commit()?;
+                Ok(val)
+            }
+            Err(error) => {
+                let _ = tx.rollback();
+                Err(error)
+            }
+        }
+    }
+
+    // Calls the callback after every commit of a transaction that changed
+    // records of the table, with the changes it made, so that a cache of
+    // the table can be invalidated without polling it. The callback runs
+    // on the committing thread once the transaction has released its
+    // locks; it must not use the transaction that committed.
+    pub fn on_change(&self, table: &str, callback: impl Fn(&[RowChange]) + Send + Sync + 'static) {
+        self.observers.register(table, Arc::new(callback));
+    }
+
+    pub fn file_mgr(&self) -> Arc<FileMgr> {
+        self.fm.clone()
+    }
+
+    pub fn log_mgr(&self) -> Arc<Mutex<LogMgr>> {
+        self.lm.clone()
+    }
+
+    pub fn buffer_mgr(&self) -> Arc<Mutex<BufferMgr>> {
+        self.bm.clone()
+    }
+
+    pub(crate) fn admission_ctl(&self) -> Arc<AdmissionCtl> {
+        self.admission.clone()
+    }
+
+    pub fn tx_registry(&self) -> Arc<TxRegistry> {
+        self.registry.clone()
+    }
+
+    pub fn metadata_mgr(&self) -> Arc<MetadataMgr> {
+        self.mdm.clone()
+    }
+}
+
+impl Drop for SimpleDB {
+    fn drop(&mut self) {
+        if let Some(dir) = &self.ephemeral_dir {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+mod tests {
+    use std::fs;
+    use std::thread;
+    use super::*;
+    use crate::file::page::Page;
+    use crate::query::constant::Constant;
+    use crate::query::scan::Scan;
+    use crate::record::schema::Schema;
+    use crate::record::tablescan::{RecordId, TableScan, UpdateScan};
+
+    #[test]
+    fn test_simple_db() {
+        let _ = fs::remove_dir_all("simpledbtestdb");
+        let db = SimpleDB::new("simpledbtestdb", 400, 8).unwrap();
+        assert!(db.file_mgr().is_new());
+        let tx = db.new_tx().unwrap();
+        let mut schema = Schema::new();
+        schema.add_int_field("a");
+        db.metadata_mgr().create_table("t", &schema, tx.clone()).unwrap();
+        let mut ts = TableScan::new(tx.clone(), db.metadata_mgr().layout("t", tx.clone()).unwrap(), "t").unwrap();
+        ts.insert().unwrap();
+        ts.set_int("a", 7).unwrap();
+        ts.close();
+        tx.lock().unwrap().commit().unwrap();
+        drop(db);
+
+        // Reopening finds the catalog and the committed record.
+        let db = SimpleDB::new("simpledbtestdb", 400, 8).unwrap();
+        assert!(!db.file_mgr().is_new());
+        let tx = db.new_tx().unwrap();
+        let mut ts = TableScan::new(tx.clone(), db.metadata_mgr().layout("t", tx.clone()).unwrap(), "t").unwrap();
+        assert!(ts.next().unwrap());
+        assert_eq!(ts.get_int("a").unwrap(), 7);
+        ts.close();
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    #[test]
+    fn test_ephemeral() {
+        let db1 = SimpleDB::ephemeral().unwrap();
+        let db2 = SimpleDB::ephemeral().unwrap();
+        let dir1 = db1.ephemeral_dir.clone().unwrap();
+        assert_ne!(Some(&dir1), db2.ephemeral_dir.as_ref());
+        assert!(db1.file_mgr().is_new());
+        let tx = db1.new_tx().unwrap();
+        let mut schema = Schema::new();
+        schema.add_int_field("a");
+        db1.metadata_mgr().create_table("t", &schema, tx.clone()).unwrap();
+        tx.lock().unwrap().commit().unwrap();
+        assert!(dir1.join("tblcat.tbl").exists());
+        drop(db1);
+        assert!(!dir1.exists());
+        assert!(db2.ephemeral_dir.as_ref().unwrap().exists());
+    }
+
+    #[test]
+    fn test_on_change() {
+        use crate::tx::rowchange::ChangeOp;
+        let db = SimpleDB::ephemeral().unwrap();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        db.on_change("watched", move |changes| sink.lock().unwrap().push(changes.to_vec()));
+        let tx = db.new_tx().unwrap();
+        let mut schema = Schema::new();
+        schema.add_int_field("a");
+        db.metadata_mgr().create_table("watched", &schema, tx.clone()).unwrap();
+        db.metadata_mgr().create_table("unwatched", &schema, tx.clone()).unwrap();
+        let layout = db.metadata_mgr().layout("watched", tx.clone()).unwrap();
+        let mut ts = TableScan::new(tx.clone(), layout.clone(), "watched").unwrap();
+        for i in 0..2 {
+            ts.insert().unwrap();
+            ts.set_int("a", i).unwrap();
+        }
+        ts.close();
+        let mut ts = TableScan::new(tx.clone(), layout.clone(), "unwatched").unwrap();
+        ts.insert().unwrap();
+        ts.close();
+        assert!(seen.lock().unwrap().is_empty());
+        tx.lock().unwrap().commit().unwrap();
+
+        // A rolled back transaction reports nothing.
+        let tx = db.new_tx().unwrap();
+        let mut ts = TableScan::new(tx.clone(), layout.clone(), "watched").unwrap();
+        ts.next().unwrap();
+        ts.delete().unwrap();
+        ts.close();
+        tx.lock().unwrap().rollback().unwrap();
+
+        let tx = db.new_tx().unwrap();
+        let mut ts = TableScan::new(tx.clone(), layout, "watched").unwrap();
+        ts.next().unwrap();
+        ts.set_int("a", 5).unwrap();
+        ts.next().unwrap();
+        ts.delete().unwrap();
+        ts.close();
+        tx.lock().unwrap().commit().unwrap();
+
+        let rid = |slot| RecordId { blocknum: 0, slot };
+        let change = |op, slot| RowChange { table: "watched".to_string(), op, rid: rid(slot) };
+        assert_eq!(*seen.lock().unwrap(), vec![
+            vec![change(ChangeOp::Insert, 0), change(ChangeOp::Insert, 1)],
+            vec![change(ChangeOp::Modify, 0), change(ChangeOp::Delete, 1)],
+        ]);
+    }
+
+    #[test]
+    fn test_dump_and_load() {
+        use crate::parse::parser::Parser;
+        let _ = fs::remove_dir_all("dumptestdb");
+        let db = SimpleDB::new("dumptestdb", 400, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let planner = IndexUpdatePlanner::new(db.metadata_mgr());
+        let update = |sql: &str| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone()).unwrap();
+        update("create schema app");
+        update("create table app.\"Emp\" (id int not null check (id > 0), name varchar(20), pay double, hired date, photo blob)");
+        update("create table dept (did bigint, open boolean)");
+        update("create table empty (a int)");
+        update("insert into app.\"Emp\" (id, name, pay, hired, photo) values (1, 'O''Brien', 2.5, date '2020-02-29', x'cafe')");
+        update("insert into app.\"Emp\" (id, name) values (2, 'two\nlines')");
+        update("insert into dept (did, open) values (12345678901, true)");
+        update("create index empname on app.\"Emp\" (name)");
+        update("create index deptopen on dept (did) where open = true");
+        update("create text index empwords on app.\"Emp\" (name)");
+        update("create view rich as select name from app.\"Emp\" where pay = 2.5");
+        update("create view richer as select name from rich");
+        tx.lock().unwrap().commit().unwrap();
+
+        let mut dump = Vec::new();
+        assert_eq!(db.dump(&mut dump).unwrap(), 12);
+        let text = String::from_utf8(dump.clone()).unwrap();
+        assert!(text.starts_with("create schema app\ncreate table app.\"Emp\" (id int not null check (id > 0), name varchar(20), "));
+        assert!(text.contains("values (2, 'two\nlines', null, null, null)\n"));
+        assert!(text.contains("\ncreate index deptopen on dept (did) where open=true\n"));
+        assert!(text.contains("\ncreate text index empwords on app.\"Emp\" (name)\n"));
+        assert!(text.ends_with("create view rich as select name from app.\"Emp\" where pay=2.5\ncreate view richer as select name from rich\n"));
+
+        // The dump loads into a database with another block size, and
+        // dumps the same from there.
+        let copy = SimpleDB::ephemeral().unwrap();
+        assert_eq!(copy.load(&mut dump.as_slice()).unwrap(), 12);
+        let mut again = Vec::new();
+        copy.dump(&mut again).unwrap();
+        assert_eq!(String::from_utf8(again).unwrap(), text);
+        let tx = copy.new_tx().unwrap();
+        assert!(copy.metadata_mgr().index_info("app.Emp", tx.clone()).unwrap().contains_key("name"));
+        tx.lock().unwrap().commit().unwrap();
+
+        // A load that fails leaves nothing behind.
+        let other = SimpleDB::ephemeral().unwrap();
+        let bad = "create table t (a int)\ninsert into t (a) values (1)\ninsert into t (a) values ('x";
+        assert!(matches!(other.load(&mut bad.as_bytes()), Err(ScanError::Syntax(_))));
+        let mut empty = Vec::new();
+        assert_eq!(other.dump(&mut empty).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_clone_to() {
+        let _ = fs::remove_dir_all("clonesourcetestdb");
+        let _ = fs::remove_dir_all("clonetestdb");
+        let db = Arc::new(SimpleDB::new("clonesourcetestdb", 400, 8).unwrap());
+        let mut schema = Schema::new();
+        schema.add_int_field("a");
+        let tx = db.new_tx().unwrap();
+        db.metadata_mgr().create_table("t", &schema, tx.clone()).unwrap();
+        tx.lock().unwrap().commit().unwrap();
+        let layout = {
+            let tx = db.new_tx().unwrap();
+            let layout = db.metadata_mgr().layout("t", tx.clone()).unwrap();
+            tx.lock().unwrap().commit().unwrap();
+            layout
+        };
+        let insert = |tx: &Arc<Mutex<Transaction>>, a: i32| {
+            let mut ts = TableScan::new(tx.clone(), layout.clone(), "t").unwrap();
+            ts.insert().unwrap();
+            ts.set_int("a", a).unwrap();
+            ts.close();
+        };
+        let read = |db: &SimpleDB| {
+            let tx = db.new_tx().unwrap();
+            let mut ts = TableScan::new(tx.clone(), layout.clone(), "t").unwrap();
+            let mut vals = Vec::new();
+            while ts.next().unwrap() {
+                vals.push(ts.get_int("a").unwrap());
+            }
+            ts.close();
+            tx.lock().unwrap().commit().unwrap();
+            vals
+        };
+
+        // The clone waits for the active transaction, and has its changes
+        // once it commits.
+        let tx = db.new_tx().unwrap();
+        insert(&tx, 1);
+        let source = db.clone();
+        let clone = thread::spawn(move || source.clone_to("clonetestdb"));
+        while !db.admission_ctl().is_quiescing() {
+            thread::yield_now();
+        }
+        assert!(!Path::new("clonetestdb").exists());
+        tx.lock().unwrap().commit().unwrap();
+        clone.join().unwrap().unwrap();
+
+        // The clone is independent of the database it was taken from.
+        let tx = db.new_tx().unwrap();
+        insert(&tx, 2);
+        tx.lock().unwrap().commit().unwrap();
+        let copy = SimpleDB::new("clonetestdb", 400, 8).unwrap();
+        assert_eq!(read(&copy), [1]);
+        assert_eq!(read(&db), [1, 2]);
+        assert!(matches!(db.clone_to("clonetestdb"), Err(DbError::Io(_))));
+    }
+
+    #[test]
+    fn test_disk_usage() {
+        use crate::parse::parser::Parser;
+        let db = SimpleDB::ephemeral().unwrap();
+        let tx = db.new_tx().unwrap();
+        let planner = IndexUpdatePlanner::new(db.metadata_mgr());
+        let update = |sql: &str| planner.execute_update(&Parser::new(sql).unwrap().update_cmd().unwrap(), tx.clone()).unwrap();
+        update("create table t (a int, b int)");
+        update("create index tb on t (b)");
+        update("create index ta on t (a)");
+        for i in 0..10 {
+            update(&format!("insert into t (a, b) values ({}, {})", i, i % 2));
+        }
+        update("delete from t where b = 0");
+        tx.lock().unwrap().commit().unwrap();
+
+        // The table is followed by its indexes, in the order of their names,
+        // and the deleted records left free slots in each.
+        let usage = db.disk_usage().unwrap();
+        let t = usage.iter().position(|size| size.table == "t" && size.index.is_none()).unwrap();
+        let relations: Vec<Option<&str>> = usage[t..t + 3].iter().map(|size| size.index.as_deref()).collect();
+        assert_eq!(relations, [None, Some("ta"), Some("tb")]);
+        for size in &usage[t..t + 3] {
+            assert_eq!(size.table, "t");
+            assert_eq!(size.records, 5);
+            assert!(size.blocks > 0 && size.bytes == size.blocks * db.file_mgr().block_size());
+            assert!(size.free_slots >= 5);
+        }
+        assert_eq!(usage[t + 1].quota, None);
+    }
+
+    #[test]
+    fn test_block_sizes() {
+        for block_size in SimpleDB::BLOCK_SIZES {
+            let dir = format!("blocksize{}testdb", block_size);
+            let _ = fs::remove_dir_all(&dir);
+            let db = SimpleDB::new(&dir, block_size, 8).unwrap();
+            let mut schema = Schema::new();
+            schema.add_string_field("s", 1000);
+            schema.add_blob_field("b");
+            let long = "x".repeat(1000);
+            let blob: Vec<u8> = (0..3 * block_size).map(|i| i as u8).collect();
+            let tx = db.new_tx().unwrap();
+            db.metadata_mgr().create_table("bigblocks", &schema, tx.clone()).unwrap();
+            let layout = db.metadata_mgr().layout("bigblocks", tx.clone()).unwrap();
+            assert!(!layout.is_spilled("s"));
+            let mut ts = TableScan::new(tx.clone(), layout.clone(), "bigblocks").unwrap();
+            ts.insert().unwrap();
+            ts.set_string("s", &long).unwrap();
+            ts.set_blob("b", &blob).unwrap();
+            ts.close();
+            tx.lock().unwrap().commit().unwrap();
+
+            // The log records of a long string are undone whole.
+            let tx = db.new_tx().unwrap();
+            let mut ts = TableScan::new(tx.clone(), layout.clone(), "bigblocks").unwrap();
+            ts.next().unwrap();
+            ts.set_string("s", &"y".repeat(1000)).unwrap();
+            ts.close();
+            tx.lock().unwrap().rollback().unwrap();
+            drop(db);
+
+            let db = SimpleDB::new(&dir, block_size, 8).unwrap();
+            let tx = db.new_tx().unwrap();
+            let mut ts = TableScan::new(tx.clone(), layout, "bigblocks").unwrap();
+            assert!(ts.next().unwrap());
+            assert_eq!(ts.get_string("s").unwrap(), long);
+            assert_eq!(ts.get_val("b").unwrap(), Constant::Blob(blob));
+            ts.close();
+            tx.lock().unwrap().commit().unwrap();
+            drop(db);
+
+            // Opening the database with another block size fails fast.
+            assert!(matches!(SimpleDB::new(&dir, 400, 8), Err(DbError::Corruption(_))));
+        }
+    }
+
+    #[test]
+    fn test_max_length_field() {
+        // The longest string a 16KB record slot holds: the page header, the
+        // slot flag, the null bitmap and the length prefix take 16 bytes.
+        let block_size = 16384;
+        let max = block_size - 16;
+        let dir = "maxfieldtestdb";
+        let _ = fs::remove_dir_all(dir);
+        let db = SimpleDB::new(dir, block_size, 8).unwrap();
+        let mut schema = Schema::new();
+        schema.add_string_field("s", max as i32);
+        let first = "x".repeat(max);
+        let tx = db.new_tx().unwrap();
+        db.metadata_mgr().create_table("maxfield", &schema, tx.clone()).unwrap();
+        let layout = db.metadata_mgr().layout("maxfield", tx.clone()).unwrap();
+        assert!(!layout.is_spilled("s"));
+        assert_eq!(layout.slot_size() as usize, block_size - Page::HEADER_SIZE);
+        let mut ts = TableScan::new(tx.clone(), layout.clone(), "maxfield").unwrap();
+        ts.insert().unwrap();
+        ts.set_string("s", &first).unwrap();
+        ts.close();
+        tx.lock().unwrap().commit().unwrap();
+
+        // The old value is too long for one log record, and is undone from
+        // its pieces.
+        let tx = db.new_tx().unwrap();
+        let mut ts = TableScan::new(tx.clone(), layout.clone(), "maxfield").unwrap();
+        ts.next().unwrap();
+        ts.set_string("s", &"y".repeat(max)).unwrap();
+        ts.close();
+        tx.lock().unwrap().rollback().unwrap();
+        drop(db);
+
+        let db = SimpleDB::new(dir, block_size, 8).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mut ts = TableScan::new(tx.clone(), layout, "maxfield").unwrap();
+        assert!(ts.next().unwrap());
+        assert_eq!(ts.get_string("s").unwrap(), first);
+        assert!(!ts.next().unwrap());
+        ts.close();
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
@@ -1,4 +1,10 @@
 mod bufferlist;
 pub(crate) mod transaction;
 mod recovery;
-mod concurrency;
\ No newline at end of file
+pub(crate) mod concurrency;
+pub(crate) mod admission;
+pub(crate) mod writecheck;
+pub(crate) mod journal;
+pub mod rowchange;
+pub mod txstatus;
+pub mod rawtx;
@@ -0,0 +1,225 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use crate::error::dberror::DbError;
+
+struct Queue {
+    active: usize,
+    max_active: usize,
+    next_ticket: u64,
+    waiting: VecDeque<u64>,
+    // Whether new transactions are held off until a checkpoint is written.
+    quiescing: bool,
+}
+
+// The admission controller caps the number of simultaneously active
+// transactions. Every transaction needs buffer pins to make progress, so
+// letting an unbounded number of them start can exhaust the buffer pool
+// and leave all of them waiting on each other. Transactions beyond the
+// cap wait in a FIFO queue and are admitted in arrival order as slots
+// are released; if a slot does not become available within the maximum
+// wait time, a retryable error is returned instead.
+pub struct AdmissionCtl {
+    queue: Mutex<Queue>,
+    admitted: Condvar,
+    max_time: u128,
+}
+
+// A slot held by an admitted transaction. Dropping it releases the slot
+// and wakes up the waiting transactions.
+pub struct AdmissionSlot {
+    ctl: Arc<AdmissionCtl>,
+}
+
+// Held while the active transactions are quiesced. Dropping it admits new
+// transactions again.
+pub struct Quiescence {
+    ctl: Arc<AdmissionCtl>,
+}
+
+impl AdmissionCtl {
+    const MAX_TIME: u128 = 10000;
+
+    pub fn new(max_active: usize) -> AdmissionCtl {
+        AdmissionCtl {
+            queue: Mutex::new(Queue { active: 0, max_active, next_ticket: 0, waiting: VecDeque::new(), quiescing: false }),
+            admitted: Condvar::new(),
+            max_time: Self::MAX_TIME,
+        }
+    }
+
+    // A controller that admits any number of transactions, which is the
+    // default for a database; see `SimpleDB::set_max_active`.
+    pub fn unlimited() -> Arc<AdmissionCtl> {
+        Arc::new(AdmissionCtl::new(usize::MAX))
+    }
+
+    // Changes the cap on active transactions. Raising the cap immediately
+    // admits waiting transactions; lowering it only affects new arrivals.
+    pub fn set_max_active(&self, max_active: usize) {
+        self.queue.lock().unwrap().max_active = max_active;
+        self.admitted.notify_all();
+    }
+
+    pub fn active(&self) -> usize {
+        self.queue.lock().unwrap().active
+    }
+
+    // Whether new transactions are held off by a checkpoint.
+    pub fn is_quiescing(&self) -> bool {
+        self.queue.lock().unwrap().quiescing
+    }
+
+    // Waits for a free slot. A caller is only admitted when it is at the
+    // head of the queue, so later arrivals cannot overtake earlier ones.
+    pub fn admit(self: &Arc<Self>) -> Result<AdmissionSlot, DbError> {
+        let timestamp = Instant::now();
+        let mut queue = self.queue.lock().unwrap();
+        let ticket = queue.next_ticket;
+        queue.next_ticket += 1;
+        queue.waiting.push_back(ticket);
+        while !(queue.waiting.front() == Some(&ticket) && queue.active < queue.max_active && !queue.quiescing) {
+            let elapsed = timestamp.elapsed().as_millis();
+            if elapsed >= self.max_time {
+                queue.waiting.retain(|t| *t != ticket);
+                drop(queue);
+                // The head of the queue may have changed, let the others re-check.
+                self.admitted.notify_all();
+                return Err(DbError::AdmissionTimeout);
+            }
+            let timeout = Duration::from_millis((self.max_time - elapsed) as u64);
+            queue = self.admitted.wait_timeout(queue, timeout).unwrap().0;
+        }
+        queue.waiting.pop_front();
+        queue.active += 1;
+        drop(queue);
+        self.admitted.notify_all();
+        Ok(AdmissionSlot { ctl: self.clone() })
+    }
+
+    // Holds off new transactions and waits until no more than the given
+    // number of transactions, those of the caller, are still active. Only
+    // one caller quiesces at a time; the others wait their turn. If the
+    // active transactions do not complete within the maximum wait time,
+    // new ones are admitted again and a retryable error is returned.
+    pub fn quiesce(self: &Arc<Self>, own: usize) -> Result<Quiescence, DbError> {
+        let timestamp = Instant::now();
+        let mut queue = self.queue.lock().unwrap();
+        let mut holding = false;
+        loop {
+            if !holding && !queue.quiescing {
+                queue.quiescing = true;
+                holding = true;
+            }
+            if holding && queue.active <= own {
+                return Ok(Quiescence { ctl: self.clone() });
+            }
+            let elapsed = timestamp.elapsed().as_millis();
+            if elapsed >= self.max_time {
+                if holding {
+                    queue.quiescing = false;
+                    drop(queue);
+                    self.admitted.notify_all();
+                }
+                return Err(DbError::AdmissionTimeout);
+            }
+            let timeout = Duration::from_millis((self.max_time - elapsed) as u64);
+            queue = self.admitted.wait_timeout(queue, timeout).unwrap().0;
+        }
+    }
+
+    fn release(&self) {
+        self.queue.lock().unwrap().active -= 1;
+        self.admitted.notify_all();
+    }
+}
+
+impl Drop for AdmissionSlot {
+    fn drop(&mut self) {
+        self.ctl.release();
+    }
+}
+
+impl Drop for Quiescence {
+    fn drop(&mut self) {
+        self.ctl.queue.lock().unwrap().quiescing = false;
+        self.ctl.admitted.notify_all();
+    }
+}
+
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+
+    #[test]
+    fn test_admission_ctl() {
+        let mut ctl = AdmissionCtl::new(2);
+        ctl.max_time = 100;
+        let ctl = Arc::new(ctl);
+
+        let slot1 = ctl.admit().unwrap();
+        let _slot2 = ctl.admit().unwrap();
+        assert_eq!(ctl.active(), 2);
+
+        // The cap is reached, so a third transaction times out.
+        assert!(ctl.admit().is_err());
+        assert_eq!(ctl.active(), 2);
+
+        // Releasing a slot admits the next transaction.
+        drop(slot1);
+        assert_eq!(ctl.active(), 1);
+        let _slot3 = ctl.admit().unwrap();
+        assert_eq!(ctl.active(), 2);
+    }
+
+    #[test]
+    fn test_quiesce() {
+        let mut ctl = AdmissionCtl::new(10);
+        ctl.max_time = 100;
+        let ctl = Arc::new(ctl);
+        let _slot1 = ctl.admit().unwrap();
+        let slot2 = ctl.admit().unwrap();
+
+        // Another transaction is still active, so quiescing times out and
+        // new transactions are admitted again.
+        assert!(ctl.quiesce(1).is_err());
+        let slot3 = ctl.admit().unwrap();
+        drop(slot2);
+        drop(slot3);
+
+        let quiescence = ctl.quiesce(1).unwrap();
+        assert!(ctl.admit().is_err());
+        drop(quiescence);
+        let _slot4 = ctl.admit().unwrap();
+        assert_eq!(ctl.active(), 2);
+    }
+
+    #[test]
+    fn test_admission_order() {
+        let mut ctl = AdmissionCtl::new(1);
+        ctl.max_time = 5000;
+        let ctl = Arc::new(ctl);
+        let slot = ctl.admit().unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let mut handles = Vec::new();
+        for i in 0..3 {
+            let waiter = ctl.clone();
+            let sender = sender.clone();
+            handles.push(thread::spawn(move || {
+                let _slot = waiter.admit().unwrap();
+                sender.send(i).unwrap();
+            }));
+            // Make sure the waiters enqueue in order.
+            while ctl.queue.lock().unwrap().waiting.len() < i + 1 {
+                thread::yield_now();
+            }
+        }
+
+        drop(slot);
+        handles.into_iter().for_each(|h| h.join().unwrap());
+        let order: Vec<usize> = receiver.try_iter().collect();
+        assert_eq!(order, vec![0, 1, 2]);
+    }
+}
@@ -3,45 +3,98 @@ use std::sync::{Arc, Mutex};
 use crate::buffer::buffer::Buffer;
 use crate::file::blockid::BlockId;
 use crate::buffer::buffermgr::BufferMgr;
+use crate::error::dberror::DbError;
+use crate::tx::journal::{self, JournalOp};
 
+// While the transaction holds a reservation, see `reserve`, its pins are
+// made from the reserved buffers, and the buffers it unpins go back to the
+// reservation. A reserved buffer that is still pinned by someone else when
+// the transaction unpins it is lost to the reservation, and later pins wait
+// for a buffer like any other once the reservation is used up.
 pub struct BufferList {
+    txnum: i32,
     buffers: HashMap<BlockId, usize>,
     pins: Vec<BlockId>,
+    reserved: usize,
+    reserved_pins: Vec<BlockId>,
     bm: Arc<Mutex<BufferMgr>>,
 }
 
 impl BufferList {
 
-    pub(crate) fn new(bm: Arc<Mutex<BufferMgr>>) -> BufferList {
-        BufferList { buffers: HashMap::new(), pins: Vec::new(), bm }
+    pub(crate) fn new(bm: Arc<Mutex<BufferMgr>>, txnum: i32) -> BufferList {
+        BufferList { txnum, buffers: HashMap::new(), pins: Vec::new(), reserved: 0, reserved_pins: Vec::new(), bm }
     }
 
     pub(crate) fn buffer(&mut self, blk: &BlockId) -> Option<usize> {
         self.buffers.get(blk).map(|&i| i)
     }
 
-    pub(crate) fn pin(&mut self, blk: &BlockId) {
-        match self.bm.lock().unwrap().pin(&blk) {
-            Ok(idx) => {
-                self.buffers.insert(blk.clone(), idx);
-                self.pins.push(blk.clone());
-
+    pub(crate) fn pin(&mut self, blk: &BlockId) -> Result<(), DbError> {
+        let idx = if self.reserved > 0 {
+            let (idx, claimed) = self.bm.lock().unwrap().pin_reserved(blk)?;
+            if claimed {
+                self.reserved -= 1;
+                self.reserved_pins.push(*blk);
             }
-            Err(_) => {}
-        }
+            idx
+        } else {
+            BufferMgr::pin(&self.bm, blk)?
+        };
+        self.buffers.insert(*blk, idx);
+        self.pins.push(*blk);
+        journal::record(self.txnum, JournalOp::Pin, blk);
+        Ok(())
     }
 
     pub(crate) fn unpin(&mut self, blk: &BlockId) {
-        self.buffers.get(blk).map(|&i| self.bm.lock().unwrap().unpin(i));
+        if let Some(&i) = self.buffers.get(blk) {
+            match self.reserved_pins.iter().position(|b| b == blk) {
+                Some(pos) => {
+                    self.reserved_pins.swap_remove(pos);
+                    if self.bm.lock().unwrap().unpin_reserved(i) {
+                        self.reserved += 1;
+                    }
+                }
+                None => self.bm.lock().unwrap().unpin(i),
+            }
+        }
+        journal::record(self.txnum, JournalOp::Unpin, blk);
         self.pins.retain(|b| b != blk);
         //if !self.buffers.contains_key(blk) {
         self.buffers.remove(blk);
         //}
     }
 
+    pub(crate) fn pin_count(&self) -> usize {
+        self.pins.len()
+    }
+
+    // Sets aside n buffers for the transaction, all at once or not at all,
+    // for an operator that needs them to make progress.
+    pub(crate) fn reserve(&mut self, n: usize) -> Result<(), DbError> {
+        BufferMgr::reserve(&self.bm, n)?;
+        self.reserved += n;
+        Ok(())
+    }
+
+    // Hands back n buffers of the reservation. Those that are still pinned
+    // go back to the pool, rather than to the reservation, once unpinned.
+    pub(crate) fn release(&mut self, n: usize) {
+        let now = n.min(self.reserved);
+        self.bm.lock().unwrap().release(now);
+        self.reserved -= now;
+        let later = (n - now).min(self.reserved_pins.len());
+        self.reserved_pins.truncate(self.reserved_pins.len() - later);
+    }
+
+    // Unpins every buffer and hands back what is left of the reservation.
     pub(crate) fn unpin_all(&mut self) {
+        self.reserved_pins.clear();
+        self.release(self.reserved);
         self.pins.iter().for_each(|b| {
             self.buffers.get(b).map(|&i| self.bm.lock().unwrap().unpin(i));
+            journal::record(self.txnum, JournalOp::Unpin, b);
         });
         self.buffers.clear();
         self.pins.clear();
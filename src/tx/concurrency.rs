@@ -1,2 +1,3 @@
 pub(crate) mod concurrencymgr;
+pub(crate) mod lockevent;
 mod locktable;
\ No newline at end of file
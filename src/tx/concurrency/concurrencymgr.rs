@@ -1,8 +1,11 @@
 use std::cmp::PartialEq;
 use std::collections::HashMap;
-use std::sync::{LazyLock, Mutex};
+use std::sync::LazyLock;
+use crate::error::dberror::DbError;
 use crate::file::blockid::BlockId;
+use crate::tx::concurrency::lockevent::LockEvent;
 use crate::tx::concurrency::locktable::LockTable;
+use crate::tx::journal::{self, JournalOp};
 
 #[derive(Eq, PartialEq)]
 enum LockType {
@@ -11,41 +14,110 @@ enum LockType {
 }
 
 pub struct ConcurrencyMgr {
+    txnum: i32,
     locks: HashMap<BlockId, LockType>,
+    optimistic: bool,
+    // The version of each block an optimistic transaction read, when it
+    // first read it.
+    reads: HashMap<BlockId, u64>,
 }
 
-static LOCK_TABLE: LazyLock<Mutex<LockTable>> = LazyLock::new(|| Mutex::new(LockTable::new()));
+static LOCK_TABLE: LazyLock<LockTable> = LazyLock::new(LockTable::new);
 
+// The ConcurrencyMgr locks blocks for its transaction under strict two-phase
+// locking, or, in optimistic mode, only locks the blocks the transaction
+// writes. An optimistic read takes no lock and notes the version of the
+// block instead; the commit validates that none of the blocks read has been
+// written by another transaction since. Writes still take exclusive locks,
+// as they go to the shared buffers in place and are undone from the log. A
+// read of a block that another transaction is writing fails right away,
+// rather than seeing uncommitted data.
 impl ConcurrencyMgr {
-    pub fn new() -> ConcurrencyMgr {
+    pub fn new(txnum: i32) -> ConcurrencyMgr {
         ConcurrencyMgr {
+            txnum,
             locks: HashMap::new(),
+            optimistic: false,
+            reads: HashMap::new(),
         }
     }
 
-    pub fn slock(&mut self, blk: &BlockId) {
+    // Switches to optimistic mode, before the transaction reads or writes
+    // anything.
+    pub fn set_optimistic(&mut self) {
+        assert!(self.locks.is_empty(), "transaction {} already holds locks", self.txnum);
+        self.optimistic = true;
+    }
+
+    pub fn slock(&mut self, blk: &BlockId) -> Result<(), DbError> {
+        if self.optimistic {
+            return self.read(blk);
+        }
+        self.lock_shared(blk)
+    }
+
+    // Checks that every block read still has the version it was read at,
+    // and is not being written by another transaction.
+    pub fn validate(&self) -> Result<(), DbError> {
+        for (blk, version) in &self.reads {
+            if LOCK_TABLE.version(blk, self.has_xlock(blk)) != Some(*version) {
+                return Err(DbError::Conflict(*blk));
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, blk: &BlockId) -> Result<(), DbError> {
+        if !self.locks.contains_key(blk) && !self.reads.contains_key(blk) {
+            let version = LOCK_TABLE.track(blk).ok_or(DbError::Conflict(*blk))?;
+            self.reads.insert(*blk, version);
+        }
+        Ok(())
+    }
+
+    fn lock_shared(&mut self, blk: &BlockId) -> Result<(), DbError> {
         if !self.locks.contains_key(blk) {
-            LOCK_TABLE.lock().unwrap().slock(blk).unwrap();
-            self.locks.insert(blk.clone(), LockType::SLock);
+            LOCK_TABLE.slock(blk)?;
+            journal::record(self.txnum, JournalOp::SLock, blk);
+            self.locks.insert(*blk, LockType::SLock);
         }
+        Ok(())
     }
 
-    pub fn xlock(&mut self, blk: &BlockId) {
+    pub fn xlock(&mut self, blk: &BlockId) -> Result<(), DbError> {
         if !self.has_xlock(&blk) {
-            self.slock(blk);
-            // TODO: I can't lock the table here because it's
-            // already locked by the slock method above. Is this
-            // a problem?
-            // LOCK_TABLE.lock().unwrap().xlock(blk).unwrap();
-            self.locks.insert(blk.clone(), LockType::XLock);
+            self.lock_shared(blk)?;
+            LOCK_TABLE.xlock(blk)?;
+            journal::record(self.txnum, JournalOp::XLock, blk);
+            self.locks.insert(*blk, LockType::XLock);
         }
+        Ok(())
+    }
+
+    pub fn holds_xlocks(&self) -> bool {
+        self.locks.values().any(|lock| *lock == LockType::XLock)
+    }
+
+    pub fn lock_count(&self) -> usize {
+        self.locks.len()
+    }
+
+    // Returns the lock events recorded by the lock table shared by every
+    // transaction in the process.
+    pub fn lock_events() -> Vec<LockEvent> {
+        LOCK_TABLE.events()
     }
 
     pub fn release(&mut self) {
         for blk in self.locks.keys() {
-            LOCK_TABLE.lock().unwrap().unlock(blk);
+            LOCK_TABLE.unlock(blk);
+            journal::record(self.txnum, JournalOp::Unlock, blk);
         }
         self.locks.clear();
+        for blk in self.reads.keys() {
+            LOCK_TABLE.untrack(blk);
+        }
+        self.reads.clear();
     }
 
     fn has_xlock(&self, blk: &BlockId) -> bool {
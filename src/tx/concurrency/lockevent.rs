@@ -0,0 +1,23 @@
+use crate::file::blockid::BlockId;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LockEventKind {
+    // The request was granted after waiting longer than the threshold.
+    Wait,
+    // The request gave up. Deadlocks are only detected by timing out, so
+    // this is also how a deadlock shows up, with the requester as victim.
+    Timeout,
+}
+
+// A LockEvent describes a lock request that ran into contention. `holders`
+// is what was held on the block when the request first had to wait: -1
+// for an exclusive lock, otherwise the number of shared locks. The lock
+// table does not know which transactions hold them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockEvent {
+    pub kind: LockEventKind,
+    pub block: BlockId,
+    pub exclusive: bool,
+    pub holders: i32,
+    pub waited: u128,
+}
@@ -1,66 +1,354 @@
-use std::collections::HashMap;
-use std::thread::{current, park_timeout};
-use std::time::Duration;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use crate::error::dberror::DbError;
 use crate::file::blockid::BlockId;
+use crate::tx::concurrency::lockevent::{LockEvent, LockEventKind};
 
-pub struct LockTable {
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Request {
+    Shared,
+    Exclusive,
+}
+
+struct Waiter {
+    ticket: u64,
+    request: Request,
+    overtaken: u32,
+}
+
+struct State {
     locks: HashMap<BlockId, i32>,
+    waiting: HashMap<BlockId, VecDeque<Waiter>>,
+    next_ticket: u64,
+    events: VecDeque<LockEvent>,
+    versions: HashMap<BlockId, Version>,
+}
+
+// The version of a block and the number of optimistic transactions that
+// read it and have not completed yet.
+struct Version {
+    version: u64,
+    readers: usize,
+}
+
+// The lock table keeps track of the locks held on each block: a positive
+// value is the number of shared locks, and -1 means the block is exclusively
+// locked. Requests that cannot be granted right away wait in a per-block FIFO
+// queue, so a steady stream of shared locks cannot starve an exclusive lock
+// waiter. A shared lock may still overtake waiting exclusive requests a few
+// times when it is compatible with the current holders, which keeps a reader
+// that already holds other locks from deadlocking behind a queued writer.
+//
+// Requests that wait longer than a threshold, and requests that time out,
+// are recorded as lock events in a ring buffer that keeps the most recent
+// ones, so that contention can be looked into after the fact.
+//
+// The table also keeps a version of each block read by an optimistic
+// transaction, which goes up every time an exclusive lock on the block is
+// released, at commit or rollback. Only the blocks read by optimistic
+// transactions that are still active are tracked: the version of a block
+// is dropped when the last of them completes.
+pub struct LockTable {
+    state: Mutex<State>,
+    released: Condvar,
     max_time: u128,
+    wait_threshold: u128,
 }
 
 impl LockTable {
 
     const MAX_TIME : u128 = 10000;
+    const MAX_OVERTAKES: u32 = 4;
+    const WAIT_THRESHOLD: u128 = 100;
+    const MAX_EVENTS: usize = 256;
 
     pub fn new() -> LockTable {
         LockTable {
-            locks: HashMap::new(),
+            state: Mutex::new(State { locks: HashMap::new(), waiting: HashMap::new(), next_ticket: 0, events: VecDeque::new(), versions: HashMap::new() }),
+            released: Condvar::new(),
             max_time: Self::MAX_TIME,
+            wait_threshold: Self::WAIT_THRESHOLD,
         }
     }
 
-    pub fn slock(&mut self, blk: &BlockId) -> Result<(), &str> {
-        let start = std::time::SystemTime::now();
-        let mut elapsed = start.elapsed().unwrap().as_millis();
-        while self.has_xlock(blk) && elapsed < self.max_time {
-            park_timeout(Duration::from_millis(self.max_time as u64));
-        }
-        if self.has_xlock(blk) {
-            return Err("block has an exclusive lock")
-        }
-        let locks = *self.locks.get(blk).unwrap_or(&0);
-        self.locks.insert(blk.clone(), locks + 1);
+    // Returns the recorded lock events, oldest first.
+    pub fn events(&self) -> Vec<LockEvent> {
+        self.state.lock().unwrap().events.iter().cloned().collect()
+    }
+
+    pub fn slock(&self, blk: &BlockId) -> Result<(), DbError> {
+        let mut state = self.acquire(blk, Request::Shared)?;
+        let locks = *state.locks.get(blk).unwrap_or(&0);
+        state.locks.insert(*blk, locks + 1);
         Ok(())
     }
 
-    pub fn xlock(&mut self, blk: &BlockId) -> Result<(), &str> {
-        let start = std::time::SystemTime::now();
-        let mut elapsed = start.elapsed().unwrap().as_millis();
-        while self.has_other_slocks(&blk) && elapsed < self.max_time {
-            park_timeout(Duration::from_millis(self.max_time as u64));
-        }
-        if self.has_other_slocks(&blk) {
-            return Err("block has shared locks")
-        }
-        self.locks.insert(blk.clone(), -1);
+    // Upgrades the caller's shared lock to an exclusive lock. The caller is
+    // expected to already hold a shared lock on the block.
+    pub fn xlock(&self, blk: &BlockId) -> Result<(), DbError> {
+        let mut state = self.acquire(blk, Request::Exclusive)?;
+        state.locks.insert(*blk, -1);
         Ok(())
     }
 
-    pub fn unlock(&mut self, blk: &BlockId) {
-        let locks = *self.locks.get(blk).unwrap_or(&0);
+    pub fn unlock(&self, blk: &BlockId) {
+        let mut state = self.state.lock().unwrap();
+        let locks = *state.locks.get(blk).unwrap_or(&0);
+        if locks < 0 && let Some(version) = state.versions.get_mut(blk) {
+            version.version += 1;
+        }
         if locks > 1 {
-            self.locks.insert(blk.clone(), locks - 1);
+            state.locks.insert(*blk, locks - 1);
         } else {
-            self.locks.remove(blk);
-            current().unpark();
+            state.locks.remove(blk);
+        }
+        self.released.notify_all();
+    }
+
+    // Starts tracking the block for a reader, and returns its version.
+    // There is none while another transaction holds an exclusive lock on
+    // the block.
+    pub fn track(&self, blk: &BlockId) -> Option<u64> {
+        let mut state = self.state.lock().unwrap();
+        if Self::has_xlock(&state, blk) {
+            return None;
+        }
+        let version = state.versions.entry(*blk).or_insert(Version { version: 0, readers: 0 });
+        version.readers += 1;
+        Some(version.version)
+    }
+
+    // Stops tracking the block for a reader that completed.
+    pub fn untrack(&self, blk: &BlockId) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(version) = state.versions.get_mut(blk) {
+            version.readers -= 1;
+            if version.readers == 0 {
+                state.versions.remove(blk);
+            }
+        }
+    }
+
+    // Returns the version of the block. There is no version while another
+    // transaction holds an exclusive lock on the block, unless the caller
+    // is the one holding it.
+    pub fn version(&self, blk: &BlockId, held: bool) -> Option<u64> {
+        let state = self.state.lock().unwrap();
+        if !held && Self::has_xlock(&state, blk) {
+            return None;
+        }
+        Some(state.versions.get(blk).map_or(0, |version| version.version))
+    }
+
+    // Enqueues the request and waits until it can be granted or the maximum
+    // time elapses. On success the request is removed from the queue and the
+    // state is handed back still locked, so the caller records the lock before
+    // any other request can be granted.
+    fn acquire(&self, blk: &BlockId, request: Request) -> Result<MutexGuard<'_, State>, DbError> {
+        let timestamp = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        state.waiting.entry(*blk).or_default().push_back(Waiter { ticket, request, overtaken: 0 });
+        let mut holders = None;
+        while !Self::grantable(&state, blk, ticket, request) {
+            holders.get_or_insert(*state.locks.get(blk).unwrap_or(&0));
+            let elapsed = timestamp.elapsed().as_millis();
+            if elapsed >= self.max_time {
+                Self::dequeue(&mut state, blk, ticket);
+                Self::record(&mut state, LockEventKind::Timeout, blk, request, holders.unwrap_or(0), elapsed);
+                self.released.notify_all();
+                return Err(DbError::LockTimeout(*blk));
+            }
+            let timeout = Duration::from_millis((self.max_time - elapsed) as u64);
+            state = self.released.wait_timeout(state, timeout).unwrap().0;
+        }
+        let elapsed = timestamp.elapsed().as_millis();
+        if let Some(holders) = holders && elapsed >= self.wait_threshold {
+            Self::record(&mut state, LockEventKind::Wait, blk, request, holders, elapsed);
+        }
+        if let Some(queue) = state.waiting.get_mut(blk) {
+            for waiter in queue.iter_mut().take_while(|w| w.ticket != ticket) {
+                waiter.overtaken += 1;
+            }
         }
+        Self::dequeue(&mut state, blk, ticket);
+        self.released.notify_all();
+        Ok(state)
     }
 
-    fn has_xlock(&self, blk: &BlockId) -> bool {
-        self.locks.contains_key(blk) && self.locks[blk] < 0
+    // A request is granted when it is compatible with the locks currently held
+    // and every request ahead of it in the queue is an exclusive request that
+    // can still be overtaken by a shared one.
+    fn grantable(state: &State, blk: &BlockId, ticket: u64, request: Request) -> bool {
+        let compatible = match request {
+            Request::Shared => !Self::has_xlock(state, blk),
+            Request::Exclusive => !Self::has_other_slocks(state, blk),
+        };
+        if !compatible {
+            return false;
+        }
+        let queue = &state.waiting[blk];
+        queue.iter().take_while(|w| w.ticket != ticket).all(|w| {
+            request == Request::Shared && w.request == Request::Exclusive && w.overtaken < Self::MAX_OVERTAKES
+        })
+    }
+
+    fn record(state: &mut State, kind: LockEventKind, blk: &BlockId, request: Request, holders: i32, waited: u128) {
+        if state.events.len() == Self::MAX_EVENTS {
+            state.events.pop_front();
+        }
+        state.events.push_back(LockEvent { kind, block: *blk, exclusive: request == Request::Exclusive, holders, waited });
+    }
+
+    fn dequeue(state: &mut State, blk: &BlockId, ticket: u64) {
+        if let Some(queue) = state.waiting.get_mut(blk) {
+            queue.retain(|w| w.ticket != ticket);
+            if queue.is_empty() {
+                state.waiting.remove(blk);
+            }
+        }
+    }
+
+    fn has_xlock(state: &State, blk: &BlockId) -> bool {
+        state.locks.get(blk).is_some_and(|&locks| locks < 0)
+    }
+
+    fn has_other_slocks(state: &State, blk: &BlockId) -> bool {
+        state.locks.get(blk).is_some_and(|&locks| locks > 1)
     }
+}
 
-    fn has_other_slocks(&self, blk: &BlockId) -> bool {
-        self.locks.contains_key(blk) && self.locks[blk] > 1
+mod tests {
+    use super::*;
+    use crate::file::blockid::FileNames;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    #[test]
+    fn test_lock_table() {
+        let mut lt = LockTable::new();
+        lt.max_time = 50;
+        let blk = FileNames::new().block("locktable", 0);
+
+        lt.slock(&blk).unwrap();
+        lt.slock(&blk).unwrap();
+        assert_eq!(lt.xlock(&blk), Err(DbError::LockTimeout(blk)));
+        lt.unlock(&blk);
+        lt.xlock(&blk).unwrap();
+        assert!(lt.slock(&blk).is_err());
+        lt.unlock(&blk);
+        lt.slock(&blk).unwrap();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_versions() {
+        let lt = LockTable::new();
+        let blk = FileNames::new().block("locktable", 3);
+
+        // Untracked blocks keep no version.
+        lt.slock(&blk).unwrap();
+        lt.xlock(&blk).unwrap();
+        lt.unlock(&blk);
+        assert_eq!(lt.version(&blk, false), Some(0));
+        assert!(lt.state.lock().unwrap().versions.is_empty());
+
+        assert_eq!(lt.track(&blk), Some(0));
+        lt.slock(&blk).unwrap();
+        lt.unlock(&blk);
+        assert_eq!(lt.version(&blk, false), Some(0));
+        lt.slock(&blk).unwrap();
+        lt.xlock(&blk).unwrap();
+        assert_eq!(lt.version(&blk, false), None);
+        assert_eq!(lt.track(&blk), None);
+        assert_eq!(lt.version(&blk, true), Some(0));
+        lt.unlock(&blk);
+        assert_eq!(lt.version(&blk, false), Some(1));
+
+        // The version is dropped once its last reader completes.
+        assert_eq!(lt.track(&blk), Some(1));
+        lt.untrack(&blk);
+        assert_eq!(lt.version(&blk, false), Some(1));
+        lt.untrack(&blk);
+        assert!(lt.state.lock().unwrap().versions.is_empty());
+    }
+
+    #[test]
+    fn test_lock_events() {
+        let mut lt = LockTable::new();
+        lt.max_time = 50;
+        lt.wait_threshold = 10;
+        let blk = FileNames::new().block("locktable", 2);
+
+        // Requests granted right away are not recorded.
+        lt.slock(&blk).unwrap();
+        lt.slock(&blk).unwrap();
+        assert!(lt.events().is_empty());
+
+        assert!(lt.xlock(&blk).is_err());
+        let events = lt.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].kind, events[0].block, events[0].exclusive, events[0].holders), (LockEventKind::Timeout, blk, true, 2));
+        assert!(events[0].waited >= 50);
+
+        // A request that waits past the threshold is recorded once granted.
+        lt.max_time = 5000;
+        lt.unlock(&blk);
+        lt.xlock(&blk).unwrap();
+        let lt = Arc::new(lt);
+        let reader = {
+            let lt = lt.clone();
+            thread::spawn(move || lt.slock(&blk).is_ok())
+        };
+        thread::sleep(Duration::from_millis(20));
+        lt.unlock(&blk);
+        assert!(reader.join().unwrap());
+        let events = lt.events();
+        assert_eq!(events.len(), 2);
+        assert_eq!((events[1].kind, events[1].exclusive, events[1].holders), (LockEventKind::Wait, false, -1));
+        assert!(events[1].waited >= 10);
+    }
+
+    #[test]
+    fn test_xlock_not_starved_by_readers() {
+        let mut lt = LockTable::new();
+        lt.max_time = 5000;
+        let lt = Arc::new(lt);
+        let blk = FileNames::new().block("locktable", 1);
+
+        // The writer holds a shared lock and waits for the reader's lock
+        // to go away before upgrading it.
+        lt.slock(&blk).unwrap();
+        lt.slock(&blk).unwrap();
+        let writer = {
+            let lt = lt.clone();
+            thread::spawn(move || lt.xlock(&blk).is_ok())
+        };
+        while !lt.state.lock().unwrap().waiting.contains_key(&blk) {
+            thread::yield_now();
+        }
+
+        // A stream of overlapping readers keeps the shared count above one.
+        let stop = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..4).map(|_| {
+            let lt = lt.clone();
+            let stop = stop.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    if lt.slock(&blk).is_ok() {
+                        lt.unlock(&blk);
+                    }
+                }
+            })
+        }).collect();
+
+        // Release the original reader; the writer must eventually get in.
+        lt.unlock(&blk);
+        assert!(writer.join().unwrap());
+        stop.store(true, Ordering::SeqCst);
+        lt.unlock(&blk);
+        readers.into_iter().for_each(|r| r.join().unwrap());
+    }
+}
@@ -0,0 +1,120 @@
+use std::fmt::Display;
+use std::thread::ThreadId;
+use crate::file::blockid::BlockId;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JournalOp {
+    Pin,
+    Unpin,
+    SLock,
+    XLock,
+    Unlock,
+    // A value written to the page at the offset.
+    Write(usize),
+}
+
+// A JournalEntry records an operation of a transaction on a block, along
+// with the thread that ran it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    pub txnum: i32,
+    pub thread: ThreadId,
+    pub op: JournalOp,
+    pub block: BlockId,
+}
+
+impl Display for JournalEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "tx {} on {:?}: {:?} {}", self.txnum, self.thread, self.op, self.block)
+    }
+}
+
+// The journal traces every pin, unpin, lock, unlock and page write of every
+// transaction in the process, in the order they happen, to help diagnose
+// concurrency bugs in the buffer manager and the lock table. It is only
+// kept when the crate is built with the `trace` feature; otherwise
+// recording does nothing and the journal is always empty. Like the lock
+// events, it is a ring buffer that keeps the most recent entries, and it is
+// printed to stderr when a thread panics once `install_panic_hook` has run.
+#[cfg(feature = "trace")]
+mod ring {
+    use std::collections::VecDeque;
+    use std::sync::{LazyLock, Mutex, Once};
+    use super::JournalEntry;
+
+    const MAX_ENTRIES: usize = 4096;
+
+    pub(super) static JOURNAL: LazyLock<Mutex<VecDeque<JournalEntry>>> = LazyLock::new(|| Mutex::new(VecDeque::new()));
+    pub(super) static HOOK: Once = Once::new();
+
+    pub(super) fn push(entry: JournalEntry) {
+        let mut journal = JOURNAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if journal.len() == MAX_ENTRIES {
+            journal.pop_front();
+        }
+        journal.push_back(entry);
+    }
+}
+
+#[cfg(feature = "trace")]
+pub(crate) fn record(txnum: i32, op: JournalOp, block: &BlockId) {
+    ring::push(JournalEntry { txnum, thread: std::thread::current().id(), op, block: *block });
+}
+
+#[cfg(not(feature = "trace"))]
+#[inline(always)]
+pub(crate) fn record(_txnum: i32, _op: JournalOp, _block: &BlockId) {}
+
+// Returns the journal entries, oldest first.
+#[cfg(feature = "trace")]
+pub fn entries() -> Vec<JournalEntry> {
+    ring::JOURNAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).iter().cloned().collect()
+}
+
+#[cfg(not(feature = "trace"))]
+pub fn entries() -> Vec<JournalEntry> {
+    Vec::new()
+}
+
+// Makes a panic in any thread print the journal before the panic message.
+// The hook is installed once, in front of the one already in place. The
+// journal is only read if no other thread holds it, so that a panic while
+// recording does not deadlock.
+pub fn install_panic_hook() {
+    #[cfg(feature = "trace")]
+    ring::HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if let Ok(journal) = ring::JOURNAL.try_lock() {
+                eprintln!("Operation journal, oldest first:");
+                journal.iter().for_each(|entry| eprintln!("  {}", entry));
+            }
+            previous(info);
+        }));
+    });
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "trace")]
+    fn test_journal() {
+        use crate::server::simpledb::SimpleDB;
+
+        let db = SimpleDB::ephemeral().unwrap();
+        let tx = db.new_tx().unwrap();
+        let blk = tx.lock().unwrap().append("journaltest").unwrap();
+        tx.lock().unwrap().pin(&blk).unwrap();
+        tx.lock().unwrap().set_int(&blk, 80, 7, true).unwrap();
+        tx.lock().unwrap().commit().unwrap();
+
+        // Other tests record entries too, but not from this thread.
+        let thread = std::thread::current().id();
+        let ops: Vec<JournalOp> = entries().into_iter()
+            .filter(|entry| entry.thread == thread && entry.block == blk)
+            .map(|entry| entry.op)
+            .collect();
+        assert_eq!(ops, [JournalOp::Pin, JournalOp::SLock, JournalOp::XLock, JournalOp::Write(80), JournalOp::Unpin, JournalOp::Unlock]);
+    }
+}
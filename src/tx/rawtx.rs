@@ -0,0 +1,209 @@
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+use crate::error::dberror::DbError;
+use crate::file::blockid::BlockId;
+use crate::file::page::Page;
+use crate::query::constant::Constant;
+use crate::record::schema::FieldType;
+use crate::tx::transaction::Transaction;
+
+// Errors returned by a RawTx. Reading or writing past the end of a block,
+// or a block the transaction has not pinned, is a caller bug that would
+// otherwise corrupt the page or panic.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RawError {
+    NotPinned(BlockId),
+    OutOfBounds { blk: BlockId, offset: usize },
+    // Nulls have no encoding of their own; a structure that needs them
+    // keeps a flag next to the value, as record pages do.
+    NullValue,
+    Db(DbError),
+}
+
+impl From<DbError> for RawError {
+    fn from(error: DbError) -> RawError {
+        RawError::Db(error)
+    }
+}
+
+impl Display for RawError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RawError::NotPinned(blk) => write!(f, "block {} is not pinned", blk),
+            RawError::OutOfBounds { blk, offset } => write!(f, "offset {} is outside the data of block {}", offset, blk),
+            RawError::NullValue => write!(f, "null values cannot be written to a block"),
+            RawError::Db(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+// A transaction that works on blocks directly, for structures built next to
+// the record layer rather than on it, such as a custom index or a queue. It
+// is what a TableScan does underneath: pin a block, read and write values at
+// offsets in it, and commit or roll back.
+//
+// Reads slock the block and writes xlock it, every write is logged so that
+// a rollback, or recovery after a crash, undoes it, and the locks are held
+// until the transaction completes. Values are encoded as in record pages:
+// ints, longs, doubles and dates in big-endian order, booleans as a byte,
+// and strings and blobs as their bytes after an int length. The first
+// `Page::HEADER_SIZE` bytes of each block hold its LSN and cannot be
+// written. Appended blocks are not given back on rollback, as with tables.
+//
+// The files are those of the database, so a structure should use names of
+// its own: `.tbl` files belong to tables and `.idx` files to indexes.
+//
+// A RawTx that is dropped without committing is rolled back.
+pub struct RawTx {
+    tx: Arc<Mutex<Transaction>>,
+}
+
+impl RawTx {
+    pub(crate) fn new(tx: Arc<Mutex<Transaction>>) -> RawTx {
+        RawTx { tx }
+    }
+
+    pub fn txnum(&self) -> i32 {
+        self.tx.lock().unwrap().txnum()
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.tx.lock().unwrap().block_size()
+    }
+
+    pub fn block(&self, filename: &str, number: usize) -> BlockId {
+        self.tx.lock().unwrap().block(filename, number)
+    }
+
+    // The number of blocks of the file; 0 if it does not exist yet.
+    pub fn size(&self, filename: &str) -> Result<usize, RawError> {
+        Ok(self.tx.lock().unwrap().size(filename)?)
+    }
+
+    // Adds a zeroed block at the end of the file, creating the file if
+    // needed, and returns it. The block is not pinned.
+    pub fn append(&self, filename: &str) -> Result<BlockId, RawError> {
+        Ok(self.tx.lock().unwrap().append(filename)?)
+    }
+
+    // Pins the block to a buffer until it is unpinned or the transaction
+    // completes. A block is pinned once for each call, and stays pinned
+    // until unpinned as many times.
+    pub fn pin(&self, blk: &BlockId) -> Result<(), RawError> {
+        Ok(self.tx.lock().unwrap().pin(blk)?)
+    }
+
+    pub fn unpin(&self, blk: &BlockId) {
+        self.tx.lock().unwrap().unpin(blk);
+    }
+
+    // Reads the value of the type stored at the offset of a pinned block.
+    pub fn get(&self, blk: &BlockId, offset: usize, ftype: FieldType) -> Result<Constant, RawError> {
+        let mut tx = self.tx.lock().unwrap();
+        let len = match ftype {
+            FieldType::VARCHAR | FieldType::BLOB => {
+                self.check(&tx, blk, offset, 4)?;
+                let len = tx.get_int(blk, offset)?.ok_or(RawError::NotPinned(*blk))?;
+                usize::try_from(len).map_err(|_| RawError::OutOfBounds { blk: *blk, offset })? + 4
+            }
+            ftype => Self::fixed_len(ftype),
+        };
+        self.check(&tx, blk, offset, len)?;
+        if ftype == FieldType::VARCHAR {
+            // The bytes may have been written as a blob.
+            let bytes = tx.get_range(blk, offset + 4, len - 4)?.ok_or(RawError::NotPinned(*blk))?;
+            let val = String::from_utf8(bytes).map_err(|_| DbError::Corruption(format!("the string at offset {} of {} is not UTF-8", offset, blk)))?;
+            return Ok(Constant::String(val));
+        }
+        let mut vals = tx.get_values(blk, &[(offset, ftype)])?.ok_or(RawError::NotPinned(*blk))?;
+        Ok(vals.remove(0))
+    }
+
+    // Writes the value at the offset of a pinned block.
+    pub fn set(&self, blk: &BlockId, offset: usize, val: &Constant) -> Result<(), RawError> {
+        let len = match val {
+            Constant::Null => return Err(RawError::NullValue),
+            Constant::String(val) => 4 + val.len(),
+            Constant::Blob(val) => 4 + val.len(),
+            Constant::Int(_) => Self::fixed_len(FieldType::INTEGER),
+            Constant::Bool(_) => Self::fixed_len(FieldType::BOOLEAN),
+            Constant::Long(_) | Constant::Double(_) | Constant::Date(_) => Self::fixed_len(FieldType::BIGINT),
+        };
+        let mut tx = self.tx.lock().unwrap();
+        self.check(&tx, blk, offset, len)?;
+        if !tx.is_pinned(blk) {
+            return Err(RawError::NotPinned(*blk));
+        }
+        Ok(tx.set_values(blk, &[(offset, val.clone())], true)?)
+    }
+
+    pub fn commit(self) -> Result<(), RawError> {
+        Ok(self.tx.lock().unwrap().commit()?)
+    }
+
+    pub fn rollback(self) -> Result<(), RawError> {
+        Ok(self.tx.lock().unwrap().rollback()?)
+    }
+
+    fn fixed_len(ftype: FieldType) -> usize {
+        match ftype {
+            FieldType::INTEGER => 4,
+            FieldType::BOOLEAN => 1,
+            _ => 8,
+        }
+    }
+
+    // Checks that the value lies between the page header and the end of
+    // the block.
+    fn check(&self, tx: &Transaction, blk: &BlockId, offset: usize, len: usize) -> Result<(), RawError> {
+        if offset < Page::HEADER_SIZE || offset.checked_add(len).is_none_or(|end| end > tx.block_size()) {
+            return Err(RawError::OutOfBounds { blk: *blk, offset });
+        }
+        Ok(())
+    }
+}
+
+mod tests {
+    use super::*;
+    use crate::server::simpledb::SimpleDB;
+
+    #[test]
+    fn test_raw_tx() {
+        let db = SimpleDB::ephemeral().unwrap();
+        let raw = db.raw().unwrap();
+        assert_eq!(raw.size("queue.raw").unwrap(), 0);
+        let blk = raw.append("queue.raw").unwrap();
+        assert_eq!(raw.get(&blk, 4, FieldType::INTEGER), Err(RawError::NotPinned(blk)));
+        raw.pin(&blk).unwrap();
+        raw.set(&blk, 4, &Constant::Int(2)).unwrap();
+        raw.set(&blk, 8, &Constant::String("first".to_string())).unwrap();
+        raw.set(&blk, 17, &Constant::Double(1.5)).unwrap();
+        assert_eq!(raw.get(&blk, 4, FieldType::INTEGER).unwrap(), Constant::Int(2));
+        assert_eq!(raw.get(&blk, 8, FieldType::VARCHAR).unwrap(), Constant::String("first".to_string()));
+        assert_eq!(raw.get(&blk, 17, FieldType::DOUBLE).unwrap(), Constant::Double(1.5));
+        assert_eq!(raw.set(&blk, 0, &Constant::Int(1)), Err(RawError::OutOfBounds { blk, offset: 0 }));
+        let end = raw.block_size() - 4;
+        assert_eq!(raw.set(&blk, end, &Constant::Long(1)), Err(RawError::OutOfBounds { blk, offset: end }));
+        assert_eq!(raw.set(&blk, 4, &Constant::Null), Err(RawError::NullValue));
+        raw.unpin(&blk);
+        raw.commit().unwrap();
+
+        // A rolled back write is undone, and so is one of a RawTx that is
+        // dropped.
+        let raw = db.raw().unwrap();
+        raw.pin(&blk).unwrap();
+        raw.set(&blk, 4, &Constant::Int(3)).unwrap();
+        raw.rollback().unwrap();
+        let raw = db.raw().unwrap();
+        raw.pin(&blk).unwrap();
+        raw.set(&blk, 4, &Constant::Int(4)).unwrap();
+        drop(raw);
+
+        let raw = db.raw().unwrap();
+        assert_eq!(raw.size("queue.raw").unwrap(), 1);
+        raw.pin(&blk).unwrap();
+        assert_eq!(raw.get(&blk, 4, FieldType::INTEGER).unwrap(), Constant::Int(2));
+        assert_eq!(raw.get(&blk, 8, FieldType::VARCHAR).unwrap(), Constant::String("first".to_string()));
+        raw.commit().unwrap();
+    }
+}
@@ -5,4 +5,8 @@ mod startrecord;
 mod commitrecord;
 pub(crate) mod rollbackrecord;
 mod setintrecord;
-mod setstringrecord;
\ No newline at end of file
+mod setstringrecord;
+mod setlongrecord;
+mod setboolrecord;
+mod setblobrecord;
+mod setrangerecord;
\ No newline at end of file
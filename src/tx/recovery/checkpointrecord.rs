@@ -3,6 +3,7 @@ use std::sync::{Arc, Mutex};
 use crate::file::page::Page;
 use crate::log::logmgr::LogMgr;
 use crate::tx::recovery::logrecord::{LogRecord, Op};
+use crate::error::dberror::DbError;
 use crate::tx::transaction::Transaction;
 
 pub struct CheckpointRecord {}
@@ -16,7 +17,7 @@ impl LogRecord for CheckpointRecord {
         None
     }
 
-    fn undo(&self, _tx: &mut Transaction) -> Result<(), &str> {
+    fn undo(&self, _tx: &mut Transaction) -> Result<(), DbError> {
         Ok(())
     }
 }
@@ -26,7 +27,7 @@ impl CheckpointRecord {
         CheckpointRecord {}
     }
 
-    pub fn write_to_log(lm: &Arc<Mutex<LogMgr>>) -> i32 {
+    pub fn write_to_log(lm: &Arc<Mutex<LogMgr>>) -> Result<i32, DbError> {
         let mut record = Vec::with_capacity(4);
         record.resize(4, 0);
         let mut page = Page::wrap(record);
@@ -1,12 +1,18 @@
 use std::fmt::Display;
 use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 use crate::file::page::Page;
 use crate::log::logmgr::LogMgr;
 use crate::tx::recovery::logrecord::{LogRecord, Op};
+use crate::error::dberror::DbError;
 use crate::tx::transaction::Transaction;
 
+// A commit record carries the wall-clock time of the commit, in
+// milliseconds since the epoch, so that the log can be read up to a point
+// in time. Records written before the timestamp was added have none.
 pub struct CommitRecord {
     txnum: i32,
+    timestamp: Option<i64>,
 }
 
 impl LogRecord for CommitRecord {
@@ -18,28 +24,38 @@ impl LogRecord for CommitRecord {
         Some(self.txnum)
     }
 
-    fn undo(&self, _tx: &mut Transaction) -> Result<(), &str> {
+    fn undo(&self, _tx: &mut Transaction) -> Result<(), DbError> {
         Ok(())
     }
+
+    fn timestamp(&self) -> Option<i64> {
+        self.timestamp
+    }
 }
 
 impl CommitRecord {
+    const TIMESTAMP_POS: usize = 8;
+
     pub fn new(page: Page) -> CommitRecord {
-        CommitRecord { txnum: page.get_int(4) }
+        let timestamp = (page.bytebuffer.len() >= Self::TIMESTAMP_POS + 8).then(|| page.get_long(Self::TIMESTAMP_POS));
+        CommitRecord { txnum: page.get_int(4), timestamp }
     }
 
-    pub fn write_to_log(lm: &Arc<Mutex<LogMgr>>, txnum: i32) -> i32 {
-        let mut record = Vec::with_capacity(8);
-        record.resize(8, 0);
-        let mut page = Page::wrap(record);
+    pub fn write_to_log(lm: &Arc<Mutex<LogMgr>>, txnum: i32) -> Result<i32, DbError> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as i64);
+        let mut page = Page::wrap(vec![0; Self::TIMESTAMP_POS + 8]);
         page.set_int(0, Op::Commit as i32);
         page.set_int(4, txnum);
+        page.set_long(Self::TIMESTAMP_POS, timestamp);
         lm.lock().unwrap().append(page.contents())
     }
 }
 
 impl Display for CommitRecord {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "<COMMIT {}>", self.txnum)
+        match self.timestamp {
+            Some(timestamp) => write!(f, "<COMMIT {} {}>", self.txnum, timestamp),
+            None => write!(f, "<COMMIT {}>", self.txnum),
+        }
     }
 }
\ No newline at end of file
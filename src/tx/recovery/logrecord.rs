@@ -4,9 +4,14 @@ use crate::file::page::Page;
 use crate::tx::recovery::checkpointrecord::CheckpointRecord;
 use crate::tx::recovery::commitrecord::CommitRecord;
 use crate::tx::recovery::rollbackrecord::RollbackRecord;
+use crate::tx::recovery::setblobrecord::SetBlobRecord;
+use crate::tx::recovery::setboolrecord::SetBoolRecord;
 use crate::tx::recovery::setintrecord::SetIntRecord;
+use crate::tx::recovery::setrangerecord::SetRangeRecord;
+use crate::tx::recovery::setlongrecord::SetLongRecord;
 use crate::tx::recovery::setstringrecord::SetStringRecord;
 use crate::tx::recovery::startrecord::StartRecord;
+use crate::error::dberror::DbError;
 use crate::tx::transaction::Transaction;
 
 #[derive(Eq, PartialEq)]
@@ -17,12 +22,22 @@ pub enum Op {
     Rollback = 3,
     SetInt = 4,
     SetString = 5,
+    SetLong = 6,
+    SetBool = 7,
+    SetBlob = 8,
+    SetRange = 9,
 }
 
 pub trait LogRecord: Display {
     fn op(&self) -> Op;
     fn txnum(&self) -> Option<i32>;
-    fn undo(&self, tx: &mut Transaction) -> Result<(), &str>;
+    fn undo(&self, tx: &mut Transaction) -> Result<(), DbError>;
+
+    // The wall-clock time the record was written at, in milliseconds since
+    // the epoch, for the records that carry one.
+    fn timestamp(&self) -> Option<i64> {
+        None
+    }
 }
 
 pub fn create_log_record(bytes: Vec<u8>) -> Option<Box<dyn LogRecord>> {
@@ -46,6 +61,18 @@ pub fn create_log_record(bytes: Vec<u8>) -> Option<Box<dyn LogRecord>> {
         op if op == Op::SetString as i32 => {
             Some(Box::new(SetStringRecord::new(page)))
         }
+        op if op == Op::SetLong as i32 => {
+            Some(Box::new(SetLongRecord::new(page)))
+        }
+        op if op == Op::SetBool as i32 => {
+            Some(Box::new(SetBoolRecord::new(page)))
+        }
+        op if op == Op::SetBlob as i32 => {
+            Some(Box::new(SetBlobRecord::new(page)))
+        }
+        op if op == Op::SetRange as i32 => {
+            Some(Box::new(SetRangeRecord::new(page)))
+        }
         _ => {
             None
         }
@@ -1,10 +1,16 @@
 use crate::buffer::buffer::Buffer;
 use crate::buffer::buffermgr::BufferMgr;
+use crate::error::dberror::DbError;
+use crate::file::page::Page;
 use crate::log::logmgr::LogMgr;
 use crate::tx::recovery::checkpointrecord::CheckpointRecord;
 use crate::tx::recovery::commitrecord::CommitRecord;
 use crate::tx::recovery::rollbackrecord::RollbackRecord;
+use crate::tx::recovery::setblobrecord::SetBlobRecord;
+use crate::tx::recovery::setboolrecord::SetBoolRecord;
 use crate::tx::recovery::setintrecord::SetIntRecord;
+use crate::tx::recovery::setlongrecord::SetLongRecord;
+use crate::tx::recovery::setrangerecord::SetRangeRecord;
 use crate::tx::recovery::setstringrecord::SetStringRecord;
 use crate::tx::recovery::startrecord::StartRecord;
 use std::sync::{Arc, Mutex};
@@ -16,38 +22,94 @@ pub struct RecoveryMgr {
 }
 
 impl RecoveryMgr {
-    pub(crate) fn new(txnum: i32, lm: Arc<Mutex<LogMgr>>, bm: Arc<Mutex<BufferMgr>>) -> RecoveryMgr {
-        StartRecord::write_to_log(&lm, txnum);
-        RecoveryMgr { txnum, lm, bm, }
+    pub(crate) fn new(txnum: i32, lm: Arc<Mutex<LogMgr>>, bm: Arc<Mutex<BufferMgr>>) -> Result<RecoveryMgr, DbError> {
+        StartRecord::write_to_log(&lm, txnum)?;
+        Ok(RecoveryMgr { txnum, lm, bm, })
     }
 
-    pub(crate) fn commit(&self) {
-        self.bm.lock().unwrap().flush_all(self.txnum);
-        let lsn = CommitRecord::write_to_log(&self.lm, self.txnum);
-        self.lm.lock().unwrap().flush_record(lsn);
+    pub(crate) fn commit(&self) -> Result<(), DbError> {
+        self.bm.lock().unwrap().flush_all(self.txnum)?;
+        let lsn = CommitRecord::write_to_log(&self.lm, self.txnum)?;
+        self.lm.lock().unwrap().flush_record(lsn)
     }
 
-    pub(crate) fn rollback(&self) {
-        self.bm.lock().unwrap().flush_all(self.txnum);
-        let lsn = RollbackRecord::write_to_log(&self.lm, self.txnum);
-        self.lm.lock().unwrap().flush_record(lsn);
+    pub(crate) fn rollback(&self) -> Result<(), DbError> {
+        self.bm.lock().unwrap().flush_all(self.txnum)?;
+        let lsn = RollbackRecord::write_to_log(&self.lm, self.txnum)?;
+        self.lm.lock().unwrap().flush_record(lsn)
     }
 
-    pub(crate) fn recover(&self) {
-        self.bm.lock().unwrap().flush_all(self.txnum);
-        let lsn = CheckpointRecord::write_to_log(&self.lm);
-        self.lm.lock().unwrap().flush_record(lsn);
+    pub(crate) fn recover(&self) -> Result<(), DbError> {
+        self.bm.lock().unwrap().flush_all(self.txnum)?;
+        let lsn = CheckpointRecord::write_to_log(&self.lm)?;
+        self.lm.lock().unwrap().flush_record(lsn)
     }
 
-    pub(crate) fn set_int(&self, buffer: &mut Buffer, offset: usize, _newval: i32) -> i32 {
+    pub(crate) fn set_int(&self, buffer: &mut Buffer, offset: usize, _newval: i32) -> Result<i32, DbError> {
         let oldval = buffer.contents().get_int(offset);
-        let block = buffer.block().clone().unwrap();
-        SetIntRecord::write_to_log(&self.lm, self.txnum, block, offset, oldval)
+        let (filename, blknum) = Self::block_of(buffer);
+        SetIntRecord::write_to_log(&self.lm, self.txnum, &filename, blknum, offset, oldval)
     }
 
-    pub(crate) fn set_string(&self, buffer: &mut Buffer, offset: usize, _newval: &str) -> i32 {
+    pub(crate) fn set_string(&self, buffer: &mut Buffer, offset: usize, _newval: &str) -> Result<i32, DbError> {
         let oldval = buffer.contents().get_string(offset);
-        let block = buffer.block().clone().unwrap();
-        SetStringRecord::write_to_log(&self.lm, self.txnum, block, offset, &oldval)
+        if Page::max_length(oldval.len()) > Self::piece_size(buffer) {
+            return self.set_pieces(buffer, offset, Page::max_length(oldval.len()));
+        }
+        let (filename, blknum) = Self::block_of(buffer);
+        SetStringRecord::write_to_log(&self.lm, self.txnum, &filename, blknum, offset, &oldval)
+    }
+
+    // Doubles are logged as the bits of their old value, with the long.
+    pub(crate) fn set_long(&self, buffer: &mut Buffer, offset: usize, _newval: i64) -> Result<i32, DbError> {
+        let oldval = buffer.contents().get_long(offset);
+        let (filename, blknum) = Self::block_of(buffer);
+        SetLongRecord::write_to_log(&self.lm, self.txnum, &filename, blknum, offset, oldval)
+    }
+
+    pub(crate) fn set_bool(&self, buffer: &mut Buffer, offset: usize, _newval: bool) -> Result<i32, DbError> {
+        let oldval = buffer.contents().get_bool(offset);
+        let (filename, blknum) = Self::block_of(buffer);
+        SetBoolRecord::write_to_log(&self.lm, self.txnum, &filename, blknum, offset, oldval)
+    }
+
+    pub(crate) fn set_blob(&self, buffer: &mut Buffer, offset: usize, _newval: &[u8]) -> Result<i32, DbError> {
+        let oldval = buffer.contents().get_blob(offset).to_vec();
+        if Page::max_length(oldval.len()) > Self::piece_size(buffer) {
+            return self.set_pieces(buffer, offset, Page::max_length(oldval.len()));
+        }
+        let (filename, blknum) = Self::block_of(buffer);
+        SetBlobRecord::write_to_log(&self.lm, self.txnum, &filename, blknum, offset, &oldval)
+    }
+
+    pub(crate) fn set_range(&self, buffer: &mut Buffer, offset: usize, newval: &[u8]) -> Result<i32, DbError> {
+        let oldval = buffer.contents().get_range(offset, newval.len()).to_vec();
+        let (filename, blknum) = Self::block_of(buffer);
+        SetRangeRecord::write_to_log(&self.lm, self.txnum, &filename, blknum, offset, &oldval)
+    }
+
+    // An old value too long for a single log record, as a string that fills
+    // most of a big block, is logged as ranges of its bytes, length prefix
+    // included. The pieces are half a block, as in `Transaction::set_range`.
+    fn set_pieces(&self, buffer: &mut Buffer, offset: usize, len: usize) -> Result<i32, DbError> {
+        let piece = Self::piece_size(buffer);
+        let (filename, blknum) = Self::block_of(buffer);
+        let mut lsn = -1;
+        for start in (0..len).step_by(piece) {
+            let oldval = buffer.contents().get_range(offset + start, piece.min(len - start)).to_vec();
+            lsn = SetRangeRecord::write_to_log(&self.lm, self.txnum, &filename, blknum, offset + start, &oldval)?;
+        }
+        Ok(lsn)
+    }
+
+    // Log records name the file of the block, so that they can be undone
+    // after a restart.
+    fn block_of(buffer: &Buffer) -> (Arc<str>, usize) {
+        let block = buffer.block().unwrap();
+        (buffer.filename().unwrap(), block.number())
+    }
+
+    fn piece_size(buffer: &mut Buffer) -> usize {
+        (buffer.contents().contents().len() - Page::HEADER_SIZE) / 2
     }
 }
\ No newline at end of file
@@ -3,6 +3,7 @@ use std::sync::{Arc, Mutex};
 use crate::file::page::Page;
 use crate::log::logmgr::LogMgr;
 use crate::tx::recovery::logrecord::{LogRecord, Op};
+use crate::error::dberror::DbError;
 use crate::tx::transaction::Transaction;
 
 pub struct RollbackRecord {
@@ -18,7 +19,7 @@ impl LogRecord for RollbackRecord {
         Some(self.txnum)
     }
 
-    fn undo(&self, _tx: &mut Transaction) -> Result<(), &str> {
+    fn undo(&self, _tx: &mut Transaction) -> Result<(), DbError> {
         Ok(())
     }
 }
@@ -28,7 +29,7 @@ impl RollbackRecord {
         RollbackRecord { txnum: page.get_int(4) }
     }
 
-    pub fn write_to_log(lm: &Arc<Mutex<LogMgr>>, txnum: i32) -> i32 {
+    pub fn write_to_log(lm: &Arc<Mutex<LogMgr>>, txnum: i32) -> Result<i32, DbError> {
         let mut record = Vec::with_capacity(8);
         record.resize(8, 0);
         let mut page = Page::wrap(record);
@@ -0,0 +1,73 @@
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+use crate::file::page::Page;
+use crate::log::logmgr::LogMgr;
+use crate::tx::recovery::logrecord::{LogRecord, Op};
+use crate::error::dberror::DbError;
+use crate::tx::transaction::Transaction;
+
+pub struct SetBlobRecord {
+    txnum: i32,
+    offset: usize,
+    val: Vec<u8>,
+    filename: String,
+    blknum: usize,
+}
+
+impl LogRecord for SetBlobRecord {
+    fn op(&self) -> Op {
+        Op::SetBlob
+    }
+
+    fn txnum(&self) -> Option<i32> {
+        Some(self.txnum)
+    }
+
+    fn undo(&self, tx: &mut Transaction) -> Result<(), DbError> {
+        let blk = tx.block(&self.filename, self.blknum);
+        tx.pin(&blk)?;
+        tx.set_blob(&blk, self.offset, &self.val, false)?;
+        tx.unpin(&blk);
+        Ok(())
+    }
+}
+
+impl SetBlobRecord {
+    pub fn new(page: Page) -> SetBlobRecord {
+        let filename = page.get_string(8);
+        let blkpos = 8 + Page::max_length(filename.len());
+        let offsetpos = blkpos + 4;
+        let valpos = offsetpos + 4;
+        SetBlobRecord {
+            txnum: page.get_int(4),
+            blknum: page.get_int(blkpos) as usize,
+            filename,
+            offset: page.get_int(offsetpos) as usize,
+            val: page.get_blob(valpos).to_vec(),
+        }
+    }
+
+    pub fn write_to_log(lm: &Arc<Mutex<LogMgr>>, txnum: i32, filename: &str, blknum: usize, offset: usize, val: &[u8]) -> Result<i32, DbError> {
+        let tpos = 4;
+        let filepos = tpos + 4;
+        let blkpos = filepos + Page::max_length(filename.len());
+        let offsetpos = blkpos + 4;
+        let valpos = offsetpos + 4;
+        let mut record = Vec::with_capacity(valpos + 4 + val.len());
+        record.resize(valpos + 4 + val.len(), 0);
+        let mut page = Page::wrap(record);
+        page.set_int(0, Op::SetBlob as i32);
+        page.set_int(tpos, txnum);
+        page.set_string(filepos, filename);
+        page.set_int(blkpos, blknum as i32);
+        page.set_int(offsetpos, offset as i32);
+        page.set_blob(valpos, val);
+        lm.lock().unwrap().append(page.contents())
+    }
+}
+
+impl Display for SetBlobRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<SETBLOB {} [file {}, block {}] {} {} bytes>", self.txnum, self.filename, self.blknum, self.offset, self.val.len())
+    }
+}
\ No newline at end of file
@@ -1,16 +1,17 @@
 use std::fmt::Display;
 use std::sync::{Arc, Mutex};
-use crate::file::blockid::BlockId;
 use crate::file::page::Page;
 use crate::log::logmgr::LogMgr;
 use crate::tx::recovery::logrecord::{LogRecord, Op};
+use crate::error::dberror::DbError;
 use crate::tx::transaction::Transaction;
 
 pub struct SetIntRecord {
     txnum: i32,
     offset: usize,
     val: i32,
-    blk: BlockId,
+    filename: String,
+    blknum: usize,
 }
 
 impl LogRecord for SetIntRecord {
@@ -22,10 +23,11 @@ impl LogRecord for SetIntRecord {
         Some(self.txnum)
     }
 
-    fn undo(&self, tx: &mut Transaction) -> Result<(), &str> {
-        tx.pin(&self.blk);
-        tx.set_int(&self.blk, self.offset, self.val, false);
-        tx.unpin(&self.blk);
+    fn undo(&self, tx: &mut Transaction) -> Result<(), DbError> {
+        let blk = tx.block(&self.filename, self.blknum);
+        tx.pin(&blk)?;
+        tx.set_int(&blk, self.offset, self.val, false)?;
+        tx.unpin(&blk);
         Ok(())
     }
 }
@@ -38,16 +40,17 @@ impl SetIntRecord {
         let valpos = offsetpos + 4;
         SetIntRecord {
             txnum: page.get_int(4),
-            blk: BlockId::new(&filename, page.get_int(blkpos) as usize),
+            blknum: page.get_int(blkpos) as usize,
+            filename,
             offset: page.get_int(offsetpos) as usize,
             val: page.get_int(valpos),
         }
     }
 
-    pub fn write_to_log(lm: &Arc<Mutex<LogMgr>>, txnum: i32, blk: BlockId, offset: usize, val: i32) -> i32 {
+    pub fn write_to_log(lm: &Arc<Mutex<LogMgr>>, txnum: i32, filename: &str, blknum: usize, offset: usize, val: i32) -> Result<i32, DbError> {
         let tpos = 4;
         let filepos = tpos + 4;
-        let blkpos = filepos + Page::max_length(blk.filename().len());
+        let blkpos = filepos + Page::max_length(filename.len());
         let offsetpos = blkpos + 4;
         let valpos = offsetpos + 4;
         let mut record = Vec::with_capacity(valpos + 4);
@@ -55,8 +58,8 @@ impl SetIntRecord {
         let mut page = Page::wrap(record);
         page.set_int(0, Op::SetInt as i32);
         page.set_int(tpos, txnum);
-        page.set_string(filepos, &blk.filename());
-        page.set_int(blkpos, blk.number() as i32);
+        page.set_string(filepos, filename);
+        page.set_int(blkpos, blknum as i32);
         page.set_int(offsetpos, offset as i32);
         page.set_int(valpos, val);
         lm.lock().unwrap().append(page.contents())
@@ -65,6 +68,6 @@ impl SetIntRecord {
 
 impl Display for SetIntRecord {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "<SETINT {} {} {} {}>", self.txnum, self.blk, self.offset, self.val)
+        write!(f, "<SETINT {} [file {}, block {}] {} {}>", self.txnum, self.filename, self.blknum, self.offset, self.val)
     }
 }
\ No newline at end of file
@@ -0,0 +1,73 @@
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+use crate::file::page::Page;
+use crate::log::logmgr::LogMgr;
+use crate::tx::recovery::logrecord::{LogRecord, Op};
+use crate::error::dberror::DbError;
+use crate::tx::transaction::Transaction;
+
+pub struct SetLongRecord {
+    txnum: i32,
+    offset: usize,
+    val: i64,
+    filename: String,
+    blknum: usize,
+}
+
+impl LogRecord for SetLongRecord {
+    fn op(&self) -> Op {
+        Op::SetLong
+    }
+
+    fn txnum(&self) -> Option<i32> {
+        Some(self.txnum)
+    }
+
+    fn undo(&self, tx: &mut Transaction) -> Result<(), DbError> {
+        let blk = tx.block(&self.filename, self.blknum);
+        tx.pin(&blk)?;
+        tx.set_long(&blk, self.offset, self.val, false)?;
+        tx.unpin(&blk);
+        Ok(())
+    }
+}
+
+impl SetLongRecord {
+    pub fn new(page: Page) -> SetLongRecord {
+        let filename = page.get_string(8);
+        let blkpos = 8 + Page::max_length(filename.len());
+        let offsetpos = blkpos + 4;
+        let valpos = offsetpos + 4;
+        SetLongRecord {
+            txnum: page.get_int(4),
+            blknum: page.get_int(blkpos) as usize,
+            filename,
+            offset: page.get_int(offsetpos) as usize,
+            val: page.get_long(valpos),
+        }
+    }
+
+    pub fn write_to_log(lm: &Arc<Mutex<LogMgr>>, txnum: i32, filename: &str, blknum: usize, offset: usize, val: i64) -> Result<i32, DbError> {
+        let tpos = 4;
+        let filepos = tpos + 4;
+        let blkpos = filepos + Page::max_length(filename.len());
+        let offsetpos = blkpos + 4;
+        let valpos = offsetpos + 4;
+        let mut record = Vec::with_capacity(valpos + 8);
+        record.resize(valpos + 8, 0);
+        let mut page = Page::wrap(record);
+        page.set_int(0, Op::SetLong as i32);
+        page.set_int(tpos, txnum);
+        page.set_string(filepos, filename);
+        page.set_int(blkpos, blknum as i32);
+        page.set_int(offsetpos, offset as i32);
+        page.set_long(valpos, val);
+        lm.lock().unwrap().append(page.contents())
+    }
+}
+
+impl Display for SetLongRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<SETLONG {} [file {}, block {}] {} {}>", self.txnum, self.filename, self.blknum, self.offset, self.val)
+    }
+}
\ No newline at end of file
@@ -3,6 +3,7 @@ use std::sync::{Arc, Mutex};
 use crate::file::page::Page;
 use crate::log::logmgr::LogMgr;
 use crate::tx::recovery::logrecord::{LogRecord, Op};
+use crate::error::dberror::DbError;
 use crate::tx::transaction::Transaction;
 
 pub struct StartRecord {
@@ -18,7 +19,7 @@ impl LogRecord for StartRecord {
         Some(self.txnum)
     }
 
-    fn undo(&self, _tx: &mut Transaction) -> Result<(), &str> {
+    fn undo(&self, _tx: &mut Transaction) -> Result<(), DbError> {
         Ok(())
     }
 }
@@ -28,13 +29,13 @@ impl StartRecord {
         StartRecord { txnum: page.get_int(4) }
     }
 
-    pub fn write_to_log(lm: &Arc<Mutex<LogMgr>>, txnum: i32) {
+    pub fn write_to_log(lm: &Arc<Mutex<LogMgr>>, txnum: i32) -> Result<i32, DbError> {
         let mut record = Vec::with_capacity(8);
         record.resize(8, 0);
         let mut page = Page::wrap(record);
         page.set_int(0, Op::Start as i32);
         page.set_int(4, txnum);
-        lm.lock().unwrap().append(page.contents());
+        lm.lock().unwrap().append(page.contents())
     }
 }
 
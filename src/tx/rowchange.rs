@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use crate::record::tablescan::RecordId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeOp {
+    Insert,
+    Modify,
+    Delete,
+}
+
+// A change a committed transaction made to a record of a table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowChange {
+    pub table: String,
+    pub op: ChangeOp,
+    pub rid: RecordId,
+}
+
+pub type ChangeCallback = Arc<dyn Fn(&[RowChange]) + Send + Sync>;
+
+// The observers registered for the changes to each table. A transaction
+// only records the changes to the tables that are watched, and hands them
+// over once it has committed.
+#[derive(Default)]
+pub struct ChangeObservers {
+    callbacks: Mutex<HashMap<String, Vec<ChangeCallback>>>,
+}
+
+impl ChangeObservers {
+    pub fn new() -> ChangeObservers {
+        ChangeObservers::default()
+    }
+
+    pub fn register(&self, table: &str, callback: ChangeCallback) {
+        self.callbacks.lock().unwrap().entry(table.to_string()).or_default().push(callback);
+    }
+
+    pub fn watches(&self, table: &str) -> bool {
+        self.callbacks.lock().unwrap().contains_key(table)
+    }
+
+    // Calls the observers of each table once, with the changes to that
+    // table in the order they were made. The callbacks run without the
+    // registry locked, so that they may register more observers.
+    pub fn notify(&self, changes: &[RowChange]) {
+        let mut by_table: Vec<(&str, Vec<RowChange>)> = Vec::new();
+        for change in changes {
+            match by_table.iter_mut().find(|(table, _)| *table == change.table) {
+                Some((_, changes)) => changes.push(change.clone()),
+                None => by_table.push((&change.table, vec![change.clone()])),
+            }
+        }
+        for (table, changes) in by_table {
+            let callbacks = self.callbacks.lock().unwrap().get(table).cloned().unwrap_or_default();
+            for callback in callbacks {
+                callback(&changes);
+            }
+        }
+    }
+}
+
+// Records the change unless the last change already covers it: a record
+// that was just inserted or changed is not reported as modified again for
+// each of its fields, and one inserted and deleted right away, as when it
+// breaks a constraint, is not reported at all.
+pub(crate) fn record(changes: &mut Vec<RowChange>, change: RowChange) {
+    let same_record = changes.last().is_some_and(|last| last.table == change.table && last.rid == change.rid);
+    match change.op {
+        ChangeOp::Modify if same_record => {}
+        ChangeOp::Delete if same_record => {
+            if changes.pop().is_some_and(|last| last.op != ChangeOp::Insert) {
+                changes.push(change);
+            }
+        }
+        _ => changes.push(change),
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let change = |op, slot| RowChange { table: "t".to_string(), op, rid: RecordId { blocknum: 0, slot } };
+        let mut changes = Vec::new();
+        record(&mut changes, change(ChangeOp::Insert, 0));
+        record(&mut changes, change(ChangeOp::Modify, 0));
+        record(&mut changes, change(ChangeOp::Modify, 1));
+        record(&mut changes, change(ChangeOp::Modify, 1));
+        record(&mut changes, change(ChangeOp::Delete, 1));
+        record(&mut changes, change(ChangeOp::Insert, 2));
+        record(&mut changes, change(ChangeOp::Delete, 2));
+        assert_eq!(changes, vec![change(ChangeOp::Insert, 0), change(ChangeOp::Delete, 1)]);
+    }
+}
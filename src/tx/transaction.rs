@@ -1,15 +1,30 @@
+use crate::buffer::buffer::Buffer;
 use crate::buffer::buffermgr::BufferMgr;
-use crate::file::blockid::BlockId;
+use crate::error::dberror::DbError;
+use crate::file::blockid::{BlockId, FileId};
 use crate::file::filemgr::FileMgr;
+use crate::file::freespacemap::FreeSpaceMap;
+use crate::file::page::Page;
 use crate::log::logmgr::LogMgr;
+use crate::query::constant::Constant;
+use crate::record::schema::FieldType;
+use crate::record::tablescan::RecordId;
+use crate::server::backup;
 pub use crate::tx::bufferlist::BufferList;
-use std::sync::atomic::{AtomicI32, Ordering};
-use std::sync::{Arc, Mutex};
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use crate::tx::admission::{AdmissionCtl, AdmissionSlot};
 use crate::tx::recovery::checkpointrecord::CheckpointRecord;
 use crate::tx::recovery::logrecord::{create_log_record, Op};
 use crate::tx::recovery::recoverymgr::RecoveryMgr;
 use crate::tx::recovery::rollbackrecord::RollbackRecord;
 use crate::tx::concurrency::concurrencymgr::ConcurrencyMgr;
+use crate::tx::rowchange::{self, ChangeObservers, ChangeOp, RowChange};
+use crate::tx::txstatus::{TxRegistry, TxState, TxStatus};
+use crate::tx::writecheck::WriteCheck;
+use crate::tx::journal::{self, JournalOp};
 
 pub struct Transaction {
     txnum: i32,
@@ -19,166 +34,691 @@ pub struct Transaction {
     lm: Arc<Mutex<LogMgr>>,
     cm: ConcurrencyMgr,
     bm: Arc<Mutex<BufferMgr>>,
+    admission: Option<AdmissionSlot>,
+    admission_ctl: Arc<AdmissionCtl>,
+    writes: Option<WriteCheck>,
+    deletes: Vec<String>,
+    // The files of the temporary tables the transaction created.
+    temps: Vec<String>,
+    // The catalog version to advance on commit, if the transaction changed
+    // the catalog.
+    catalog_version: Option<Arc<AtomicU64>>,
+    // The observers to hand the changes to the watched tables to on commit.
+    observers: Option<Arc<ChangeObservers>>,
+    changes: Vec<RowChange>,
+    status: Arc<TxStatus>,
+    registry: Arc<TxRegistry>,
 }
 
 static NEXT_TXNUM: AtomicI32 = AtomicI32::new(0);
 
+// Whether new transactions check their writes on commit; see `set_check_writes`.
+static CHECK_WRITES: AtomicBool = AtomicBool::new(false);
+
+
 impl Transaction {
     const END_OF_FILE: i32 = -1;
 
-    pub(crate) fn new(fm: Arc<FileMgr>, bm: Arc<Mutex<BufferMgr>>, lm: Arc<Mutex<LogMgr>>) -> Transaction {
+    // Creates a new transaction, admitted by the admission controller of
+    // its database and listed in its registry. If the maximum number of
+    // active transactions has been reached, the caller waits in line until
+    // one of them completes.
+    pub(crate) fn new(fm: Arc<FileMgr>, bm: Arc<Mutex<BufferMgr>>, lm: Arc<Mutex<LogMgr>>, admission_ctl: Arc<AdmissionCtl>, registry: Arc<TxRegistry>) -> Result<Transaction, DbError> {
+        let admission = admission_ctl.admit()?;
         let txnum = next_txnum();
-        Transaction {
+        Ok(Transaction {
             txnum,
-            buffers: BufferList::new(bm.clone()),
+            buffers: BufferList::new(bm.clone(), txnum),
             fm,
-            rm: Arc::new(RecoveryMgr::new(txnum, lm.clone(), bm.clone())),
+            rm: Arc::new(RecoveryMgr::new(txnum, lm.clone(), bm.clone())?),
             lm,
-            cm: ConcurrencyMgr::new(),
+            cm: ConcurrencyMgr::new(txnum),
             bm,
+            admission: Some(admission),
+            admission_ctl,
+            writes: CHECK_WRITES.load(Ordering::SeqCst).then(WriteCheck::new),
+            deletes: Vec::new(),
+            temps: Vec::new(),
+            catalog_version: None,
+            observers: None,
+            changes: Vec::new(),
+            status: registry.register(txnum),
+            registry,
+        })
+    }
+
+    // Turns on the debug mode in which every transaction started from now on
+    // remembers the values it writes and reads them back when it commits,
+    // from the buffer pool and from disk. Commit fails with a corruption
+    // error if any of them reads back differently.
+    pub fn set_check_writes(on: bool) {
+        CHECK_WRITES.store(on, Ordering::SeqCst);
+    }
+
+    // Runs this transaction optimistically: its reads take no locks and are
+    // validated when it commits, see `ConcurrencyMgr`. It must not have read
+    // or written anything yet; see `SimpleDB::set_optimistic`.
+    pub(crate) fn run_optimistically(&mut self) {
+        self.cm.set_optimistic();
+    }
+
+    // Reports the changes of this transaction to the observers once it has
+    // committed.
+    pub(crate) fn notify(&mut self, observers: Arc<ChangeObservers>) {
+        self.observers = Some(observers);
+    }
+
+    // Records a change to a record of the table, if the table is watched.
+    pub(crate) fn record_change(&mut self, table: &str, op: ChangeOp, rid: RecordId) {
+        if self.observers.as_ref().is_some_and(|observers| observers.watches(table)) {
+            rowchange::record(&mut self.changes, RowChange { table: table.to_string(), op, rid });
+        }
+    }
+
+    pub fn txnum(&self) -> i32 {
+        self.txnum
+    }
+
+    // The registry of the active transactions of the database.
+    pub(crate) fn registry(&self) -> Arc<TxRegistry> {
+        self.registry.clone()
+    }
+
+    // Lets a kill roll the transaction back while no one is using it.
+    pub(crate) fn set_handle(&self, tx: Weak<Mutex<Transaction>>) {
+        self.status.set_handle(tx);
+    }
+
+    // Rolls the transaction back if it was killed, and fails every use of
+    // it from then on. The undo of its own rollback goes through.
+    pub(crate) fn abort_if_killed(&mut self) -> Result<(), DbError> {
+        if !self.status.is_killed() || (self.status.state() == TxState::RollingBack && self.admission.is_some()) {
+            return Ok(());
+        }
+        if self.admission.is_some() {
+            let _ = self.rollback();
         }
+        Err(DbError::Killed(self.txnum))
+    }
+
+    // Checks the writes of this transaction on commit, whatever the mode.
+    pub(crate) fn check_writes(&mut self) {
+        self.writes.get_or_insert_with(WriteCheck::new);
+    }
+
+    // The values written by the transaction, if it checks its writes. They
+    // can be checked again on disk after a restart.
+    pub(crate) fn written(&self) -> Option<&WriteCheck> {
+        self.writes.as_ref()
     }
 
-    pub(crate) fn commit(&mut self) {
-        self.rm.commit();
+    // The writes are checked while the transaction still holds its locks,
+    // so no other transaction can have overwritten them yet. The commit is
+    // durable by then, so the locks are released even if the check fails.
+    // Files the transaction deleted go once their buffers are unpinned, but
+    // before the locks that keep other transactions out of them are freed.
+    // A commit whose log cannot be written, as when the disk is full, fails
+    // with the transaction still active: it can be committed again once
+    // there is space, or rolled back. An optimistic transaction whose reads
+    // do not validate is rolled back instead. The observers of the changed
+    // tables are called last, when the changes are visible to others.
+    pub(crate) fn commit(&mut self) -> Result<(), DbError> {
+        self.abort_if_killed()?;
+        if let Err(error) = self.cm.validate() {
+            let _ = self.rollback();
+            return Err(error);
+        }
+        self.status.set_state(TxState::Committing);
+        if let Err(error) = self.rm.commit() {
+            self.status.set_state(TxState::Active);
+            return Err(error);
+        }
+        let checked = self.verify_writes();
         println!("Transaction {} committed", self.txnum);
         println!("Stats: {:?}", self.fm.stats());
-        self.cm.release();
         self.buffers.unpin_all();
+        let deleted = self.delete_files();
+        self.delete_temp_files();
+        // Advanced before the locks are released, so that a reader of the
+        // new catalog sees the new version too.
+        if let Some(version) = self.catalog_version.take() {
+            version.fetch_add(1, Ordering::SeqCst);
+        }
+        self.cm.release();
+        self.admission = None;
+        self.registry.unregister(self.txnum);
+        let changes = std::mem::take(&mut self.changes);
+        if let Some(observers) = &self.observers && !changes.is_empty() {
+            observers.notify(&changes);
+        }
+        checked.and(deleted)
+    }
+
+    // Deletes the file when the transaction commits; a rollback keeps it.
+    // Every block of the file is xlocked first, along with its end, so that
+    // no other transaction has uncommitted changes in the file or can extend
+    // it. A crash after the commit but before the delete leaves the file
+    // behind; see `delete_stale_file`.
+    pub(crate) fn delete_file(&mut self, filename: &str) -> Result<(), DbError> {
+        self.xlock(&self.fm.block(filename, Transaction::END_OF_FILE as usize))?;
+        for number in 0..self.fm.length(filename)? as usize {
+            self.xlock(&self.fm.block(filename, number))?;
+        }
+        self.deletes.push(filename.to_string());
+        Ok(())
+    }
+
+    // Deletes a file that no table owns any more, left behind by a drop that
+    // committed just before a crash, so that a new owner starts out empty.
+    pub(crate) fn delete_stale_file(&mut self, filename: &str) -> Result<(), DbError> {
+        self.xlock(&self.fm.block(filename, Transaction::END_OF_FILE as usize))?;
+        self.bm.lock().unwrap().discard_file(filename);
+        self.fm.delete_file(filename)
+    }
+
+    // Records that the transaction changed the catalog whose version is
+    // given, so that the version advances if the transaction commits.
+    pub(crate) fn change_catalog(&mut self, version: Arc<AtomicU64>) {
+        self.catalog_version = Some(version);
+    }
+
+    pub(crate) fn changed_catalog(&self) -> bool {
+        self.catalog_version.is_some()
+    }
+
+    // Whether the file is to be deleted when the transaction commits.
+    pub(crate) fn deletes_file(&self, filename: &str) -> bool {
+        self.deletes.iter().any(|f| f == filename)
+    }
+
+    fn delete_files(&mut self) -> Result<(), DbError> {
+        let mut result = Ok(());
+        for filename in std::mem::take(&mut self.deletes) {
+            self.bm.lock().unwrap().discard_file(&filename);
+            result = result.and(self.fm.delete_file(&filename));
+        }
+        result
+    }
+
+    // Records that the file holds a temporary table of the transaction.
+    pub(crate) fn add_temp_file(&mut self, filename: &str) {
+        self.temps.push(filename.to_string());
+    }
+
+    // Temporary tables only live as long as the transaction that created
+    // them. Deleting their files, whether it commits or rolls back, also
+    // lets the file manager forget their names. A file left behind goes
+    // when the database is next opened.
+    fn delete_temp_files(&mut self) {
+        for filename in std::mem::take(&mut self.temps) {
+            self.bm.lock().unwrap().discard_file(&filename);
+            let _ = self.fm.delete_file(&filename);
+        }
+    }
+
+    fn verify_writes(&mut self) -> Result<(), DbError> {
+        let Some(writes) = &mut self.writes else {
+            return Ok(());
+        };
+        writes.name_files(&self.fm);
+        for blk in writes.blocks() {
+            if let Some(idx) = self.buffers.buffer(blk) {
+                writes.check_page(blk, self.bm.lock().unwrap().buffer(idx).contents(), "buffered")?;
+            }
+        }
+        writes.check_disk(&self.fm)
     }
 
-    fn rollback(&mut self) {
-        self.do_rollback();
-        self.bm.lock().unwrap().flush_all(self.txnum);
-        let lsn = RollbackRecord::write_to_log(&self.lm, self.txnum);
-        self.lm.lock().unwrap().flush_record(lsn);
-        println!("Transaction {} rolled back", self.txnum);
+    // Undoes the transaction's changes. The locks, pins and admission slot
+    // are given up even when undoing fails, so that a transaction that hit
+    // an I/O error does not block the others.
+    pub(crate) fn rollback(&mut self) -> Result<(), DbError> {
+        self.status.set_state(TxState::RollingBack);
+        let result = self.do_rollback();
+        if result.is_ok() {
+            println!("Transaction {} rolled back", self.txnum);
+        }
+        self.deletes.clear();
+        self.catalog_version = None;
+        self.changes.clear();
         self.cm.release();
         self.buffers.unpin_all();
+        self.delete_temp_files();
+        self.admission = None;
+        self.registry.unregister(self.txnum);
+        result
+    }
+
+    fn do_rollback(&mut self) -> Result<(), DbError> {
+        self.undo_changes()?;
+        self.bm.lock().unwrap().flush_all(self.txnum)?;
+        let lsn = RollbackRecord::write_to_log(&self.lm, self.txnum)?;
+        self.lm.lock().unwrap().flush_record(lsn)
     }
 
-    fn do_rollback(&mut self) {
-        let mut iter = self.lm.lock().unwrap().iterator();
+    fn undo_changes(&mut self) -> Result<(), DbError> {
+        let mut iter = self.lm.lock().unwrap().iterator()?;
         while let Some(record) = iter.next() {
-            match create_log_record(record) {
+            match create_log_record(record?) {
                 Some(lr) => {
-                    if lr.txnum().unwrap() == self.txnum {
+                    if lr.txnum() == Some(self.txnum) {
                         if lr.op() == Op::Start {
                             break;
                         }
-                        lr.undo(self).unwrap()
+                        // The transaction already holds an xlock on every
+                        // block it modified, so undoing cannot time out.
+                        lr.undo(self)?
                     }
                 }
                 None => {}
             }
         }
+        Ok(())
+    }
+
+    // Writes a quiescent checkpoint: new transactions are held off until
+    // the active ones complete, so that every change before the checkpoint
+    // is on disk and recovery can stop reading the log there. Only this
+    // transaction may stay active, as long as it has changed nothing yet.
+    // Only the transactions of the same database are waited for.
+    pub(crate) fn checkpoint(&mut self) -> Result<(), DbError> {
+        self.checkpoint_then(|_, _| Ok(()))
+    }
+
+    // Copies the files of the database into a new directory right after a
+    // checkpoint, before new transactions are admitted again. The copy holds
+    // the changes of every committed transaction and no others, and its log
+    // ends with the checkpoint, so it opens without undoing anything.
+    // Returns the LSN of the checkpoint.
+    pub(crate) fn copy_database(&mut self, dest: &Path) -> Result<i32, DbError> {
+        self.checkpoint_then(|fm, lsn| fm.copy_to(dest).map(|_| lsn))
+    }
+
+    // Writes the blocks changed since the LSN right after a checkpoint, as
+    // `copy_database` copies every block, and returns the LSN of the
+    // checkpoint; see `backup::write_incremental`.
+    pub(crate) fn write_incremental(&mut self, since: i32, out: &mut dyn Write) -> Result<i32, DbError> {
+        self.checkpoint_then(|fm, lsn| backup::write_incremental(fm, since, lsn, out).map(|_| lsn))
+    }
+
+    fn checkpoint_then<T>(&mut self, op: impl FnOnce(&FileMgr, i32) -> Result<T, DbError>) -> Result<T, DbError> {
+        if self.cm.holds_xlocks() {
+            return Err(DbError::PendingChanges);
+        }
+        let _quiescence = self.admission_ctl.quiesce(usize::from(self.admission.is_some()))?;
+        self.bm.lock().unwrap().flush_all(self.txnum)?;
+        let lsn = CheckpointRecord::write_to_log(&self.lm)?;
+        self.lm.lock().unwrap().flush_record(lsn)?;
+        println!("Checkpoint written by transaction {}", self.txnum);
+        op(&self.fm, lsn)
     }
 
-    fn recover(&mut self) {
-        self.bm.lock().unwrap().flush_all(self.txnum);
-        self.do_recover();
-        self.bm.lock().unwrap().flush_all(self.txnum);
-        let lsn = CheckpointRecord::write_to_log(&self.lm);
-        self.lm.lock().unwrap().flush_record(lsn);
+    // Undoes the changes of every transaction that did not complete before
+    // the last checkpoint, then writes a new checkpoint. It must run before
+    // any other transaction starts.
+    pub(crate) fn recover(&mut self) -> Result<(), DbError> {
+        self.bm.lock().unwrap().flush_all(self.txnum)?;
+        self.do_recover()?;
+        self.bm.lock().unwrap().flush_all(self.txnum)?;
+        let lsn = CheckpointRecord::write_to_log(&self.lm)?;
+        self.lm.lock().unwrap().flush_record(lsn)?;
+        // The undo operations lock every block they restore; now that
+        // they are on disk, the locks can go, and the transaction is done.
+        self.cm.release();
+        self.admission = None;
+        self.registry.unregister(self.txnum);
+        Ok(())
     }
 
-    fn do_recover(&mut self) {
+    fn do_recover(&mut self) -> Result<(), DbError> {
         let mut finished_txs = Vec::new();
-        let mut iter = self.lm.lock().unwrap().iterator();
+        let mut iter = self.lm.lock().unwrap().iterator()?;
         while let Some(record) = iter.next() {
-            if let Some(lr) = create_log_record(record) {
+            if let Some(lr) = create_log_record(record?) {
                 match lr.op() {
                     Op::Checkpoint => break,
                     Op::Commit | Op::Rollback => finished_txs.push(lr.txnum().unwrap()),
                     _ => {
                         if !finished_txs.contains(&lr.txnum().unwrap()) {
-                            lr.undo(self).unwrap();
+                            lr.undo(self)?;
                         }
                     }
                 }
             }
         }
+        Ok(())
     }
 
-    pub(crate) fn pin(&mut self, blk: &BlockId) {
-        self.buffers.pin(blk);
+    pub(crate) fn pin(&mut self, blk: &BlockId) -> Result<(), DbError> {
+        self.abort_if_killed()?;
+        self.buffers.pin(blk)?;
+        self.status.set_pins(self.buffers.pin_count());
+        Ok(())
     }
 
     pub(crate) fn unpin(&mut self, blk: &BlockId) {
         self.buffers.unpin(blk);
+        self.status.set_pins(self.buffers.pin_count());
+    }
+
+    pub(crate) fn is_pinned(&mut self, blk: &BlockId) -> bool {
+        self.buffers.buffer(blk).is_some()
     }
 
-    pub fn get_int(&mut self, blk: &BlockId, offset: usize) -> Option<i32> {
-        self.cm.slock(blk);
+    // Reserves buffers for a multibuffer operator, such as a sort merging
+    // its runs. The transaction pins from the reservation until it is used
+    // up or released, and whatever is left of it is released at commit or
+    // rollback.
+    pub(crate) fn reserve_buffers(&mut self, n: usize) -> Result<(), DbError> {
+        self.abort_if_killed()?;
+        self.buffers.reserve(n)
+    }
+
+    pub(crate) fn release_buffers(&mut self, n: usize) {
+        self.buffers.release(n);
+    }
+
+    // The number of buffers a multibuffer operator may use, see
+    // `BufferMgr::work_mem`.
+    pub(crate) fn work_mem(&self) -> usize {
+        self.bm.lock().unwrap().work_mem()
+    }
+
+    pub fn get_int(&mut self, blk: &BlockId, offset: usize) -> Result<Option<i32>, DbError> {
+        self.slock(blk)?;
         match self.buffers.buffer(blk) {
-            Some(idx) => Some(self.bm.lock().unwrap().buffer(idx).contents().get_int(offset)),
-            None => None
+            Some(idx) => Ok(Some(self.bm.lock().unwrap().buffer(idx).contents().get_int(offset))),
+            None => Ok(None)
         }
     }
 
-    pub(crate) fn set_int(&mut self, blk: &BlockId, offset: usize, val: i32, log: bool) {
-        self.cm.xlock(blk);
+    pub(crate) fn set_int(&mut self, blk: &BlockId, offset: usize, val: i32, log: bool) -> Result<(), DbError> {
+        debug_assert!(offset >= Page::HEADER_SIZE, "write at offset {} overlaps the page header", offset);
+        self.xlock(blk)?;
         match self.buffers.buffer(blk) {
             Some(idx) => {
                 let mut bm = self.bm.lock().unwrap();
                 let buffer = bm.buffer(idx);
                 let mut lsn = -1;
                 if log {
-                    lsn = self.rm.set_int(buffer, offset, val);
+                    lsn = self.rm.set_int(buffer, offset, val)?;
                 }
                 buffer.contents().set_int(offset, val);
+                journal::record(self.txnum, JournalOp::Write(offset), blk);
+                Self::stamp(buffer, lsn);
                 buffer.set_modified(self.txnum, lsn);
+                if let Some(writes) = self.writes.as_mut() {
+                    writes.record(blk, offset, Constant::Int(val));
+                }
             }
             _ => {}
         }
+        Ok(())
     }
 
-    pub(crate) fn get_string(&mut self, blk: &BlockId, offset: usize) -> Option<String> {
-        self.cm.slock(blk);
+    pub(crate) fn get_string(&mut self, blk: &BlockId, offset: usize) -> Result<Option<String>, DbError> {
+        self.slock(blk)?;
         match self.buffers.buffer(blk) {
-            Some(idx) => Some(self.bm.lock().unwrap().buffer(idx).contents().get_string(offset)),
-            None => None
+            Some(idx) => Ok(Some(self.bm.lock().unwrap().buffer(idx).contents().get_string(offset))),
+            None => Ok(None)
         }
     }
 
-    pub(crate) fn set_string(&mut self, blk: &BlockId, offset: usize, val: &str, log: bool) {
-        self.cm.xlock(blk);
+    pub(crate) fn set_string(&mut self, blk: &BlockId, offset: usize, val: &str, log: bool) -> Result<(), DbError> {
+        debug_assert!(offset >= Page::HEADER_SIZE, "write at offset {} overlaps the page header", offset);
+        self.xlock(blk)?;
         match self.buffers.buffer(blk) {
             Some(idx) => {
                 let mut bm = self.bm.lock().unwrap();
                 let buffer = bm.buffer(idx);
                 let mut lsn = -1;
                 if log {
-                    lsn = self.rm.set_string(buffer, offset, val);
+                    lsn = self.rm.set_string(buffer, offset, val)?;
                 }
                 buffer.contents().set_string(offset, val);
+                journal::record(self.txnum, JournalOp::Write(offset), blk);
+                Self::stamp(buffer, lsn);
                 buffer.set_modified(self.txnum, lsn);
+                if let Some(writes) = self.writes.as_mut() {
+                    writes.record(blk, offset, Constant::String(val.to_string()));
+                }
             }
             _ => {}
         }
+        Ok(())
+    }
+
+    // Longs and booleans are written through `set_values`. Doubles have no
+    // setter of their own, since undoing a double writes back the bits of
+    // the old value as a long.
+    pub(crate) fn set_long(&mut self, blk: &BlockId, offset: usize, val: i64, log: bool) -> Result<(), DbError> {
+        self.set_values(blk, &[(offset, Constant::Long(val))], log)
+    }
+
+    pub(crate) fn set_bool(&mut self, blk: &BlockId, offset: usize, val: bool, log: bool) -> Result<(), DbError> {
+        self.set_values(blk, &[(offset, Constant::Bool(val))], log)
+    }
+
+    pub(crate) fn set_blob(&mut self, blk: &BlockId, offset: usize, val: &[u8], log: bool) -> Result<(), DbError> {
+        self.set_values(blk, &[(offset, Constant::Blob(val.to_vec()))], log)
+    }
+
+    // Reads the bytes of the block from the offset on, however they are
+    // encoded.
+    pub(crate) fn get_range(&mut self, blk: &BlockId, offset: usize, len: usize) -> Result<Option<Vec<u8>>, DbError> {
+        self.slock(blk)?;
+        match self.buffers.buffer(blk) {
+            Some(idx) => Ok(Some(self.bm.lock().unwrap().buffer(idx).contents().get_range(offset, len).to_vec())),
+            None => Ok(None)
+        }
+    }
+
+    // Overwrites the bytes of the block from the offset on. The write is
+    // logged in pieces of less than half a block, so that the log record of
+    // each piece, which holds the bytes it overwrites, fits in a log block.
+    // Ranges are not followed by the WriteCheck, which compares typed
+    // values.
+    pub(crate) fn set_range(&mut self, blk: &BlockId, offset: usize, val: &[u8], log: bool) -> Result<(), DbError> {
+        debug_assert!(offset >= Page::HEADER_SIZE, "write at offset {} overlaps the page header", offset);
+        self.xlock(blk)?;
+        let piece = (self.block_size() - Page::HEADER_SIZE) / 2;
+        if let Some(idx) = self.buffers.buffer(blk) {
+            let mut bm = self.bm.lock().unwrap();
+            let buffer = bm.buffer(idx);
+            let mut lsn = -1;
+            for (i, bytes) in val.chunks(piece).enumerate() {
+                let offset = offset + i * piece;
+                if log {
+                    lsn = self.rm.set_range(buffer, offset, bytes)?;
+                }
+                buffer.contents().set_range(offset, bytes);
+                journal::record(self.txnum, JournalOp::Write(offset), blk);
+            }
+            Self::stamp(buffer, lsn);
+            buffer.set_modified(self.txnum, lsn);
+        }
+        Ok(())
+    }
+
+    // Reads several values from the block at once. The block is slocked
+    // and the buffer manager is locked a single time for all of them,
+    // instead of once per value as `get_int`/`get_string` do.
+    pub(crate) fn get_values(&mut self, blk: &BlockId, fields: &[(usize, FieldType)]) -> Result<Option<Vec<Constant>>, DbError> {
+        self.slock(blk)?;
+        match self.buffers.buffer(blk) {
+            Some(idx) => {
+                let mut bm = self.bm.lock().unwrap();
+                let page = bm.buffer(idx).contents();
+                Ok(Some(fields.iter().map(|(offset, ftype)| match ftype {
+                    FieldType::INTEGER => Constant::Int(page.get_int(*offset)),
+                    FieldType::VARCHAR => Constant::String(page.get_string(*offset)),
+                    FieldType::BIGINT => Constant::Long(page.get_long(*offset)),
+                    FieldType::DOUBLE => Constant::Double(page.get_double(*offset)),
+                    FieldType::BOOLEAN => Constant::Bool(page.get_bool(*offset)),
+                    FieldType::DATE => Constant::Date(page.get_long(*offset)),
+                    FieldType::BLOB => Constant::Blob(page.get_blob(*offset).to_vec()),
+                }).collect()))
+            }
+            None => Ok(None)
+        }
+    }
+
+    // Writes several values to the block under a single xlock and buffer
+    // manager lock. Each value still gets its own log record so that it
+    // can be undone.
+    pub(crate) fn set_values(&mut self, blk: &BlockId, values: &[(usize, Constant)], log: bool) -> Result<(), DbError> {
+        debug_assert!(values.iter().all(|(offset, _)| *offset >= Page::HEADER_SIZE), "write overlaps the page header");
+        self.xlock(blk)?;
+        if let Some(idx) = self.buffers.buffer(blk) {
+            let mut bm = self.bm.lock().unwrap();
+            let buffer = bm.buffer(idx);
+            let mut lsn = -1;
+            for (offset, val) in values {
+                match val {
+                    Constant::Int(val) => {
+                        if log {
+                            lsn = self.rm.set_int(buffer, *offset, *val)?;
+                        }
+                        buffer.contents().set_int(*offset, *val);
+                    }
+                    Constant::String(val) => {
+                        if log {
+                            lsn = self.rm.set_string(buffer, *offset, val)?;
+                        }
+                        buffer.contents().set_string(*offset, val);
+                    }
+                    Constant::Long(val) | Constant::Date(val) => {
+                        if log {
+                            lsn = self.rm.set_long(buffer, *offset, *val)?;
+                        }
+                        buffer.contents().set_long(*offset, *val);
+                    }
+                    Constant::Double(val) => {
+                        if log {
+                            lsn = self.rm.set_long(buffer, *offset, val.to_bits() as i64)?;
+                        }
+                        buffer.contents().set_double(*offset, *val);
+                    }
+                    Constant::Bool(val) => {
+                        if log {
+                            lsn = self.rm.set_bool(buffer, *offset, *val)?;
+                        }
+                        buffer.contents().set_bool(*offset, *val);
+                    }
+                    Constant::Blob(val) => {
+                        if log {
+                            lsn = self.rm.set_blob(buffer, *offset, val)?;
+                        }
+                        buffer.contents().set_blob(*offset, val);
+                    }
+                    Constant::Null => unreachable!("nulls are written as bits of the null bitmap"),
+                }
+                journal::record(self.txnum, JournalOp::Write(*offset), blk);
+            }
+            Self::stamp(buffer, lsn);
+            buffer.set_modified(self.txnum, lsn);
+            if let Some(writes) = self.writes.as_mut() {
+                values.iter().for_each(|(offset, val)| writes.record(blk, *offset, val.clone()));
+            }
+        }
+        Ok(())
     }
 
-    pub fn size(&mut self, filename: &str) -> usize {
-        let block = BlockId::new(filename, Transaction::END_OF_FILE as usize);
-        self.cm.slock(&block);
-        self.fm.length(filename) as usize
+    // Records the LSN of a logged change in the page header. Unlogged
+    // changes (undo, formatting) leave the header as it is.
+    fn stamp(buffer: &mut Buffer, lsn: i32) {
+        if lsn >= 0 {
+            buffer.contents().set_lsn(lsn);
+        }
     }
 
-    pub fn append(&mut self, filename: &str) -> BlockId {
-        let block = BlockId::new(filename, Transaction::END_OF_FILE as usize);
-        self.cm.slock(&block);
+    pub fn size(&mut self, filename: &str) -> Result<usize, DbError> {
+        let block = self.fm.block(filename, Transaction::END_OF_FILE as usize);
+        self.slock(&block)?;
+        Ok(self.fm.length(filename)? as usize)
+    }
+
+    pub fn append(&mut self, filename: &str) -> Result<BlockId, DbError> {
+        let block = self.fm.block(filename, Transaction::END_OF_FILE as usize);
+        self.slock(&block)?;
         self.fm.append(filename)
     }
 
+    // A lock that cannot be granted means the transaction is likely part of
+    // a deadlock, and an optimistic read that conflicts means it would not
+    // validate. It is rolled back right away, releasing its locks and pins,
+    // so that the others can make progress; the caller gets the error.
+    // A killed transaction is rolled back before it takes another lock.
+    fn slock(&mut self, blk: &BlockId) -> Result<(), DbError> {
+        self.abort_if_killed()?;
+        let result = self.cm.slock(blk);
+        if result.is_err() {
+            let _ = self.rollback();
+        }
+        self.status.set_locks(self.cm.lock_count());
+        result
+    }
+
+    fn xlock(&mut self, blk: &BlockId) -> Result<(), DbError> {
+        self.abort_if_killed()?;
+        let result = self.cm.xlock(blk);
+        if result.is_err() {
+            let _ = self.rollback();
+        }
+        self.status.set_locks(self.cm.lock_count());
+        result
+    }
+
     pub fn block_size(&self) -> usize {
         self.fm.block_size()
     }
 
+    // The block of the file with the given number.
+    pub fn block(&self, filename: &str, number: usize) -> BlockId {
+        self.fm.block(filename, number)
+    }
+
+    pub(crate) fn file_id(&self, filename: &str) -> FileId {
+        self.fm.file_id(filename)
+    }
+
+    // The name of the file, or None if it is not a file of this database
+    // or was deleted.
+    pub(crate) fn filename(&self, file: FileId) -> Option<Arc<str>> {
+        self.fm.filename(file)
+    }
+
+    // Asks the buffer manager to keep the blocks of the file in memory.
+    pub(crate) fn keep_resident(&self, filename: &str) {
+        self.bm.lock().unwrap().keep_resident(filename);
+    }
+
+    pub(crate) fn table_quota(&self, table: &str) -> Option<usize> {
+        self.fm.table_quota(table)
+    }
+
+    pub(crate) fn free_space(&self) -> &FreeSpaceMap {
+        self.fm.free_space()
+    }
+
     fn available_buffers(&self) -> usize {
         self.bm.lock().unwrap().available()
     }
 }
 
+// A transaction dropped while still active, as when its owner returns
+// early with an error or panics, is rolled back, so that its changes are
+// undone and its locks, pins and admission slot are not held until the
+// database is closed.
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if self.admission.is_some() {
+            let _ = self.rollback();
+        }
+        self.registry.unregister(self.txnum);
+    }
+}
+
 fn next_txnum() -> i32 {
     NEXT_TXNUM.fetch_add(1, Ordering::SeqCst);
     NEXT_TXNUM.load(Ordering::SeqCst)
@@ -189,93 +729,101 @@ mod tests {
     use crate::buffer::buffermgr::BufferMgr;
     use crate::file::filemgr::FileMgr;
     use crate::log::logmgr::LogMgr;
+    use crate::server::simpledb::SimpleDB;
+    use std::fs;
     use std::path::PathBuf;
     use std::sync::Arc;
     use std::thread;
     use std::thread::{current, sleep};
-    use crate::file::page::Page;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
     #[test]
     fn test_transaction() {
-        let fm = Arc::new(FileMgr::new(PathBuf::from("testdb"), 400));
-        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log")));
+        let fm = Arc::new(FileMgr::new(PathBuf::from("testdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
         let bm = Arc::new(Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 3)));
 
-        let mut tx1 = Transaction::new(fm.clone(), bm.clone(), lm.clone());
-        let blk = BlockId::new("testfile", 1);
-        tx1.pin(&blk.clone());
-        tx1.set_int(&blk, 80, 1, true);
-        tx1.commit();
+        let mut tx1 = Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap();
+        let blk = fm.block("testfile", 1);
+        tx1.pin(&blk).unwrap();
+        tx1.set_int(&blk, 80, 1, true).unwrap();
+        tx1.commit().unwrap();
 
-        let mut tx2 = Transaction::new(fm.clone(), bm.clone(), lm.clone());
-        tx2.pin(&blk.clone());
-        assert_eq!(tx2.get_int(&blk, 80).unwrap(), 1);
-        tx2.set_int(&blk, 80, 2, true);
-        tx2.commit();
+        let mut tx2 = Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap();
+        tx2.pin(&blk).unwrap();
+        assert_eq!(tx2.get_int(&blk, 80).unwrap().unwrap(), 1);
+        tx2.set_int(&blk, 80, 2, true).unwrap();
+        tx2.commit().unwrap();
 
-        let mut tx3 = Transaction::new(fm.clone(), bm.clone(), lm.clone());
-        tx3.pin(&blk.clone());
-        assert_eq!(tx3.get_int(&blk, 80).unwrap(), 2);
-        tx3.set_int(&blk, 80, 9999, true);
-        tx3.rollback();
+        let mut tx3 = Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap();
+        tx3.pin(&blk).unwrap();
+        assert_eq!(tx3.get_int(&blk, 80).unwrap().unwrap(), 2);
+        tx3.set_int(&blk, 80, 9999, true).unwrap();
+        tx3.rollback().unwrap();
 
-        let mut tx4 = Transaction::new(fm.clone(), bm.clone(), lm.clone());
-        tx4.pin(&blk.clone());
-        assert_eq!(tx4.get_int(&blk, 80).unwrap(), 2);
-        tx4.commit();
+        let mut tx4 = Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap();
+        tx4.pin(&blk).unwrap();
+        assert_eq!(tx4.get_int(&blk, 80).unwrap().unwrap(), 2);
+        tx4.commit().unwrap();
     }
 
     #[test]
     fn test_recovery() {
-        let fm = Arc::new(FileMgr::new(PathBuf::from("recoverytestdb"), 400));
-        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log")));
+        let fm = Arc::new(FileMgr::new(PathBuf::from("recoverytestdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
         let bm = Arc::new(Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 3)));
 
-        let blk0 = BlockId::new("testfile", 0);
-        let blk1 = BlockId::new("testfile", 1);
+        let blk0 = fm.block("testfile", 0);
+        let blk1 = fm.block("testfile", 1);
 
-        let mut tx1 = Transaction::new(fm.clone(), bm.clone(), lm.clone());
-        let mut tx2 = Transaction::new(fm.clone(), bm.clone(), lm.clone());
+        let mut tx1 = Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap();
+        let mut tx2 = Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap();
 
-        tx1.pin(&blk0.clone());
-        tx2.pin(&blk1.clone());
+        tx1.pin(&blk0).unwrap();
+        tx2.pin(&blk1).unwrap();
 
         (0..6).for_each(|i| {
-            tx1.set_int(&blk0, i * 4, (i * 4) as i32, true);
-            tx2.set_int(&blk1, i * 4, (i * 4) as i32, true);
+            tx1.set_int(&blk0, Page::HEADER_SIZE + i * 4, (i * 4) as i32, true).unwrap();
+            tx2.set_int(&blk1, Page::HEADER_SIZE + i * 4, (i * 4) as i32, true).unwrap();
         });
 
-        tx1.set_string(&blk0, 30, "abc", true);
-        tx2.set_string(&blk1, 30, "abc", true);
+        tx1.set_string(&blk0, 30, "abc", true).unwrap();
+        tx2.set_string(&blk1, 30, "abc", true).unwrap();
 
-        tx1.commit();
-        tx2.commit();
+        tx1.commit().unwrap();
+        tx2.commit().unwrap();
 
-        let mut tx3 = Transaction::new(fm.clone(), bm.clone(), lm.clone());
-        let mut tx4 = Transaction::new(fm.clone(), bm.clone(), lm.clone());
-        tx3.pin(&blk0.clone());
-        tx4.pin(&blk1.clone());
+        let mut tx3 = Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap();
+        let mut tx4 = Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap();
+        tx3.pin(&blk0).unwrap();
+        tx4.pin(&blk1).unwrap();
 
         print_values("After committed changes:", &fm, &blk0, &blk1);
 
         (0..6).for_each(|i| {
-            tx3.set_int(&blk0, i * 4, (i * 4 + 100) as i32, true);
-            tx4.set_int(&blk1, i * 4, (i * 4 + 200) as i32, true);
+            tx3.set_int(&blk0, Page::HEADER_SIZE + i * 4, (i * 4 + 100) as i32, true).unwrap();
+            tx4.set_int(&blk1, Page::HEADER_SIZE + i * 4, (i * 4 + 200) as i32, true).unwrap();
         });
 
-        tx3.set_string(&blk0, 30, "uvw", true);
-        tx4.set_string(&blk1, 30, "xyz", true);
+        tx3.set_string(&blk0, 30, "uvw", true).unwrap();
+        tx4.set_string(&blk1, 30, "xyz", true).unwrap();
 
-        bm.lock().unwrap().flush_all(tx3.txnum);
-        bm.lock().unwrap().flush_all(tx4.txnum);
+        bm.lock().unwrap().flush_all(tx3.txnum).unwrap();
+        bm.lock().unwrap().flush_all(tx4.txnum).unwrap();
 
         print_values("After uncommitted changes:", &fm, &blk0, &blk1);
 
-        tx3.rollback();
+        tx3.rollback().unwrap();
 
         print_values("After rollback:", &fm, &blk0, &blk1);
 
-        Transaction::new(fm.clone(), bm.clone(), lm.clone()).recover();
+        // tx4 never completes. Simulate the crash wiping out the lock table
+        // so that recovery can undo its changes. It is forgotten, as
+        // dropping it would roll it back.
+        tx4.cm.release();
+        std::mem::forget(tx4);
+
+        Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap().recover().unwrap();
 
         print_values("After recovery:", &fm, &blk0, &blk1);
 
@@ -283,67 +831,328 @@ mod tests {
 
         let mut page0 = Page::new(fm.block_size());
         let mut page1 = Page::new(fm.block_size());
-        fm.read(&blk0, &mut page0);
-        fm.read(&blk1, &mut page1);
-        assert_eq!(page0.bytebuffer, page1.bytebuffer);
+        fm.read(&blk0, &mut page0).unwrap();
+        fm.read(&blk1, &mut page1).unwrap();
+        // The page headers carry different LSNs; the data must match.
+        assert_eq!(page0.bytebuffer[Page::HEADER_SIZE..], page1.bytebuffer[Page::HEADER_SIZE..]);
+        assert!(page0.lsn() > 0 && page1.lsn() > 0);
+    }
+
+    #[test]
+    fn test_commit_timestamp() {
+        let _ = fs::remove_dir_all("committimetestdb");
+        let db = SimpleDB::new("committimetestdb", 400, 8).unwrap();
+        let now = || SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as i64;
+        let before = now();
+        let mut tx = Transaction::new(db.file_mgr(), db.buffer_mgr(), db.log_mgr(), db.admission_ctl(), db.tx_registry()).unwrap();
+        tx.commit().unwrap();
+        let after = now();
+
+        let mut iter = db.log_mgr().lock().unwrap().iterator().unwrap();
+        let record = create_log_record(iter.next().unwrap().unwrap()).unwrap();
+        assert!(record.op() == Op::Commit && record.txnum() == Some(tx.txnum));
+        let timestamp = record.timestamp().unwrap();
+        assert!(before <= timestamp && timestamp <= after);
+        assert_eq!(record.to_string(), format!("<COMMIT {} {}>", tx.txnum, timestamp));
+
+        // Commit records from before timestamps were added still read.
+        let mut page = Page::wrap(vec![0; 8]);
+        page.set_int(0, Op::Commit as i32);
+        page.set_int(4, 7);
+        let record = create_log_record(page.bytebuffer).unwrap();
+        assert_eq!((record.timestamp(), record.to_string()), (None, "<COMMIT 7>".to_string()));
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let _ = fs::remove_dir_all("checkpointtestdb");
+        let db = SimpleDB::new("checkpointtestdb", 400, 8).unwrap();
+        let new_tx = || Transaction::new(db.file_mgr(), db.buffer_mgr(), db.log_mgr(), db.admission_ctl(), db.tx_registry()).unwrap();
+        let last_op = || {
+            let mut iter = db.log_mgr().lock().unwrap().iterator().unwrap();
+            create_log_record(iter.next().unwrap().unwrap()).unwrap().op()
+        };
+        let mut tx1 = new_tx();
+        let blk = tx1.append("checkpointfile").unwrap();
+        tx1.pin(&blk).unwrap();
+        tx1.set_int(&blk, 80, 1, true).unwrap();
+        assert_eq!(tx1.checkpoint(), Err(DbError::PendingChanges));
+
+        // The checkpoint waits for the other transaction to complete. Once
+        // new transactions are held off, it cannot finish before tx1 does.
+        let mut tx2 = new_tx();
+        let checkpoint = thread::spawn(move || {
+            tx2.checkpoint().unwrap();
+            tx2
+        });
+        while !db.admission_ctl().is_quiescing() {
+            thread::yield_now();
+        }
+        assert!(last_op() != Op::Checkpoint);
+        tx1.commit().unwrap();
+        let mut tx2 = checkpoint.join().unwrap();
+        assert!(last_op() == Op::Checkpoint);
+        tx2.commit().unwrap();
+
+        // The transactions of another database are not waited for.
+        let _ = fs::remove_dir_all("checkpointothertestdb");
+        let other = SimpleDB::new("checkpointothertestdb", 400, 8).unwrap();
+        let _active = other.new_tx().unwrap();
+        db.checkpoint().unwrap();
+        assert!(last_op() == Op::Commit);
+    }
+
+    #[test]
+    fn test_recovery_on_open() {
+        let _ = fs::remove_dir_all("recoveryopentestdb");
+        let db = SimpleDB::new("recoveryopentestdb", 400, 8).unwrap();
+        let mut tx1 = Transaction::new(db.file_mgr(), db.buffer_mgr(), db.log_mgr(), db.admission_ctl(), db.tx_registry()).unwrap();
+        let blk = tx1.append("testfile").unwrap();
+        tx1.pin(&blk).unwrap();
+        tx1.set_int(&blk, 80, 1, true).unwrap();
+        tx1.commit().unwrap();
+
+        // An uncommitted change reaches the disk, then the process crashes,
+        // which also wipes out the lock table.
+        let mut tx2 = Transaction::new(db.file_mgr(), db.buffer_mgr(), db.log_mgr(), db.admission_ctl(), db.tx_registry()).unwrap();
+        tx2.pin(&blk).unwrap();
+        tx2.set_int(&blk, 80, 2, true).unwrap();
+        db.buffer_mgr().lock().unwrap().flush_all(tx2.txnum).unwrap();
+        tx2.cm.release();
+        std::mem::forget(tx2);
+        let fm = db.file_mgr();
+        let mut page = Page::new(fm.block_size());
+        fm.read(&blk, &mut page).unwrap();
+        assert_eq!(page.get_int(80), 2);
+        drop(db);
+
+        let db = SimpleDB::new("recoveryopentestdb", 400, 8).unwrap();
+        let fm = db.file_mgr();
+        fm.read(&fm.block("testfile", blk.number), &mut page).unwrap();
+        assert_eq!(page.get_int(80), 1);
+    }
+
+    #[test]
+    fn test_commit_disk_full() {
+        let _ = fs::remove_dir_all("commitdiskfulltestdb");
+        let db = SimpleDB::new("commitdiskfulltestdb", 400, 8).unwrap();
+        let mut tx = Transaction::new(db.file_mgr(), db.buffer_mgr(), db.log_mgr(), db.admission_ctl(), db.tx_registry()).unwrap();
+        let blk = tx.append("testfile").unwrap();
+        tx.pin(&blk).unwrap();
+        tx.set_int(&blk, 80, 1, true).unwrap();
+        db.file_mgr().simulate_disk_space(Some(0));
+        assert_eq!(tx.commit(), Err(DbError::DiskFull));
+        db.file_mgr().simulate_disk_space(None);
+        tx.commit().unwrap();
+        drop(db);
+
+        // The retried commit is durable: recovery keeps the change.
+        let db = SimpleDB::new("commitdiskfulltestdb", 400, 8).unwrap();
+        let mut page = Page::new(400);
+        db.file_mgr().read(&db.file_mgr().block("testfile", blk.number), &mut page).unwrap();
+        assert_eq!(page.get_int(80), 1);
+    }
+
+    #[test]
+    fn test_drop_rolls_back() {
+        let _ = fs::remove_dir_all("droptestdb");
+        let db = SimpleDB::new("droptestdb", 400, 8).unwrap();
+        let new_tx = || Transaction::new(db.file_mgr(), db.buffer_mgr(), db.log_mgr(), db.admission_ctl(), db.tx_registry()).unwrap();
+        let mut tx1 = new_tx();
+        let blk = tx1.append("dropfile").unwrap();
+        tx1.pin(&blk).unwrap();
+        tx1.set_int(&blk, 80, 1, true).unwrap();
+        tx1.commit().unwrap();
+
+        let mut tx2 = new_tx();
+        tx2.pin(&blk).unwrap();
+        tx2.set_int(&blk, 80, 2, true).unwrap();
+        assert_eq!(db.tx_registry().active().len(), 1);
+        drop(tx2);
+
+        // The change is undone and the lock, pin and slot are given up, so
+        // the next transaction writes the block right away.
+        assert!(db.tx_registry().active().is_empty());
+        assert_eq!(db.admission_ctl().active(), 0);
+        let mut tx3 = new_tx();
+        tx3.pin(&blk).unwrap();
+        assert_eq!(tx3.get_int(&blk, 80).unwrap(), Some(1));
+        tx3.set_int(&blk, 80, 3, true).unwrap();
+        tx3.commit().unwrap();
+    }
+
+    #[test]
+    fn test_check_writes() {
+        let _ = fs::remove_dir_all("checkwritestestdb");
+        let db = SimpleDB::new("checkwritestestdb", 400, 8).unwrap();
+        let mut tx1 = Transaction::new(db.file_mgr(), db.buffer_mgr(), db.log_mgr(), db.admission_ctl(), db.tx_registry()).unwrap();
+        tx1.check_writes();
+        let blk = tx1.append("testfile").unwrap();
+        tx1.pin(&blk).unwrap();
+        tx1.set_int(&blk, 80, 1, true).unwrap();
+        tx1.set_int(&blk, 80, 2, true).unwrap();
+        tx1.set_string(&blk, 100, "abc", true).unwrap();
+        tx1.set_values(&blk, &[(120, Constant::Int(3)), (130, Constant::String("def".to_string()))], true).unwrap();
+        tx1.commit().unwrap();
+        let written = tx1.written().unwrap().clone();
+
+        // An uncommitted change overwrites the committed ones on disk before
+        // the process crashes; recovery must bring the committed values back.
+        let mut tx2 = Transaction::new(db.file_mgr(), db.buffer_mgr(), db.log_mgr(), db.admission_ctl(), db.tx_registry()).unwrap();
+        tx2.pin(&blk).unwrap();
+        tx2.set_int(&blk, 80, 4, true).unwrap();
+        tx2.set_string(&blk, 100, "xyz", true).unwrap();
+        db.buffer_mgr().lock().unwrap().flush_all(tx2.txnum).unwrap();
+        tx2.cm.release();
+        std::mem::forget(tx2);
+        assert!(matches!(written.check_disk(&db.file_mgr()), Err(DbError::Corruption(_))));
+        drop(db);
+
+        let db = SimpleDB::new("checkwritestestdb", 400, 8).unwrap();
+        written.check_disk(&db.file_mgr()).unwrap();
+        let blk = db.file_mgr().block("testfile", blk.number);
+
+        // A write clobbered in the buffer pool fails the commit.
+        let mut tx3 = Transaction::new(db.file_mgr(), db.buffer_mgr(), db.log_mgr(), db.admission_ctl(), db.tx_registry()).unwrap();
+        tx3.check_writes();
+        tx3.pin(&blk).unwrap();
+        tx3.set_int(&blk, 80, 5, true).unwrap();
+        let idx = tx3.buffers.buffer(&blk).unwrap();
+        db.buffer_mgr().lock().unwrap().buffer(idx).contents().set_int(80, 6);
+        assert!(matches!(tx3.commit(), Err(DbError::Corruption(_))));
+    }
+
+    #[test]
+    fn test_optimistic() {
+        let _ = fs::remove_dir_all("optimistictestdb");
+        let fm = Arc::new(FileMgr::new(PathBuf::from("optimistictestdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
+        let bm = Arc::new(Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 8)));
+        let new_tx = || {
+            let mut tx = Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap();
+            tx.run_optimistically();
+            tx
+        };
+        let blk = fm.block("optimistic", 0);
+        let mut tx = new_tx();
+        tx.pin(&blk).unwrap();
+        tx.set_int(&blk, 80, 1, true).unwrap();
+        tx.commit().unwrap();
+
+        // Readers do not lock each other out, or a writer.
+        let mut tx1 = new_tx();
+        let mut tx2 = new_tx();
+        tx1.pin(&blk).unwrap();
+        tx2.pin(&blk).unwrap();
+        assert_eq!(tx1.get_int(&blk, 80).unwrap(), Some(1));
+        assert_eq!(tx2.get_int(&blk, 80).unwrap(), Some(1));
+        tx2.set_int(&blk, 80, 2, true).unwrap();
+        tx2.commit().unwrap();
+        // tx1 read the value tx2 overwrote.
+        assert_eq!(tx1.commit(), Err(DbError::Conflict(blk)));
+
+        // A read of a block being written fails right away.
+        let mut tx3 = new_tx();
+        let mut tx4 = new_tx();
+        tx3.pin(&blk).unwrap();
+        tx4.pin(&blk).unwrap();
+        tx3.set_int(&blk, 80, 3, true).unwrap();
+        assert_eq!(tx4.get_int(&blk, 80), Err(DbError::Conflict(blk)));
+        tx3.rollback().unwrap();
+
+        // A retry after the writer is done goes through.
+        let mut tx5 = new_tx();
+        tx5.pin(&blk).unwrap();
+        assert_eq!(tx5.get_int(&blk, 80).unwrap(), Some(2));
+        tx5.set_int(&blk, 80, 5, true).unwrap();
+        assert_eq!(tx5.get_int(&blk, 80).unwrap(), Some(5));
+        tx5.commit().unwrap();
+
+        let mut tx6 = Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap();
+        tx6.pin(&blk).unwrap();
+        assert_eq!(tx6.get_int(&blk, 80).unwrap(), Some(5));
+        tx6.commit().unwrap();
+    }
+
+    #[test]
+    fn test_optimistic_per_database() {
+        let _ = fs::remove_dir_all("optimisticdbtestdb");
+        let _ = fs::remove_dir_all("pessimisticdbtestdb");
+        let optimistic = SimpleDB::new("optimisticdbtestdb", 400, 8).unwrap();
+        let pessimistic = SimpleDB::new("pessimisticdbtestdb", 400, 8).unwrap();
+        optimistic.set_optimistic(true);
+
+        // Only the reads of the database made optimistic take no locks.
+        for (db, locks) in [(&optimistic, 0), (&pessimistic, 1)] {
+            let tx = db.new_tx().unwrap();
+            let mut tx = tx.lock().unwrap();
+            let blk = tx.append("optimistic").unwrap();
+            tx.commit().unwrap();
+            let tx = db.new_tx().unwrap();
+            let mut tx = tx.lock().unwrap();
+            tx.pin(&blk).unwrap();
+            tx.get_int(&blk, 80).unwrap();
+            assert_eq!(tx.cm.lock_count(), locks);
+            tx.commit().unwrap();
+        }
     }
 
     #[test]
     fn test_concurrency() {
-        let fm = Arc::new(FileMgr::new(PathBuf::from("concurrencytestdb"), 400));
-        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log")));
+        let fm = Arc::new(FileMgr::new(PathBuf::from("concurrencytestdb"), 400).unwrap());
+        let lm = Arc::new(Mutex::new(LogMgr::new(fm.clone(), "testlog.log").unwrap()));
         let bm = Arc::new(Mutex::new(BufferMgr::new(fm.clone(), lm.clone(), 3)));
 
-        let mut txA = Transaction::new(fm.clone(), bm.clone(), lm.clone());
-        let mut txB = Transaction::new(fm.clone(), bm.clone(), lm.clone());
-        let mut txC = Transaction::new(fm.clone(), bm.clone(), lm.clone());
+        let mut txA = Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap();
+        let mut txB = Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap();
+        let mut txC = Transaction::new(fm.clone(), bm.clone(), lm.clone(), AdmissionCtl::unlimited(), Arc::default()).unwrap();
 
         let A = thread::spawn(move || {
-            let blk0 = BlockId::new("testfile", 0);
-            let blk1 = BlockId::new("testfile", 1);
-            txA.pin(&blk0.clone());
-            txA.pin(&blk1.clone());
+            let blk0 = txA.block("testfile", 0);
+            let blk1 = txA.block("testfile", 1);
+            txA.pin(&blk0).unwrap();
+            txA.pin(&blk1).unwrap();
             println!("Tx A: requesting slock 0");
-            txA.get_int(&blk0, 0);
+            txA.get_int(&blk0, Page::HEADER_SIZE).unwrap();
             println!("Tx A: received slock 0");
             sleep(std::time::Duration::from_secs(1));
             println!("Tx A: requesting slock 1");
-            txA.get_int(&blk1, 0);
+            txA.get_int(&blk1, Page::HEADER_SIZE).unwrap();
             println!("Tx A: received slock 1");
-            txA.commit();
+            txA.commit().unwrap();
             println!("Tx A: committed");
         });
 
         let B = thread::spawn(move || {
-            let blk0 = BlockId::new("testfile", 0);
-            let blk1 = BlockId::new("testfile", 1);
-            txB.pin(&blk0.clone());
-            txB.pin(&blk1.clone());
+            let blk0 = txB.block("testfile", 0);
+            let blk1 = txB.block("testfile", 1);
+            txB.pin(&blk0).unwrap();
+            txB.pin(&blk1).unwrap();
             println!("Tx B: requesting xlock 1");
-            txB.set_int(&blk1, 0, 0, false);
+            txB.set_int(&blk1, Page::HEADER_SIZE, 0, false).unwrap();
             println!("Tx B: received xlock 1");
             sleep(std::time::Duration::from_secs(1));
             println!("Tx B: requesting slock 0");
-            txB.get_int(&blk0, 0);
+            txB.get_int(&blk0, Page::HEADER_SIZE).unwrap();
             println!("Tx B: received slock 0");
-            txB.commit();
+            txB.commit().unwrap();
             println!("Tx B: committed");
         });
 
         let C = thread::spawn(move || {
-            let blk0 = BlockId::new("testfile", 0);
-            let blk1 = BlockId::new("testfile", 1);
-            txC.pin(&blk0.clone());
-            txC.pin(&blk1.clone());
+            let blk0 = txC.block("testfile", 0);
+            let blk1 = txC.block("testfile", 1);
+            txC.pin(&blk0).unwrap();
+            txC.pin(&blk1).unwrap();
             sleep(std::time::Duration::from_millis(500));
             println!("Tx C: requesting xlock 0");
-            txC.set_int(&blk0, 0, 0, false);
+            txC.set_int(&blk0, Page::HEADER_SIZE, 0, false).unwrap();
             println!("Tx C: received xlock 0");
             sleep(std::time::Duration::from_secs(1));
             println!("Tx C: requesting slock 1");
-            txC.get_int(&blk1, 0);
+            txC.get_int(&blk1, Page::HEADER_SIZE).unwrap();
             println!("Tx C: received slock 1");
-            txC.commit();
+            txC.commit().unwrap();
             println!("Tx C: committed");
         });
 
@@ -364,23 +1173,23 @@ mod tests {
         println!("{}", msg);
         let mut page0 = Page::new(fm.block_size());
         let mut page1 = Page::new(fm.block_size());
-        fm.read(&blk0, &mut page0);
-        fm.read(&blk1, &mut page1);
+        fm.read(&blk0, &mut page0).unwrap();
+        fm.read(&blk1, &mut page1).unwrap();
         (0..6).for_each(|i| {
-            print!("{:?} ", page0.get_int(i * 4));
-            print!("{:?} ", page1.get_string(i * 4));
+            print!("{:?} ", page0.get_int(Page::HEADER_SIZE + i * 4));
+            print!("{:?} ", page1.get_int(Page::HEADER_SIZE + i * 4));
         });
         println!("{:?} {:?}", page0.get_string(30), page1.get_string(30));
         println!();
     }
 
     fn print_log_file(fm: &Arc<FileMgr>, lm: &Arc<Mutex<LogMgr>>) {
-        let block = BlockId::new("testlog.log", (fm.length("testlog.log") - 1) as usize);
+        let block = fm.block("testlog.log", (fm.length("testlog.log").unwrap() - 1) as usize);
         let mut page = Page::new(fm.block_size());
-        fm.read(&block, &mut page);
-        let mut iter = lm.lock().unwrap().iterator();
+        fm.read(&block, &mut page).unwrap();
+        let mut iter = lm.lock().unwrap().iterator().unwrap();
         while let Some(record) = iter.next() {
-            let log_record = create_log_record(record).unwrap();
+            let log_record = create_log_record(record.unwrap()).unwrap();
             println!("{}", log_record);
         }
     }
@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::tx::transaction::Transaction;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxState {
+    Active,
+    Committing,
+    RollingBack,
+}
+
+impl TxState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TxState::Active => "active",
+            TxState::Committing => "committing",
+            TxState::RollingBack => "rolling back",
+        }
+    }
+}
+
+// The status of a transaction as others see it while it runs. The
+// transaction keeps it up to date as it takes locks and pins buffers, so
+// that it can be read without waiting for the transaction.
+pub(crate) struct TxStatus {
+    txnum: i32,
+    started: i64,
+    state: AtomicU8,
+    locks: AtomicUsize,
+    pins: AtomicUsize,
+    killed: AtomicBool,
+    // The transaction itself, once it is shared, so that a kill can roll
+    // it back while it is idle.
+    handle: Mutex<Option<Weak<Mutex<Transaction>>>>,
+}
+
+// A snapshot of the status of an active transaction. The start time is in
+// milliseconds since the epoch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TxInfo {
+    pub txnum: i32,
+    pub started: i64,
+    pub state: TxState,
+    pub locks: usize,
+    pub pins: usize,
+}
+
+// The active transactions of a database, by number. Each database has
+// its own, so sys_transactions and KILL only reach its transactions.
+#[derive(Default)]
+pub struct TxRegistry {
+    active: Mutex<BTreeMap<i32, Arc<TxStatus>>>,
+}
+
+impl TxStatus {
+    pub(crate) fn set_state(&self, state: TxState) {
+        self.state.store(state as u8, Ordering::SeqCst);
+    }
+
+    pub(crate) fn set_locks(&self, locks: usize) {
+        self.locks.store(locks, Ordering::SeqCst);
+    }
+
+    pub(crate) fn set_pins(&self, pins: usize) {
+        self.pins.store(pins, Ordering::SeqCst);
+    }
+
+    pub(crate) fn set_handle(&self, tx: Weak<Mutex<Transaction>>) {
+        *self.handle.lock().unwrap() = Some(tx);
+    }
+
+    pub(crate) fn is_killed(&self) -> bool {
+        self.killed.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn state(&self) -> TxState {
+        match self.state.load(Ordering::SeqCst) {
+            s if s == TxState::Committing as u8 => TxState::Committing,
+            s if s == TxState::RollingBack as u8 => TxState::RollingBack,
+            _ => TxState::Active,
+        }
+    }
+
+    fn info(&self) -> TxInfo {
+        TxInfo {
+            txnum: self.txnum,
+            started: self.started,
+            state: self.state(),
+            locks: self.locks.load(Ordering::SeqCst),
+            pins: self.pins.load(Ordering::SeqCst),
+        }
+    }
+}
+
+impl TxRegistry {
+    pub fn new() -> TxRegistry {
+        TxRegistry::default()
+    }
+
+    pub(crate) fn register(&self, txnum: i32) -> Arc<TxStatus> {
+        let started = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as i64);
+        let status = Arc::new(TxStatus {
+            txnum,
+            started,
+            state: AtomicU8::new(TxState::Active as u8),
+            locks: AtomicUsize::new(0),
+            pins: AtomicUsize::new(0),
+            killed: AtomicBool::new(false),
+            handle: Mutex::new(None),
+        });
+        self.active.lock().unwrap().insert(txnum, status.clone());
+        status
+    }
+
+    pub(crate) fn unregister(&self, txnum: i32) {
+        self.active.lock().unwrap().remove(&txnum);
+    }
+
+    // The active transactions, oldest first.
+    pub fn active(&self) -> Vec<TxInfo> {
+        self.active.lock().unwrap().values().map(|status| status.info()).collect()
+    }
+
+    // Marks the transaction as killed, and rolls it back right away if it is
+    // idle. A transaction in the middle of an operation notices the next time
+    // it locks a block or pins a buffer, and rolls itself back then. Returns
+    // false if no such transaction is active.
+    pub(crate) fn kill(&self, txnum: i32) -> bool {
+        let Some(status) = self.active.lock().unwrap().get(&txnum).cloned() else {
+            return false;
+        };
+        status.killed.store(true, Ordering::SeqCst);
+        let tx = status.handle.lock().unwrap().as_ref().and_then(Weak::upgrade);
+        if let Some(tx) = tx && let Ok(mut tx) = tx.try_lock() {
+            let _ = tx.abort_if_killed();
+        }
+        true
+    }
+}
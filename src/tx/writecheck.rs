@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::error::dberror::DbError;
+use crate::file::blockid::{BlockId, FileId};
+use crate::file::filemgr::FileMgr;
+use crate::file::page::Page;
+use crate::query::constant::Constant;
+
+// The WriteCheck remembers the last value a transaction wrote at each
+// offset of each block, so that the values can be read back once the
+// transaction commits and compared with what was written. It is a debug
+// harness for the buffer and recovery managers: a committed value that
+// reads back differently, from the buffer pool or from disk, means one of
+// them lost or misplaced a write.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WriteCheck {
+    values: HashMap<BlockId, HashMap<usize, Constant>>,
+    // The names of the written files, taken when the transaction commits,
+    // so that the blocks can be found again after a restart.
+    names: HashMap<FileId, Arc<str>>,
+}
+
+impl WriteCheck {
+    pub(crate) fn new() -> WriteCheck {
+        WriteCheck::default()
+    }
+
+    pub(crate) fn record(&mut self, blk: &BlockId, offset: usize, val: Constant) {
+        self.values.entry(*blk).or_default().insert(offset, val);
+    }
+
+    pub(crate) fn name_files(&mut self, fm: &FileMgr) {
+        for blk in self.values.keys() {
+            if let Some(name) = fm.filename(blk.file) {
+                self.names.insert(blk.file, name);
+            }
+        }
+    }
+
+    pub(crate) fn blocks(&self) -> impl Iterator<Item = &BlockId> {
+        self.values.keys()
+    }
+
+    // Compares the written values of the block with the page's contents.
+    pub(crate) fn check_page(&self, blk: &BlockId, page: &Page, source: &str) -> Result<(), DbError> {
+        for (offset, val) in self.values.get(blk).into_iter().flatten() {
+            let read = match val {
+                Constant::Int(_) => Constant::Int(page.get_int(*offset)),
+                Constant::String(_) => Constant::String(page.get_string(*offset)),
+                Constant::Long(_) => Constant::Long(page.get_long(*offset)),
+                Constant::Double(_) => Constant::Double(page.get_double(*offset)),
+                Constant::Bool(_) => Constant::Bool(page.get_bool(*offset)),
+                Constant::Date(_) => Constant::Date(page.get_long(*offset)),
+                Constant::Blob(_) => Constant::Blob(page.get_blob(*offset).to_vec()),
+                Constant::Null => unreachable!("nulls are written as bits of the null bitmap"),
+            };
+            if read != *val {
+                let filename = self.names.get(&blk.file).map_or(blk.to_string(), |name| name.to_string());
+                return Err(DbError::Corruption(format!("{} block {} of {} has {} at offset {} where {} was committed",
+                    source, blk.number(), filename, read, offset, val)));
+            }
+        }
+        Ok(())
+    }
+
+    // Reads every written block from disk and checks its values, as after
+    // a restart, when the committed values must have survived recovery.
+    pub(crate) fn check_disk(&self, fm: &FileMgr) -> Result<(), DbError> {
+        let mut page = Page::new(fm.block_size());
+        for blk in self.blocks() {
+            let home = self.names.get(&blk.file).map_or(*blk, |name| fm.block(name, blk.number));
+            fm.read(&home, &mut page)?;
+            self.check_page(blk, &page, "disk")?;
+        }
+        Ok(())
+    }
+}